@@ -0,0 +1,2 @@
+DROP TABLE avito_feed_imports;
+DROP TABLE avito_feed_import_schedules;
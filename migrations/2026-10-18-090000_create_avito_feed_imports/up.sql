@@ -0,0 +1,36 @@
+CREATE TABLE avito_feed_import_schedules (
+	schedule_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	feed_id UUID NOT NULL UNIQUE REFERENCES avito_feeds (feed_id) ON DELETE CASCADE,
+	source_url TEXT NOT NULL,
+	interval_minutes INTEGER,
+	cron_expression VARCHAR,
+	enabled BOOLEAN NOT NULL DEFAULT TRUE,
+	last_run_ts TIMESTAMPTZ,
+	next_run_ts TIMESTAMPTZ,
+	created_ts TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_ts TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	CHECK (interval_minutes IS NOT NULL OR cron_expression IS NOT NULL)
+);
+
+CREATE INDEX avito_feed_import_schedules_next_run_ts_idx
+	ON avito_feed_import_schedules (next_run_ts)
+	WHERE enabled;
+
+CREATE TABLE avito_feed_imports (
+	import_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	feed_id UUID NOT NULL REFERENCES avito_feeds (feed_id) ON DELETE CASCADE,
+	schedule_id UUID REFERENCES avito_feed_import_schedules (schedule_id) ON DELETE SET NULL,
+	source_url TEXT NOT NULL,
+	status VARCHAR NOT NULL,
+	ads_processed INTEGER NOT NULL DEFAULT 0,
+	ads_added INTEGER NOT NULL DEFAULT 0,
+	ads_updated INTEGER NOT NULL DEFAULT 0,
+	ads_unchanged INTEGER NOT NULL DEFAULT 0,
+	ads_deactivated INTEGER NOT NULL DEFAULT 0,
+	error TEXT,
+	started_ts TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	finished_ts TIMESTAMPTZ
+);
+
+CREATE INDEX avito_feed_imports_feed_id_started_ts_idx
+	ON avito_feed_imports (feed_id, started_ts DESC);
@@ -0,0 +1,3 @@
+DROP TABLE avito_field_values_cache;
+DROP TABLE avito_category_fields_cache;
+DROP TABLE avito_category_tree_cache;
@@ -0,0 +1,20 @@
+CREATE TABLE avito_category_tree_cache (
+	cache_key VARCHAR PRIMARY KEY,
+	data JSONB NOT NULL,
+	fetched_ts TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	expires_ts TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE avito_category_fields_cache (
+	avito_slug VARCHAR PRIMARY KEY,
+	data JSONB NOT NULL,
+	fetched_ts TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	expires_ts TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE avito_field_values_cache (
+	values_link TEXT PRIMARY KEY,
+	data JSONB NOT NULL,
+	fetched_ts TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	expires_ts TIMESTAMPTZ NOT NULL
+);
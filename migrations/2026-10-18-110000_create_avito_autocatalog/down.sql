@@ -0,0 +1,4 @@
+DROP TABLE avito_car_modifications;
+DROP TABLE avito_car_generations;
+DROP TABLE avito_car_models;
+DROP TABLE avito_car_makes;
@@ -0,0 +1,35 @@
+CREATE TABLE avito_car_makes (
+	make_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	avito_id VARCHAR,
+	name VARCHAR NOT NULL UNIQUE,
+	created_ts TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE avito_car_models (
+	model_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	make_id UUID NOT NULL REFERENCES avito_car_makes (make_id) ON DELETE CASCADE,
+	avito_id VARCHAR,
+	name VARCHAR NOT NULL,
+	created_ts TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	UNIQUE (make_id, name)
+);
+
+CREATE TABLE avito_car_generations (
+	generation_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	model_id UUID NOT NULL REFERENCES avito_car_models (model_id) ON DELETE CASCADE,
+	avito_id VARCHAR,
+	name VARCHAR NOT NULL,
+	created_ts TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	UNIQUE (model_id, name)
+);
+
+CREATE TABLE avito_car_modifications (
+	modification_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	generation_id UUID NOT NULL REFERENCES avito_car_generations (generation_id) ON DELETE CASCADE,
+	avito_id VARCHAR,
+	name VARCHAR NOT NULL,
+	body_type VARCHAR,
+	doors VARCHAR,
+	created_ts TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	UNIQUE (generation_id, name)
+);
@@ -0,0 +1,2 @@
+DROP INDEX avito_ads_avito_ad_id_idx;
+DROP TABLE avito_item_syncs;
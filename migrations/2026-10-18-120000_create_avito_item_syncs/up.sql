@@ -0,0 +1,23 @@
+CREATE TABLE avito_item_syncs (
+	sync_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	account_id UUID NOT NULL REFERENCES avito_accounts (account_id) ON DELETE CASCADE,
+	status VARCHAR NOT NULL,
+	items_seen INTEGER NOT NULL DEFAULT 0,
+	ads_added INTEGER NOT NULL DEFAULT 0,
+	ads_updated INTEGER NOT NULL DEFAULT 0,
+	ads_unchanged INTEGER NOT NULL DEFAULT 0,
+	ads_removed INTEGER NOT NULL DEFAULT 0,
+	error TEXT,
+	started_ts TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	finished_ts TIMESTAMPTZ
+);
+
+CREATE INDEX avito_item_syncs_account_id_started_ts_idx
+	ON avito_item_syncs (account_id, started_ts DESC);
+
+-- One running sync per account
+CREATE UNIQUE INDEX avito_item_syncs_running_idx
+	ON avito_item_syncs (account_id)
+	WHERE status = 'running';
+
+CREATE INDEX avito_ads_avito_ad_id_idx ON avito_ads (avito_ad_id);
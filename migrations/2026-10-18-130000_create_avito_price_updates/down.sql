@@ -0,0 +1,2 @@
+DROP TABLE avito_price_update_items;
+DROP TABLE avito_price_update_jobs;
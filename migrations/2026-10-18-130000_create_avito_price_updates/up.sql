@@ -0,0 +1,35 @@
+CREATE TABLE avito_price_update_jobs (
+	job_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	account_id UUID NOT NULL REFERENCES avito_accounts (account_id) ON DELETE CASCADE,
+	user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	-- preview, running, completed or failed
+	status VARCHAR NOT NULL,
+	items_total INTEGER NOT NULL DEFAULT 0,
+	items_updated INTEGER NOT NULL DEFAULT 0,
+	items_failed INTEGER NOT NULL DEFAULT 0,
+	items_skipped INTEGER NOT NULL DEFAULT 0,
+	error TEXT,
+	created_ts TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	started_ts TIMESTAMPTZ,
+	finished_ts TIMESTAMPTZ
+);
+
+CREATE INDEX avito_price_update_jobs_user_id_created_ts_idx
+	ON avito_price_update_jobs (user_id, created_ts DESC);
+
+CREATE TABLE avito_price_update_items (
+	job_item_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	job_id UUID NOT NULL REFERENCES avito_price_update_jobs (job_id) ON DELETE CASCADE,
+	-- Position in the uploaded file, so the report keeps the input order
+	line INTEGER NOT NULL,
+	item_id VARCHAR NOT NULL,
+	old_price DOUBLE PRECISION,
+	new_price DOUBLE PRECISION,
+	-- invalid, pending, unchanged, updated or failed
+	status VARCHAR NOT NULL,
+	error TEXT,
+	updated_ts TIMESTAMPTZ
+);
+
+CREATE INDEX avito_price_update_items_job_id_line_idx
+	ON avito_price_update_items (job_id, line);
@@ -0,0 +1,2 @@
+DROP TABLE avito_repricing_decisions;
+DROP TABLE avito_repricing_rules;
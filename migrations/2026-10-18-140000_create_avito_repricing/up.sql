@@ -0,0 +1,50 @@
+-- One repricing rule per ad, priced against the competitors found by a search request
+CREATE TABLE avito_repricing_rules (
+	rule_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	ad_id UUID NOT NULL UNIQUE REFERENCES avito_ads (ad_id) ON DELETE CASCADE,
+	avito_request_id UUID NOT NULL REFERENCES avito_requests (request_id) ON DELETE CASCADE,
+	-- min, median or average of the competitor prices
+	strategy VARCHAR NOT NULL DEFAULT 'median',
+	-- Applied to the reference price; -3 means 3% below it
+	offset_percent DOUBLE PRECISION NOT NULL DEFAULT 0,
+	offset_amount DOUBLE PRECISION NOT NULL DEFAULT 0,
+	floor_price DOUBLE PRECISION NOT NULL,
+	ceiling_price DOUBLE PRECISION,
+	-- Largest step from the current price in one run
+	max_change_percent DOUBLE PRECISION,
+	min_competitors INTEGER NOT NULL DEFAULT 3,
+	exclude_promoted BOOLEAN NOT NULL DEFAULT TRUE,
+	require_approval BOOLEAN NOT NULL DEFAULT TRUE,
+	interval_minutes INTEGER NOT NULL DEFAULT 60,
+	enabled BOOLEAN NOT NULL DEFAULT TRUE,
+	last_run_ts TIMESTAMPTZ,
+	next_run_ts TIMESTAMPTZ,
+	created_ts TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_ts TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX avito_repricing_rules_next_run_ts_idx
+	ON avito_repricing_rules (next_run_ts)
+	WHERE enabled;
+
+CREATE TABLE avito_repricing_decisions (
+	decision_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	rule_id UUID NOT NULL REFERENCES avito_repricing_rules (rule_id) ON DELETE CASCADE,
+	ad_id UUID NOT NULL REFERENCES avito_ads (ad_id) ON DELETE CASCADE,
+	avito_item_id VARCHAR,
+	competitors_count INTEGER NOT NULL DEFAULT 0,
+	reference_price DOUBLE PRECISION,
+	current_price DOUBLE PRECISION,
+	proposed_price DOUBLE PRECISION,
+	-- skipped, pending, approved, applied, failed, rejected or superseded
+	status VARCHAR NOT NULL,
+	reason TEXT,
+	decided_by UUID REFERENCES users (id) ON DELETE SET NULL,
+	created_ts TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	decided_ts TIMESTAMPTZ
+);
+
+CREATE INDEX avito_repricing_decisions_rule_id_created_ts_idx
+	ON avito_repricing_decisions (rule_id, created_ts DESC);
+CREATE INDEX avito_repricing_decisions_ad_id_created_ts_idx
+	ON avito_repricing_decisions (ad_id, created_ts DESC);
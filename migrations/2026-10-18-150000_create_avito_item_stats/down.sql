@@ -0,0 +1 @@
+DROP TABLE avito_item_stats;
@@ -0,0 +1,18 @@
+-- Daily statistics of the live Avito items, kept past Avito's own retention window
+CREATE TABLE avito_item_stats (
+	account_id UUID NOT NULL REFERENCES avito_accounts (account_id) ON DELETE CASCADE,
+	item_id VARCHAR NOT NULL,
+	stat_date DATE NOT NULL,
+	impressions BIGINT NOT NULL DEFAULT 0,
+	views BIGINT NOT NULL DEFAULT 0,
+	contacts BIGINT NOT NULL DEFAULT 0,
+	favorites BIGINT NOT NULL DEFAULT 0,
+	spending DOUBLE PRECISION NOT NULL DEFAULT 0,
+	-- Every metric Avito returned for the day, including the ones above
+	metrics JSONB NOT NULL DEFAULT '{}',
+	collected_ts TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	PRIMARY KEY (account_id, item_id, stat_date)
+);
+
+CREATE INDEX avito_item_stats_account_id_stat_date_idx
+	ON avito_item_stats (account_id, stat_date);
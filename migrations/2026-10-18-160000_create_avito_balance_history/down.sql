@@ -0,0 +1,2 @@
+DROP TABLE avito_balance_alerts;
+DROP TABLE avito_balance_history;
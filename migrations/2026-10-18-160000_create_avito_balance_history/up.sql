@@ -0,0 +1,23 @@
+CREATE TABLE avito_balance_history (
+	history_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	account_id UUID NOT NULL REFERENCES avito_accounts (account_id) ON DELETE CASCADE,
+	balance DOUBLE PRECISION NOT NULL,
+	recorded_ts TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX avito_balance_history_account_id_recorded_ts_idx
+	ON avito_balance_history (account_id, recorded_ts);
+
+-- Low-balance alert settings of an account
+CREATE TABLE avito_balance_alerts (
+	account_id UUID PRIMARY KEY REFERENCES avito_accounts (account_id) ON DELETE CASCADE,
+	threshold DOUBLE PRECISION NOT NULL,
+	-- Overrides BALANCE_ALERT_WEBHOOK_URL for this account
+	webhook_url TEXT,
+	enabled BOOLEAN NOT NULL DEFAULT TRUE,
+	-- Set while the balance stays under the threshold, so the alert is sent once per drop
+	below_threshold BOOLEAN NOT NULL DEFAULT FALSE,
+	last_alert_ts TIMESTAMPTZ,
+	created_ts TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_ts TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
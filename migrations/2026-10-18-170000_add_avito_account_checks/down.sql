@@ -0,0 +1,3 @@
+ALTER TABLE avito_accounts
+	DROP COLUMN last_check_error,
+	DROP COLUMN last_check_ts;
@@ -0,0 +1,3 @@
+ALTER TABLE avito_accounts
+	ADD COLUMN last_check_ts TIMESTAMPTZ,
+	ADD COLUMN last_check_error TEXT;
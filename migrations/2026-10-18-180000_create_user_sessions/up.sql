@@ -0,0 +1,14 @@
+-- A login; its refresh token is rotated on every refresh and only its hash is kept
+CREATE TABLE user_sessions (
+	session_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	refresh_token_hash TEXT NOT NULL,
+	created_ts TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	refreshed_ts TIMESTAMPTZ,
+	expires_ts TIMESTAMPTZ NOT NULL,
+	revoked_ts TIMESTAMPTZ,
+	-- logout, token_reuse, ...
+	revoke_reason VARCHAR
+);
+
+CREATE INDEX user_sessions_user_id_idx ON user_sessions (user_id);
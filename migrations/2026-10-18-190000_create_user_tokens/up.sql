@@ -0,0 +1,13 @@
+-- Single-use links mailed to a user; only the token's hash is kept
+CREATE TABLE user_tokens (
+	token_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	-- verify_email, reset_password
+	purpose VARCHAR(32) NOT NULL,
+	token_hash TEXT NOT NULL UNIQUE,
+	created_ts TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	expires_ts TIMESTAMPTZ NOT NULL,
+	used_ts TIMESTAMPTZ
+);
+
+CREATE INDEX user_tokens_user_id_idx ON user_tokens (user_id, purpose);
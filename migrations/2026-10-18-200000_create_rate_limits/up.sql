@@ -0,0 +1,15 @@
+-- Requests of one client to one route group in the current fixed window
+CREATE TABLE rate_limit_counters (
+	-- {group}:ip:{addr} or {group}:user:{id}
+	bucket_key TEXT PRIMARY KEY,
+	window_start TIMESTAMPTZ NOT NULL,
+	hits INTEGER NOT NULL
+);
+
+-- Failed logins per email, whether or not an account has it
+CREATE TABLE login_attempts (
+	email TEXT PRIMARY KEY,
+	failed_count INTEGER NOT NULL DEFAULT 0,
+	last_failed_ts TIMESTAMPTZ NOT NULL,
+	locked_until TIMESTAMPTZ
+);
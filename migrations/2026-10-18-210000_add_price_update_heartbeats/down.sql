@@ -0,0 +1,2 @@
+ALTER TABLE avito_price_update_jobs
+	DROP COLUMN heartbeat_ts;
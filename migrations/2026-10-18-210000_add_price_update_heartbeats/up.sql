@@ -0,0 +1,3 @@
+-- Refreshed while a job runs; a running job whose heartbeat stopped was cut off with its server
+ALTER TABLE avito_price_update_jobs
+	ADD COLUMN heartbeat_ts TIMESTAMPTZ;
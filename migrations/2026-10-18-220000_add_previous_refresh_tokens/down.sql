@@ -0,0 +1,2 @@
+ALTER TABLE user_sessions
+	DROP COLUMN previous_token_hash;
@@ -0,0 +1,3 @@
+-- The token a refresh replaced; still accepted for a moment from requests sent alongside it
+ALTER TABLE user_sessions
+	ADD COLUMN previous_token_hash TEXT;
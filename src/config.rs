@@ -5,20 +5,288 @@ use std::env;
 pub struct Config {
 	pub database_url: String,
 	pub jwt_secret: String,
+	pub access_token_ttl_minutes: i64,
+	pub refresh_token_ttl_days: i64,
+	pub secure_cookies: bool,
 	pub server_port: u16,
+	pub xml_max_document_size: u64,
+	pub xml_max_ad_size: u64,
+	pub xml_import_batch_size: usize,
+	// Scheduled feed imports that may run at the same time
+	pub feed_import_concurrency: usize,
+	pub avito_catalog_cache_ttl_secs: i64,
+	pub avito_values_cache_ttl_secs: i64,
+	pub avito_catalog_fetch_concurrency: usize,
+	pub autocatalog_url: String,
+	pub avito_base_url: String,
+	pub avito_accept_invalid_certs: bool,
+	pub avito_max_retries: u32,
+	pub avito_retry_base_delay_ms: u64,
+	pub avito_retry_max_delay_ms: u64,
+	pub avito_rate_limit_per_sec: f64,
+	pub avito_rate_limit_burst: u32,
+	pub avito_circuit_failure_threshold: u32,
+	pub avito_circuit_cooldown_secs: u64,
+	pub avito_items_sync_interval_minutes: u64,
+	pub avito_bulk_price_concurrency: usize,
+	pub avito_item_stats_interval_minutes: u64,
+	pub avito_item_stats_lookback_days: u32,
+	pub avito_balance_poll_interval_minutes: u64,
+	pub balance_alert_webhook_url: Option<String>,
+	pub avito_account_check_interval_minutes: u64,
+	// `id:hex` key entries from ENCRYPTION_KEY_FILE or ENCRYPTION_KEYS
+	pub encryption_keys: String,
+	pub encryption_active_key_id: Option<String>,
+	// "smtp", or "outbox" to write mail to files in mail_outbox_dir instead of sending it
+	pub mail_transport: String,
+	pub mail_outbox_dir: String,
+	pub mail_from: String,
+	pub smtp_host: Option<String>,
+	pub smtp_port: Option<u16>,
+	pub smtp_username: Option<String>,
+	pub smtp_password: Option<String>,
+	// "starttls", "tls" or "none"
+	pub smtp_tls: String,
+	// Frontend the links in mail point to
+	pub app_url: String,
+	pub email_verification_ttl_hours: i64,
+	pub password_reset_ttl_minutes: i64,
+	pub require_verified_email: bool,
+	// Argon2id parameters for new password hashes
+	pub argon2_memory_kib: u32,
+	pub argon2_iterations: u32,
+	pub argon2_parallelism: u32,
+	pub password_min_length: usize,
+	// One breached password per line
+	pub breached_passwords_file: Option<String>,
+	// Requests per window and client to /api/auth and to the rest of /api; 0 turns a limit off
+	pub rate_limit_auth_requests: u32,
+	pub rate_limit_auth_window_secs: u64,
+	pub rate_limit_api_requests: u32,
+	pub rate_limit_api_window_secs: u64,
+	// Take the client address from X-Forwarded-For; only behind a proxy that sets it
+	pub trust_forwarded_for: bool,
+	// Failed logins of an email before it is locked; the wait between attempts doubles
+	// from the base delay up to the max delay until then
+	pub login_max_failures: u32,
+	pub login_delay_base_secs: u64,
+	pub login_delay_max_secs: u64,
+	pub login_lockout_minutes: i64,
 }
 
 impl Config {
 	pub fn init() -> Self {
 		dotenv().ok();
 
+		Config::from_vars(|key| env::var(key))
+	}
+
+	// Settings for tests: defaults, with placeholders for the settings that have none
+	#[cfg(test)]
+	pub fn for_tests() -> Self {
+		Config::from_vars(|key| match key {
+			"DATABASE_URL" => Ok("postgres://localhost/unused".to_string()),
+			"JWT_SECRET" => Ok("test-secret".to_string()),
+			"ENCRYPTION_KEYS" => Ok(format!("test:{}", "00".repeat(32))),
+			_ => Err(env::VarError::NotPresent),
+		})
+	}
+
+	fn from_vars(var: impl Fn(&str) -> Result<String, env::VarError>) -> Self {
 		Config {
-			database_url: env::var("DATABASE_URL").expect("DATABASE_URL must be set"),
-			jwt_secret: env::var("JWT_SECRET").expect("JWT_SECRET must be set"),
-			server_port: env::var("SERVER_PORT")
+			database_url: var("DATABASE_URL").expect("DATABASE_URL must be set"),
+			jwt_secret: var("JWT_SECRET").expect("JWT_SECRET must be set"),
+			access_token_ttl_minutes: var("ACCESS_TOKEN_TTL_MINUTES")
+				.unwrap_or_else(|_| "15".to_string())
+				.parse()
+				.expect("ACCESS_TOKEN_TTL_MINUTES must be a valid number of minutes"),
+			refresh_token_ttl_days: var("REFRESH_TOKEN_TTL_DAYS")
+				.unwrap_or_else(|_| "30".to_string())
+				.parse()
+				.expect("REFRESH_TOKEN_TTL_DAYS must be a valid number of days"),
+			secure_cookies: var("SECURE_COOKIES")
+				.map(|value| value == "true" || value == "1")
+				.unwrap_or(true),
+			server_port: var("SERVER_PORT")
 				.unwrap_or_else(|_| "8081".to_string())
 				.parse()
 				.expect("SERVER_PORT must be a valid number"),
+			xml_max_document_size: var("XML_MAX_DOCUMENT_SIZE")
+				.unwrap_or_else(|_| "1073741824".to_string())
+				.parse()
+				.expect("XML_MAX_DOCUMENT_SIZE must be a valid number of bytes"),
+			xml_max_ad_size: var("XML_MAX_AD_SIZE")
+				.unwrap_or_else(|_| "1048576".to_string())
+				.parse()
+				.expect("XML_MAX_AD_SIZE must be a valid number of bytes"),
+			xml_import_batch_size: var("XML_IMPORT_BATCH_SIZE")
+				.unwrap_or_else(|_| "500".to_string())
+				.parse()
+				.expect("XML_IMPORT_BATCH_SIZE must be a valid number"),
+			feed_import_concurrency: var("FEED_IMPORT_CONCURRENCY")
+				.unwrap_or_else(|_| "2".to_string())
+				.parse()
+				.expect("FEED_IMPORT_CONCURRENCY must be a valid number"),
+			avito_catalog_cache_ttl_secs: var("AVITO_CATALOG_CACHE_TTL_SECS")
+				.unwrap_or_else(|_| "86400".to_string())
+				.parse()
+				.expect("AVITO_CATALOG_CACHE_TTL_SECS must be a valid number of seconds"),
+			avito_values_cache_ttl_secs: var("AVITO_VALUES_CACHE_TTL_SECS")
+				.unwrap_or_else(|_| "604800".to_string())
+				.parse()
+				.expect("AVITO_VALUES_CACHE_TTL_SECS must be a valid number of seconds"),
+			avito_catalog_fetch_concurrency: var("AVITO_CATALOG_FETCH_CONCURRENCY")
+				.unwrap_or_else(|_| "8".to_string())
+				.parse()
+				.expect("AVITO_CATALOG_FETCH_CONCURRENCY must be a valid number"),
+			autocatalog_url: var("AUTOCATALOG_URL")
+				.unwrap_or_else(|_| "https://autoload.avito.ru/format/Autocatalog.xml".to_string()),
+			avito_base_url: var("AVITO_BASE_URL")
+				.unwrap_or_else(|_| "https://api.avito.ru".to_string()),
+			avito_accept_invalid_certs: var("AVITO_ACCEPT_INVALID_CERTS")
+				.map(|value| value == "true" || value == "1")
+				.unwrap_or(false),
+			avito_max_retries: var("AVITO_MAX_RETRIES")
+				.unwrap_or_else(|_| "3".to_string())
+				.parse()
+				.expect("AVITO_MAX_RETRIES must be a valid number"),
+			avito_retry_base_delay_ms: var("AVITO_RETRY_BASE_DELAY_MS")
+				.unwrap_or_else(|_| "500".to_string())
+				.parse()
+				.expect("AVITO_RETRY_BASE_DELAY_MS must be a valid number of milliseconds"),
+			avito_retry_max_delay_ms: var("AVITO_RETRY_MAX_DELAY_MS")
+				.unwrap_or_else(|_| "30000".to_string())
+				.parse()
+				.expect("AVITO_RETRY_MAX_DELAY_MS must be a valid number of milliseconds"),
+			avito_rate_limit_per_sec: var("AVITO_RATE_LIMIT_PER_SEC")
+				.unwrap_or_else(|_| "5".to_string())
+				.parse()
+				.expect("AVITO_RATE_LIMIT_PER_SEC must be a valid number"),
+			avito_rate_limit_burst: var("AVITO_RATE_LIMIT_BURST")
+				.unwrap_or_else(|_| "10".to_string())
+				.parse()
+				.expect("AVITO_RATE_LIMIT_BURST must be a valid number"),
+			avito_circuit_failure_threshold: var("AVITO_CIRCUIT_FAILURE_THRESHOLD")
+				.unwrap_or_else(|_| "5".to_string())
+				.parse()
+				.expect("AVITO_CIRCUIT_FAILURE_THRESHOLD must be a valid number"),
+			avito_circuit_cooldown_secs: var("AVITO_CIRCUIT_COOLDOWN_SECS")
+				.unwrap_or_else(|_| "30".to_string())
+				.parse()
+				.expect("AVITO_CIRCUIT_COOLDOWN_SECS must be a valid number of seconds"),
+			avito_items_sync_interval_minutes: var("AVITO_ITEMS_SYNC_INTERVAL_MINUTES")
+				.unwrap_or_else(|_| "60".to_string())
+				.parse()
+				.expect("AVITO_ITEMS_SYNC_INTERVAL_MINUTES must be a valid number of minutes"),
+			avito_bulk_price_concurrency: var("AVITO_BULK_PRICE_CONCURRENCY")
+				.unwrap_or_else(|_| "4".to_string())
+				.parse()
+				.expect("AVITO_BULK_PRICE_CONCURRENCY must be a valid number"),
+			avito_item_stats_interval_minutes: var("AVITO_ITEM_STATS_INTERVAL_MINUTES")
+				.unwrap_or_else(|_| "360".to_string())
+				.parse()
+				.expect("AVITO_ITEM_STATS_INTERVAL_MINUTES must be a valid number of minutes"),
+			avito_item_stats_lookback_days: var("AVITO_ITEM_STATS_LOOKBACK_DAYS")
+				.unwrap_or_else(|_| "7".to_string())
+				.parse()
+				.expect("AVITO_ITEM_STATS_LOOKBACK_DAYS must be a valid number of days"),
+			avito_balance_poll_interval_minutes: var("AVITO_BALANCE_POLL_INTERVAL_MINUTES")
+				.unwrap_or_else(|_| "30".to_string())
+				.parse()
+				.expect("AVITO_BALANCE_POLL_INTERVAL_MINUTES must be a valid number of minutes"),
+			balance_alert_webhook_url: var("BALANCE_ALERT_WEBHOOK_URL")
+				.ok()
+				.filter(|url| !url.trim().is_empty()),
+			avito_account_check_interval_minutes: var("AVITO_ACCOUNT_CHECK_INTERVAL_MINUTES")
+				.unwrap_or_else(|_| "60".to_string())
+				.parse()
+				.expect("AVITO_ACCOUNT_CHECK_INTERVAL_MINUTES must be a valid number of minutes"),
+			encryption_keys: match var("ENCRYPTION_KEY_FILE") {
+				Ok(path) => std::fs::read_to_string(&path).unwrap_or_else(|e| {
+					panic!("Failed to read ENCRYPTION_KEY_FILE {}: {}", path, e)
+				}),
+				Err(_) => var("ENCRYPTION_KEYS")
+					.expect("ENCRYPTION_KEYS or ENCRYPTION_KEY_FILE must be set"),
+			},
+			encryption_active_key_id: var("ENCRYPTION_ACTIVE_KEY_ID")
+				.ok()
+				.filter(|key_id| !key_id.trim().is_empty()),
+			mail_transport: var("MAIL_TRANSPORT").unwrap_or_else(|_| "outbox".to_string()),
+			mail_outbox_dir: var("MAIL_OUTBOX_DIR").unwrap_or_else(|_| "mail_outbox".to_string()),
+			mail_from: var("MAIL_FROM").unwrap_or_else(|_| "no-reply@localhost".to_string()),
+			smtp_host: var("SMTP_HOST").ok().filter(|host| !host.trim().is_empty()),
+			smtp_port: var("SMTP_PORT")
+				.ok()
+				.map(|port| port.parse().expect("SMTP_PORT must be a valid port")),
+			smtp_username: var("SMTP_USERNAME").ok(),
+			smtp_password: var("SMTP_PASSWORD").ok(),
+			smtp_tls: var("SMTP_TLS").unwrap_or_else(|_| "starttls".to_string()),
+			app_url: var("APP_URL").unwrap_or_else(|_| "http://localhost:3000".to_string()),
+			email_verification_ttl_hours: var("EMAIL_VERIFICATION_TTL_HOURS")
+				.unwrap_or_else(|_| "24".to_string())
+				.parse()
+				.expect("EMAIL_VERIFICATION_TTL_HOURS must be a valid number of hours"),
+			password_reset_ttl_minutes: var("PASSWORD_RESET_TTL_MINUTES")
+				.unwrap_or_else(|_| "60".to_string())
+				.parse()
+				.expect("PASSWORD_RESET_TTL_MINUTES must be a valid number of minutes"),
+			require_verified_email: var("REQUIRE_VERIFIED_EMAIL")
+				.map(|value| value == "true" || value == "1")
+				.unwrap_or(false),
+			argon2_memory_kib: var("ARGON2_MEMORY_KIB")
+				.unwrap_or_else(|_| "19456".to_string())
+				.parse()
+				.expect("ARGON2_MEMORY_KIB must be a valid number of KiB"),
+			argon2_iterations: var("ARGON2_ITERATIONS")
+				.unwrap_or_else(|_| "2".to_string())
+				.parse()
+				.expect("ARGON2_ITERATIONS must be a valid number"),
+			argon2_parallelism: var("ARGON2_PARALLELISM")
+				.unwrap_or_else(|_| "1".to_string())
+				.parse()
+				.expect("ARGON2_PARALLELISM must be a valid number"),
+			password_min_length: var("PASSWORD_MIN_LENGTH")
+				.unwrap_or_else(|_| "8".to_string())
+				.parse()
+				.expect("PASSWORD_MIN_LENGTH must be a valid number"),
+			breached_passwords_file: var("BREACHED_PASSWORDS_FILE")
+				.ok()
+				.filter(|path| !path.trim().is_empty()),
+			rate_limit_auth_requests: var("RATE_LIMIT_AUTH_REQUESTS")
+				.unwrap_or_else(|_| "20".to_string())
+				.parse()
+				.expect("RATE_LIMIT_AUTH_REQUESTS must be a valid number"),
+			rate_limit_auth_window_secs: var("RATE_LIMIT_AUTH_WINDOW_SECS")
+				.unwrap_or_else(|_| "60".to_string())
+				.parse()
+				.expect("RATE_LIMIT_AUTH_WINDOW_SECS must be a valid number of seconds"),
+			rate_limit_api_requests: var("RATE_LIMIT_API_REQUESTS")
+				.unwrap_or_else(|_| "300".to_string())
+				.parse()
+				.expect("RATE_LIMIT_API_REQUESTS must be a valid number"),
+			rate_limit_api_window_secs: var("RATE_LIMIT_API_WINDOW_SECS")
+				.unwrap_or_else(|_| "60".to_string())
+				.parse()
+				.expect("RATE_LIMIT_API_WINDOW_SECS must be a valid number of seconds"),
+			trust_forwarded_for: var("TRUST_FORWARDED_FOR")
+				.map(|value| value == "true" || value == "1")
+				.unwrap_or(false),
+			login_max_failures: var("LOGIN_MAX_FAILURES")
+				.unwrap_or_else(|_| "5".to_string())
+				.parse()
+				.expect("LOGIN_MAX_FAILURES must be a valid number"),
+			login_delay_base_secs: var("LOGIN_DELAY_BASE_SECS")
+				.unwrap_or_else(|_| "1".to_string())
+				.parse()
+				.expect("LOGIN_DELAY_BASE_SECS must be a valid number of seconds"),
+			login_delay_max_secs: var("LOGIN_DELAY_MAX_SECS")
+				.unwrap_or_else(|_| "30".to_string())
+				.parse()
+				.expect("LOGIN_DELAY_MAX_SECS must be a valid number of seconds"),
+			login_lockout_minutes: var("LOGIN_LOCKOUT_MINUTES")
+				.unwrap_or_else(|_| "15".to_string())
+				.parse()
+				.expect("LOGIN_LOCKOUT_MINUTES must be a valid number of minutes"),
 		}
 	}
 }
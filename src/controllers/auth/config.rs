@@ -5,6 +5,10 @@ pub fn auth_routes(cfg: &mut web::ServiceConfig) {
 		.service(super::register::register)
 		.service(super::refresh::refresh_token)
 		.service(super::logout::logout)
+		.service(super::verify_email::request_email_verification)
+		.service(super::verify_email::confirm_email_verification)
+		.service(super::password_reset::request_password_reset)
+		.service(super::password_reset::confirm_password_reset)
 		.service(super::role::get_role)
 		.service(super::role::update_role);
 }
@@ -1,9 +1,16 @@
+use crate::controllers::auth::login_throttle::{
+	clear_login_failures, normalize_email, reserve_login_attempt, LoginReservation, LoginThrottle,
+};
+use crate::controllers::auth::sessions::{create_session, refresh_token_cookie};
+use crate::jwt_auth::generate_token;
+use crate::models::LoginAttempt;
+use crate::rate_limit::too_many_requests;
+use crate::utils::password::PasswordHashing;
 use crate::{
 	models::{AuthResponse, LoginRequest, User},
 	AppState,
 };
 use actix_web::{web, HttpResponse, Result};
-use bcrypt::verify;
 use diesel::prelude::*;
 use serde_json::json;
 
@@ -14,29 +21,43 @@ pub async fn login(
 ) -> Result<HttpResponse> {
 	let mut conn = data.db.get().unwrap();
 
+	// Checked and counted before the password, so a locked email gives nothing away
+	let throttle = LoginThrottle::from_config(&data.env);
+	let email = normalize_email(&body.email);
+	let attempt = match reserve_login_attempt(&mut conn, &throttle, &email) {
+		Ok(LoginReservation::Throttled(wait)) => {
+			return Ok(too_many_requests(
+				wait,
+				"Too many failed login attempts, please try again later",
+			));
+		}
+		Ok(LoginReservation::Reserved(attempt)) => Some(attempt),
+		Err(e) => {
+			log::error!("Failed to check login attempts of {}: {}", email, e);
+			None
+		}
+	};
+
 	let user: User = match crate::schema::users::table
 		.filter(crate::schema::users::email.eq(&body.email))
 		.first(&mut conn)
 	{
 		Ok(user) => user,
 		Err(_) => {
-			return Ok(HttpResponse::Unauthorized().json(json!({
-				"status": "fail",
-				"message": "Invalid email or password"
-			})));
+			// Unknown emails take as long as a wrong password, so they can't be told apart
+			data.password_hashing.verify_dummy(&body.password);
+			return Ok(login_failed(&email, attempt.as_ref()));
 		}
 	};
 
-	match verify(&body.password, &user.password) {
+	match data.password_hashing.verify(&body.password, &user.password) {
 		Ok(valid) => {
 			if !valid {
-				return Ok(HttpResponse::Unauthorized().json(json!({
-					"status": "fail",
-					"message": "Invalid email or password"
-				})));
+				return Ok(login_failed(&email, attempt.as_ref()));
 			}
 		}
-		Err(_) => {
+		Err(e) => {
+			log::error!("Failed to verify the password of user {}: {}", user.id, e);
 			return Ok(HttpResponse::InternalServerError().json(json!({
 				"status": "error",
 				"message": "Error while verifying password"
@@ -44,28 +65,100 @@ pub async fn login(
 		}
 	}
 
-	// Generate JWT token
-	let expiration = chrono::Utc::now()
-		.checked_add_signed(chrono::Duration::hours(24))
-		.unwrap()
-		.timestamp() as usize;
-	let iat = chrono::Utc::now().timestamp() as usize;
+	if let Err(e) = clear_login_failures(&mut conn, &email) {
+		log::error!("Failed to clear login attempts of {}: {}", email, e);
+	}
+
+	// The password is known only now, so older hashes are upgraded on login
+	if data.password_hashing.needs_rehash(&user.password) {
+		rehash_password(&mut conn, &data.password_hashing, &user, &body.password);
+	}
+
+	if data.env.require_verified_email && user.verified != Some(true) {
+		return Ok(HttpResponse::Forbidden().json(json!({
+			"status": "fail",
+			"message": "Please verify your email before logging in"
+		})));
+	}
 
-	let claims = crate::models::TokenClaims {
-		sub: user.id.to_string(),
-		exp: expiration,
-		iat,
+	// Start a session and issue its tokens
+	let session = match create_session(&mut conn, user.id, &data.env) {
+		Ok(session) => session,
+		Err(e) => {
+			eprintln!("Database error when creating a session: {}", e);
+			return Ok(HttpResponse::InternalServerError().json(json!({
+				"status": "error",
+				"message": "Failed to create a session"
+			})));
+		}
 	};
 
-	let token = jsonwebtoken::encode(
-		&jsonwebtoken::Header::default(),
-		&claims,
-		&jsonwebtoken::EncodingKey::from_secret(data.env.jwt_secret.as_ref()),
-	)
-	.unwrap();
+	let token = match generate_token(user.id, session.session_id, &data.env) {
+		Ok(token) => token,
+		Err(_) => {
+			return Ok(HttpResponse::InternalServerError().json(json!({
+				"status": "error",
+				"message": "Failed to generate token"
+			})));
+		}
+	};
+
+	Ok(HttpResponse::Ok()
+		.cookie(refresh_token_cookie(&session.refresh_token, &data.env))
+		.json(AuthResponse {
+			status: "success".to_string(),
+			token,
+		}))
+}
+
+// The failure was already counted when the attempt was reserved
+fn login_failed(email: &str, attempt: Option<&LoginAttempt>) -> HttpResponse {
+	if let Some(attempt) = attempt.filter(|attempt| attempt.locked_until.is_some()) {
+		log::warn!(
+			"Login of {} locked after {} failed attempts",
+			email,
+			attempt.failed_count
+		);
+	}
 
-	Ok(HttpResponse::Ok().json(AuthResponse {
-		status: "success".to_string(),
-		token,
+	HttpResponse::Unauthorized().json(json!({
+		"status": "fail",
+		"message": "Invalid email or password"
 	}))
 }
+
+// Replace the stored hash with an Argon2id one made with the current parameters. Failures
+// are only logged: the old hash keeps working and is upgraded on a later login.
+fn rehash_password(
+	conn: &mut PgConnection,
+	password_hashing: &PasswordHashing,
+	user: &User,
+	password: &str,
+) {
+	let hashed_password = match password_hashing.hash(password) {
+		Ok(hashed) => hashed,
+		Err(e) => {
+			log::error!("Failed to rehash the password of user {}: {}", user.id, e);
+			return;
+		}
+	};
+
+	// Left alone if the password changed since it was read
+	let updated = diesel::update(
+		crate::schema::users::table
+			.find(user.id)
+			.filter(crate::schema::users::password.eq(&user.password)),
+	)
+	.set(crate::schema::users::password.eq(hashed_password))
+	.execute(conn);
+
+	match updated {
+		Ok(1) => log::info!("Password hash of user {} upgraded to Argon2id", user.id),
+		Ok(_) => {}
+		Err(e) => log::error!(
+			"Failed to store the rehashed password of user {}: {}",
+			user.id,
+			e
+		),
+	}
+}
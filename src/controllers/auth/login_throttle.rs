@@ -0,0 +1,258 @@
+use crate::config::Config;
+use crate::models::LoginAttempt;
+use crate::schema::login_attempts;
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+
+// Brute-force protection of login, per email: after each failure the next attempt has to
+// wait twice as long as before, and `max_failures` failures lock the email for a while.
+#[derive(Debug, Clone)]
+pub struct LoginThrottle {
+	pub max_failures: u32,
+	pub base_delay: Duration,
+	pub max_delay: Duration,
+	pub lockout: Duration,
+}
+
+impl LoginThrottle {
+	pub fn from_config(config: &Config) -> Self {
+		Self {
+			max_failures: config.login_max_failures,
+			base_delay: Duration::seconds(config.login_delay_base_secs as i64),
+			max_delay: Duration::seconds(config.login_delay_max_secs as i64),
+			lockout: Duration::minutes(config.login_lockout_minutes),
+		}
+	}
+
+	fn delay(&self, failed_count: i32) -> Duration {
+		if failed_count <= 0 {
+			return Duration::zero();
+		}
+		let doublings = (failed_count - 1).min(30) as u32;
+		(self.base_delay * 2i32.pow(doublings)).min(self.max_delay)
+	}
+
+	// Failures from before an expired lock or older than the lockout don't count anymore
+	fn is_stale(&self, attempt: &LoginAttempt, now: DateTime<Utc>) -> bool {
+		attempt.locked_until.is_some_and(|until| until <= now)
+			|| attempt.last_failed_ts + self.lockout <= now
+	}
+
+	// How long the email has to wait before it may try again, if at all
+	pub fn retry_after(&self, attempt: &LoginAttempt, now: DateTime<Utc>) -> Option<Duration> {
+		if let Some(until) = attempt.locked_until.filter(|until| *until > now) {
+			return Some(until - now);
+		}
+		if self.is_stale(attempt, now) {
+			return None;
+		}
+		let allowed_at = attempt.last_failed_ts + self.delay(attempt.failed_count);
+		(allowed_at > now).then(|| allowed_at - now)
+	}
+
+	// Let an attempt through only if the email doesn't have to wait, counting it as failed
+	// up front
+	pub fn reserve(&self, attempt: &LoginAttempt, now: DateTime<Utc>) -> LoginReservation {
+		match self.retry_after(attempt, now) {
+			Some(wait) => LoginReservation::Throttled(wait),
+			None => LoginReservation::Reserved(self.record_failure(attempt, now)),
+		}
+	}
+
+	pub fn record_failure(&self, previous: &LoginAttempt, now: DateTime<Utc>) -> LoginAttempt {
+		let failed_count = if self.is_stale(previous, now) {
+			1
+		} else {
+			previous.failed_count + 1
+		};
+		let locked = self.max_failures > 0 && failed_count >= self.max_failures as i32;
+
+		LoginAttempt {
+			email: previous.email.clone(),
+			failed_count,
+			last_failed_ts: now,
+			locked_until: locked.then(|| now + self.lockout),
+		}
+	}
+}
+
+#[derive(Debug)]
+pub enum LoginReservation {
+	// The attempt may go ahead; it already counts as failed until the login succeeds
+	Reserved(LoginAttempt),
+	// The email has to wait this long before trying again
+	Throttled(Duration),
+}
+
+// Attempts are tracked by the email trimmed and lowercased, whether or not an account has
+// it, so the responses don't tell which emails are registered
+pub fn normalize_email(email: &str) -> String {
+	email.trim().to_lowercase()
+}
+
+// Check and count an attempt before the password is verified, with the row locked, so
+// concurrent attempts, from any instance, each see the ones before them and can't all get
+// through at once. A successful login clears the count again.
+pub fn reserve_login_attempt(
+	conn: &mut PgConnection,
+	throttle: &LoginThrottle,
+	email: &str,
+) -> QueryResult<LoginReservation> {
+	conn.transaction(|conn| {
+		let now = Utc::now();
+		diesel::insert_into(login_attempts::table)
+			.values(&LoginAttempt {
+				email: email.to_string(),
+				failed_count: 0,
+				last_failed_ts: now,
+				locked_until: None,
+			})
+			.on_conflict_do_nothing()
+			.execute(conn)?;
+
+		let previous = login_attempts::table
+			.find(email)
+			.for_update()
+			.first::<LoginAttempt>(conn)?;
+		let reservation = throttle.reserve(&previous, now);
+
+		if let LoginReservation::Reserved(attempt) = &reservation {
+			diesel::update(login_attempts::table.find(email))
+				.set(attempt)
+				.execute(conn)?;
+		}
+		Ok(reservation)
+	})
+}
+
+pub fn clear_login_failures(conn: &mut PgConnection, email: &str) -> QueryResult<usize> {
+	diesel::delete(login_attempts::table.find(email)).execute(conn)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn throttle() -> LoginThrottle {
+		LoginThrottle {
+			max_failures: 5,
+			base_delay: Duration::seconds(1),
+			max_delay: Duration::seconds(4),
+			lockout: Duration::minutes(15),
+		}
+	}
+
+	fn fresh(now: DateTime<Utc>) -> LoginAttempt {
+		LoginAttempt {
+			email: "user@example.com".to_string(),
+			failed_count: 0,
+			last_failed_ts: now,
+			locked_until: None,
+		}
+	}
+
+	#[test]
+	fn test_delays_double_until_lockout() {
+		let throttle = throttle();
+		let mut now = Utc::now();
+		let mut attempt = fresh(now);
+		assert_eq!(throttle.retry_after(&attempt, now), None);
+
+		for (failures, delay) in [(1, 1), (2, 2), (3, 4), (4, 4)] {
+			attempt = throttle.record_failure(&attempt, now);
+			assert_eq!(attempt.failed_count, failures);
+			assert_eq!(attempt.locked_until, None);
+			assert_eq!(
+				throttle.retry_after(&attempt, now),
+				Some(Duration::seconds(delay))
+			);
+
+			// Once the delay passed the next attempt is let through
+			now += Duration::seconds(delay);
+			assert_eq!(throttle.retry_after(&attempt, now), None);
+		}
+
+		attempt = throttle.record_failure(&attempt, now);
+		assert_eq!(attempt.failed_count, 5);
+		assert_eq!(attempt.locked_until, Some(now + Duration::minutes(15)));
+		assert_eq!(
+			throttle.retry_after(&attempt, now + Duration::minutes(5)),
+			Some(Duration::minutes(10))
+		);
+	}
+
+	#[test]
+	fn test_failures_expire() {
+		let throttle = throttle();
+		let now = Utc::now();
+
+		// An expired lock starts the count over
+		let locked = LoginAttempt {
+			failed_count: 5,
+			locked_until: Some(now - Duration::seconds(1)),
+			..fresh(now - Duration::minutes(15))
+		};
+		assert_eq!(throttle.retry_after(&locked, now), None);
+		assert_eq!(throttle.record_failure(&locked, now).failed_count, 1);
+
+		// So do failures older than the lockout
+		let old = LoginAttempt {
+			failed_count: 4,
+			..fresh(now - Duration::minutes(20))
+		};
+		assert_eq!(throttle.retry_after(&old, now), None);
+		let attempt = throttle.record_failure(&old, now);
+		assert_eq!(attempt.failed_count, 1);
+		assert_eq!(attempt.locked_until, None);
+	}
+
+	#[test]
+	fn test_lockout_can_be_turned_off() {
+		let throttle = LoginThrottle {
+			max_failures: 0,
+			..throttle()
+		};
+		let now = Utc::now();
+		let attempt = throttle.record_failure(
+			&LoginAttempt {
+				failed_count: 50,
+				..fresh(now)
+			},
+			now,
+		);
+		assert_eq!(attempt.locked_until, None);
+		assert_eq!(
+			throttle.retry_after(&attempt, now),
+			Some(Duration::seconds(4))
+		);
+	}
+
+	#[test]
+	fn test_reserved_attempts_count_at_once() {
+		let throttle = throttle();
+		let now = Utc::now();
+
+		let LoginReservation::Reserved(first) = throttle.reserve(&fresh(now), now) else {
+			panic!("The first attempt must go through");
+		};
+		assert_eq!(first.failed_count, 1);
+
+		// A concurrent guess that comes after the first one was reserved has to wait
+		assert!(matches!(
+			throttle.reserve(&first, now),
+			LoginReservation::Throttled(wait) if wait == Duration::seconds(1)
+		));
+		assert!(matches!(
+			throttle.reserve(&first, now + Duration::seconds(1)),
+			LoginReservation::Reserved(LoginAttempt {
+				failed_count: 2,
+				..
+			})
+		));
+	}
+
+	#[test]
+	fn test_normalize_email() {
+		assert_eq!(normalize_email(" User@Example.COM "), "user@example.com");
+	}
+}
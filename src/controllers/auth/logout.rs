@@ -1,10 +1,38 @@
-use actix_web::{HttpResponse, Result};
+use crate::controllers::auth::sessions::{
+	expired_refresh_token_cookie, revoke_session, revoke_session_by_token, REFRESH_TOKEN_COOKIE,
+};
+use crate::jwt_auth::JwtMiddleware;
+use crate::AppState;
+use actix_web::{web, HttpRequest, HttpResponse, Result};
 use serde_json::json;
 
+// Revoke the session of the access token, or of the refresh token when the access token
+// has already expired, so neither of them is accepted anymore
 #[actix_web::post("/auth/logout")]
-pub async fn logout() -> Result<HttpResponse> {
-	Ok(HttpResponse::Ok().json(json!({
-		"status": "success",
-		"message": "Logged out successfully"
-	})))
+pub async fn logout(
+	req: HttpRequest,
+	user: Option<JwtMiddleware>,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+	let mut conn = data.db.get().unwrap();
+
+	let revoked = match (user, req.cookie(REFRESH_TOKEN_COOKIE)) {
+		(Some(user), _) => revoke_session(&mut conn, user.session_id, "logout"),
+		(None, Some(cookie)) => revoke_session_by_token(&mut conn, cookie.value(), "logout"),
+		(None, None) => Ok(0),
+	};
+	if let Err(e) = revoked {
+		eprintln!("Database error when revoking a session: {}", e);
+		return Ok(HttpResponse::InternalServerError().json(json!({
+			"status": "error",
+			"message": "Failed to log out"
+		})));
+	}
+
+	Ok(HttpResponse::Ok()
+		.cookie(expired_refresh_token_cookie(&data.env))
+		.json(json!({
+			"status": "success",
+			"message": "Logged out successfully"
+		})))
 }
@@ -1,9 +1,14 @@
 pub mod config;
 pub mod login;
+pub mod login_throttle;
 pub mod logout;
+pub mod password_reset;
 pub mod refresh;
 pub mod register;
 pub mod role;
+pub mod sessions;
+pub mod user_tokens;
+pub mod verify_email;
 
 use actix_web::web;
 
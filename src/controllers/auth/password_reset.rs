@@ -0,0 +1,149 @@
+use crate::controllers::auth::sessions::revoke_user_sessions;
+use crate::controllers::auth::user_tokens::{consume_user_token, issue_user_token, TokenPurpose};
+use crate::utils::mailer::Email;
+use crate::{
+	models::{EmailRequest, ResetPasswordRequest, User},
+	AppState,
+};
+use actix_web::{web, HttpResponse, Result};
+use diesel::prelude::*;
+use serde_json::json;
+
+// Always answers the same and at once, so it can't be used to find out which emails are
+// registered: the lookup and the mail happen after the response
+#[actix_web::post("/auth/password_reset/request")]
+pub async fn request_password_reset(
+	body: web::Json<EmailRequest>,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+	let email = body.into_inner().email;
+	actix_web::rt::spawn(async move { mail_password_reset_link(&data, &email).await });
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"message": "If the email belongs to an account, a password reset link has been sent"
+	})))
+}
+
+async fn mail_password_reset_link(data: &AppState, email: &str) {
+	let user = data
+		.db
+		.get()
+		.map_err(|e| e.to_string())
+		.and_then(|mut conn| {
+			crate::schema::users::table
+				.filter(crate::schema::users::email.eq(email))
+				.first::<User>(&mut conn)
+				.optional()
+				.map(|user| user.map(|user| (conn, user)))
+				.map_err(|e| e.to_string())
+		});
+
+	let (mut conn, user) = match user {
+		Ok(Some(found)) => found,
+		Ok(None) => return,
+		Err(e) => {
+			log::error!(
+				"Database error when looking up a user to reset the password of: {}",
+				e
+			);
+			return;
+		}
+	};
+
+	let sent = match issue_user_token(&mut conn, user.id, TokenPurpose::ResetPassword, &data.env) {
+		Ok(token) => {
+			let link = format!(
+				"{}/reset-password?token={}",
+				data.env.app_url.trim_end_matches('/'),
+				token
+			);
+			data.mailer
+				.send(&Email {
+					to: user.email.clone(),
+					subject: "Reset your password".to_string(),
+					body: format!(
+						"Open the link below to set a new password:\n\n{}\n\nThe link is valid for {} minutes. If you did not ask to reset your password, ignore this email.\n",
+						link, data.env.password_reset_ttl_minutes
+					),
+				})
+				.await
+				.map_err(|e| e.to_string())
+		}
+		Err(e) => Err(format!("Failed to issue a password reset token: {}", e)),
+	};
+	if let Err(e) = sent {
+		log::error!(
+			"Failed to send the password reset email to user {}: {}",
+			user.id,
+			e
+		);
+	}
+}
+
+#[actix_web::post("/auth/password_reset/confirm")]
+pub async fn confirm_password_reset(
+	body: web::Json<ResetPasswordRequest>,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+	// Checked before the token is used up, so a typo doesn't cost the user the link
+	if let Err(message) = data
+		.password_policy
+		.check(&body.password, &body.password_confirm)
+	{
+		return Ok(HttpResponse::BadRequest().json(json!({
+			"status": "fail",
+			"message": message
+		})));
+	}
+
+	let hashed_password = match data.password_hashing.hash(&body.password) {
+		Ok(hashed) => hashed,
+		Err(e) => {
+			log::error!("Failed to hash a password: {}", e);
+			return Ok(HttpResponse::InternalServerError().json(json!({
+				"status": "error",
+				"message": "Error while hashing password"
+			})));
+		}
+	};
+
+	let mut conn = data.db.get().unwrap();
+
+	let result = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+		let Some(user_id) = consume_user_token(conn, &body.token, TokenPurpose::ResetPassword)?
+		else {
+			return Ok(None);
+		};
+
+		// The link was opened from the user's mailbox, which proves the email too
+		diesel::update(crate::schema::users::table.find(user_id))
+			.set((
+				crate::schema::users::password.eq(&hashed_password),
+				crate::schema::users::verified.eq(Some(true)),
+				crate::schema::users::updated_at.eq(Some(chrono::Utc::now().naive_utc())),
+			))
+			.execute(conn)?;
+		// Whoever knew the old password is logged out
+		revoke_user_sessions(conn, user_id, "password_reset")?;
+		Ok(Some(user_id))
+	});
+
+	match result {
+		Ok(Some(user_id)) => {
+			log::info!("Password of user {} was reset", user_id);
+			Ok(HttpResponse::Ok().json(json!({
+				"status": "success",
+				"message": "Password has been reset, please log in"
+			})))
+		}
+		Ok(None) => Ok(HttpResponse::BadRequest().json(json!({
+			"status": "fail",
+			"message": "Invalid or expired password reset token"
+		}))),
+		Err(e) => Ok(HttpResponse::InternalServerError().json(json!({
+			"status": "error",
+			"message": format!("Failed to reset password: {}", e)
+		}))),
+	}
+}
@@ -1,14 +1,44 @@
-use crate::jwt_auth::JwtMiddleware;
+use crate::controllers::auth::sessions::{
+	expired_refresh_token_cookie, refresh_token_cookie, rotate_session, RefreshError,
+	REFRESH_TOKEN_COOKIE,
+};
+use crate::jwt_auth::generate_token;
 use crate::{models::AuthResponse, AppState};
-use actix_web::{web, HttpResponse, Result};
+use actix_web::{web, HttpRequest, HttpResponse, Result};
 use serde_json::json;
 
+// Exchange the refresh token cookie for a new access token; the refresh token is rotated
 #[actix_web::post("/auth/refresh")]
-pub async fn refresh_token(user: JwtMiddleware, data: web::Data<AppState>) -> Result<HttpResponse> {
-	// In a real application, you would validate the refresh token here
-	// For now, we'll just return a new access token
+pub async fn refresh_token(req: HttpRequest, data: web::Data<AppState>) -> Result<HttpResponse> {
+	let Some(refresh_token) = req.cookie(REFRESH_TOKEN_COOKIE) else {
+		return Ok(HttpResponse::Unauthorized().json(json!({
+			"status": "fail",
+			"message": "Refresh token is missing, please log in"
+		})));
+	};
+
+	let mut conn = data.db.get().unwrap();
+
+	let (session, issued) = match rotate_session(&mut conn, refresh_token.value()) {
+		Ok(rotated) => rotated,
+		Err(RefreshError::Database(e)) => {
+			eprintln!("Database error when refreshing a session: {}", e);
+			return Ok(HttpResponse::InternalServerError().json(json!({
+				"status": "error",
+				"message": RefreshError::Database(e).message()
+			})));
+		}
+		Err(e) => {
+			return Ok(HttpResponse::Unauthorized()
+				.cookie(expired_refresh_token_cookie(&data.env))
+				.json(json!({
+					"status": "fail",
+					"message": e.message()
+				})));
+		}
+	};
 
-	let token = match crate::jwt_auth::generate_token(user.user_id, &data.env.jwt_secret) {
+	let token = match generate_token(session.user_id, session.session_id, &data.env) {
 		Ok(token) => token,
 		Err(_) => {
 			return Ok(HttpResponse::InternalServerError().json(json!({
@@ -18,7 +48,12 @@ pub async fn refresh_token(user: JwtMiddleware, data: web::Data<AppState>) -> Re
 		}
 	};
 
-	Ok(HttpResponse::Ok().json(AuthResponse {
+	let mut response = HttpResponse::Ok();
+	// No new token when a concurrent refresh already rotated it; that response sets the cookie
+	if let Some(issued) = issued {
+		response.cookie(refresh_token_cookie(&issued.refresh_token, &data.env));
+	}
+	Ok(response.json(AuthResponse {
 		status: "success".to_string(),
 		token,
 	}))
@@ -1,9 +1,11 @@
+use crate::controllers::auth::sessions::{create_session, refresh_token_cookie};
+use crate::controllers::auth::verify_email::send_verification_email;
+use crate::jwt_auth::generate_token;
 use crate::{
 	models::{AuthResponse, RegisterRequest, User},
 	AppState,
 };
 use actix_web::{web, HttpResponse, Result};
-use bcrypt::DEFAULT_COST;
 use diesel::prelude::*;
 use diesel::QueryResult;
 use serde_json::json;
@@ -27,18 +29,18 @@ pub async fn register(
 		})));
 	}
 
-	// Verify passwords match
-	if body.password != body.password_confirm {
+	if let Err(message) = body.validate(&data.password_policy) {
 		return Ok(HttpResponse::BadRequest().json(json!({
 			"status": "fail",
-			"message": "Passwords do not match"
+			"message": message
 		})));
 	}
 
 	// Hash the password
-	let hashed_password = match bcrypt::hash(&body.password, DEFAULT_COST) {
+	let hashed_password = match data.password_hashing.hash(&body.password) {
 		Ok(hashed) => hashed,
-		Err(_) => {
+		Err(e) => {
+			log::error!("Failed to hash a password: {}", e);
 			return Ok(HttpResponse::InternalServerError().json(json!({
 				"status": "error",
 				"message": "Error while hashing password"
@@ -64,28 +66,50 @@ pub async fn register(
 		.get_result(&mut conn)
 		.expect("Error saving new user");
 
-	// Generate JWT token
-	let expiration = chrono::Utc::now()
-		.checked_add_signed(chrono::Duration::hours(24))
-		.unwrap()
-		.timestamp() as usize;
-	let iat = chrono::Utc::now().timestamp() as usize;
+	if let Err(e) =
+		send_verification_email(&mut conn, data.mailer.as_ref(), &data.env, &created_user).await
+	{
+		log::error!(
+			"Failed to send the verification email to user {}: {}",
+			created_user.id,
+			e
+		);
+	}
+
+	// Without a verified email the user can't log in yet, so no session is started
+	if data.env.require_verified_email {
+		return Ok(HttpResponse::Created().json(json!({
+			"status": "success",
+			"message": "Account created, please check your email to verify it"
+		})));
+	}
 
-	let claims = crate::models::TokenClaims {
-		sub: created_user.id.to_string(),
-		exp: expiration,
-		iat,
+	// Start a session and issue its tokens
+	let session = match create_session(&mut conn, created_user.id, &data.env) {
+		Ok(session) => session,
+		Err(e) => {
+			eprintln!("Database error when creating a session: {}", e);
+			return Ok(HttpResponse::InternalServerError().json(json!({
+				"status": "error",
+				"message": "Failed to create a session"
+			})));
+		}
 	};
 
-	let token = jsonwebtoken::encode(
-		&jsonwebtoken::Header::default(),
-		&claims,
-		&jsonwebtoken::EncodingKey::from_secret(data.env.jwt_secret.as_ref()),
-	)
-	.unwrap();
+	let token = match generate_token(created_user.id, session.session_id, &data.env) {
+		Ok(token) => token,
+		Err(_) => {
+			return Ok(HttpResponse::InternalServerError().json(json!({
+				"status": "error",
+				"message": "Failed to generate token"
+			})));
+		}
+	};
 
-	Ok(HttpResponse::Created().json(AuthResponse {
-		status: "success".to_string(),
-		token,
-	}))
+	Ok(HttpResponse::Created()
+		.cookie(refresh_token_cookie(&session.refresh_token, &data.env))
+		.json(AuthResponse {
+			status: "success".to_string(),
+			token,
+		}))
 }
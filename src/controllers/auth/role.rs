@@ -1,11 +1,20 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission, Role};
 use crate::{models::User, AppState};
 use actix_web::{web, HttpResponse, Result};
 use diesel::prelude::*;
+use serde::Deserialize;
 use serde_json::json;
+use uuid::Uuid;
 
-// Example role management endpoints
-#[actix_web::get("/auth/role")]
+#[derive(Deserialize)]
+pub struct UpdateRoleRequest {
+	pub user_id: Uuid,
+	pub role: String,
+}
+
+// Role of the current user with the permissions it grants
+#[actix_web::get("/auth/role", wrap = "RequirePermission(Permission::ReadProfile)")]
 pub async fn get_role(user: JwtMiddleware, data: web::Data<AppState>) -> Result<HttpResponse> {
 	let mut conn = data.db.get().unwrap();
 
@@ -13,12 +22,19 @@ pub async fn get_role(user: JwtMiddleware, data: web::Data<AppState>) -> Result<
 		.find(user.user_id)
 		.first::<User>(&mut conn)
 	{
-		Ok(user) => Ok(HttpResponse::Ok().json(json!({
-			"status": "success",
-			"data": {
-				"role": user.role
-			}
-		}))),
+		Ok(user) => {
+			let permissions = Role::from_db(user.role.as_deref())
+				.map(|role| role.permissions())
+				.unwrap_or_default();
+
+			Ok(HttpResponse::Ok().json(json!({
+				"status": "success",
+				"data": {
+					"role": user.role,
+					"permissions": permissions
+				}
+			})))
+		}
 		Err(_) => Ok(HttpResponse::NotFound().json(json!({
 			"status": "fail",
 			"message": "User not found"
@@ -26,49 +42,53 @@ pub async fn get_role(user: JwtMiddleware, data: web::Data<AppState>) -> Result<
 	}
 }
 
-#[actix_web::post("/auth/role")]
+// Set the role of any user (admin only)
+#[actix_web::post("/auth/role", wrap = "RequirePermission(Permission::ManageRoles)")]
 pub async fn update_role(
 	user: JwtMiddleware,
-	body: web::Json<serde_json::Value>,
+	body: web::Json<UpdateRoleRequest>,
 	data: web::Data<AppState>,
 ) -> Result<HttpResponse> {
-	let mut conn = data.db.get().unwrap();
-
-	// Check if user exists
-	match crate::schema::users::table
-		.find(user.user_id)
-		.first::<User>(&mut conn)
-	{
-		Ok(_) => {}
-		Err(_) => {
-			return Ok(HttpResponse::NotFound().json(json!({
-				"status": "fail",
-				"message": "User not found"
-			})));
-		}
-	}
-
-	// Extract role from request body
-	let new_role = match body.get("role") {
-		Some(role_value) => role_value.as_str().unwrap_or("user").to_string(),
-		None => {
-			return Ok(HttpResponse::BadRequest().json(json!({
-				"status": "fail",
-				"message": "Role is required"
-			})));
-		}
+	let Some(new_role) = Role::parse(&body.role) else {
+		let roles: Vec<&str> = Role::ALL.iter().map(Role::as_str).collect();
+		return Ok(HttpResponse::BadRequest().json(json!({
+			"status": "fail",
+			"message": format!("Unknown role, expected one of: {}", roles.join(", "))
+		})));
 	};
 
-	// Update user role
-	let updated_user: User = diesel::update(crate::schema::users::table.find(user.user_id))
-		.set(crate::schema::users::role.eq(new_role))
-		.get_result(&mut conn)
-		.expect("Error updating user role");
+	let mut conn = data.db.get().unwrap();
+
+	let updated_user = diesel::update(crate::schema::users::table.find(body.user_id))
+		.set((
+			crate::schema::users::role.eq(new_role.as_str()),
+			crate::schema::users::updated_at.eq(Some(chrono::Utc::now().naive_utc())),
+		))
+		.get_result::<User>(&mut conn)
+		.optional();
 
-	Ok(HttpResponse::Ok().json(json!({
-		"status": "success",
-		"data": {
-			"user": updated_user
+	match updated_user {
+		Ok(Some(updated_user)) => {
+			log::info!(
+				"User {} set the role of user {} to '{}'",
+				user.user_id,
+				updated_user.id,
+				new_role.as_str()
+			);
+			Ok(HttpResponse::Ok().json(json!({
+				"status": "success",
+				"data": {
+					"user": updated_user
+				}
+			})))
 		}
-	})))
+		Ok(None) => Ok(HttpResponse::NotFound().json(json!({
+			"status": "fail",
+			"message": "User not found"
+		}))),
+		Err(e) => Ok(HttpResponse::InternalServerError().json(json!({
+			"status": "error",
+			"message": format!("Error updating user role: {}", e)
+		}))),
+	}
 }
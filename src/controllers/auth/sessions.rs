@@ -0,0 +1,341 @@
+use crate::config::Config;
+use crate::models::{CreateUserSession, UserSession};
+use crate::schema::user_sessions;
+use actix_web::cookie::{time::Duration as CookieDuration, Cookie, SameSite};
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+pub const REFRESH_TOKEN_COOKIE: &str = "refresh_token";
+// The refresh token is only sent to the auth endpoints
+const REFRESH_TOKEN_COOKIE_PATH: &str = "/api/auth";
+// Two refreshes sent at once both present the same token; the one that loses the race still
+// gets an access token if it comes this soon after the other
+const REFRESH_GRACE_SECS: i64 = 10;
+
+pub struct IssuedSession {
+	pub session_id: Uuid,
+	// `{session_id}.{secret}`; handed to the client once, only the secret's hash is stored
+	pub refresh_token: String,
+}
+
+#[derive(Debug)]
+pub enum RefreshError {
+	Invalid,
+	Expired,
+	Revoked,
+	// An already rotated token was presented, so it leaked; the session is revoked
+	Reused,
+	Database(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for RefreshError {
+	fn from(error: diesel::result::Error) -> Self {
+		RefreshError::Database(error)
+	}
+}
+
+impl RefreshError {
+	pub fn message(&self) -> &'static str {
+		match self {
+			RefreshError::Invalid => "Invalid refresh token",
+			RefreshError::Expired => "Session has expired, please log in",
+			RefreshError::Revoked => "Session has been revoked, please log in",
+			RefreshError::Reused => "Refresh token reuse detected, please log in",
+			RefreshError::Database(_) => "Failed to refresh the session",
+		}
+	}
+}
+
+pub fn hash_refresh_secret(secret: &str) -> String {
+	hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+fn generate_refresh_secret() -> String {
+	let mut secret = [0u8; 32];
+	OsRng.fill_bytes(&mut secret);
+	hex::encode(secret)
+}
+
+pub fn parse_refresh_token(token: &str) -> Option<(Uuid, &str)> {
+	let (session_id, secret) = token.split_once('.')?;
+	let session_id = Uuid::parse_str(session_id).ok()?;
+	(!secret.is_empty()).then_some((session_id, secret))
+}
+
+// Start a session for a login; it lives for the refresh token lifetime from config
+pub fn create_session(
+	conn: &mut PgConnection,
+	user_id: Uuid,
+	config: &Config,
+) -> QueryResult<IssuedSession> {
+	let secret = generate_refresh_secret();
+	let session = diesel::insert_into(user_sessions::table)
+		.values(&CreateUserSession {
+			user_id,
+			refresh_token_hash: hash_refresh_secret(&secret),
+			expires_ts: Utc::now() + Duration::days(config.refresh_token_ttl_days),
+		})
+		.get_result::<UserSession>(conn)?;
+
+	Ok(IssuedSession {
+		session_id: session.session_id,
+		refresh_token: format!("{}.{}", session.session_id, secret),
+	})
+}
+
+// Which token of the session was presented
+#[derive(Debug, PartialEq)]
+pub enum PresentedToken {
+	Current,
+	// The token a concurrent refresh just replaced
+	Previous,
+}
+
+// Match a presented secret against the session. The previous token is only accepted within
+// the grace period after its refresh; any other token was rotated before and leaked.
+pub fn check_refresh_secret(
+	session: &UserSession,
+	secret: &str,
+	now: chrono::DateTime<Utc>,
+) -> Result<PresentedToken, RefreshError> {
+	if session.revoked_ts.is_some() {
+		return Err(RefreshError::Revoked);
+	}
+	if session.expires_ts <= now {
+		return Err(RefreshError::Expired);
+	}
+
+	let hash = hash_refresh_secret(secret);
+	if session.refresh_token_hash == hash {
+		return Ok(PresentedToken::Current);
+	}
+	let in_grace = session
+		.refreshed_ts
+		.is_some_and(|refreshed_ts| now - refreshed_ts <= Duration::seconds(REFRESH_GRACE_SECS));
+	if in_grace && session.previous_token_hash.as_deref() == Some(hash.as_str()) {
+		return Ok(PresentedToken::Previous);
+	}
+
+	Err(RefreshError::Reused)
+}
+
+// Swap the presented refresh token for a new one. A token that matches the session but not
+// its current secret was rotated before, so whoever holds the session is not trusted anymore.
+// The exception is the token replaced moments ago by a concurrent refresh: the session is
+// returned without a new token, the client keeps the one that refresh hands out.
+pub fn rotate_session(
+	conn: &mut PgConnection,
+	refresh_token: &str,
+) -> Result<(UserSession, Option<IssuedSession>), RefreshError> {
+	let (session_id, secret) = parse_refresh_token(refresh_token).ok_or(RefreshError::Invalid)?;
+
+	let result = conn.transaction::<_, RefreshError, _>(|conn| {
+		let session = user_sessions::table
+			.find(session_id)
+			.for_update()
+			.first::<UserSession>(conn)
+			.optional()?
+			.ok_or(RefreshError::Invalid)?;
+
+		if check_refresh_secret(&session, secret, Utc::now())? == PresentedToken::Previous {
+			return Ok((session, None));
+		}
+
+		let new_secret = generate_refresh_secret();
+		let session = diesel::update(user_sessions::table.find(session_id))
+			.set((
+				user_sessions::refresh_token_hash.eq(hash_refresh_secret(&new_secret)),
+				user_sessions::previous_token_hash.eq(&session.refresh_token_hash),
+				user_sessions::refreshed_ts.eq(Utc::now()),
+			))
+			.get_result::<UserSession>(conn)?;
+
+		Ok((
+			session,
+			Some(IssuedSession {
+				session_id,
+				refresh_token: format!("{}.{}", session_id, new_secret),
+			}),
+		))
+	});
+
+	// Revoked outside the transaction, which the error rolled back
+	if let Err(RefreshError::Reused) = result {
+		log::warn!(
+			"Refresh token reuse detected for session {}, revoking it",
+			session_id
+		);
+		revoke_session(conn, session_id, "token_reuse")?;
+	}
+
+	result
+}
+
+pub fn revoke_session(
+	conn: &mut PgConnection,
+	session_id: Uuid,
+	reason: &str,
+) -> QueryResult<usize> {
+	diesel::update(
+		user_sessions::table
+			.find(session_id)
+			.filter(user_sessions::revoked_ts.is_null()),
+	)
+	.set((
+		user_sessions::revoked_ts.eq(Utc::now()),
+		user_sessions::revoke_reason.eq(reason),
+	))
+	.execute(conn)
+}
+
+// Log the user out everywhere, e.g. after the password changed
+pub fn revoke_user_sessions(
+	conn: &mut PgConnection,
+	user_id: Uuid,
+	reason: &str,
+) -> QueryResult<usize> {
+	diesel::update(
+		user_sessions::table
+			.filter(user_sessions::user_id.eq(user_id))
+			.filter(user_sessions::revoked_ts.is_null()),
+	)
+	.set((
+		user_sessions::revoked_ts.eq(Utc::now()),
+		user_sessions::revoke_reason.eq(reason),
+	))
+	.execute(conn)
+}
+
+// Revoke the session of a refresh token, as long as the token is its current one
+pub fn revoke_session_by_token(
+	conn: &mut PgConnection,
+	refresh_token: &str,
+	reason: &str,
+) -> QueryResult<usize> {
+	let Some((session_id, secret)) = parse_refresh_token(refresh_token) else {
+		return Ok(0);
+	};
+
+	diesel::update(
+		user_sessions::table
+			.find(session_id)
+			.filter(user_sessions::refresh_token_hash.eq(hash_refresh_secret(secret)))
+			.filter(user_sessions::revoked_ts.is_null()),
+	)
+	.set((
+		user_sessions::revoked_ts.eq(Utc::now()),
+		user_sessions::revoke_reason.eq(reason),
+	))
+	.execute(conn)
+}
+
+// Whether access tokens of the session are still accepted
+pub fn session_is_active(conn: &mut PgConnection, session_id: Uuid) -> QueryResult<bool> {
+	let revoked_ts = user_sessions::table
+		.find(session_id)
+		.select(user_sessions::revoked_ts)
+		.first::<Option<chrono::DateTime<Utc>>>(conn)
+		.optional()?;
+
+	Ok(matches!(revoked_ts, Some(None)))
+}
+
+pub fn refresh_token_cookie(refresh_token: &str, config: &Config) -> Cookie<'static> {
+	Cookie::build(REFRESH_TOKEN_COOKIE, refresh_token.to_string())
+		.path(REFRESH_TOKEN_COOKIE_PATH)
+		.http_only(true)
+		.secure(config.secure_cookies)
+		.same_site(if config.secure_cookies {
+			SameSite::None
+		} else {
+			SameSite::Lax
+		})
+		.max_age(CookieDuration::days(config.refresh_token_ttl_days))
+		.finish()
+}
+
+pub fn expired_refresh_token_cookie(config: &Config) -> Cookie<'static> {
+	let mut cookie = refresh_token_cookie("", config);
+	cookie.make_removal();
+	cookie
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_refresh_token() {
+		let session_id = Uuid::new_v4();
+		let secret = generate_refresh_secret();
+		let token = format!("{}.{}", session_id, secret);
+
+		assert_eq!(
+			parse_refresh_token(&token),
+			Some((session_id, secret.as_str()))
+		);
+		assert_eq!(parse_refresh_token(&format!("{}.", session_id)), None);
+		assert_eq!(parse_refresh_token("not-a-uuid.secret"), None);
+		assert_eq!(parse_refresh_token(&secret), None);
+	}
+
+	#[test]
+	fn test_check_refresh_secret() {
+		let now = Utc::now();
+		let (current, previous, older) = ("current", "previous", "older");
+		let mut session = UserSession {
+			session_id: Uuid::new_v4(),
+			user_id: Uuid::new_v4(),
+			refresh_token_hash: hash_refresh_secret(current),
+			created_ts: now - Duration::hours(1),
+			refreshed_ts: Some(now - Duration::seconds(2)),
+			expires_ts: now + Duration::days(1),
+			revoked_ts: None,
+			revoke_reason: None,
+			previous_token_hash: Some(hash_refresh_secret(previous)),
+		};
+
+		assert_eq!(
+			check_refresh_secret(&session, current, now).unwrap(),
+			PresentedToken::Current
+		);
+		assert_eq!(
+			check_refresh_secret(&session, previous, now).unwrap(),
+			PresentedToken::Previous
+		);
+		assert!(matches!(
+			check_refresh_secret(&session, older, now),
+			Err(RefreshError::Reused)
+		));
+
+		// Past the grace period the previous token counts as reused
+		session.refreshed_ts = Some(now - Duration::seconds(REFRESH_GRACE_SECS + 1));
+		assert!(matches!(
+			check_refresh_secret(&session, previous, now),
+			Err(RefreshError::Reused)
+		));
+
+		session.revoked_ts = Some(now);
+		assert!(matches!(
+			check_refresh_secret(&session, current, now),
+			Err(RefreshError::Revoked)
+		));
+	}
+
+	#[test]
+	fn test_hash_refresh_secret() {
+		let secret = generate_refresh_secret();
+		assert_eq!(secret.len(), 64);
+		assert_ne!(secret, generate_refresh_secret());
+
+		assert_eq!(hash_refresh_secret(&secret), hash_refresh_secret(&secret));
+		assert_ne!(hash_refresh_secret(&secret), secret);
+		assert_ne!(
+			hash_refresh_secret(&secret),
+			hash_refresh_secret(&generate_refresh_secret())
+		);
+	}
+}
@@ -0,0 +1,112 @@
+use crate::config::Config;
+use crate::models::CreateUserToken;
+use crate::schema::user_tokens;
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+// What a mailed token lets its holder do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenPurpose {
+	VerifyEmail,
+	ResetPassword,
+}
+
+impl TokenPurpose {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			TokenPurpose::VerifyEmail => "verify_email",
+			TokenPurpose::ResetPassword => "reset_password",
+		}
+	}
+
+	pub fn ttl(&self, config: &Config) -> Duration {
+		match self {
+			TokenPurpose::VerifyEmail => Duration::hours(config.email_verification_ttl_hours),
+			TokenPurpose::ResetPassword => Duration::minutes(config.password_reset_ttl_minutes),
+		}
+	}
+}
+
+pub fn hash_user_token(token: &str) -> String {
+	hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn generate_user_token() -> String {
+	let mut token = [0u8; 32];
+	OsRng.fill_bytes(&mut token);
+	hex::encode(token)
+}
+
+// Issue a token for the user, returned once to be mailed. Earlier unused tokens of the same
+// purpose stop working, so only the latest link does.
+pub fn issue_user_token(
+	conn: &mut PgConnection,
+	user_id: Uuid,
+	purpose: TokenPurpose,
+	config: &Config,
+) -> QueryResult<String> {
+	let token = generate_user_token();
+
+	conn.transaction(|conn| {
+		diesel::update(
+			user_tokens::table
+				.filter(user_tokens::user_id.eq(user_id))
+				.filter(user_tokens::purpose.eq(purpose.as_str()))
+				.filter(user_tokens::used_ts.is_null()),
+		)
+		.set(user_tokens::used_ts.eq(Utc::now()))
+		.execute(conn)?;
+
+		diesel::insert_into(user_tokens::table)
+			.values(&CreateUserToken {
+				user_id,
+				purpose: purpose.as_str().to_string(),
+				token_hash: hash_user_token(&token),
+				expires_ts: Utc::now() + purpose.ttl(config),
+			})
+			.execute(conn)
+	})?;
+
+	Ok(token)
+}
+
+// Use up the token in one statement, so two requests with the same token can't both succeed.
+// Returns the token's user, or None when it is unknown, expired, already used or issued for
+// another purpose.
+pub fn consume_user_token(
+	conn: &mut PgConnection,
+	token: &str,
+	purpose: TokenPurpose,
+) -> QueryResult<Option<Uuid>> {
+	diesel::update(
+		user_tokens::table
+			.filter(user_tokens::token_hash.eq(hash_user_token(token)))
+			.filter(user_tokens::purpose.eq(purpose.as_str()))
+			.filter(user_tokens::used_ts.is_null())
+			.filter(user_tokens::expires_ts.gt(Utc::now())),
+	)
+	.set(user_tokens::used_ts.eq(Utc::now()))
+	.returning(user_tokens::user_id)
+	.get_result::<Uuid>(conn)
+	.optional()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_user_tokens_are_random_and_hashed() {
+		let token = generate_user_token();
+		assert_eq!(token.len(), 64);
+		assert_ne!(token, generate_user_token());
+
+		let hash = hash_user_token(&token);
+		assert_eq!(hash, hash_user_token(&token));
+		assert_ne!(hash, token);
+		assert_ne!(hash, hash_user_token(&generate_user_token()));
+	}
+}
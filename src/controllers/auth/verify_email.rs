@@ -0,0 +1,123 @@
+use crate::config::Config;
+use crate::controllers::auth::user_tokens::{consume_user_token, issue_user_token, TokenPurpose};
+use crate::utils::mailer::{Email, Mailer};
+use crate::{
+	models::{ConfirmTokenRequest, EmailRequest, User},
+	AppState,
+};
+use actix_web::{web, HttpResponse, Result};
+use diesel::prelude::*;
+use serde_json::json;
+
+// Mail the user a fresh verification link
+pub async fn send_verification_email(
+	conn: &mut PgConnection,
+	mailer: &dyn Mailer,
+	config: &Config,
+	user: &User,
+) -> Result<(), String> {
+	let token = issue_user_token(conn, user.id, TokenPurpose::VerifyEmail, config)
+		.map_err(|e| format!("Failed to issue a verification token: {}", e))?;
+
+	let link = format!(
+		"{}/verify-email?token={}",
+		config.app_url.trim_end_matches('/'),
+		token
+	);
+	mailer
+		.send(&Email {
+			to: user.email.clone(),
+			subject: "Confirm your email".to_string(),
+			body: format!(
+				"Open the link below to confirm your email:\n\n{}\n\nThe link is valid for {} hours.\n",
+				link, config.email_verification_ttl_hours
+			),
+		})
+		.await
+		.map_err(|e| e.to_string())
+}
+
+// Always answers the same and at once, so it can't be used to find out which emails are
+// registered: the lookup and the mail happen after the response
+#[actix_web::post("/auth/verify_email/request")]
+pub async fn request_email_verification(
+	body: web::Json<EmailRequest>,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+	let email = body.into_inner().email;
+	actix_web::rt::spawn(async move { mail_verification_link(&data, &email).await });
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"message": "If the email belongs to an unverified account, a verification link has been sent"
+	})))
+}
+
+async fn mail_verification_link(data: &AppState, email: &str) {
+	let user = data
+		.db
+		.get()
+		.map_err(|e| e.to_string())
+		.and_then(|mut conn| {
+			crate::schema::users::table
+				.filter(crate::schema::users::email.eq(email))
+				.first::<User>(&mut conn)
+				.optional()
+				.map(|user| user.map(|user| (conn, user)))
+				.map_err(|e| e.to_string())
+		});
+
+	match user {
+		Ok(Some((mut conn, user))) if user.verified != Some(true) => {
+			if let Err(e) =
+				send_verification_email(&mut conn, data.mailer.as_ref(), &data.env, &user).await
+			{
+				log::error!(
+					"Failed to send the verification email to user {}: {}",
+					user.id,
+					e
+				);
+			}
+		}
+		Ok(_) => {}
+		Err(e) => log::error!("Database error when looking up a user to verify: {}", e),
+	}
+}
+
+#[actix_web::post("/auth/verify_email/confirm")]
+pub async fn confirm_email_verification(
+	body: web::Json<ConfirmTokenRequest>,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+	let mut conn = data.db.get().unwrap();
+
+	let result = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+		let Some(user_id) = consume_user_token(conn, &body.token, TokenPurpose::VerifyEmail)?
+		else {
+			return Ok(false);
+		};
+
+		diesel::update(crate::schema::users::table.find(user_id))
+			.set((
+				crate::schema::users::verified.eq(Some(true)),
+				crate::schema::users::updated_at.eq(Some(chrono::Utc::now().naive_utc())),
+			))
+			.execute(conn)?;
+		Ok(true)
+	});
+
+	match result {
+		Ok(true) => Ok(HttpResponse::Ok().json(json!({
+			"status": "success",
+			"message": "Email verified"
+		}))),
+		Ok(false) => Ok(HttpResponse::BadRequest().json(json!({
+			"status": "fail",
+			"message": "Invalid or expired verification token"
+		}))),
+		Err(e) => Ok(HttpResponse::InternalServerError().json(json!({
+			"status": "error",
+			"message": format!("Failed to verify email: {}", e)
+		}))),
+	}
+}
@@ -0,0 +1,135 @@
+use crate::controllers::avito_client::token_manager::AvitoTokenManager;
+use crate::models::{ApiError, AvitoAccount};
+use crate::utils::encryption::decrypt_avito_credentials;
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use tokio::time::{sleep, Duration};
+use uuid::Uuid;
+
+// What a failed token exchange says about the credentials: Some(false) when Avito
+// rejected them, None when Avito could not be asked and the old state stands
+pub fn connection_status(error: &ApiError) -> Option<bool> {
+	match error {
+		ApiError::AvitoApiError(status, _) => {
+			(matches!(status, 400..=499) && *status != 429).then_some(false)
+		}
+		ApiError::AvitoRetriesExhausted(_, last_error) => connection_status(last_error),
+		_ => None,
+	}
+}
+
+// Try a token exchange with the stored credentials and record the result on the account
+pub async fn check_account_connection(
+	db_pool: &Pool<ConnectionManager<PgConnection>>,
+	tokens: &AvitoTokenManager,
+	account_id: Uuid,
+) -> Result<AvitoAccount, ApiError> {
+	let account = {
+		let mut conn = db_pool.get().map_err(|e| ApiError::Other(e.to_string()))?;
+		crate::schema::avito_accounts::table
+			.find(account_id)
+			.first::<AvitoAccount>(&mut conn)
+			.optional()?
+			.ok_or_else(|| ApiError::NotFound(format!("Avito account {} not found", account_id)))?
+	};
+
+	let decrypted =
+		decrypt_avito_credentials(&account.avito_client_secret, &account.avito_client_id)
+			.map(|_| ())
+			.map_err(|e| e.to_string());
+	let (is_connected, error) = match decrypted {
+		Err(e) => (
+			Some(false),
+			Some(format!("Stored credentials cannot be decrypted: {}", e)),
+		),
+		Ok(()) => match tokens.refresh_token(db_pool, account_id).await {
+			Ok(_) => (Some(true), None),
+			Err(e) => (
+				connection_status(&e).or(account.is_connected),
+				Some(e.to_string()),
+			),
+		},
+	};
+
+	if let Some(error) = &error {
+		log::warn!("Avito account {} failed its check: {}", account_id, error);
+	}
+
+	let mut conn = db_pool.get().map_err(|e| ApiError::Other(e.to_string()))?;
+	Ok(
+		diesel::update(crate::schema::avito_accounts::table.find(account_id))
+			.set((
+				crate::schema::avito_accounts::is_connected.eq(is_connected),
+				crate::schema::avito_accounts::last_check_ts.eq(Utc::now()),
+				crate::schema::avito_accounts::last_check_error.eq(error),
+			))
+			.get_result::<AvitoAccount>(&mut conn)?,
+	)
+}
+
+// Check every account each `interval_minutes`, broken ones included, so fixed credentials
+// are picked up again; 0 turns the job off
+pub async fn start_account_check_scheduler(
+	db_pool: Pool<ConnectionManager<PgConnection>>,
+	tokens: AvitoTokenManager,
+	interval_minutes: u64,
+) {
+	if interval_minutes == 0 {
+		log::info!("Scheduled Avito account checks are disabled");
+		return;
+	}
+
+	loop {
+		sleep(Duration::from_secs(interval_minutes * 60)).await;
+
+		let account_ids = match db_pool.get() {
+			Ok(mut conn) => crate::schema::avito_accounts::table
+				.select(crate::schema::avito_accounts::account_id)
+				.load::<Uuid>(&mut conn)
+				.map_err(|e| e.to_string()),
+			Err(e) => Err(e.to_string()),
+		};
+		let account_ids = match account_ids {
+			Ok(account_ids) => account_ids,
+			Err(e) => {
+				log::error!("Failed to load Avito accounts for checks: {}", e);
+				continue;
+			}
+		};
+
+		for account_id in account_ids {
+			if let Err(e) = check_account_connection(&db_pool, &tokens, account_id).await {
+				log::error!("Checking Avito account {} failed: {}", account_id, e);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_connection_status() {
+		let rejected = ApiError::AvitoApiError(401, "invalid_client".to_string());
+		assert_eq!(connection_status(&rejected), Some(false));
+		assert_eq!(
+			connection_status(&ApiError::AvitoApiError(400, "bad request".to_string())),
+			Some(false)
+		);
+		assert_eq!(
+			connection_status(&ApiError::AvitoApiError(429, "slow down".to_string())),
+			None
+		);
+		assert_eq!(
+			connection_status(&ApiError::AvitoApiError(503, "unavailable".to_string())),
+			None
+		);
+		assert_eq!(connection_status(&ApiError::AvitoCircuitOpen(30)), None);
+		assert_eq!(
+			connection_status(&ApiError::ReqwestError("timed out".to_string())),
+			None
+		);
+	}
+}
@@ -0,0 +1,35 @@
+use crate::controllers::avito_accounts::account_health::check_account_connection;
+use crate::controllers::avito_client::token_manager::authorize_account;
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::ApiError;
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
+use actix_web::{post, web, HttpResponse, Result};
+use serde_json::json;
+use uuid::Uuid;
+
+// Verify the stored credentials now instead of waiting for the scheduled check
+#[post(
+	"/avito/accounts/{id}/check",
+	wrap = "RequirePermission(Permission::ManageAvitoAccounts)"
+)]
+pub async fn check_avito_account(
+	path: web::Path<Uuid>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	let account_id = path.into_inner();
+	authorize_account(&data, account_id, user.user_id)?;
+
+	let account = check_account_connection(&data.db, &data.avito_tokens, account_id).await?;
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": {
+			"account_id": account.account_id,
+			"is_connected": account.is_connected,
+			"last_check_ts": account.last_check_ts,
+			"last_check_error": account.last_check_error
+		}
+	})))
+}
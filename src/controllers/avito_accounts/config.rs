@@ -1,6 +1,6 @@
 use crate::controllers::avito_accounts::{
-	create_avito_account, delete_avito_account, get_all_avito_accounts, get_avito_account_by_id,
-	update_avito_account,
+	check_avito_account, create_avito_account, delete_avito_account, get_all_avito_accounts,
+	get_avito_account_by_id, rotate_encryption_keys, update_avito_account,
 };
 use actix_web::web;
 
@@ -9,5 +9,7 @@ pub fn avito_account_routes(cfg: &mut web::ServiceConfig) {
 		.service(get_avito_account_by_id::get_avito_account_by_id)
 		.service(create_avito_account::create_avito_account)
 		.service(update_avito_account::update_avito_account)
-		.service(delete_avito_account::delete_avito_account);
+		.service(delete_avito_account::delete_avito_account)
+		.service(check_avito_account::check_avito_account)
+		.service(rotate_encryption_keys::rotate_encryption_keys);
 }
@@ -1,4 +1,7 @@
+use crate::controllers::avito_accounts::account_health::check_account_connection;
 use crate::models::AvitoAccount;
+use crate::permissions::{Permission, RequirePermission};
+use crate::utils::encryption;
 use crate::{
 	models::{AvitoAccountData, AvitoAccountResponse, CreateAvitoAccount},
 	AppState,
@@ -7,7 +10,10 @@ use actix_web::{web, HttpResponse, Result};
 use diesel::prelude::*;
 use serde_json::json;
 
-#[actix_web::post("/avito/accounts")]
+#[actix_web::post(
+	"/avito/accounts",
+	wrap = "RequirePermission(Permission::ManageAvitoAccounts)"
+)]
 pub async fn create_avito_account(
 	body: web::Json<CreateAvitoAccount>,
 	data: web::Data<AppState>,
@@ -23,7 +29,7 @@ pub async fn create_avito_account(
 	}
 
 	// Encrypt sensitive data before storing
-	let encrypted_secret = match encrypt_field(&body.avito_client_secret) {
+	let encrypted_secret = match encryption::encrypt_secret(&body.avito_client_secret) {
 		Ok(encrypted) => encrypted,
 		Err(e) => {
 			eprintln!("Failed to encrypt avito_client_secret: {}", e);
@@ -34,7 +40,7 @@ pub async fn create_avito_account(
 		}
 	};
 
-	let encrypted_client_id = match encrypt_field(&body.avito_client_id) {
+	let encrypted_client_id = match encryption::encrypt_secret(&body.avito_client_id) {
 		Ok(encrypted) => encrypted,
 		Err(e) => {
 			eprintln!("Failed to encrypt avito_client_id: {}", e);
@@ -57,9 +63,26 @@ pub async fn create_avito_account(
 		.values(&new_avito_account)
 		.get_result::<AvitoAccount>(&mut conn)
 	{
-		Ok(mut avito_account) => {
+		Ok(avito_account) => {
+			drop(conn);
+
+			// Try the credentials right away, so broken ones show up as disconnected
+			let mut avito_account = match check_account_connection(
+				&data.db,
+				&data.avito_tokens,
+				avito_account.account_id,
+			)
+			.await
+			{
+				Ok(checked_account) => checked_account,
+				Err(e) => {
+					eprintln!("Failed to check the new Avito account: {}", e);
+					avito_account
+				}
+			};
+
 			// Decrypt credentials for the response
-			match crate::utils::encryption::decrypt_avito_credentials(
+			match encryption::decrypt_avito_credentials(
 				&avito_account.avito_client_secret,
 				&avito_account.avito_client_id,
 			) {
@@ -90,19 +113,3 @@ pub async fn create_avito_account(
 		}
 	}
 }
-
-// Helper function to encrypt a field
-fn encrypt_field(field: &str) -> Result<String, Box<dyn std::error::Error>> {
-	// Global key for encryption (in production, this should be stored securely)
-	static ENCRYPTION_KEY: [u8; 32] = [
-		1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25,
-		26, 27, 28, 29, 30, 31, 32,
-	];
-
-	let iv = crate::utils::encryption::generate_iv();
-	let encrypted_data = crate::utils::encryption::encrypt_data(field, &ENCRYPTION_KEY, &iv);
-	Ok(crate::utils::encryption::combine_iv_and_data(
-		&iv,
-		&encrypted_data,
-	))
-}
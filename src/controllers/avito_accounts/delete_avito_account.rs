@@ -1,3 +1,4 @@
+use crate::permissions::{Permission, RequirePermission};
 use crate::{models::AvitoAccount, AppState};
 use actix_web::{web, HttpResponse, Result};
 use diesel::prelude::*;
@@ -6,7 +7,10 @@ use uuid::Uuid;
 
 use crate::jwt_auth::JwtMiddleware;
 
-#[actix_web::delete("/avito/accounts/{id}")]
+#[actix_web::delete(
+	"/avito/accounts/{id}",
+	wrap = "RequirePermission(Permission::ManageAvitoAccounts)"
+)]
 pub async fn delete_avito_account(
 	path: web::Path<Uuid>,
 	user: JwtMiddleware,
@@ -50,6 +54,7 @@ pub async fn delete_avito_account(
 	match diesel::delete(crate::schema::avito_accounts::table.find(account_id)).execute(&mut conn) {
 		Ok(rows_affected) => {
 			if rows_affected > 0 {
+				data.avito_tokens.forget(account_id);
 				Ok(HttpResponse::Ok().json(json!({
 					"status": "success",
 					"message": "Avito account deleted successfully"
@@ -1,4 +1,6 @@
+use crate::controllers::avito_accounts::rotate_encryption_keys::upgrade_account_secrets;
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	models::{AvitoAccount, PaginationParams, PaginationResponse, ResponseWithPagination},
 	AppState,
@@ -7,7 +9,10 @@ use actix_web::{web, HttpResponse, Result};
 use diesel::prelude::*;
 use serde_json::json;
 
-#[actix_web::get("/avito/accounts")]
+#[actix_web::get(
+	"/avito/accounts",
+	wrap = "RequirePermission(Permission::ManageAvitoAccounts)"
+)]
 pub async fn get_all_avito_accounts(
 	user: JwtMiddleware,
 	pagination: web::Query<PaginationParams>,
@@ -54,6 +59,7 @@ pub async fn get_all_avito_accounts(
 			&acc.avito_client_id,
 		) {
 			Ok((decrypted_secret, decrypted_client_id)) => {
+				upgrade_account_secrets(&mut conn, &acc, &decrypted_secret, &decrypted_client_id);
 				acc.avito_client_secret = decrypted_secret;
 				acc.avito_client_id = decrypted_client_id;
 			}
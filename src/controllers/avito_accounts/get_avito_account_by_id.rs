@@ -1,3 +1,4 @@
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	models::{AvitoAccount, AvitoAccountData, AvitoAccountResponse},
 	AppState,
@@ -7,10 +8,14 @@ use diesel::prelude::*;
 use serde_json::json;
 use uuid::Uuid;
 
+use crate::controllers::avito_accounts::rotate_encryption_keys::upgrade_account_secrets;
 use crate::jwt_auth::JwtMiddleware;
 use crate::utils::encryption;
 
-#[actix_web::get("/avito/accounts/{id}")]
+#[actix_web::get(
+	"/avito/accounts/{id}",
+	wrap = "RequirePermission(Permission::ManageAvitoAccounts)"
+)]
 pub async fn get_avito_account_by_id(
 	path: web::Path<Uuid>,
 	user: JwtMiddleware,
@@ -39,6 +44,12 @@ pub async fn get_avito_account_by_id(
 				&avito_account.avito_client_id,
 			) {
 				Ok((decrypted_secret, decrypted_client_id)) => {
+					upgrade_account_secrets(
+						&mut conn,
+						&avito_account,
+						&decrypted_secret,
+						&decrypted_client_id,
+					);
 					avito_account.avito_client_secret = decrypted_secret;
 					avito_account.avito_client_id = decrypted_client_id;
 				}
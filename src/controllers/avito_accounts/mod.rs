@@ -1,8 +1,11 @@
+pub mod account_health;
+pub mod check_avito_account;
 pub mod config;
 pub mod create_avito_account;
 pub mod delete_avito_account;
 pub mod get_all_avito_accounts;
 pub mod get_avito_account_by_id;
+pub mod rotate_encryption_keys;
 pub mod update_avito_account;
 
 use actix_web::web;
@@ -0,0 +1,160 @@
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::{ApiError, AvitoAccount};
+use crate::permissions::{Permission, RequirePermission};
+use crate::schema::avito_accounts;
+use crate::utils::encryption::{keyring, Keyring};
+use crate::AppState;
+use actix_web::{post, web, HttpResponse, Result};
+use diesel::prelude::*;
+use serde::Serialize;
+use serde_json::json;
+use uuid::Uuid;
+
+#[derive(Serialize, Default)]
+pub struct KeyRotationReport {
+	pub active_key_id: String,
+	pub rotated: usize,
+	pub up_to_date: usize,
+	// Changed by someone else while being rotated; they already carry fresh values
+	pub skipped: usize,
+	pub failed: Vec<Uuid>,
+}
+
+// Re-encrypt the credentials of every Avito account that is not on the active key and the
+// authenticated scheme yet.
+// To rotate, add the new key to the configured keys, make it active, restart and call this;
+// the old key can be dropped from config once the report shows no failures.
+#[post(
+	"/avito/accounts/encryption/rotate",
+	wrap = "RequirePermission(Permission::RotateEncryptionKeys)"
+)]
+pub async fn rotate_encryption_keys(
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	let mut conn = data.db.get().map_err(|e| ApiError::Other(e.to_string()))?;
+
+	let keyring = keyring().map_err(|e| ApiError::Other(e.to_string()))?;
+	let report = rotate_account_secrets(&mut conn, keyring)?;
+
+	log::info!(
+		"Encryption key rotation to '{}' by user {}: {} rotated, {} up to date, {} skipped, {} failed",
+		report.active_key_id,
+		user.user_id,
+		report.rotated,
+		report.up_to_date,
+		report.skipped,
+		report.failed.len()
+	);
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": report
+	})))
+}
+
+fn rotate_account_secrets(
+	conn: &mut PgConnection,
+	keyring: &Keyring,
+) -> Result<KeyRotationReport, ApiError> {
+	let accounts = avito_accounts::table.load::<AvitoAccount>(conn)?;
+	let mut report = KeyRotationReport {
+		active_key_id: keyring.active_key_id().to_string(),
+		..Default::default()
+	};
+
+	for account in accounts {
+		if !keyring.needs_rotation(&account.avito_client_secret)
+			&& !keyring.needs_rotation(&account.avito_client_id)
+		{
+			report.up_to_date += 1;
+			continue;
+		}
+
+		let decrypted = keyring
+			.decrypt(&account.avito_client_secret)
+			.and_then(|secret| Ok((secret, keyring.decrypt(&account.avito_client_id)?)));
+		let (secret, client_id) = match decrypted {
+			Ok(decrypted) => decrypted,
+			Err(e) => {
+				log::error!(
+					"Cannot decrypt credentials of Avito account {} for rotation: {}",
+					account.account_id,
+					e
+				);
+				report.failed.push(account.account_id);
+				continue;
+			}
+		};
+
+		if reencrypt_account_secrets(conn, keyring, &account, &secret, &client_id)? {
+			report.rotated += 1;
+		} else {
+			report.skipped += 1;
+		}
+	}
+
+	Ok(report)
+}
+
+// Write the decrypted credentials back under the active key. Only the values that were read
+// are replaced, so a concurrent credentials update wins; returns whether the row was written.
+fn reencrypt_account_secrets(
+	conn: &mut PgConnection,
+	keyring: &Keyring,
+	account: &AvitoAccount,
+	secret: &str,
+	client_id: &str,
+) -> Result<bool, ApiError> {
+	let encrypted_secret = keyring
+		.encrypt(secret)
+		.map_err(|e| ApiError::Other(e.to_string()))?;
+	let encrypted_client_id = keyring
+		.encrypt(client_id)
+		.map_err(|e| ApiError::Other(e.to_string()))?;
+
+	let updated = diesel::update(
+		avito_accounts::table
+			.find(account.account_id)
+			.filter(avito_accounts::avito_client_secret.eq(&account.avito_client_secret))
+			.filter(avito_accounts::avito_client_id.eq(&account.avito_client_id)),
+	)
+	.set((
+		avito_accounts::avito_client_secret.eq(encrypted_secret),
+		avito_accounts::avito_client_id.eq(encrypted_client_id),
+	))
+	.execute(conn)?;
+
+	Ok(updated > 0)
+}
+
+// Upgrade credentials that were just decrypted when they are on an old key or scheme.
+// A failure is only logged: the read already succeeded and the next one tries again.
+pub fn upgrade_account_secrets(
+	conn: &mut PgConnection,
+	account: &AvitoAccount,
+	secret: &str,
+	client_id: &str,
+) {
+	let Ok(keyring) = keyring() else {
+		return;
+	};
+	if !keyring.needs_rotation(&account.avito_client_secret)
+		&& !keyring.needs_rotation(&account.avito_client_id)
+	{
+		return;
+	}
+
+	match reencrypt_account_secrets(conn, keyring, account, secret, client_id) {
+		Ok(true) => log::info!(
+			"Upgraded credentials encryption of Avito account {}",
+			account.account_id
+		),
+		Ok(false) => {}
+		Err(e) => log::warn!(
+			"Failed to upgrade credentials encryption of Avito account {}: {}",
+			account.account_id,
+			e
+		),
+	}
+}
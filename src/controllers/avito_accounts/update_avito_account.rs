@@ -1,3 +1,4 @@
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	models::{AvitoAccountData, AvitoAccountResponse, UpdateAvitoAccount},
 	AppState,
@@ -7,11 +8,15 @@ use diesel::prelude::*;
 use serde_json::json;
 use uuid::Uuid;
 
+use crate::controllers::avito_accounts::account_health::check_account_connection;
 use crate::jwt_auth::JwtMiddleware;
 use crate::models::AvitoAccount;
 use crate::utils::encryption;
 
-#[actix_web::put("/avito/accounts/{id}")]
+#[actix_web::put(
+	"/avito/accounts/{id}",
+	wrap = "RequirePermission(Permission::ManageAvitoAccounts)"
+)]
 pub async fn update_avito_account(
 	path: web::Path<Uuid>,
 	user: JwtMiddleware,
@@ -76,7 +81,21 @@ pub async fn update_avito_account(
 		.set(&update_data)
 		.get_result::<AvitoAccount>(&mut conn)
 	{
-		Ok(mut avito_account) => {
+		Ok(avito_account) => {
+			drop(conn);
+
+			// The cached Avito token was issued for the old credentials
+			data.avito_tokens.forget(account_id);
+
+			let mut avito_account =
+				match check_account_connection(&data.db, &data.avito_tokens, account_id).await {
+					Ok(checked_account) => checked_account,
+					Err(e) => {
+						eprintln!("Failed to check the updated Avito account: {}", e);
+						avito_account
+					}
+				};
+
 			// Decrypt credentials for the response
 			match encryption::decrypt_avito_credentials(
 				&avito_account.avito_client_secret,
@@ -122,7 +141,7 @@ fn encrypt_if_provided(
 				"message": "Avito client secret and client ID cannot be empty"
 			})));
 		}
-		match encrypt_field(value) {
+		match encryption::encrypt_secret(value) {
 			Ok(encrypted) => Ok(Some(encrypted)),
 			Err(e) => {
 				eprintln!("Failed to encrypt field: {}", e);
@@ -136,16 +155,3 @@ fn encrypt_if_provided(
 		Ok(Some(existing_encrypted.to_string()))
 	}
 }
-
-// Helper function to encrypt a field
-fn encrypt_field(field: &str) -> Result<String, Box<dyn std::error::Error>> {
-	// Global key for encryption (in production, this should be stored securely)
-	static ENCRYPTION_KEY: [u8; 32] = [
-		1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25,
-		26, 27, 28, 29, 30, 31, 32,
-	];
-
-	let iv = encryption::generate_iv();
-	let encrypted_data = encryption::encrypt_data(field, &ENCRYPTION_KEY, &iv);
-	Ok(encryption::combine_iv_and_data(&iv, &encrypted_data))
-}
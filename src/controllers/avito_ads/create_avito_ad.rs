@@ -1,4 +1,5 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	models::{AvitoAd, AvitoAdData, AvitoAdResponse, CreateAvitoAd},
 	AppState,
@@ -8,7 +9,7 @@ use chrono::Utc;
 use diesel::prelude::*;
 use serde_json::json;
 
-#[actix_web::post("/avito_ads")]
+#[actix_web::post("/avito_ads", wrap = "RequirePermission(Permission::ManageAds)")]
 pub async fn create_avito_ad(
 	user: JwtMiddleware,
 	body: web::Json<CreateAvitoAd>,
@@ -1,11 +1,12 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{models::AvitoAd, AppState};
 use actix_web::{web, HttpResponse, Result};
 use diesel::prelude::*;
 use serde_json::json;
 use uuid::Uuid;
 
-#[actix_web::delete("/avito_ads/{id}")]
+#[actix_web::delete("/avito_ads/{id}", wrap = "RequirePermission(Permission::ManageAds)")]
 pub async fn delete_avito_ad(
 	user: JwtMiddleware,
 	path: web::Path<String>,
@@ -1,4 +1,5 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	models::{AvitoAd, PaginationParams, PaginationResponse, ResponseWithPagination},
 	AppState,
@@ -7,7 +8,7 @@ use actix_web::{web, HttpResponse, Result};
 use diesel::prelude::*;
 use serde_json::json;
 
-#[actix_web::get("/avito_ads")]
+#[actix_web::get("/avito_ads", wrap = "RequirePermission(Permission::ManageAds)")]
 pub async fn get_all_avito_ads(
 	user: JwtMiddleware,
 	pagination: web::Query<PaginationParams>,
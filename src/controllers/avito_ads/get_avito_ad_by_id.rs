@@ -1,4 +1,5 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	models::{AvitoAd, AvitoAdData, AvitoAdResponse},
 	AppState,
@@ -8,7 +9,7 @@ use diesel::prelude::*;
 use serde_json::json;
 use uuid::Uuid;
 
-#[actix_web::get("/avito_ads/{id}")]
+#[actix_web::get("/avito_ads/{id}", wrap = "RequirePermission(Permission::ManageAds)")]
 pub async fn get_avito_ad_by_id(
 	user: JwtMiddleware,
 	path: web::Path<String>,
@@ -1,4 +1,5 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	models::{AvitoAd, AvitoAdData, AvitoAdResponse, UpdateAvitoAd},
 	AppState,
@@ -8,7 +9,7 @@ use diesel::prelude::*;
 use serde_json::json;
 use uuid::Uuid;
 
-#[actix_web::patch("/avito_ads/{id}")]
+#[actix_web::patch("/avito_ads/{id}", wrap = "RequirePermission(Permission::ManageAds)")]
 pub async fn update_avito_ad(
 	user: JwtMiddleware,
 	path: web::Path<String>,
@@ -1,3 +1,4 @@
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	controllers::websocket::WebSocketConnections,
 	jwt_auth::JwtMiddleware,
@@ -85,7 +86,10 @@ fn is_date_not_today(ad_date_str: &str) -> bool {
 }
 
 // Create AI description processing task handler
-#[post("/ai_description_processing")]
+#[post(
+	"/ai_description_processing",
+	wrap = "RequirePermission(Permission::UseAiProcessing)"
+)]
 pub async fn create_ai_description_processing_handler(
 	body: web::Json<AiDescriptionProcessingRequest>,
 	data: web::Data<AppState>,
@@ -1,3 +1,4 @@
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	controllers::websocket::WebSocketConnections,
 	jwt_auth::JwtMiddleware,
@@ -73,7 +74,10 @@ fn is_date_not_today(ad_date_str: &str) -> bool {
 }
 
 // Create AI title processing task handler
-#[post("/ai_title_processing")]
+#[post(
+	"/ai_title_processing",
+	wrap = "RequirePermission(Permission::UseAiProcessing)"
+)]
 pub async fn create_ai_title_processing_handler(
 	body: web::Json<AiTitleProcessingRequest>,
 	data: web::Data<AppState>,
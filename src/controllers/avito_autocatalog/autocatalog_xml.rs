@@ -0,0 +1,245 @@
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::io::BufRead;
+
+// One level of the Autocatalog.xml hierarchy, identified by its name attribute
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CatalogMake {
+	pub avito_id: Option<String>,
+	pub name: String,
+	pub models: Vec<CatalogModel>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CatalogModel {
+	pub avito_id: Option<String>,
+	pub name: String,
+	pub generations: Vec<CatalogGeneration>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CatalogGeneration {
+	pub avito_id: Option<String>,
+	pub name: String,
+	pub modifications: Vec<CatalogModification>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CatalogModification {
+	pub avito_id: Option<String>,
+	pub name: String,
+	pub body_type: Option<String>,
+	pub doors: Option<String>,
+}
+
+// Read <Make>/<Model>/<Generation>/<Modification> elements with their BodyType and Doors.
+// Other tags of a modification (engine, drive and so on) are not needed by the editor.
+pub fn parse_autocatalog<R: BufRead>(source: R) -> Result<Vec<CatalogMake>, String> {
+	let mut reader = Reader::from_reader(source);
+	let mut buf = Vec::new();
+	let mut makes: Vec<CatalogMake> = Vec::new();
+	let mut path: Vec<String> = Vec::new();
+	let mut text = String::new();
+
+	loop {
+		buf.clear();
+		let event = reader
+			.read_event_into(&mut buf)
+			.map_err(|e| format!("XML parse error at {}: {}", reader.buffer_position(), e))?;
+
+		match event {
+			Event::Start(e) => {
+				let name = element_name(&e)?;
+				open_element(&mut makes, &path, &name, &e)?;
+				path.push(name);
+				text.clear();
+			}
+			Event::Empty(e) => {
+				let name = element_name(&e)?;
+				open_element(&mut makes, &path, &name, &e)?;
+			}
+			Event::Text(e) => {
+				let value = e
+					.unescape()
+					.map_err(|e| format!("XML unescape error: {}", e))?;
+				text.push_str(&value);
+			}
+			Event::End(_) => {
+				let Some(name) = path.pop() else {
+					continue;
+				};
+
+				let value = text.trim();
+				if path.last().map(String::as_str) == Some("Modification") && !value.is_empty() {
+					if let Some(modification) = last_modification(&mut makes) {
+						match name.as_str() {
+							"BodyType" => modification.body_type = Some(value.to_string()),
+							"Doors" => modification.doors = Some(value.to_string()),
+							_ => {}
+						}
+					}
+				}
+				text.clear();
+			}
+			Event::Eof => break,
+			_ => {}
+		}
+	}
+
+	Ok(makes)
+}
+
+fn element_name(e: &BytesStart) -> Result<String, String> {
+	std::str::from_utf8(e.name().as_ref())
+		.map(|name| name.to_string())
+		.map_err(|e| format!("UTF-8 error: {}", e))
+}
+
+fn attribute(e: &BytesStart, key: &[u8]) -> Option<String> {
+	e.attributes()
+		.flatten()
+		.find(|attr| attr.key.as_ref() == key)
+		.and_then(|attr| attr.unescape_value().ok())
+		.map(|value| value.trim().to_string())
+		.filter(|value| !value.is_empty())
+}
+
+// Add a catalog entry for a hierarchy element under the entry its parent element created
+fn open_element(
+	makes: &mut Vec<CatalogMake>,
+	path: &[String],
+	name: &str,
+	e: &BytesStart,
+) -> Result<(), String> {
+	let parent = path.last().map(String::as_str);
+	let expected_parent = match name {
+		"Make" => None,
+		"Model" => Some("Make"),
+		"Generation" => Some("Model"),
+		"Modification" => Some("Generation"),
+		_ => return Ok(()),
+	};
+	if expected_parent.is_some() && parent != expected_parent {
+		return Ok(());
+	}
+
+	let Some(entry_name) = attribute(e, b"name").or_else(|| attribute(e, b"value")) else {
+		return Err(format!("<{}> without a name attribute", name));
+	};
+	let avito_id = attribute(e, b"id");
+
+	match name {
+		"Make" => makes.push(CatalogMake {
+			avito_id,
+			name: entry_name,
+			models: Vec::new(),
+		}),
+		"Model" => {
+			if let Some(make) = makes.last_mut() {
+				make.models.push(CatalogModel {
+					avito_id,
+					name: entry_name,
+					generations: Vec::new(),
+				});
+			}
+		}
+		"Generation" => {
+			if let Some(model) = makes.last_mut().and_then(|make| make.models.last_mut()) {
+				model.generations.push(CatalogGeneration {
+					avito_id,
+					name: entry_name,
+					modifications: Vec::new(),
+				});
+			}
+		}
+		_ => {
+			let generation = makes
+				.last_mut()
+				.and_then(|make| make.models.last_mut())
+				.and_then(|model| model.generations.last_mut());
+			if let Some(generation) = generation {
+				generation.modifications.push(CatalogModification {
+					avito_id,
+					name: entry_name,
+					..Default::default()
+				});
+			}
+		}
+	}
+
+	Ok(())
+}
+
+fn last_modification(makes: &mut [CatalogMake]) -> Option<&mut CatalogModification> {
+	makes
+		.last_mut()?
+		.models
+		.last_mut()?
+		.generations
+		.last_mut()?
+		.modifications
+		.last_mut()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_autocatalog() {
+		let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Catalog>
+	<Make id="1" name="Audi">
+		<Model id="11" name="A4">
+			<Generation id="111" name="B8 (2007—2011)">
+				<Modification id="1111" name="1.8 AT (160 л.с.)">
+					<BodyType>Седан</BodyType>
+					<Doors>4</Doors>
+					<FuelType>Бензин</FuelType>
+				</Modification>
+				<Modification id="1112" name="2.0 MT (211 л.с.)"/>
+			</Generation>
+		</Model>
+		<Model name="A6"/>
+	</Make>
+	<Make name="Lada &amp; Co"/>
+</Catalog>"#;
+
+		let makes = parse_autocatalog(xml.as_bytes()).unwrap();
+
+		assert_eq!(makes.len(), 2);
+		assert_eq!(makes[1].name, "Lada & Co");
+		assert_eq!(makes[1].avito_id, None);
+
+		let audi = &makes[0];
+		assert_eq!(audi.avito_id.as_deref(), Some("1"));
+		assert_eq!(
+			audi.models
+				.iter()
+				.map(|m| m.name.as_str())
+				.collect::<Vec<_>>(),
+			vec!["A4", "A6"]
+		);
+
+		let generation = &audi.models[0].generations[0];
+		assert_eq!(generation.name, "B8 (2007—2011)");
+		assert_eq!(
+			generation.modifications,
+			vec![
+				CatalogModification {
+					avito_id: Some("1111".to_string()),
+					name: "1.8 AT (160 л.с.)".to_string(),
+					body_type: Some("Седан".to_string()),
+					doors: Some("4".to_string()),
+				},
+				CatalogModification {
+					avito_id: Some("1112".to_string()),
+					name: "2.0 MT (211 л.с.)".to_string(),
+					..Default::default()
+				},
+			]
+		);
+
+		assert!(parse_autocatalog("<Catalog><Make/></Catalog>".as_bytes()).is_err());
+	}
+}
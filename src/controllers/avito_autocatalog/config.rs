@@ -0,0 +1,14 @@
+use crate::controllers::avito_autocatalog::{
+	get_autocatalog_generations, get_autocatalog_makes, get_autocatalog_models,
+	get_autocatalog_modifications, import_autocatalog, upload_autocatalog,
+};
+use actix_web::web;
+
+pub fn avito_autocatalog_routes(cfg: &mut web::ServiceConfig) {
+	cfg.service(import_autocatalog::import_autocatalog)
+		.service(upload_autocatalog::upload_autocatalog)
+		.service(get_autocatalog_makes::get_autocatalog_makes)
+		.service(get_autocatalog_models::get_autocatalog_models)
+		.service(get_autocatalog_generations::get_autocatalog_generations)
+		.service(get_autocatalog_modifications::get_autocatalog_modifications);
+}
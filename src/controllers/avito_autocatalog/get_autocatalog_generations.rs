@@ -0,0 +1,38 @@
+use crate::permissions::{Permission, RequirePermission};
+use crate::{
+	jwt_auth::JwtMiddleware,
+	models::{ApiError, AutocatalogGenerationsQuery, AvitoCarGeneration},
+	AppState,
+};
+use actix_web::{web, HttpResponse, Result};
+use diesel::prelude::*;
+use serde_json::json;
+
+// GET generations of a model, e.g. /avito/autocatalog/generations?make=Audi&model=A4
+#[actix_web::get(
+	"/avito/autocatalog/generations",
+	wrap = "RequirePermission(Permission::ReadAutocatalog)"
+)]
+pub async fn get_autocatalog_generations(
+	query: web::Query<AutocatalogGenerationsQuery>,
+	data: web::Data<AppState>,
+	_: JwtMiddleware,
+) -> Result<HttpResponse, ApiError> {
+	let mut conn = data.db.get().unwrap();
+
+	let generations = crate::schema::avito_car_generations::table
+		.inner_join(
+			crate::schema::avito_car_models::table
+				.inner_join(crate::schema::avito_car_makes::table),
+		)
+		.filter(crate::schema::avito_car_makes::name.eq(&query.make))
+		.filter(crate::schema::avito_car_models::name.eq(&query.model))
+		.order(crate::schema::avito_car_generations::name.asc())
+		.select(AvitoCarGeneration::as_select())
+		.load::<AvitoCarGeneration>(&mut conn)?;
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": generations
+	})))
+}
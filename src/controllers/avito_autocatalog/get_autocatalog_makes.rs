@@ -0,0 +1,46 @@
+use crate::permissions::{Permission, RequirePermission};
+use crate::{
+	jwt_auth::JwtMiddleware,
+	models::{ApiError, AvitoCarMake},
+	AppState,
+};
+use actix_web::{web, HttpResponse, Result};
+use diesel::prelude::*;
+use serde_json::json;
+
+// GET every make of the auto catalog, alphabetically
+#[actix_web::get(
+	"/avito/autocatalog/makes",
+	wrap = "RequirePermission(Permission::ReadAutocatalog)"
+)]
+pub async fn get_autocatalog_makes(
+	data: web::Data<AppState>,
+	_: JwtMiddleware,
+) -> Result<HttpResponse, ApiError> {
+	let mut conn = data.db.get().unwrap();
+
+	let makes = crate::schema::avito_car_makes::table
+		.order(crate::schema::avito_car_makes::name.asc())
+		.select(AvitoCarMake::as_select())
+		.load::<AvitoCarMake>(&mut conn)?;
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": makes
+	})))
+}
+
+// Make names in the dictionary format of values_link_json, for the Make field of a category
+pub fn load_make_values(
+	conn: &mut PgConnection,
+) -> Result<Vec<serde_json::Value>, diesel::result::Error> {
+	let names = crate::schema::avito_car_makes::table
+		.select(crate::schema::avito_car_makes::name)
+		.order(crate::schema::avito_car_makes::name.asc())
+		.load::<String>(conn)?;
+
+	Ok(names
+		.into_iter()
+		.map(|name| json!({ "value": name }))
+		.collect())
+}
@@ -0,0 +1,34 @@
+use crate::permissions::{Permission, RequirePermission};
+use crate::{
+	jwt_auth::JwtMiddleware,
+	models::{ApiError, AutocatalogModelsQuery, AvitoCarModel},
+	AppState,
+};
+use actix_web::{web, HttpResponse, Result};
+use diesel::prelude::*;
+use serde_json::json;
+
+// GET models of a make, e.g. /avito/autocatalog/models?make=Audi
+#[actix_web::get(
+	"/avito/autocatalog/models",
+	wrap = "RequirePermission(Permission::ReadAutocatalog)"
+)]
+pub async fn get_autocatalog_models(
+	query: web::Query<AutocatalogModelsQuery>,
+	data: web::Data<AppState>,
+	_: JwtMiddleware,
+) -> Result<HttpResponse, ApiError> {
+	let mut conn = data.db.get().unwrap();
+
+	let models = crate::schema::avito_car_models::table
+		.inner_join(crate::schema::avito_car_makes::table)
+		.filter(crate::schema::avito_car_makes::name.eq(&query.make))
+		.order(crate::schema::avito_car_models::name.asc())
+		.select(AvitoCarModel::as_select())
+		.load::<AvitoCarModel>(&mut conn)?;
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": models
+	})))
+}
@@ -0,0 +1,42 @@
+use crate::permissions::{Permission, RequirePermission};
+use crate::{
+	jwt_auth::JwtMiddleware,
+	models::{ApiError, AutocatalogModificationsQuery, AvitoCarModification},
+	AppState,
+};
+use actix_web::{web, HttpResponse, Result};
+use diesel::prelude::*;
+use serde_json::json;
+
+// GET modifications of a generation with their BodyType and Doors,
+// e.g. /avito/autocatalog/modifications?make=Audi&model=A4&generation=B8
+#[actix_web::get(
+	"/avito/autocatalog/modifications",
+	wrap = "RequirePermission(Permission::ReadAutocatalog)"
+)]
+pub async fn get_autocatalog_modifications(
+	query: web::Query<AutocatalogModificationsQuery>,
+	data: web::Data<AppState>,
+	_: JwtMiddleware,
+) -> Result<HttpResponse, ApiError> {
+	let mut conn = data.db.get().unwrap();
+
+	let modifications = crate::schema::avito_car_modifications::table
+		.inner_join(
+			crate::schema::avito_car_generations::table.inner_join(
+				crate::schema::avito_car_models::table
+					.inner_join(crate::schema::avito_car_makes::table),
+			),
+		)
+		.filter(crate::schema::avito_car_makes::name.eq(&query.make))
+		.filter(crate::schema::avito_car_models::name.eq(&query.model))
+		.filter(crate::schema::avito_car_generations::name.eq(&query.generation))
+		.order(crate::schema::avito_car_modifications::name.asc())
+		.select(AvitoCarModification::as_select())
+		.load::<AvitoCarModification>(&mut conn)?;
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": modifications
+	})))
+}
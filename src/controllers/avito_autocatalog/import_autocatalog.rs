@@ -0,0 +1,243 @@
+use crate::controllers::avito_autocatalog::autocatalog_xml::{parse_autocatalog, CatalogMake};
+use crate::controllers::avito_feeds::import_avito_xml::fetch_feed;
+use crate::controllers::avito_feeds::xml_stream::{open_feed_source, stream_response, XmlLimits};
+use crate::models::{
+	ImportAutocatalogRequest, NewAvitoCarGeneration, NewAvitoCarMake, NewAvitoCarModel,
+	NewAvitoCarModification,
+};
+use crate::permissions::{Permission, RequirePermission};
+use crate::{jwt_auth::JwtMiddleware, AppState};
+use actix_web::{web, HttpResponse, Result};
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use uuid::Uuid;
+
+// Rows per INSERT; keeps every statement well below the Postgres bind parameter limit
+const UPSERT_CHUNK_SIZE: usize = 1000;
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct AutocatalogImportSummary {
+	pub makes: usize,
+	pub models: usize,
+	pub generations: usize,
+	pub modifications: usize,
+}
+
+// POST import Autocatalog.xml from a URL, the public Avito catalog by default
+#[actix_web::post(
+	"/avito/autocatalog/import",
+	wrap = "RequirePermission(Permission::ImportAutocatalog)"
+)]
+pub async fn import_autocatalog(
+	body: web::Json<ImportAutocatalogRequest>,
+	data: web::Data<AppState>,
+	_: JwtMiddleware,
+) -> Result<HttpResponse> {
+	let source_url = body
+		.source_url
+		.clone()
+		.unwrap_or_else(|| data.env.autocatalog_url.clone());
+
+	let response = match fetch_feed(&source_url).await {
+		Ok(response) => response,
+		Err(e) => {
+			return Ok(HttpResponse::BadRequest().json(json!({
+				"status": "error",
+				"message": e
+			})))
+		}
+	};
+
+	Ok(run_autocatalog_import(&data, Box::new(stream_response(response))).await)
+}
+
+// Parse a catalog document (plain, .gz or .zip) and store it, answering with the summary
+pub async fn run_autocatalog_import(data: &AppState, source: Box<dyn Read + Send>) -> HttpResponse {
+	let pool = data.db.clone();
+	let limits = XmlLimits::from_config(&data.env);
+
+	let result = web::block(move || {
+		let source = open_feed_source(source, &limits)?;
+		let makes = parse_autocatalog(source)?;
+		if makes.is_empty() {
+			return Err("Autocatalog contains no <Make> elements".to_string());
+		}
+
+		let mut conn = pool.get().map_err(|e| e.to_string())?;
+		store_autocatalog(&mut conn, &makes).map_err(|e| format!("Database error: {}", e))
+	})
+	.await
+	.unwrap_or_else(|e| Err(e.to_string()));
+
+	match result {
+		Ok(summary) => {
+			log::info!("Autocatalog imported: {:?}", summary);
+			HttpResponse::Ok().json(json!({
+				"status": "success",
+				"data": summary
+			}))
+		}
+		Err(e) => {
+			log::error!("Autocatalog import failed: {}", e);
+			HttpResponse::BadRequest().json(json!({
+				"status": "error",
+				"message": e
+			}))
+		}
+	}
+}
+
+// Upsert the whole hierarchy in one transaction. Entries missing from a newer catalog are
+// kept, so ads that still refer to a discontinued modification keep their values.
+pub fn store_autocatalog(
+	conn: &mut PgConnection,
+	makes: &[CatalogMake],
+) -> Result<AutocatalogImportSummary, diesel::result::Error> {
+	conn.transaction(|conn| {
+		// A name may repeat in the document; the last entry wins, as one upsert can't touch a row twice
+		let new_makes: BTreeMap<&str, NewAvitoCarMake> = makes
+			.iter()
+			.map(|make| {
+				(
+					make.name.as_str(),
+					NewAvitoCarMake {
+						avito_id: make.avito_id.as_deref(),
+						name: &make.name,
+					},
+				)
+			})
+			.collect();
+		let new_makes: Vec<NewAvitoCarMake> = new_makes.into_values().collect();
+
+		let mut make_ids: HashMap<String, Uuid> = HashMap::new();
+		for chunk in new_makes.chunks(UPSERT_CHUNK_SIZE) {
+			use crate::schema::avito_car_makes::dsl;
+			let rows = diesel::insert_into(dsl::avito_car_makes)
+				.values(chunk)
+				.on_conflict(dsl::name)
+				.do_update()
+				.set(dsl::avito_id.eq(excluded(dsl::avito_id)))
+				.returning((dsl::make_id, dsl::name))
+				.get_results::<(Uuid, String)>(conn)?;
+			make_ids.extend(rows.into_iter().map(|(id, name)| (name, id)));
+		}
+
+		let mut new_models: BTreeMap<(Uuid, &str), NewAvitoCarModel> = BTreeMap::new();
+		for make in makes {
+			let make_id = make_ids[&make.name];
+			for model in &make.models {
+				new_models.insert(
+					(make_id, model.name.as_str()),
+					NewAvitoCarModel {
+						make_id,
+						avito_id: model.avito_id.as_deref(),
+						name: &model.name,
+					},
+				);
+			}
+		}
+		let new_models: Vec<NewAvitoCarModel> = new_models.into_values().collect();
+
+		let mut model_ids: HashMap<(Uuid, String), Uuid> = HashMap::new();
+		for chunk in new_models.chunks(UPSERT_CHUNK_SIZE) {
+			use crate::schema::avito_car_models::dsl;
+			let rows = diesel::insert_into(dsl::avito_car_models)
+				.values(chunk)
+				.on_conflict((dsl::make_id, dsl::name))
+				.do_update()
+				.set(dsl::avito_id.eq(excluded(dsl::avito_id)))
+				.returning((dsl::model_id, dsl::make_id, dsl::name))
+				.get_results::<(Uuid, Uuid, String)>(conn)?;
+			model_ids.extend(
+				rows.into_iter()
+					.map(|(id, make_id, name)| ((make_id, name), id)),
+			);
+		}
+
+		let mut new_generations: BTreeMap<(Uuid, &str), NewAvitoCarGeneration> = BTreeMap::new();
+		for make in makes {
+			let make_id = make_ids[&make.name];
+			for model in &make.models {
+				let model_id = model_ids[&(make_id, model.name.clone())];
+				for generation in &model.generations {
+					new_generations.insert(
+						(model_id, generation.name.as_str()),
+						NewAvitoCarGeneration {
+							model_id,
+							avito_id: generation.avito_id.as_deref(),
+							name: &generation.name,
+						},
+					);
+				}
+			}
+		}
+		let new_generations: Vec<NewAvitoCarGeneration> = new_generations.into_values().collect();
+
+		let mut generation_ids: HashMap<(Uuid, String), Uuid> = HashMap::new();
+		for chunk in new_generations.chunks(UPSERT_CHUNK_SIZE) {
+			use crate::schema::avito_car_generations::dsl;
+			let rows = diesel::insert_into(dsl::avito_car_generations)
+				.values(chunk)
+				.on_conflict((dsl::model_id, dsl::name))
+				.do_update()
+				.set(dsl::avito_id.eq(excluded(dsl::avito_id)))
+				.returning((dsl::generation_id, dsl::model_id, dsl::name))
+				.get_results::<(Uuid, Uuid, String)>(conn)?;
+			generation_ids.extend(
+				rows.into_iter()
+					.map(|(id, model_id, name)| ((model_id, name), id)),
+			);
+		}
+
+		let mut new_modifications: BTreeMap<(Uuid, &str), NewAvitoCarModification> =
+			BTreeMap::new();
+		for make in makes {
+			let make_id = make_ids[&make.name];
+			for model in &make.models {
+				let model_id = model_ids[&(make_id, model.name.clone())];
+				for generation in &model.generations {
+					let generation_id = generation_ids[&(model_id, generation.name.clone())];
+					for modification in &generation.modifications {
+						new_modifications.insert(
+							(generation_id, modification.name.as_str()),
+							NewAvitoCarModification {
+								generation_id,
+								avito_id: modification.avito_id.as_deref(),
+								name: &modification.name,
+								body_type: modification.body_type.as_deref(),
+								doors: modification.doors.as_deref(),
+							},
+						);
+					}
+				}
+			}
+		}
+		let new_modifications: Vec<NewAvitoCarModification> =
+			new_modifications.into_values().collect();
+
+		for chunk in new_modifications.chunks(UPSERT_CHUNK_SIZE) {
+			use crate::schema::avito_car_modifications::dsl;
+			diesel::insert_into(dsl::avito_car_modifications)
+				.values(chunk)
+				.on_conflict((dsl::generation_id, dsl::name))
+				.do_update()
+				.set((
+					dsl::avito_id.eq(excluded(dsl::avito_id)),
+					dsl::body_type.eq(excluded(dsl::body_type)),
+					dsl::doors.eq(excluded(dsl::doors)),
+				))
+				.execute(conn)?;
+		}
+
+		Ok(AutocatalogImportSummary {
+			makes: new_makes.len(),
+			models: new_models.len(),
+			generations: new_generations.len(),
+			modifications: new_modifications.len(),
+		})
+	})
+}
@@ -0,0 +1,14 @@
+pub mod autocatalog_xml;
+pub mod config;
+pub mod get_autocatalog_generations;
+pub mod get_autocatalog_makes;
+pub mod get_autocatalog_models;
+pub mod get_autocatalog_modifications;
+pub mod import_autocatalog;
+pub mod upload_autocatalog;
+
+use actix_web::web;
+
+pub fn avito_autocatalog_config(cfg: &mut web::ServiceConfig) {
+	cfg.configure(config::avito_autocatalog_routes);
+}
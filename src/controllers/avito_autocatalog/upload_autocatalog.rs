@@ -0,0 +1,52 @@
+use crate::controllers::avito_autocatalog::import_autocatalog::run_autocatalog_import;
+use crate::permissions::{Permission, RequirePermission};
+use crate::{jwt_auth::JwtMiddleware, AppState};
+use actix_multipart::Multipart;
+use actix_web::{web, HttpResponse, Result};
+use futures::StreamExt;
+use serde_json::json;
+use std::io::Cursor;
+
+// POST multipart form with an Autocatalog.xml file (plain, .gz or .zip) in the "file" field
+#[actix_web::post(
+	"/avito/autocatalog/import/upload",
+	wrap = "RequirePermission(Permission::ImportAutocatalog)"
+)]
+pub async fn upload_autocatalog(
+	mut payload: Multipart,
+	data: web::Data<AppState>,
+	_: JwtMiddleware,
+) -> Result<HttpResponse> {
+	let max_upload_size = data.env.xml_max_document_size;
+	let mut file: Option<Vec<u8>> = None;
+
+	while let Some(field) = payload.next().await {
+		let mut field = field?;
+		let is_file = field.name() == Some("file");
+
+		let mut bytes = Vec::new();
+		while let Some(chunk) = field.next().await {
+			let chunk = chunk?;
+			if (bytes.len() + chunk.len()) as u64 > max_upload_size {
+				return Ok(HttpResponse::PayloadTooLarge().json(json!({
+					"status": "error",
+					"message": format!("Uploaded file exceeds {} bytes", max_upload_size)
+				})));
+			}
+			bytes.extend_from_slice(&chunk);
+		}
+
+		if is_file {
+			file = Some(bytes);
+		}
+	}
+
+	let Some(file) = file.filter(|file| !file.is_empty()) else {
+		return Ok(HttpResponse::BadRequest().json(json!({
+			"status": "error",
+			"message": "Autocatalog file is required"
+		})));
+	};
+
+	Ok(run_autocatalog_import(&data, Box::new(Cursor::new(file))).await)
+}
@@ -0,0 +1,306 @@
+use crate::config::Config;
+use crate::models::{
+	ApiError, AvitoGetBalanceApiResponse, AvitoGetItemsApiResponse, AvitoItemAnalyticsRequest,
+	AvitoItemAnalyticsResponse, AvitoTokenResponse, AvitoUpdatePriceResponse, AvitoUserProfile,
+};
+use async_trait::async_trait;
+use reqwest::{
+	header::{
+		HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER, USER_AGENT,
+	},
+	Client, RequestBuilder,
+};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use std::time::Duration;
+
+// Avito answers the catalog docs within a few seconds; slower responses fall back to the cache
+const CATALOG_TIMEOUT_SECS: u64 = 15;
+const FIELD_VALUES_TIMEOUT_SECS: u64 = 5;
+
+// Everything the service asks Avito for. Handlers reach it through AppState, so tests can
+// swap the HTTP implementation for FakeAvitoApi.
+#[async_trait]
+pub trait AvitoApi: Send + Sync {
+	async fn request_token(
+		&self,
+		client_id: &str,
+		client_secret: &str,
+	) -> Result<AvitoTokenResponse, ApiError>;
+
+	// Avito returns only active items unless `status` lists others, e.g. "active,old"
+	async fn get_items(
+		&self,
+		token: &str,
+		page: i32,
+		per_page: i32,
+		status: Option<&str>,
+	) -> Result<AvitoGetItemsApiResponse, ApiError>;
+
+	async fn get_balance(&self, token: &str) -> Result<AvitoGetBalanceApiResponse, ApiError>;
+
+	async fn get_user_profile(&self, token: &str) -> Result<AvitoUserProfile, ApiError>;
+
+	async fn get_item_analytics(
+		&self,
+		token: &str,
+		avito_user_id: &str,
+		request: &AvitoItemAnalyticsRequest,
+	) -> Result<AvitoItemAnalyticsResponse, ApiError>;
+
+	async fn update_price(
+		&self,
+		token: &str,
+		item_id: &str,
+		price: f64,
+	) -> Result<AvitoUpdatePriceResponse, ApiError>;
+
+	// Autoload docs are passed to the editor as they are, so they stay untyped
+	async fn get_categories_tree(&self, token: &str) -> Result<serde_json::Value, ApiError>;
+
+	async fn get_category_fields(
+		&self,
+		token: &str,
+		avito_slug: &str,
+	) -> Result<serde_json::Value, ApiError>;
+
+	async fn get_field_values(
+		&self,
+		token: &str,
+		values_link: &str,
+	) -> Result<serde_json::Value, ApiError>;
+}
+
+// Production client; one reqwest::Client, and so one connection pool, for all requests
+pub struct AvitoHttpApi {
+	client: Client,
+	base_url: String,
+}
+
+impl AvitoHttpApi {
+	pub fn new(config: &Config) -> Self {
+		let client = Client::builder()
+			.danger_accept_invalid_certs(config.avito_accept_invalid_certs)
+			.default_headers(HeaderMap::from_iter([
+				(USER_AGENT, HeaderValue::from_static("YourApp/1.0")),
+				(ACCEPT, HeaderValue::from_static("application/json")),
+			]))
+			.build()
+			.expect("Failed to build the Avito HTTP client");
+
+		Self {
+			client,
+			base_url: config.avito_base_url.trim_end_matches('/').to_string(),
+		}
+	}
+
+	fn authorized(&self, request: RequestBuilder, token: &str) -> RequestBuilder {
+		request.header(AUTHORIZATION, format!("Bearer {}", token))
+	}
+}
+
+// Retry-After is either a number of seconds or an HTTP date
+fn parse_retry_after(value: &str) -> Option<u64> {
+	let value = value.trim();
+	value.parse().ok().or_else(|| {
+		let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+		Some((date.timestamp() - chrono::Utc::now().timestamp()).max(0) as u64)
+	})
+}
+
+// Turn an Avito response into the typed body, keeping Avito's status and message on errors
+async fn parse_response<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, ApiError> {
+	if !response.status().is_success() {
+		let status_code = response.status().as_u16();
+		let retry_after = response
+			.headers()
+			.get(RETRY_AFTER)
+			.and_then(|value| value.to_str().ok())
+			.and_then(parse_retry_after);
+		let error_body = response.text().await?;
+		return Err(match retry_after {
+			Some(secs) => ApiError::AvitoRetryAfter(status_code, secs, error_body),
+			None => ApiError::AvitoApiError(status_code, error_body),
+		});
+	}
+
+	let response_text = response.text().await?;
+	serde_json::from_str(&response_text)
+		.map_err(|e| ApiError::JsonParseError(format!("{}: {}", e, response_text)))
+}
+
+#[async_trait]
+impl AvitoApi for AvitoHttpApi {
+	async fn request_token(
+		&self,
+		client_id: &str,
+		client_secret: &str,
+	) -> Result<AvitoTokenResponse, ApiError> {
+		let params = [
+			("client_id", client_id),
+			("client_secret", client_secret),
+			("grant_type", "client_credentials"),
+		];
+
+		let response = self
+			.client
+			.post(format!("{}/token", self.base_url))
+			.form(&params)
+			.send()
+			.await?;
+
+		parse_response(response).await
+	}
+
+	async fn get_items(
+		&self,
+		token: &str,
+		page: i32,
+		per_page: i32,
+		status: Option<&str>,
+	) -> Result<AvitoGetItemsApiResponse, ApiError> {
+		let mut request = self
+			.client
+			.get(format!("{}/core/v1/items", self.base_url))
+			.query(&[("page", page), ("per_page", per_page)]);
+		if let Some(status) = status {
+			request = request.query(&[("status", status)]);
+		}
+
+		parse_response(self.authorized(request, token).send().await?).await
+	}
+
+	async fn get_balance(&self, token: &str) -> Result<AvitoGetBalanceApiResponse, ApiError> {
+		let request = self
+			.client
+			.post(format!("{}/cpa/v3/balanceInfo", self.base_url))
+			.json(&json!({}));
+
+		parse_response(self.authorized(request, token).send().await?).await
+	}
+
+	async fn get_user_profile(&self, token: &str) -> Result<AvitoUserProfile, ApiError> {
+		let request = self.client.get(format!("{}/user/me", self.base_url));
+
+		parse_response(self.authorized(request, token).send().await?).await
+	}
+
+	async fn get_item_analytics(
+		&self,
+		token: &str,
+		avito_user_id: &str,
+		request: &AvitoItemAnalyticsRequest,
+	) -> Result<AvitoItemAnalyticsResponse, ApiError> {
+		let request = self
+			.client
+			.post(format!(
+				"{}/stats/v2/accounts/{}/items",
+				self.base_url, avito_user_id
+			))
+			.header(CONTENT_TYPE, "application/json")
+			.json(request);
+
+		parse_response(self.authorized(request, token).send().await?).await
+	}
+
+	async fn update_price(
+		&self,
+		token: &str,
+		item_id: &str,
+		price: f64,
+	) -> Result<AvitoUpdatePriceResponse, ApiError> {
+		let request = self
+			.client
+			.post(format!(
+				"{}/core/v1/items/{}/update_price",
+				self.base_url, item_id
+			))
+			.json(&json!({ "price": price }));
+
+		parse_response(self.authorized(request, token).send().await?).await
+	}
+
+	async fn get_categories_tree(&self, token: &str) -> Result<serde_json::Value, ApiError> {
+		let request = self
+			.client
+			.get(format!("{}/autoload/v1/user-docs/tree", self.base_url))
+			.timeout(Duration::from_secs(CATALOG_TIMEOUT_SECS));
+
+		parse_response(self.authorized(request, token).send().await?).await
+	}
+
+	async fn get_category_fields(
+		&self,
+		token: &str,
+		avito_slug: &str,
+	) -> Result<serde_json::Value, ApiError> {
+		let request = self
+			.client
+			.get(format!(
+				"{}/autoload/v1/user-docs/node/{}/fields",
+				self.base_url, avito_slug
+			))
+			.timeout(Duration::from_secs(CATALOG_TIMEOUT_SECS));
+
+		parse_response(self.authorized(request, token).send().await?).await
+	}
+
+	async fn get_field_values(
+		&self,
+		token: &str,
+		values_link: &str,
+	) -> Result<serde_json::Value, ApiError> {
+		let request = self
+			.client
+			.get(values_link)
+			.timeout(Duration::from_secs(FIELD_VALUES_TIMEOUT_SECS));
+
+		parse_response(self.authorized(request, token).send().await?).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::models::{AvitoGetItemsApiResponse, AvitoItemAnalyticsResponse};
+	use serde_json::json;
+
+	#[test]
+	fn test_typed_responses() {
+		let items: AvitoGetItemsApiResponse = serde_json::from_value(json!({
+			"meta": { "page": 1, "per_page": 25 },
+			"resources": [{
+				"id": 24122231,
+				"title": "Зимние шины R16",
+				"price": 35000,
+				"status": "active",
+				"url": "https://www.avito.ru/items/24122231",
+				"address": "Москва",
+				"category": { "id": 10, "name": "Запчасти и аксессуары" }
+			}]
+		}))
+		.unwrap();
+		assert_eq!(items.meta.per_page, 25);
+		assert_eq!(items.resources[0].price, Some(35000.0));
+		assert_eq!(items.resources[0].category.as_ref().unwrap().id, 10);
+
+		let analytics: AvitoItemAnalyticsResponse = serde_json::from_value(json!({
+			"result": {
+				"items": [{
+					"itemId": 24122231,
+					"stats": [{ "date": "2026-10-17", "uniqViews": 12, "uniqContacts": 1 }]
+				}]
+			}
+		}))
+		.unwrap();
+		let point = &analytics.result.items[0].stats[0];
+		assert_eq!(point.date, "2026-10-17");
+		assert_eq!(point.metrics["uniqViews"], json!(12));
+
+		assert_eq!(super::parse_retry_after(" 30 "), Some(30));
+		assert_eq!(
+			super::parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+			Some(0)
+		);
+		assert_eq!(super::parse_retry_after("soon"), None);
+	}
+}
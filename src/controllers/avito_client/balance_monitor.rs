@@ -0,0 +1,246 @@
+use crate::controllers::avito_client::avito_api::AvitoApi;
+use crate::controllers::avito_client::items_sync_scheduler::load_connected_accounts;
+use crate::controllers::avito_client::token_manager::AvitoTokenManager;
+use crate::controllers::websocket::WebSocketConnections;
+use crate::models::{
+	ApiError, AvitoAccount, AvitoBalanceAlert, AvitoBalanceHistory, BalanceAlertMessage,
+	CreateAvitoBalanceHistory,
+};
+use crate::utils::webhook::resolve_webhook_url;
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+use uuid::Uuid;
+
+const WEBHOOK_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThresholdTransition {
+	Dropped,
+	Recovered,
+	Unchanged,
+}
+
+// Alert only when the balance crosses the threshold downwards, not on every poll below it
+pub fn threshold_transition(was_below: bool, balance: f64, threshold: f64) -> ThresholdTransition {
+	match (was_below, balance < threshold) {
+		(false, true) => ThresholdTransition::Dropped,
+		(true, false) => ThresholdTransition::Recovered,
+		_ => ThresholdTransition::Unchanged,
+	}
+}
+
+// Polls the balance of the connected accounts, keeps the history and sends low-balance
+// alerts to the user's WebSocket sessions and the alert webhook
+#[derive(Clone)]
+pub struct BalanceMonitor {
+	db_pool: Pool<ConnectionManager<PgConnection>>,
+	tokens: AvitoTokenManager,
+	api: Arc<dyn AvitoApi>,
+	ws_server: WebSocketConnections,
+	http: reqwest::Client,
+	default_webhook_url: Option<String>,
+}
+
+impl BalanceMonitor {
+	pub fn new(
+		db_pool: Pool<ConnectionManager<PgConnection>>,
+		tokens: AvitoTokenManager,
+		api: Arc<dyn AvitoApi>,
+		ws_server: WebSocketConnections,
+		default_webhook_url: Option<String>,
+	) -> Self {
+		Self {
+			db_pool,
+			tokens,
+			api,
+			ws_server,
+			http: reqwest::Client::builder()
+				.timeout(std::time::Duration::from_secs(WEBHOOK_TIMEOUT_SECS))
+				.build()
+				.expect("Failed to build the webhook HTTP client"),
+			default_webhook_url,
+		}
+	}
+
+	// Poll every connected account each `interval_minutes`; 0 turns the job off
+	pub async fn run(self, interval_minutes: u64) {
+		if interval_minutes == 0 {
+			log::info!("Avito balance polling is disabled");
+			return;
+		}
+
+		loop {
+			let account_ids = match load_connected_accounts(&self.db_pool) {
+				Ok(account_ids) => account_ids,
+				Err(e) => {
+					log::error!("Failed to load Avito accounts for balance polling: {}", e);
+					Vec::new()
+				}
+			};
+
+			for account_id in account_ids {
+				if let Err(e) = self.poll_account(account_id).await {
+					log::warn!("Balance poll of account {} failed: {}", account_id, e);
+				}
+			}
+
+			sleep(Duration::from_secs(interval_minutes * 60)).await;
+		}
+	}
+
+	// Record the current balance and alert if it has just dropped under the threshold
+	pub async fn poll_account(&self, account_id: Uuid) -> Result<AvitoBalanceHistory, ApiError> {
+		let api = self.api.as_ref();
+		let balance = self
+			.tokens
+			.with_token(&self.db_pool, account_id, |avito_token| async move {
+				api.get_balance(&avito_token).await
+			})
+			.await?
+			.balance;
+
+		let mut conn = self
+			.db_pool
+			.get()
+			.map_err(|e| ApiError::Other(e.to_string()))?;
+		let point: AvitoBalanceHistory =
+			diesel::insert_into(crate::schema::avito_balance_history::table)
+				.values(CreateAvitoBalanceHistory {
+					account_id,
+					balance,
+				})
+				.get_result(&mut conn)?;
+
+		let Some(alert) = crate::schema::avito_balance_alerts::table
+			.find(account_id)
+			.filter(crate::schema::avito_balance_alerts::enabled.eq(true))
+			.first::<AvitoBalanceAlert>(&mut conn)
+			.optional()?
+		else {
+			return Ok(point);
+		};
+
+		match threshold_transition(alert.below_threshold, balance, alert.threshold) {
+			ThresholdTransition::Unchanged => {}
+			ThresholdTransition::Recovered => {
+				diesel::update(crate::schema::avito_balance_alerts::table.find(account_id))
+					.set(crate::schema::avito_balance_alerts::below_threshold.eq(false))
+					.execute(&mut conn)?;
+			}
+			ThresholdTransition::Dropped => {
+				diesel::update(crate::schema::avito_balance_alerts::table.find(account_id))
+					.set((
+						crate::schema::avito_balance_alerts::below_threshold.eq(true),
+						crate::schema::avito_balance_alerts::last_alert_ts.eq(Utc::now()),
+					))
+					.execute(&mut conn)?;
+				let account = crate::schema::avito_accounts::table
+					.find(account_id)
+					.first::<AvitoAccount>(&mut conn)?;
+				drop(conn);
+
+				let message = BalanceAlertMessage {
+					kind: "avito_balance_low",
+					account_id,
+					client_id: account.client_id,
+					balance,
+					threshold: alert.threshold,
+					recorded_ts: point.recorded_ts,
+				};
+				log::warn!(
+					"Balance of Avito account {} dropped to {} (threshold {})",
+					account_id,
+					balance,
+					alert.threshold
+				);
+				self.notify(account.user_id, alert.webhook_url.as_deref(), &message)
+					.await;
+			}
+		}
+
+		Ok(point)
+	}
+
+	async fn notify(
+		&self,
+		user_id: Uuid,
+		webhook_url: Option<&str>,
+		message: &BalanceAlertMessage,
+	) {
+		if let Ok(text) = serde_json::to_string(message) {
+			self.ws_server
+				.broadcast_message_to_user(&user_id.to_string(), &text)
+				.await;
+		}
+
+		// The host of a user's webhook is checked again, it may resolve elsewhere by now.
+		// The configured default webhook is trusted.
+		let (webhook_url, client) = match webhook_url {
+			Some(webhook_url) => {
+				let client = resolve_webhook_url(webhook_url).await.and_then(|target| {
+					target
+						.client(Duration::from_secs(WEBHOOK_TIMEOUT_SECS))
+						.map_err(|e| e.to_string())
+				});
+				match client {
+					Ok(client) => (webhook_url, client),
+					Err(e) => {
+						log::warn!(
+							"Refused to deliver the balance alert of account {} to {}: {}",
+							message.account_id,
+							webhook_url,
+							e
+						);
+						return;
+					}
+				}
+			}
+			None => match self.default_webhook_url.as_deref() {
+				Some(webhook_url) => (webhook_url, self.http.clone()),
+				None => return,
+			},
+		};
+		let sent = client
+			.post(webhook_url)
+			.json(message)
+			.send()
+			.await
+			.and_then(|response| response.error_for_status());
+		if let Err(e) = sent {
+			log::error!(
+				"Failed to deliver the balance alert of account {} to {}: {}",
+				message.account_id,
+				webhook_url,
+				e
+			);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_threshold_transition() {
+		assert_eq!(
+			threshold_transition(false, 900.0, 1000.0),
+			ThresholdTransition::Dropped
+		);
+		assert_eq!(
+			threshold_transition(true, 500.0, 1000.0),
+			ThresholdTransition::Unchanged
+		);
+		assert_eq!(
+			threshold_transition(true, 1000.0, 1000.0),
+			ThresholdTransition::Recovered
+		);
+		assert_eq!(
+			threshold_transition(false, 5000.0, 1000.0),
+			ThresholdTransition::Unchanged
+		);
+	}
+}
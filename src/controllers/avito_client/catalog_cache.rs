@@ -0,0 +1,215 @@
+use crate::models::ApiError;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use serde_json::Value;
+use std::future::Future;
+
+// Tables the Avito catalog is cached in. The docs are the same for every account,
+// so entries are shared between users and not tied to a token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatalogCache {
+	// The whole category tree, stored under CATEGORY_TREE_KEY
+	Tree,
+	// Node fields of a category, keyed by its slug, with dictionary links left unresolved
+	Fields,
+	// Dictionaries behind values_link_json, keyed by the link
+	Values,
+}
+
+pub const CATEGORY_TREE_KEY: &str = "tree";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheMode {
+	// Serve fresh entries from the cache; fall back to a stale entry when Avito fails
+	PreferCache,
+	// Always ask Avito and report its errors, used by the manual refresh
+	Refresh,
+}
+
+pub struct CachedEntry {
+	pub data: Value,
+	pub expires_ts: DateTime<Utc>,
+}
+
+impl CachedEntry {
+	pub fn is_fresh(&self, now: DateTime<Utc>) -> bool {
+		self.expires_ts > now
+	}
+}
+
+pub fn load_cached(
+	conn: &mut PgConnection,
+	cache: CatalogCache,
+	key: &str,
+) -> Result<Option<CachedEntry>, diesel::result::Error> {
+	let entry = match cache {
+		CatalogCache::Tree => crate::schema::avito_category_tree_cache::table
+			.find(key)
+			.select((
+				crate::schema::avito_category_tree_cache::data,
+				crate::schema::avito_category_tree_cache::expires_ts,
+			))
+			.first::<(Value, DateTime<Utc>)>(conn)
+			.optional()?,
+		CatalogCache::Fields => crate::schema::avito_category_fields_cache::table
+			.find(key)
+			.select((
+				crate::schema::avito_category_fields_cache::data,
+				crate::schema::avito_category_fields_cache::expires_ts,
+			))
+			.first::<(Value, DateTime<Utc>)>(conn)
+			.optional()?,
+		CatalogCache::Values => crate::schema::avito_field_values_cache::table
+			.find(key)
+			.select((
+				crate::schema::avito_field_values_cache::data,
+				crate::schema::avito_field_values_cache::expires_ts,
+			))
+			.first::<(Value, DateTime<Utc>)>(conn)
+			.optional()?,
+	};
+
+	Ok(entry.map(|(data, expires_ts)| CachedEntry { data, expires_ts }))
+}
+
+pub fn store_cached(
+	conn: &mut PgConnection,
+	cache: CatalogCache,
+	key: &str,
+	data: &Value,
+	ttl_secs: i64,
+) -> Result<(), diesel::result::Error> {
+	let fetched_ts = Utc::now();
+	let expires_ts = fetched_ts + ChronoDuration::seconds(ttl_secs);
+
+	match cache {
+		CatalogCache::Tree => {
+			use crate::schema::avito_category_tree_cache::dsl;
+			diesel::insert_into(dsl::avito_category_tree_cache)
+				.values((
+					dsl::cache_key.eq(key),
+					dsl::data.eq(data),
+					dsl::fetched_ts.eq(fetched_ts),
+					dsl::expires_ts.eq(expires_ts),
+				))
+				.on_conflict(dsl::cache_key)
+				.do_update()
+				.set((
+					dsl::data.eq(data),
+					dsl::fetched_ts.eq(fetched_ts),
+					dsl::expires_ts.eq(expires_ts),
+				))
+				.execute(conn)?;
+		}
+		CatalogCache::Fields => {
+			use crate::schema::avito_category_fields_cache::dsl;
+			diesel::insert_into(dsl::avito_category_fields_cache)
+				.values((
+					dsl::avito_slug.eq(key),
+					dsl::data.eq(data),
+					dsl::fetched_ts.eq(fetched_ts),
+					dsl::expires_ts.eq(expires_ts),
+				))
+				.on_conflict(dsl::avito_slug)
+				.do_update()
+				.set((
+					dsl::data.eq(data),
+					dsl::fetched_ts.eq(fetched_ts),
+					dsl::expires_ts.eq(expires_ts),
+				))
+				.execute(conn)?;
+		}
+		CatalogCache::Values => {
+			use crate::schema::avito_field_values_cache::dsl;
+			diesel::insert_into(dsl::avito_field_values_cache)
+				.values((
+					dsl::values_link.eq(key),
+					dsl::data.eq(data),
+					dsl::fetched_ts.eq(fetched_ts),
+					dsl::expires_ts.eq(expires_ts),
+				))
+				.on_conflict(dsl::values_link)
+				.do_update()
+				.set((
+					dsl::data.eq(data),
+					dsl::fetched_ts.eq(fetched_ts),
+					dsl::expires_ts.eq(expires_ts),
+				))
+				.execute(conn)?;
+		}
+	}
+
+	Ok(())
+}
+
+// Slugs of every category whose fields are cached, for a full refresh
+pub fn cached_category_slugs(
+	conn: &mut PgConnection,
+) -> Result<Vec<String>, diesel::result::Error> {
+	crate::schema::avito_category_fields_cache::table
+		.select(crate::schema::avito_category_fields_cache::avito_slug)
+		.order(crate::schema::avito_category_fields_cache::avito_slug.asc())
+		.load(conn)
+}
+
+// Return the cached entry when it is fresh, otherwise fetch it from Avito and store it.
+// Cache failures never fail the request: the worst case is a live call to Avito.
+pub async fn cached_or_fetch<F, Fut>(
+	db_pool: &Pool<ConnectionManager<PgConnection>>,
+	cache: CatalogCache,
+	key: &str,
+	ttl_secs: i64,
+	mode: CacheMode,
+	fetch: F,
+) -> Result<Value, ApiError>
+where
+	F: FnOnce() -> Fut,
+	Fut: Future<Output = Result<Value, ApiError>>,
+{
+	let cached = match db_pool.get() {
+		Ok(mut conn) => load_cached(&mut conn, cache, key).unwrap_or_else(|e| {
+			log::warn!("Failed to read {:?} cache for {}: {}", cache, key, e);
+			None
+		}),
+		Err(e) => {
+			log::warn!("No connection for {:?} cache: {}", cache, e);
+			None
+		}
+	};
+
+	if mode == CacheMode::PreferCache {
+		if let Some(entry) = &cached {
+			if entry.is_fresh(Utc::now()) {
+				return Ok(entry.data.clone());
+			}
+		}
+	}
+
+	match fetch().await {
+		Ok(data) => {
+			let stored = db_pool
+				.get()
+				.map_err(|e| e.to_string())
+				.and_then(|mut conn| {
+					store_cached(&mut conn, cache, key, &data, ttl_secs).map_err(|e| e.to_string())
+				});
+			if let Err(e) = stored {
+				log::warn!("Failed to store {:?} cache for {}: {}", cache, key, e);
+			}
+			Ok(data)
+		}
+		Err(e) => match cached {
+			Some(entry) if mode == CacheMode::PreferCache => {
+				log::warn!(
+					"Avito request for {:?} {} failed, serving stale cache: {}",
+					cache,
+					key,
+					e
+				);
+				Ok(entry.data)
+			}
+			_ => Err(e),
+		},
+	}
+}
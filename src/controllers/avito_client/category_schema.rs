@@ -0,0 +1,329 @@
+use crate::models::AdFieldValues;
+use chrono::{DateTime, NaiveDate};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+// Rules for one tag, collected from the autoload node fields description
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FieldRule {
+	pub tag: String,
+	pub required: bool,
+	pub data_type: Option<String>,
+	// None when the field has no dictionary and accepts any value
+	pub allowed_values: Option<BTreeSet<String>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CategorySchema {
+	pub fields: BTreeMap<String, FieldRule>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationErrorCode {
+	MissingRequired,
+	ValueNotAllowed,
+	InvalidDataType,
+	UnknownTag,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FieldValidationError {
+	pub tag: String,
+	pub code: ValidationErrorCode,
+	pub value: Option<String>,
+	pub message: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct AdValidationReport {
+	pub valid: bool,
+	pub errors: Vec<FieldValidationError>,
+	// Tags Avito does not know for this category; they are ignored on autoload
+	pub warnings: Vec<FieldValidationError>,
+}
+
+impl CategorySchema {
+	// Build the schema from the response of fetch_category_fields. A tag is required when one
+	// of its content variants is required without depending on another field.
+	pub fn from_fields_json(node_fields: &Value) -> Self {
+		let mut schema = CategorySchema::default();
+
+		let fields = node_fields
+			.get("fields")
+			.and_then(|fields| fields.as_array())
+			.cloned()
+			.unwrap_or_default();
+
+		for field in &fields {
+			schema.add_field(field);
+			if let Some(children) = field.get("children").and_then(|c| c.as_array()) {
+				for child in children {
+					schema.add_field(child);
+				}
+			}
+		}
+
+		schema
+	}
+
+	fn add_field(&mut self, field: &Value) {
+		let Some(tag) = field.get("tag").and_then(|t| t.as_str()) else {
+			return;
+		};
+
+		let rule = self
+			.fields
+			.entry(tag.to_string())
+			.or_insert_with(|| FieldRule {
+				tag: tag.to_string(),
+				..Default::default()
+			});
+
+		let content = field
+			.get("content")
+			.and_then(|c| c.as_array())
+			.cloned()
+			.unwrap_or_default();
+
+		for item in &content {
+			let conditional = item
+				.get("dependency")
+				.is_some_and(|d| !d.is_null() && d.as_array().is_none_or(|d| !d.is_empty()));
+			if item.get("required").and_then(|r| r.as_bool()) == Some(true) && !conditional {
+				rule.required = true;
+			}
+
+			if rule.data_type.is_none() {
+				rule.data_type = item
+					.get("data_type")
+					.and_then(|t| t.as_str())
+					.map(|t| t.to_lowercase());
+			}
+
+			if let Some(values) = item.get("values") {
+				let mut allowed = rule.allowed_values.take().unwrap_or_default();
+				collect_dictionary_values(values, &mut allowed);
+				rule.allowed_values = Some(allowed).filter(|allowed| !allowed.is_empty());
+			}
+		}
+	}
+
+	// Check one ad's stored (tag, values) pairs against the schema
+	pub fn validate_ad(&self, ad: &AdFieldValues) -> AdValidationReport {
+		let mut report = AdValidationReport::default();
+
+		let present: BTreeSet<&str> = ad
+			.iter()
+			.filter(|(_, values)| values.iter().any(|v| !v.trim().is_empty()))
+			.map(|(tag, _)| tag.as_str())
+			.collect();
+
+		for rule in self.fields.values() {
+			if rule.required && !present.contains(rule.tag.as_str()) {
+				report.errors.push(FieldValidationError {
+					tag: rule.tag.clone(),
+					code: ValidationErrorCode::MissingRequired,
+					value: None,
+					message: format!("Required tag {} is missing", rule.tag),
+				});
+			}
+		}
+
+		for (tag, values) in ad {
+			let Some(rule) = self.fields.get(tag) else {
+				report.warnings.push(FieldValidationError {
+					tag: tag.clone(),
+					code: ValidationErrorCode::UnknownTag,
+					value: None,
+					message: format!("Tag {} is not part of the category", tag),
+				});
+				continue;
+			};
+
+			for value in values.iter().map(|v| v.trim()).filter(|v| !v.is_empty()) {
+				if let Some(allowed) = &rule.allowed_values {
+					if !allowed.contains(value) {
+						report.errors.push(FieldValidationError {
+							tag: tag.clone(),
+							code: ValidationErrorCode::ValueNotAllowed,
+							value: Some(value.to_string()),
+							message: format!("Value is not in the dictionary of {}", tag),
+						});
+						continue;
+					}
+				}
+
+				if let Some(data_type) = &rule.data_type {
+					if !matches_data_type(data_type, value) {
+						report.errors.push(FieldValidationError {
+							tag: tag.clone(),
+							code: ValidationErrorCode::InvalidDataType,
+							value: Some(value.to_string()),
+							message: format!("Value of {} must be {}", tag, data_type),
+						});
+					}
+				}
+			}
+		}
+
+		report.valid = report.errors.is_empty();
+		report
+	}
+}
+
+// Dictionaries come either as a plain list or wrapped in an object, with entries being
+// strings or objects that carry the value under "value", "name" or "title"
+fn collect_dictionary_values(values: &Value, allowed: &mut BTreeSet<String>) {
+	match values {
+		Value::Array(entries) => {
+			for entry in entries {
+				match entry {
+					Value::String(value) => {
+						allowed.insert(value.trim().to_string());
+					}
+					Value::Number(value) => {
+						allowed.insert(value.to_string());
+					}
+					Value::Object(object) => {
+						let value = ["value", "name", "title"]
+							.iter()
+							.find_map(|key| object.get(*key))
+							.and_then(|value| match value {
+								Value::String(value) => Some(value.trim().to_string()),
+								Value::Number(value) => Some(value.to_string()),
+								_ => None,
+							});
+						if let Some(value) = value {
+							allowed.insert(value);
+						}
+					}
+					_ => {}
+				}
+			}
+		}
+		Value::Object(object) => {
+			if let Some(values) = object.get("values") {
+				collect_dictionary_values(values, allowed);
+			}
+		}
+		_ => {}
+	}
+}
+
+fn matches_data_type(data_type: &str, value: &str) -> bool {
+	match data_type {
+		"integer" | "int" => value.parse::<i64>().is_ok(),
+		"float" | "double" | "number" | "decimal" => value.replace(',', ".").parse::<f64>().is_ok(),
+		"bool" | "boolean" => matches!(
+			value.to_lowercase().as_str(),
+			"true" | "false" | "1" | "0" | "да" | "нет"
+		),
+		"date" => {
+			NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok()
+				|| DateTime::parse_from_rfc3339(value).is_ok()
+		}
+		_ => true,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	fn schema() -> CategorySchema {
+		CategorySchema::from_fields_json(&json!({
+			"fields": [
+				{ "tag": "Id", "content": [{ "data_type": "string", "required": true }] },
+				{ "tag": "Price", "content": [{ "data_type": "integer", "required": true }] },
+				{
+					"tag": "Condition",
+					"content": [{
+						"data_type": "string",
+						"required": true,
+						"values": [{ "value": "Новое" }, { "value": "Б/у" }]
+					}]
+				},
+				{
+					"tag": "TireType",
+					"content": [{
+						"required": true,
+						"dependency": [{ "tag": "GoodsType", "value": "Шины" }],
+						"values": { "values": ["Летние", "Зимние"] }
+					}],
+					"children": [
+						{ "tag": "RimDiameter", "content": [{ "data_type": "float" }] }
+					]
+				}
+			]
+		}))
+	}
+
+	fn ad(fields: &[(&str, &[&str])]) -> AdFieldValues {
+		fields
+			.iter()
+			.map(|(tag, values)| {
+				(
+					tag.to_string(),
+					values.iter().map(|v| v.to_string()).collect(),
+				)
+			})
+			.collect()
+	}
+
+	#[test]
+	fn test_schema_from_fields_json() {
+		let schema = schema();
+
+		assert!(schema.fields["Price"].required);
+		assert!(!schema.fields["TireType"].required);
+		assert_eq!(
+			schema.fields["TireType"].allowed_values,
+			Some(BTreeSet::from(["Летние".to_string(), "Зимние".to_string()]))
+		);
+		assert_eq!(
+			schema.fields["RimDiameter"].data_type.as_deref(),
+			Some("float")
+		);
+		assert_eq!(schema.fields["Id"].allowed_values, None);
+	}
+
+	#[test]
+	fn test_validate_ad() {
+		let schema = schema();
+
+		let valid = schema.validate_ad(&ad(&[
+			("Id", &["1"]),
+			("Price", &["1500"]),
+			("Condition", &["Новое"]),
+			("RimDiameter", &["16,5"]),
+		]));
+		assert!(valid.valid, "{:?}", valid.errors);
+
+		let invalid = schema.validate_ad(&ad(&[
+			("Id", &["2"]),
+			("Price", &["дорого"]),
+			("TireType", &["Всесезонные"]),
+			("Color", &["red"]),
+		]));
+		assert!(!invalid.valid);
+
+		let codes: Vec<(&str, ValidationErrorCode)> = invalid
+			.errors
+			.iter()
+			.map(|error| (error.tag.as_str(), error.code))
+			.collect();
+		assert_eq!(
+			codes,
+			vec![
+				("Condition", ValidationErrorCode::MissingRequired),
+				("Price", ValidationErrorCode::InvalidDataType),
+				("TireType", ValidationErrorCode::ValueNotAllowed),
+			]
+		);
+		assert_eq!(invalid.warnings.len(), 1);
+		assert_eq!(invalid.warnings[0].code, ValidationErrorCode::UnknownTag);
+	}
+}
@@ -0,0 +1,37 @@
+use crate::controllers::avito_client::item_stats_collector::collect_account_item_stats;
+use crate::controllers::avito_client::token_manager::authorize_account;
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::ApiError;
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
+use actix_web::{post, web, HttpResponse, Result};
+use serde_json::json;
+use uuid::Uuid;
+
+// POST collect the account's recent item statistics now instead of waiting for the scheduler
+#[post(
+	"/avito/accounts/{account_id}/item_stats/collect",
+	wrap = "RequirePermission(Permission::UseAvitoApi)"
+)]
+pub async fn collect_item_stats(
+	path: web::Path<Uuid>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	let account_id = path.into_inner();
+	authorize_account(&data, account_id, user.user_id)?;
+
+	let stored = collect_account_item_stats(
+		&data.db,
+		&data.avito_tokens,
+		data.avito_api.as_ref(),
+		account_id,
+		data.env.avito_item_stats_lookback_days,
+	)
+	.await?;
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": { "rows_stored": stored }
+	})))
+}
@@ -1,16 +1,32 @@
 use crate::controllers::avito_client::{
-	get_avito_balance, get_avito_item_analytics, get_avito_items, get_avito_token,
-	get_avito_user_profile, get_categories_tree, get_category_fields, update_avito_price,
+	collect_item_stats, get_avito_balance, get_avito_item_analytics, get_avito_items,
+	get_avito_token, get_avito_user_profile, get_balance_alert, get_balance_history,
+	get_categories_tree, get_category_fields, get_item_stats, get_item_syncs, get_price_update,
+	get_price_update_report, preview_price_update, refresh_category_cache, start_price_update,
+	sync_avito_items, update_avito_price, upload_price_update, upsert_balance_alert,
 };
 use actix_web::web;
 
 pub fn avito_client_routes(cfg: &mut web::ServiceConfig) {
 	cfg.service(get_categories_tree::get_categories_tree)
 		.service(get_category_fields::get_avito_category_fields)
+		.service(refresh_category_cache::refresh_category_cache)
 		.service(get_avito_token::get_avito_token_handler)
 		.service(get_avito_items::get_avito_items)
 		.service(get_avito_balance::get_avito_balance)
+		.service(upsert_balance_alert::upsert_balance_alert)
+		.service(get_balance_alert::get_balance_alert)
+		.service(get_balance_history::get_balance_history)
 		.service(get_avito_user_profile::get_avito_user_profile)
 		.service(get_avito_item_analytics::get_avito_item_analytics)
-		.service(update_avito_price::update_avito_price);
+		.service(update_avito_price::update_avito_price)
+		.service(sync_avito_items::sync_avito_items)
+		.service(get_item_syncs::get_item_syncs)
+		.service(collect_item_stats::collect_item_stats)
+		.service(get_item_stats::get_item_stats)
+		.service(upload_price_update::upload_price_update)
+		.service(preview_price_update::preview_price_update)
+		.service(start_price_update::start_price_update)
+		.service(get_price_update_report::get_price_update_report)
+		.service(get_price_update::get_price_update);
 }
@@ -0,0 +1,362 @@
+use crate::config::Config;
+use crate::controllers::avito_client::avito_api::AvitoApi;
+use crate::controllers::avito_client::request_policy::no_retry_policy;
+use crate::controllers::avito_client::token_manager::{
+	AccountCredentials, AvitoAccounts, AvitoTokenManager,
+};
+use crate::controllers::websocket::WebSocketConnections;
+use crate::jwt_auth::{Authentication, JwtMiddleware};
+use crate::models::{
+	ApiError, AvitoAccount, AvitoGetBalanceApiResponse, AvitoGetItemsApiResponse, AvitoItem,
+	AvitoItemAnalyticsRequest, AvitoItemAnalyticsResponse, AvitoItemsMeta, AvitoTokenResponse,
+	AvitoUpdatePriceResponse, AvitoUpdatePriceResult, AvitoUserProfile,
+};
+use crate::permissions::{Permission, Role};
+use crate::utils::mailer::OutboxMailer;
+use crate::utils::password::{PasswordHashing, PasswordPolicy};
+use crate::AppState;
+use actix_web::dev::ServiceRequest;
+use actix_web::{web, HttpMessage};
+use async_trait::async_trait;
+use chrono::Utc;
+use diesel::r2d2::{ConnectionManager, Pool};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+#[derive(Default)]
+pub struct FakeAvitoState {
+	// client_id -> client_secret of the accounts Avito knows
+	pub credentials: HashMap<String, String>,
+	pub issued_tokens: HashSet<String>,
+	pub items: Vec<AvitoItem>,
+	pub balance: f64,
+	pub profile: Option<AvitoUserProfile>,
+	pub analytics: AvitoItemAnalyticsResponse,
+	// (item_id, price) in the order the updates arrived
+	pub price_updates: Vec<(String, f64)>,
+	pub categories_tree: serde_json::Value,
+	pub category_fields: HashMap<String, serde_json::Value>,
+	pub field_values: HashMap<String, serde_json::Value>,
+	pub token_requests: usize,
+}
+
+// In-memory Avito for tests: issues tokens for known credentials and answers 401 to anything else
+#[derive(Default)]
+pub struct FakeAvitoApi {
+	pub state: Mutex<FakeAvitoState>,
+}
+
+impl FakeAvitoApi {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn with_account(self, client_id: &str, client_secret: &str) -> Self {
+		self.state
+			.lock()
+			.unwrap()
+			.credentials
+			.insert(client_id.to_string(), client_secret.to_string());
+		self
+	}
+
+	// Forget every issued token, as if they all expired on Avito's side
+	pub fn expire_tokens(&self) {
+		self.state.lock().unwrap().issued_tokens.clear();
+	}
+
+	fn check_token(&self, token: &str) -> Result<(), ApiError> {
+		if self.state.lock().unwrap().issued_tokens.contains(token) {
+			Ok(())
+		} else {
+			Err(ApiError::AvitoApiError(
+				401,
+				r#"{"error":{"code":401,"message":"unauthorized"}}"#.to_string(),
+			))
+		}
+	}
+
+	fn not_found(what: &str) -> ApiError {
+		ApiError::AvitoApiError(404, format!("{} not found", what))
+	}
+}
+
+#[async_trait]
+impl AvitoApi for FakeAvitoApi {
+	async fn request_token(
+		&self,
+		client_id: &str,
+		client_secret: &str,
+	) -> Result<AvitoTokenResponse, ApiError> {
+		let mut state = self.state.lock().unwrap();
+		if state.credentials.get(client_id).map(String::as_str) != Some(client_secret) {
+			return Err(ApiError::AvitoApiError(
+				400,
+				r#"{"error":"invalid_client"}"#.to_string(),
+			));
+		}
+
+		state.token_requests += 1;
+		let access_token = format!("fake-token-{}-{}", client_id, state.token_requests);
+		state.issued_tokens.insert(access_token.clone());
+
+		Ok(AvitoTokenResponse {
+			access_token,
+			token_type: Some("Bearer".to_string()),
+			expires_in: Some(86400),
+		})
+	}
+
+	async fn get_items(
+		&self,
+		token: &str,
+		page: i32,
+		per_page: i32,
+		status: Option<&str>,
+	) -> Result<AvitoGetItemsApiResponse, ApiError> {
+		self.check_token(token)?;
+		let state = self.state.lock().unwrap();
+		let skip = (page.max(1) - 1) as usize * per_page.max(0) as usize;
+		let statuses: Vec<&str> = status.unwrap_or("active").split(',').collect();
+
+		Ok(AvitoGetItemsApiResponse {
+			meta: AvitoItemsMeta { page, per_page },
+			resources: state
+				.items
+				.iter()
+				.filter(|item| statuses.contains(&item.status.as_deref().unwrap_or("active")))
+				.skip(skip)
+				.take(per_page.max(0) as usize)
+				.cloned()
+				.collect(),
+		})
+	}
+
+	async fn get_balance(&self, token: &str) -> Result<AvitoGetBalanceApiResponse, ApiError> {
+		self.check_token(token)?;
+		Ok(AvitoGetBalanceApiResponse {
+			balance: self.state.lock().unwrap().balance,
+		})
+	}
+
+	async fn get_user_profile(&self, token: &str) -> Result<AvitoUserProfile, ApiError> {
+		self.check_token(token)?;
+		self.state
+			.lock()
+			.unwrap()
+			.profile
+			.clone()
+			.ok_or_else(|| Self::not_found("Profile"))
+	}
+
+	async fn get_item_analytics(
+		&self,
+		token: &str,
+		_avito_user_id: &str,
+		_request: &AvitoItemAnalyticsRequest,
+	) -> Result<AvitoItemAnalyticsResponse, ApiError> {
+		self.check_token(token)?;
+		Ok(self.state.lock().unwrap().analytics.clone())
+	}
+
+	async fn update_price(
+		&self,
+		token: &str,
+		item_id: &str,
+		price: f64,
+	) -> Result<AvitoUpdatePriceResponse, ApiError> {
+		self.check_token(token)?;
+		let mut state = self.state.lock().unwrap();
+		let Some(item) = state
+			.items
+			.iter_mut()
+			.find(|item| item.id.to_string() == item_id)
+		else {
+			return Err(Self::not_found("Item"));
+		};
+
+		item.price = Some(price);
+		state.price_updates.push((item_id.to_string(), price));
+
+		Ok(AvitoUpdatePriceResponse {
+			result: AvitoUpdatePriceResult { success: true },
+		})
+	}
+
+	async fn get_categories_tree(&self, token: &str) -> Result<serde_json::Value, ApiError> {
+		self.check_token(token)?;
+		Ok(self.state.lock().unwrap().categories_tree.clone())
+	}
+
+	async fn get_category_fields(
+		&self,
+		token: &str,
+		avito_slug: &str,
+	) -> Result<serde_json::Value, ApiError> {
+		self.check_token(token)?;
+		self.state
+			.lock()
+			.unwrap()
+			.category_fields
+			.get(avito_slug)
+			.cloned()
+			.ok_or_else(|| Self::not_found("Category"))
+	}
+
+	async fn get_field_values(
+		&self,
+		token: &str,
+		values_link: &str,
+	) -> Result<serde_json::Value, ApiError> {
+		self.check_token(token)?;
+		self.state
+			.lock()
+			.unwrap()
+			.field_values
+			.get(values_link)
+			.cloned()
+			.ok_or_else(|| Self::not_found("Values"))
+	}
+}
+
+// User every request to a fake app comes from
+pub const FAKE_USER_ID: Uuid = Uuid::from_u128(1);
+
+// Accounts as (account_id, user_id) pairs, in place of the avito_accounts table
+pub struct FakeAvitoAccounts(pub Vec<(Uuid, Uuid)>);
+
+impl AvitoAccounts for FakeAvitoAccounts {
+	fn find_user_account(&self, account_id: Uuid, user_id: Uuid) -> Result<AvitoAccount, ApiError> {
+		if !self.0.contains(&(account_id, user_id)) {
+			return Err(ApiError::NotFound(format!(
+				"Avito account {} not found",
+				account_id
+			)));
+		}
+
+		Ok(AvitoAccount {
+			account_id,
+			user_id,
+			client_id: "client".to_string(),
+			avito_client_secret: String::new(),
+			avito_client_id: String::new(),
+			is_connected: Some(true),
+			created_ts: Utc::now().naive_utc(),
+			updated_ts: Utc::now().naive_utc(),
+			last_check_ts: None,
+			last_check_error: None,
+		})
+	}
+}
+
+// App state around the fake Avito with one account of FAKE_USER_ID whose token is already
+// issued. The database pool never connects, so handlers under test must not reach it.
+pub async fn fake_app_state(api: Arc<FakeAvitoApi>) -> (web::Data<AppState>, Uuid) {
+	api.state
+		.lock()
+		.unwrap()
+		.credentials
+		.insert("client".to_string(), "secret".to_string());
+
+	let account_id = Uuid::new_v4();
+	let avito_tokens = AvitoTokenManager::new(api.clone(), no_retry_policy());
+	avito_tokens
+		.access_token_from(account_id, || {
+			Ok(AccountCredentials {
+				client_id: "client".to_string(),
+				client_secret: "secret".to_string(),
+			})
+		})
+		.await
+		.unwrap();
+
+	let config = Config::for_tests();
+	let state = AppState {
+		db: Pool::builder()
+			.min_idle(Some(0))
+			.build_unchecked(ConnectionManager::new(config.database_url.as_str())),
+		rabbitmq_channel: None,
+		ws_server: WebSocketConnections::new(),
+		avito_tokens,
+		avito_api: api,
+		avito_accounts: Arc::new(FakeAvitoAccounts(vec![(account_id, FAKE_USER_ID)])),
+		mailer: Arc::new(OutboxMailer::new(&config.mail_outbox_dir, &config.mail_from).unwrap()),
+		password_hashing: PasswordHashing::from_config(&config).unwrap(),
+		password_policy: Arc::new(PasswordPolicy::new(config.password_min_length, "")),
+		env: config,
+	};
+
+	(web::Data::new(state), account_id)
+}
+
+// Stands in for `extract_permissions`: every request comes from FAKE_USER_ID as a user
+pub async fn extract_fake_user(
+	req: &ServiceRequest,
+) -> Result<HashSet<Permission>, actix_web::Error> {
+	req.extensions_mut()
+		.insert(Authentication(Ok(JwtMiddleware {
+			user_id: FAKE_USER_ID,
+			session_id: Uuid::nil(),
+		})));
+	Ok(Role::User.permissions().iter().copied().collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn item(id: i64, price: f64) -> AvitoItem {
+		AvitoItem {
+			id,
+			title: Some(format!("Item {}", id)),
+			price: Some(price),
+			status: Some("active".to_string()),
+			url: None,
+			address: None,
+			category: None,
+		}
+	}
+
+	#[actix_web::test]
+	async fn test_fake_avito_api() {
+		let api = FakeAvitoApi::new().with_account("client", "secret");
+		api.state.lock().unwrap().items = vec![item(1, 100.0), item(2, 200.0), item(3, 300.0)];
+
+		assert!(matches!(
+			api.request_token("client", "wrong").await,
+			Err(ApiError::AvitoApiError(400, _))
+		));
+		assert!(matches!(
+			api.get_balance("unknown").await,
+			Err(ApiError::AvitoApiError(401, _))
+		));
+
+		let token = api.request_token("client", "secret").await.unwrap();
+		let page = api
+			.get_items(&token.access_token, 2, 2, None)
+			.await
+			.unwrap();
+		assert_eq!(
+			page.resources
+				.iter()
+				.map(|item| item.id)
+				.collect::<Vec<_>>(),
+			vec![3]
+		);
+
+		api.update_price(&token.access_token, "2", 250.0)
+			.await
+			.unwrap();
+		assert_eq!(
+			api.state.lock().unwrap().price_updates,
+			vec![("2".to_string(), 250.0)]
+		);
+
+		api.expire_tokens();
+		assert!(matches!(
+			api.get_items(&token.access_token, 1, 10, None).await,
+			Err(ApiError::AvitoApiError(401, _))
+		));
+	}
+}
@@ -1,58 +1,86 @@
+use crate::controllers::avito_client::token_manager::authorize_account;
 use crate::jwt_auth::JwtMiddleware;
-use crate::models::{ApiError, AvitoTokenParams};
+use crate::models::{ApiError, AvitoAccountParams};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
 use actix_web::{post, web, HttpResponse, Result};
-use reqwest::{
-	header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE},
-	Client,
-};
 use serde_json::json;
-use std::env;
 
-#[post("/avito/get_balance")]
+#[post(
+	"/avito/get_balance",
+	wrap = "RequirePermission(Permission::UseAvitoApi)"
+)]
 pub async fn get_avito_balance(
-	opts: web::Json<AvitoTokenParams>,
-	_: JwtMiddleware,
+	opts: web::Json<AvitoAccountParams>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
 ) -> Result<HttpResponse, ApiError> {
-	let avito_token = opts.avito_token.clone();
+	authorize_account(&data, opts.account_id, user.user_id)?;
 
-	let url = env::var("AVITO_BASE_URL").expect("AVITO_BASE_URL not set");
-
-	let headers: HeaderMap<HeaderValue> = HeaderMap::from_iter(vec![
-		(CONTENT_TYPE, "application/json".parse().unwrap()),
-		(
-			AUTHORIZATION,
-			format!("Bearer {}", avito_token).parse().unwrap(),
-		),
-	]);
-
-	let body = json!({});
-
-	let response = Client::builder()
-		.danger_accept_invalid_certs(true)
-		.build()?
-		.post(format!("{}/cpa/v3/balanceInfo", url))
-		.headers(headers)
-		.json(&body)
-		.send()
+	let api = data.avito_api.as_ref();
+	let response_data = data
+		.avito_tokens
+		.with_token(&data.db, opts.account_id, |avito_token| async move {
+			api.get_balance(&avito_token).await
+		})
 		.await?;
 
-	// Check response status
-	if !response.status().is_success() {
-		let status_code = response.status().as_u16();
-		let error_body = response.text().await?;
-		return Err(ApiError::AvitoApiError(status_code, error_body));
-	}
-
-	let response_text: String = response.text().await?;
-
-	// Parse the response
-	let response_data: serde_json::Value = serde_json::from_str(&response_text)
-		.map_err(|e| ApiError::JsonParseError(e.to_string()))?;
-
 	Ok(HttpResponse::Ok().json(json!({
 		"status": "success",
 		"data": {
-			"balance": &response_data["balance"],
+			"balance": response_data.balance,
 		}
 	})))
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::controllers::avito_client::fake_avito_api::{
+		extract_fake_user, fake_app_state, FakeAvitoApi,
+	};
+	use actix_web::http::StatusCode;
+	use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+	use actix_web::App;
+	use actix_web_grants::GrantsMiddleware;
+	use std::sync::Arc;
+	use uuid::Uuid;
+
+	#[actix_web::test]
+	async fn test_get_avito_balance() {
+		let api = Arc::new(FakeAvitoApi::new());
+		api.state.lock().unwrap().balance = 1520.5;
+		let (data, account_id) = fake_app_state(api.clone()).await;
+		let app = init_service(
+			App::new()
+				.app_data(data)
+				.wrap(GrantsMiddleware::with_extractor(extract_fake_user))
+				.service(get_avito_balance),
+		)
+		.await;
+
+		let response = call_service(
+			&app,
+			TestRequest::post()
+				.uri("/avito/get_balance")
+				.set_json(json!({ "account_id": account_id }))
+				.to_request(),
+		)
+		.await;
+		assert_eq!(response.status(), StatusCode::OK);
+		let body: serde_json::Value = read_body_json(response).await;
+		assert_eq!(body["data"]["balance"], 1520.5);
+
+		// Someone else's account is reported as missing, and Avito is never asked
+		let response = call_service(
+			&app,
+			TestRequest::post()
+				.uri("/avito/get_balance")
+				.set_json(json!({ "account_id": Uuid::new_v4() }))
+				.to_request(),
+		)
+		.await;
+		assert_eq!(response.status(), StatusCode::NOT_FOUND);
+		assert_eq!(api.state.lock().unwrap().token_requests, 1);
+	}
+}
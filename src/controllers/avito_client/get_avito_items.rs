@@ -1,64 +1,38 @@
+use crate::controllers::avito_client::token_manager::authorize_account;
 use crate::jwt_auth::JwtMiddleware;
 use crate::models::{ApiError, GetAvitoItemsParams};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
 use actix_web::{post, web, HttpResponse, Result};
-use reqwest::{
-	header::{HeaderMap, AUTHORIZATION, CONTENT_TYPE},
-	Client,
-};
 use serde_json::json;
-use std::env;
 
-#[post("/avito/get_items")]
+#[post(
+	"/avito/get_items",
+	wrap = "RequirePermission(Permission::UseAvitoApi)"
+)]
 pub async fn get_avito_items(
 	opts: web::Json<GetAvitoItemsParams>,
-	_: JwtMiddleware,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
 ) -> Result<HttpResponse, ApiError> {
-	let avito_token = opts.avito_token.clone();
 	let page = opts.page.unwrap_or(0);
 	let per_page = opts.per_page.unwrap_or(50).min(1000); // Avito API max per_page is 1000
 
-	let url = env::var("AVITO_BASE_URL")
-		.map_err(|_| ApiError::Other("AVITO_BASE_URL not set".to_string()))?;
+	authorize_account(&data, opts.account_id, user.user_id)?;
 
-	let mut headers = HeaderMap::new();
-	headers.insert(
-		CONTENT_TYPE,
-		"application/x-www-form-urlencoded".parse().unwrap(),
-	);
-	headers.insert(
-		AUTHORIZATION,
-		format!("Bearer {}", avito_token).parse().unwrap(),
-	);
-
-	// Build URL with pagination parameters
-	let api_url = format!("{}/core/v1/items?page={}&per_page={}", url, page, per_page);
-
-	// Make request
-	let response = Client::builder()
-		.danger_accept_invalid_certs(true)
-		.build()?
-		.get(&api_url)
-		.headers(headers)
-		.send()
+	let api = data.avito_api.as_ref();
+	let response_data = data
+		.avito_tokens
+		.with_token(&data.db, opts.account_id, |avito_token| async move {
+			api.get_items(&avito_token, page, per_page, None).await
+		})
 		.await?;
 
-	// Check response status
-	if !response.status().is_success() {
-		let status_code = response.status().as_u16();
-		let error_body = response.text().await?;
-		return Err(ApiError::AvitoApiError(status_code, error_body));
-	}
-
-	// Parse response
-	let response_text = response.text().await?;
-	let response_data: serde_json::Value = serde_json::from_str(&response_text)
-		.map_err(|e| ApiError::JsonParseError(e.to_string()))?;
-
 	Ok(HttpResponse::Ok().json(json!({
 		"status": "success",
 		"data": {
-			"meta": &response_data["meta"],
-			"items": &response_data["resources"],
+			"meta": response_data.meta,
+			"items": response_data.resources,
 		},
 	})))
 }
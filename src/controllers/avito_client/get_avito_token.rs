@@ -1,72 +1,48 @@
+use crate::controllers::avito_client::token_manager::authorize_account;
 use crate::jwt_auth::JwtMiddleware;
-use crate::models::{ApiError, AvitoTokenCredentials};
+use crate::models::{ApiError, GetAvitoTokenParams};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
 use actix_web::{
-	cookie::{time::Duration as ActixWebDuration, Cookie, SameSite},
 	post,
 	web::{self},
 	HttpResponse,
 };
-use reqwest::{header::CONTENT_TYPE, Client};
 use serde_json::json;
-use std::env;
-
-#[post("/avito/get_token")]
+use tokio::time::Instant;
+
+// Make sure one of the user's accounts has a token, issued from the credentials stored on the
+// server. The token itself stays on the server; only its expiry is returned.
+#[post(
+	"/avito/get_token",
+	wrap = "RequirePermission(Permission::UseAvitoApi)"
+)]
 pub async fn get_avito_token_handler(
-	credentials: web::Json<AvitoTokenCredentials>,
-	_: JwtMiddleware,
+	opts: web::Json<GetAvitoTokenParams>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
 ) -> Result<HttpResponse, ApiError> {
-	let client_id = credentials.client_id.clone();
-	let client_secret = credentials.client_secret.clone();
-	let grant_type = credentials.grant_type.clone();
-
-	// Prepare form data for token request
-	let params = [
-		("client_id", &client_id),
-		("client_secret", &client_secret),
-		("grant_type", &grant_type),
-	];
-
-	let client = Client::new();
-	let token_url = format!(
-		"{}/token",
-		env::var("AVITO_BASE_URL").expect("AVITO_BASE_URL not set")
-	);
-
-	let response = client
-		.post(&token_url)
-		.header(CONTENT_TYPE, "application/x-www-form-urlencoded")
-		.form(&params)
-		.send()
-		.await?;
-
-	if !response.status().is_success() {
-		let status_code = response.status().as_u16();
-		let error_body = response.text().await?;
-		return Err(ApiError::AvitoApiError(status_code, error_body));
-	}
-
-	let response_text = response.text().await?;
-	let token_response: serde_json::Value = serde_json::from_str(&response_text)
-		.map_err(|e| ApiError::JsonParseError(e.to_string()))?;
-
-	let access_token = token_response["access_token"]
-		.as_str()
-		.ok_or_else(|| ApiError::Other("Access token not found in response".to_string()))?;
-
-	// Build cookie
-	let cookie = Cookie::build("avito_token", access_token)
-		.same_site(SameSite::None)
-		.path("/")
-		.max_age(ActixWebDuration::new(3600, 0)) // Default expiration of 1 hour
-		.secure(true)
-		.finish();
-
-	Ok(HttpResponse::Ok().cookie(cookie).json(json!({
+	authorize_account(&data, opts.account_id, user.user_id)?;
+
+	let token = if opts.refresh.unwrap_or(false) {
+		data.avito_tokens
+			.refresh_token(&data.db, opts.account_id)
+			.await?
+	} else {
+		data.avito_tokens
+			.access_token(&data.db, opts.account_id)
+			.await?
+	};
+	let expires_in = token
+		.expires_at
+		.saturating_duration_since(Instant::now())
+		.as_secs() as i64;
+
+	Ok(HttpResponse::Ok().json(json!({
 		"status": "success",
 		"data": {
-			"access_token": access_token,
-			"token_type": "Bearer",
-			"expires_in": 3600,
+			"account_id": opts.account_id,
+			"expires_in": expires_in,
 		}
 	})))
 }
@@ -1,60 +1,32 @@
+use crate::controllers::avito_client::token_manager::authorize_account;
 use crate::jwt_auth::JwtMiddleware;
-use crate::models::{ApiError, AvitoTokenParams};
+use crate::models::{ApiError, AvitoAccountParams};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
 use actix_web::{post, web, HttpResponse, Result};
-use reqwest::{
-	header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE},
-	Client,
-};
 use serde_json::json;
-use std::env;
 
-#[post("/avito/get_user_profile")]
+#[post(
+	"/avito/get_user_profile",
+	wrap = "RequirePermission(Permission::UseAvitoApi)"
+)]
 pub async fn get_avito_user_profile(
-	opts: web::Json<AvitoTokenParams>,
-	_: JwtMiddleware,
+	opts: web::Json<AvitoAccountParams>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
 ) -> Result<HttpResponse, ApiError> {
-	let avito_token = opts.avito_token.clone();
-
-	let url = env::var("AVITO_BASE_URL").expect("AVITO_BASE_URL not set");
-
-	let headers: HeaderMap<HeaderValue> = HeaderMap::from_iter(vec![
-		(CONTENT_TYPE, "application/json".parse().unwrap()),
-		(
-			AUTHORIZATION,
-			format!("Bearer {}", avito_token).parse().unwrap(),
-		),
-	]);
-
-	let response = Client::builder()
-		.danger_accept_invalid_certs(true)
-		.build()?
-		.get(format!("{}/user/me", url))
-		.headers(headers)
-		.send()
+	authorize_account(&data, opts.account_id, user.user_id)?;
+
+	let api = data.avito_api.as_ref();
+	let profile = data
+		.avito_tokens
+		.with_token(&data.db, opts.account_id, |avito_token| async move {
+			api.get_user_profile(&avito_token).await
+		})
 		.await?;
 
-	// Check response status
-	if !response.status().is_success() {
-		let status_code = response.status().as_u16();
-		let error_body = response.text().await?;
-		return Err(ApiError::AvitoApiError(status_code, error_body));
-	}
-
-	let response_text: String = response.text().await?;
-
-	// Parse the response
-	let response_data: serde_json::Value = serde_json::from_str(&response_text)
-		.map_err(|e| ApiError::JsonParseError(e.to_string()))?;
-
 	Ok(HttpResponse::Ok().json(json!({
 		"status": "success",
-		"data": {
-			"id": &response_data["id"],
-			"name": &response_data["name"],
-			"email": &response_data["email"],
-			"phone": &response_data["phone"],
-			"phones": &response_data["phones"],
-			"profile_url": &response_data["profile_url"]
-		}
+		"data": profile
 	})))
 }
@@ -0,0 +1,36 @@
+use crate::controllers::avito_client::token_manager::authorize_account;
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::{ApiError, AvitoBalanceAlert};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
+use actix_web::{get, web, HttpResponse, Result};
+use diesel::prelude::*;
+use serde_json::json;
+use uuid::Uuid;
+
+#[get(
+	"/avito/accounts/{account_id}/balance_alert",
+	wrap = "RequirePermission(Permission::UseAvitoApi)"
+)]
+pub async fn get_balance_alert(
+	path: web::Path<Uuid>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	let account_id = path.into_inner();
+	authorize_account(&data, account_id, user.user_id)?;
+
+	let mut conn = data.db.get().map_err(|e| ApiError::Other(e.to_string()))?;
+	let alert = crate::schema::avito_balance_alerts::table
+		.find(account_id)
+		.first::<AvitoBalanceAlert>(&mut conn)
+		.optional()?
+		.ok_or_else(|| {
+			ApiError::NotFound(format!("Account {} has no balance alert", account_id))
+		})?;
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": { "alert": alert }
+	})))
+}
@@ -0,0 +1,53 @@
+use crate::controllers::avito_client::token_manager::authorize_account;
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::{ApiError, AvitoBalanceHistory, GetBalanceHistoryParams};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
+use actix_web::{get, web, HttpResponse, Result};
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use serde_json::json;
+use uuid::Uuid;
+
+// Points returned at most; a month of polls every 30 minutes fits
+const MAX_HISTORY_POINTS: i64 = 5000;
+
+// GET recorded balances of an account, oldest first, for charting
+#[get(
+	"/avito/accounts/{account_id}/balance_history",
+	wrap = "RequirePermission(Permission::UseAvitoApi)"
+)]
+pub async fn get_balance_history(
+	path: web::Path<Uuid>,
+	params: web::Query<GetBalanceHistoryParams>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	use crate::schema::avito_balance_history::dsl;
+
+	let account_id = path.into_inner();
+	authorize_account(&data, account_id, user.user_id)?;
+
+	let to = params.to.unwrap_or_else(Utc::now);
+	let from = params.from.unwrap_or_else(|| to - Duration::days(30));
+
+	let mut conn = data.db.get().map_err(|e| ApiError::Other(e.to_string()))?;
+	// The newest points win when the range holds more than the limit
+	let mut history = dsl::avito_balance_history
+		.filter(dsl::account_id.eq(account_id))
+		.filter(dsl::recorded_ts.ge(from))
+		.filter(dsl::recorded_ts.le(to))
+		.order(dsl::recorded_ts.desc())
+		.limit(MAX_HISTORY_POINTS)
+		.load::<AvitoBalanceHistory>(&mut conn)?;
+	history.reverse();
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": {
+			"from": from,
+			"to": to,
+			"history": history
+		}
+	})))
+}
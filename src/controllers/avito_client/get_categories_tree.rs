@@ -2,7 +2,7 @@
 ///
 /// Expected request body:
 /// {
-///     "avito_token": "your_avito_token_here"
+///     "account_id": "uuid-of-the-avito-account"
 /// }
 ///
 /// Response:
@@ -12,61 +12,57 @@
 ///         "categories": [...]
 ///     }
 /// }
+use crate::controllers::avito_client::catalog_cache::{
+	cached_or_fetch, CacheMode, CatalogCache, CATEGORY_TREE_KEY,
+};
+use crate::controllers::avito_client::token_manager::authorize_account;
 use crate::jwt_auth::JwtMiddleware;
 use crate::models::{ApiError, GetCategoriesTreeParams};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
 use actix_web::{post, web, HttpResponse, Result};
-use reqwest::{
-	header::{HeaderMap, AUTHORIZATION, CONTENT_TYPE},
-	Client,
-};
 use serde_json::json;
-use std::env;
+use uuid::Uuid;
 
-#[post("/avito/get_categories_tree")]
+#[post(
+	"/avito/get_categories_tree",
+	wrap = "RequirePermission(Permission::UseAvitoApi)"
+)]
 pub async fn get_categories_tree(
 	opts: web::Json<GetCategoriesTreeParams>,
-	_: JwtMiddleware,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
 ) -> Result<HttpResponse, ApiError> {
-	let avito_token = opts.avito_token.clone();
-	let url = env::var("AVITO_BASE_URL")
-		.map_err(|_| ApiError::Other("AVITO_BASE_URL not set".to_string()))?;
-
-	let mut headers = HeaderMap::new();
-	headers.insert(
-		CONTENT_TYPE,
-		"application/x-www-form-urlencoded".parse().unwrap(),
-	);
-	headers.insert(
-		AUTHORIZATION,
-		format!("Bearer {}", avito_token).parse().unwrap(),
-	);
-
-	// Build URL for categories tree
-	let api_url = format!("{}/autoload/v1/user-docs/tree", url);
+	authorize_account(&data, opts.account_id, user.user_id)?;
 
-	// Make request
-	let response = Client::builder()
-		.danger_accept_invalid_certs(true)
-		.build()?
-		.get(&api_url)
-		.headers(headers)
-		.send()
-		.await?;
-
-	// Check response status
-	if !response.status().is_success() {
-		let status_code = response.status().as_u16();
-		let error_body = response.text().await?;
-		return Err(ApiError::AvitoApiError(status_code, error_body));
-	}
-
-	// Parse response
-	let response_text = response.text().await?;
-	let response_data: serde_json::Value = serde_json::from_str(&response_text)
-		.map_err(|e| ApiError::JsonParseError(e.to_string()))?;
+	let response_data =
+		fetch_categories_tree(&data, opts.account_id, CacheMode::PreferCache).await?;
 
 	Ok(HttpResponse::Ok().json(json!({
 		"status": "success",
 		"data": response_data
 	})))
 }
+
+// Category tree from the cache, or from Avito once the cached copy has expired
+pub async fn fetch_categories_tree(
+	data: &AppState,
+	account_id: Uuid,
+	mode: CacheMode,
+) -> Result<serde_json::Value, ApiError> {
+	cached_or_fetch(
+		&data.db,
+		CatalogCache::Tree,
+		CATEGORY_TREE_KEY,
+		data.env.avito_catalog_cache_ttl_secs,
+		mode,
+		|| {
+			let api = data.avito_api.as_ref();
+			data.avito_tokens
+				.with_token(&data.db, account_id, move |avito_token| async move {
+					api.get_categories_tree(&avito_token).await
+				})
+		},
+	)
+	.await
+}
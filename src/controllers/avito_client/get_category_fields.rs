@@ -1,210 +1,296 @@
+use crate::controllers::avito_autocatalog::get_autocatalog_makes::load_make_values;
+use crate::controllers::avito_client::catalog_cache::{cached_or_fetch, CacheMode, CatalogCache};
+use crate::controllers::avito_client::token_manager::authorize_account;
 use crate::jwt_auth::JwtMiddleware;
 use crate::models::{ApiError, AvitoEditorCategoryFieldsParams};
+use crate::permissions::{Permission, RequirePermission};
 use crate::AppState;
 use actix_web::{post, web, HttpResponse, Result};
-use reqwest::{header, Client};
+use futures::StreamExt;
 use serde_json::json;
-use std::env;
+use std::collections::{BTreeSet, HashMap};
+use uuid::Uuid;
 
-#[post("/avito/get_category_fields")]
+#[post(
+	"/avito/get_category_fields",
+	wrap = "RequirePermission(Permission::UseAvitoApi)"
+)]
 pub async fn get_avito_category_fields(
 	opts: web::Json<AvitoEditorCategoryFieldsParams>,
-	_: JwtMiddleware,
+	user: JwtMiddleware,
 	data: web::Data<AppState>,
 ) -> Result<HttpResponse, ApiError> {
-	let avito_token = opts.avito_token.clone();
-	let avito_slug = opts.avito_slug.clone();
-
-	let url = env::var("AVITO_BASE_URL")
-		.map_err(|_| ApiError::Other("AVITO_BASE_URL not set".to_string()))?;
-
-	// Build headers
-	let mut headers = header::HeaderMap::new();
-	headers.insert(
-		header::AUTHORIZATION,
-		format!("Bearer {}", avito_token).parse().unwrap(),
-	);
-	headers.insert(
-		header::ACCEPT,
-		header::HeaderValue::from_static("application/json"),
-	);
-
-	// Build URL for user docs node fields endpoint
-	let api_url = format!("{}/autoload/v1/user-docs/node/{}/fields", url, avito_slug);
-
-	// Make request
-	let response = Client::builder()
-		.danger_accept_invalid_certs(true)
-		.build()?
-		.get(&api_url)
-		.headers(headers.clone())
-		.send()
-		.await?;
-
-	// Check response status
-	if !response.status().is_success() {
-		let status_code = response.status().as_u16();
-		let error_body = response.text().await?;
-		return Err(ApiError::AvitoApiError(status_code, error_body));
-	}
+	authorize_account(&data, opts.account_id, user.user_id)?;
+
+	let node_fields_data = fetch_category_fields(
+		&data,
+		opts.account_id,
+		&opts.avito_slug,
+		CacheMode::PreferCache,
+	)
+	.await?;
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": node_fields_data
+	})))
+}
 
-	// Parse response
-	let response_text = response.text().await?;
-	let mut node_fields_data: serde_json::Value = serde_json::from_str(&response_text)
-		.map_err(|_e| ApiError::JsonParseError(response_text.clone()))?;
+// Field description of a category node with dictionary values resolved from values_link_json.
+// Node fields and dictionaries are cached separately, as dictionaries are shared between categories.
+pub async fn fetch_category_fields(
+	data: &AppState,
+	account_id: Uuid,
+	avito_slug: &str,
+	mode: CacheMode,
+) -> Result<serde_json::Value, ApiError> {
+	let mut node_fields_data = cached_or_fetch(
+		&data.db,
+		CatalogCache::Fields,
+		avito_slug,
+		data.env.avito_catalog_cache_ttl_secs,
+		mode,
+		|| {
+			let api = data.avito_api.as_ref();
+			data.avito_tokens
+				.with_token(&data.db, account_id, move |avito_token| async move {
+					api.get_category_fields(&avito_token, avito_slug).await
+				})
+		},
+	)
+	.await?;
+
+	let dictionaries = fetch_dictionaries(
+		data,
+		account_id,
+		collect_values_links(&node_fields_data),
+		mode,
+	)
+	.await;
+
+	let make_values = if uses_autocatalog_makes(&node_fields_data) {
+		let mut conn = data.db.get().map_err(|e| ApiError::Other(e.to_string()))?;
+		load_make_values(&mut conn)?
+	} else {
+		Vec::new()
+	};
+	attach_dictionary_values(&mut node_fields_data, &dictionaries, &make_values);
+
+	Ok(node_fields_data)
+}
+
+// Resolve the dictionaries, at most avito_catalog_fetch_concurrency requests at a time.
+// A dictionary that cannot be loaded is left out, as before, instead of failing the whole node.
+async fn fetch_dictionaries(
+	data: &AppState,
+	account_id: Uuid,
+	values_links: BTreeSet<String>,
+	mode: CacheMode,
+) -> HashMap<String, serde_json::Value> {
+	futures::stream::iter(values_links)
+		.map(|values_link| async move {
+			let values = cached_or_fetch(
+				&data.db,
+				CatalogCache::Values,
+				&values_link,
+				data.env.avito_values_cache_ttl_secs,
+				mode,
+				|| {
+					let (api, values_link) = (data.avito_api.as_ref(), &values_link);
+					data.avito_tokens.with_token(
+						&data.db,
+						account_id,
+						move |avito_token| async move {
+							api.get_field_values(&avito_token, values_link).await
+						},
+					)
+				},
+			)
+			.await;
+			(values_link, values)
+		})
+		.buffer_unordered(data.env.avito_catalog_fetch_concurrency.max(1))
+		.filter_map(|(values_link, values)| async move {
+			match values {
+				Ok(values) => Some((values_link, values)),
+				Err(e) => {
+					log::warn!("Failed to load dictionary {}: {}", values_link, e);
+					None
+				}
+			}
+		})
+		.collect()
+		.await
+}
 
-	// Process the response to fetch additional data from values_link_json and values_link_xml
-	if let Some(fields_array) = node_fields_data
+// Visit the content items of every field and of the fields' children, with the tag they belong to
+fn for_each_content_item(
+	node_fields_data: &mut serde_json::Value,
+	visit: &mut impl FnMut(Option<&str>, &mut serde_json::Value),
+) {
+	let Some(fields_array) = node_fields_data
 		.get_mut("fields")
 		.and_then(|f| f.as_array_mut())
-	{
-		for field in fields_array.iter_mut() {
-			// Process content array of the main field
-			if let Some(content_array) = field.get_mut("content").and_then(|c| c.as_array_mut()) {
-				for content_item in content_array.iter_mut() {
-					// Process values_link_json
-					if let Some(values_link_json) = content_item
-						.get("values_link_json")
-						.and_then(|v| v.as_str())
-					{
-						// Make additional request to fetch values from the JSON link
-						let values_response = Client::builder()
-							.danger_accept_invalid_certs(true)
-							.timeout(std::time::Duration::from_secs(5)) // Add 5 second timeout
-							.build()?
-							.get(values_link_json)
-							.headers(headers.clone())
-							.send()
-							.await?;
-
-						if values_response.status().is_success() {
-							let values_text = values_response.text().await?;
-							let values_data: serde_json::Value = serde_json::from_str(&values_text)
-								.map_err(|e| ApiError::JsonParseError(values_text.clone()))?;
-
-							// Add the fetched values to the content item as a new "values" field
-							content_item
-								.as_object_mut()
-								.unwrap()
-								.insert("values".to_string(), values_data);
-						}
-					} else if let Some(values_link_xml) =
-						content_item.get("values_link_xml").and_then(|v| v.as_str())
-					{
-						// Check if the values_link_xml contains "Autocatalog.xml"
-						if values_link_xml.contains("Autocatalog.xml") {
-							// Since avito_car_marks table may not exist, we'll simulate the data or make an HTTP request
-							// This preserves the original behavior when the database is not set up
-
-							// For now, we'll skip the database query and just continue
-							// In a real scenario, you'd need to implement the database query properly
-						} else {
-							// For other XML links, make HTTP request as before (if needed)
-							// This preserves the original behavior for non-Autocatalog.xml links
-						}
-					}
+	else {
+		return;
+	};
 
-					// Remove the values_link_json and values_link_xml fields since we've fetched the data
-					content_item
-						.as_object_mut()
-						.unwrap()
-						.remove("values_link_json");
-					content_item
-						.as_object_mut()
-						.unwrap()
-						.remove("values_link_xml");
-				}
+	for field in fields_array.iter_mut() {
+		visit_field_content(field, visit);
+
+		if let Some(children_array) = field.get_mut("children").and_then(|c| c.as_array_mut()) {
+			for child in children_array.iter_mut() {
+				visit_field_content(child, visit);
 			}
+		}
+	}
+}
+
+fn visit_field_content(
+	field: &mut serde_json::Value,
+	visit: &mut impl FnMut(Option<&str>, &mut serde_json::Value),
+) {
+	// Extract the tag value before mutable borrows to avoid borrowing conflicts
+	let tag_value = field
+		.get("tag")
+		.and_then(|t| t.as_str())
+		.map(|s| s.to_string());
+
+	if let Some(content_array) = field.get_mut("content").and_then(|c| c.as_array_mut()) {
+		for content_item in content_array.iter_mut() {
+			visit(tag_value.as_deref(), content_item);
+		}
+	}
+}
 
-			// Process children array if it exists
-			if let Some(children_array) = field.get_mut("children").and_then(|c| c.as_array_mut()) {
-				for child in children_array.iter_mut() {
-					// Extract the tag value before mutable borrows to avoid borrowing conflicts
-					let tag_value = child
-						.get("tag")
-						.and_then(|t| t.as_str())
-						.map(|s| s.to_string());
-
-					// Process content array of each child
-					if let Some(child_content_array) =
-						child.get_mut("content").and_then(|c| c.as_array_mut())
+fn collect_values_links(node_fields_data: &serde_json::Value) -> BTreeSet<String> {
+	let mut values_links = BTreeSet::new();
+	for_each_content_item(&mut node_fields_data.clone(), &mut |_, content_item| {
+		if let Some(link) = content_item
+			.get("values_link_json")
+			.and_then(|v| v.as_str())
+		{
+			values_links.insert(link.to_string());
+		}
+	});
+	values_links
+}
+
+fn is_autocatalog_item(content_item: &serde_json::Value) -> bool {
+	content_item
+		.get("values_link_xml")
+		.and_then(|v| v.as_str())
+		.is_some_and(|link| link.contains("Autocatalog.xml"))
+}
+
+fn uses_autocatalog_makes(node_fields_data: &serde_json::Value) -> bool {
+	let mut uses_makes = false;
+	for_each_content_item(&mut node_fields_data.clone(), &mut |tag, content_item| {
+		uses_makes |= tag == Some("Make") && is_autocatalog_item(content_item);
+	});
+	uses_makes
+}
+
+// Add the fetched values to the content items as a new "values" field and drop the links.
+// Autocatalog.xml makes come from the imported catalog; Model, Generation, Modification,
+// BodyType and Doors depend on the chosen make and are served by the /avito/autocatalog lookups.
+fn attach_dictionary_values(
+	node_fields_data: &mut serde_json::Value,
+	dictionaries: &HashMap<String, serde_json::Value>,
+	make_values: &[serde_json::Value],
+) {
+	for_each_content_item(node_fields_data, &mut |tag, content_item| {
+		let values = content_item
+			.get("values_link_json")
+			.and_then(|v| v.as_str())
+			.and_then(|link| dictionaries.get(link))
+			.cloned()
+			.or_else(|| {
+				(tag == Some("Make")
+					&& is_autocatalog_item(content_item)
+					&& !make_values.is_empty())
+				.then(|| serde_json::Value::Array(make_values.to_vec()))
+			});
+
+		let Some(content_item) = content_item.as_object_mut() else {
+			return;
+		};
+		if let Some(values) = values {
+			content_item.insert("values".to_string(), values);
+		}
+
+		content_item.remove("values_link_json");
+		content_item.remove("values_link_xml");
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_attach_dictionary_values() {
+		let mut node_fields = json!({
+			"fields": [
+				{
+					"tag": "Condition",
+					"content": [{ "values_link_json": "https://avito.ru/values/condition" }]
+				},
+				{
+					"tag": "Make",
+					"content": [{ "values_link_xml": "https://autoload.avito.ru/Autocatalog.xml" }],
+					"children": [
+						{
+							"tag": "TireType",
+							"content": [
+								{ "values_link_json": "https://avito.ru/values/tires" },
+								{ "values_link_json": "https://avito.ru/values/condition" }
+							]
+						}
+					]
+				}
+			]
+		});
+
+		assert_eq!(
+			collect_values_links(&node_fields),
+			BTreeSet::from([
+				"https://avito.ru/values/condition".to_string(),
+				"https://avito.ru/values/tires".to_string(),
+			])
+		);
+
+		let dictionaries = HashMap::from([(
+			"https://avito.ru/values/condition".to_string(),
+			json!([{ "value": "Новое" }]),
+		)]);
+		assert!(uses_autocatalog_makes(&node_fields));
+		attach_dictionary_values(
+			&mut node_fields,
+			&dictionaries,
+			&[json!({ "value": "Audi" })],
+		);
+
+		assert_eq!(
+			node_fields,
+			json!({
+				"fields": [
 					{
-						for child_content_item in child_content_array.iter_mut() {
-							// Process values_link_json in children
-							if let Some(values_link_json) = child_content_item
-								.get("values_link_json")
-								.and_then(|v| v.as_str())
-							{
-								// Make additional request to fetch values from the JSON link
-								let values_response = Client::builder()
-									.danger_accept_invalid_certs(true)
-									.timeout(std::time::Duration::from_secs(5)) // Add 5 second timeout
-									.build()?
-									.get(values_link_json)
-									.headers(headers.clone())
-									.send()
-									.await?;
-
-								if values_response.status().is_success() {
-									let values_text = values_response.text().await?;
-									let values_data: serde_json::Value =
-										serde_json::from_str(&values_text).map_err(|e| {
-											ApiError::JsonParseError(values_text.clone())
-										})?;
-
-									// Add the fetched values to the content item as a new "values" field
-									child_content_item
-										.as_object_mut()
-										.unwrap()
-										.insert("values".to_string(), values_data);
-								}
-							} else if let Some(values_link_xml) = child_content_item
-								.get("values_link_xml")
-								.and_then(|v| v.as_str())
+						"tag": "Condition",
+						"content": [{ "values": [{ "value": "Новое" }] }]
+					},
+					{
+						"tag": "Make",
+						"content": [{ "values": [{ "value": "Audi" }] }],
+						"children": [
 							{
-								// Check if the values_link_xml contains "Autocatalog.xml"
-								if values_link_xml.contains("Autocatalog.xml") {
-									// Skip database query if the field's tag is one of the specified values
-									let should_skip_db_query = if let Some(ref tag_val) = tag_value
-									{
-										tag_val == "Model"
-											|| tag_val == "Generation" || tag_val == "Modification"
-											|| tag_val == "BodyType" || tag_val == "Doors"
-									} else {
-										false
-									};
-
-									if !should_skip_db_query {
-										// Since avito_car_marks table may not exist, we'll simulate the data or make an HTTP request
-										// This preserves the original behavior when the database is not set up
-
-										// For now, we'll skip the database query and just continue
-										// In a real scenario, you'd need to implement the database query properly
-									}
-								} else {
-									// For other XML links, make HTTP request as before (if needed)
-									// This preserves the original behavior for non-Autocatalog.xml links
-								}
+								"tag": "TireType",
+								"content": [{}, { "values": [{ "value": "Новое" }] }]
 							}
-
-							// Remove the values_link_json and values_link_xml fields since we've fetched the data
-							child_content_item
-								.as_object_mut()
-								.unwrap()
-								.remove("values_link_json");
-							child_content_item
-								.as_object_mut()
-								.unwrap()
-								.remove("values_link_xml");
-						}
+						]
 					}
-				}
-			}
-		}
+				]
+			})
+		);
 	}
-
-	Ok(HttpResponse::Ok().json(json!({
-		"status": "success",
-		"data": node_fields_data
-	})))
 }
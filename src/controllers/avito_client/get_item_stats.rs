@@ -0,0 +1,139 @@
+use crate::controllers::avito_client::item_stats_aggregation::{
+	aggregate_item_stats, period_start, previous_period_start, STATS_PERIODS,
+};
+use crate::controllers::avito_client::token_manager::authorize_account;
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::{ApiError, AvitoItemStat, GetItemStatsParams, ItemStatsTotals};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
+use actix_web::{get, web, HttpResponse, Result};
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use serde_json::json;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+const GROUP_BY: [&str; 3] = ["account", "ad", "feed"];
+// Longest range one query may cover
+const MAX_RANGE_DAYS: i64 = 731;
+
+// GET stored item statistics summed per day, week or month, for the account or per ad or
+// feed, each period with its change against the previous one. date_from is moved back to
+// the start of its period.
+#[get(
+	"/avito/accounts/{account_id}/item_stats",
+	wrap = "RequirePermission(Permission::UseAvitoApi)"
+)]
+pub async fn get_item_stats(
+	path: web::Path<Uuid>,
+	params: web::Query<GetItemStatsParams>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	use crate::schema::avito_item_stats::dsl;
+
+	let account_id = path.into_inner();
+	let params = params.into_inner();
+	authorize_account(&data, account_id, user.user_id)?;
+
+	let period = params.period.unwrap_or_else(|| "day".to_string());
+	let group_by = params.group_by.unwrap_or_else(|| "account".to_string());
+	let date_to = params.date_to.unwrap_or_else(|| Utc::now().date_naive());
+	let date_from = params
+		.date_from
+		.unwrap_or_else(|| date_to - Duration::days(29));
+
+	let invalid = if !STATS_PERIODS.contains(&period.as_str()) {
+		Some(format!(
+			"period must be one of {}",
+			STATS_PERIODS.join(", ")
+		))
+	} else if !GROUP_BY.contains(&group_by.as_str()) {
+		Some(format!("group_by must be one of {}", GROUP_BY.join(", ")))
+	} else if date_from > date_to {
+		Some("date_from must not be after date_to".to_string())
+	} else if (date_to - date_from).num_days() > MAX_RANGE_DAYS {
+		Some(format!(
+			"The range may span at most {} days",
+			MAX_RANGE_DAYS
+		))
+	} else {
+		None
+	};
+	if let Some(message) = invalid {
+		return Ok(HttpResponse::BadRequest().json(json!({
+			"status": "fail",
+			"message": message
+		})));
+	}
+
+	let mut conn = data.db.get().map_err(|e| ApiError::Other(e.to_string()))?;
+
+	// Feed of every item that has a local ad, the oldest ad winning
+	let item_feeds: HashMap<String, Uuid> = if group_by == "feed" || params.feed_id.is_some() {
+		let links: Vec<(Option<String>, Uuid)> = crate::schema::avito_ads::table
+			.inner_join(crate::schema::avito_feeds::table)
+			.filter(crate::schema::avito_feeds::account_id.eq(account_id))
+			.filter(crate::schema::avito_ads::avito_ad_id.is_not_null())
+			.order(crate::schema::avito_ads::created_ts.desc())
+			.select((
+				crate::schema::avito_ads::avito_ad_id,
+				crate::schema::avito_ads::feed_id,
+			))
+			.load(&mut conn)?;
+		links
+			.into_iter()
+			.filter_map(|(item_id, feed_id)| Some((item_id?, feed_id)))
+			.collect()
+	} else {
+		HashMap::new()
+	};
+
+	let mut query = dsl::avito_item_stats
+		.filter(dsl::account_id.eq(account_id))
+		.filter(dsl::stat_date.ge(previous_period_start(
+			period_start(date_from, &period),
+			&period,
+		)))
+		.filter(dsl::stat_date.le(date_to))
+		.into_boxed();
+	if let Some(item_id) = &params.item_id {
+		query = query.filter(dsl::item_id.eq(item_id.clone()));
+	}
+	let stats = query.load::<AvitoItemStat>(&mut conn)?;
+
+	let rows: Vec<_> = stats
+		.into_iter()
+		.filter(|stat| {
+			params
+				.feed_id
+				.is_none_or(|feed_id| item_feeds.get(&stat.item_id) == Some(&feed_id))
+		})
+		.map(|stat| {
+			let key = match group_by.as_str() {
+				"ad" => Some(stat.item_id.clone()),
+				"feed" => item_feeds.get(&stat.item_id).map(Uuid::to_string),
+				_ => None,
+			};
+			let totals = ItemStatsTotals {
+				impressions: stat.impressions,
+				views: stat.views,
+				contacts: stat.contacts,
+				favorites: stat.favorites,
+				spending: stat.spending,
+			};
+			(stat.stat_date, key, totals)
+		})
+		.collect();
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": {
+			"period": period,
+			"group_by": group_by,
+			"date_from": period_start(date_from, &period),
+			"date_to": date_to,
+			"buckets": aggregate_item_stats(&rows, &period, date_from, date_to)
+		}
+	})))
+}
@@ -0,0 +1,52 @@
+use crate::controllers::avito_client::token_manager::authorize_account;
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::{
+	ApiError, AvitoItemSync, PaginationParams, PaginationResponse, ResponseWithPagination,
+};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
+use actix_web::{get, web, HttpResponse, Result};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+// GET items sync history of an account, newest run first
+#[get(
+	"/avito/accounts/{account_id}/item_syncs",
+	wrap = "RequirePermission(Permission::UseAvitoApi)"
+)]
+pub async fn get_item_syncs(
+	path: web::Path<Uuid>,
+	pagination: web::Query<PaginationParams>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	let account_id = path.into_inner();
+	let page = pagination.page.unwrap_or(1).max(1);
+	let limit = pagination.limit.unwrap_or(10).clamp(1, 100);
+	let offset = (page - 1) * limit;
+
+	authorize_account(&data, account_id, user.user_id)?;
+
+	let mut conn = data.db.get().map_err(|e| ApiError::Other(e.to_string()))?;
+	let total = crate::schema::avito_item_syncs::table
+		.filter(crate::schema::avito_item_syncs::account_id.eq(account_id))
+		.count()
+		.get_result::<i64>(&mut conn)?;
+	let syncs = crate::schema::avito_item_syncs::table
+		.filter(crate::schema::avito_item_syncs::account_id.eq(account_id))
+		.order(crate::schema::avito_item_syncs::started_ts.desc())
+		.limit(limit as i64)
+		.offset(offset as i64)
+		.load::<AvitoItemSync>(&mut conn)?;
+
+	Ok(HttpResponse::Ok().json(ResponseWithPagination {
+		status: "success".to_string(),
+		data: syncs,
+		pagination: PaginationResponse {
+			page,
+			limit,
+			total,
+			pages: ((total as f64) / (limit as f64)).ceil() as u32,
+		},
+	}))
+}
@@ -0,0 +1,30 @@
+use crate::controllers::avito_client::preview_price_update::price_update_response;
+use crate::controllers::avito_client::start_price_update::find_user_price_update;
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::{ApiError, AvitoPriceUpdateItem};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
+use actix_web::{get, web, HttpResponse, Result};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+// GET a price update with the per-item diff and results
+#[get(
+	"/avito/price_updates/{job_id}",
+	wrap = "RequirePermission(Permission::UseAvitoApi)"
+)]
+pub async fn get_price_update(
+	path: web::Path<Uuid>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	let mut conn = data.db.get().map_err(|e| ApiError::Other(e.to_string()))?;
+	let job = find_user_price_update(&mut conn, path.into_inner(), user.user_id)?;
+
+	let items = crate::schema::avito_price_update_items::table
+		.filter(crate::schema::avito_price_update_items::job_id.eq(job.job_id))
+		.order(crate::schema::avito_price_update_items::line.asc())
+		.load::<AvitoPriceUpdateItem>(&mut conn)?;
+
+	Ok(HttpResponse::Ok().json(price_update_response(job, items)))
+}
@@ -0,0 +1,75 @@
+use crate::controllers::avito_client::start_price_update::find_user_price_update;
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::{ApiError, AvitoPriceUpdateItem};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
+use actix_web::{get, web, HttpResponse, Result};
+use csv::Writer;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+// GET the per-item result of a price update as a CSV file
+#[get(
+	"/avito/price_updates/{job_id}/report",
+	wrap = "RequirePermission(Permission::UseAvitoApi)"
+)]
+pub async fn get_price_update_report(
+	path: web::Path<Uuid>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	let mut conn = data.db.get().map_err(|e| ApiError::Other(e.to_string()))?;
+	let job = find_user_price_update(&mut conn, path.into_inner(), user.user_id)?;
+
+	let items = crate::schema::avito_price_update_items::table
+		.filter(crate::schema::avito_price_update_items::job_id.eq(job.job_id))
+		.order(crate::schema::avito_price_update_items::line.asc())
+		.load::<AvitoPriceUpdateItem>(&mut conn)?;
+
+	let csv_error = |e: csv::Error| ApiError::Other(format!("CSV write error: {}", e));
+	let price = |price: Option<f64>| price.map(|p| p.to_string()).unwrap_or_default();
+
+	let mut writer = Writer::from_writer(Vec::new());
+	writer
+		.write_record([
+			"line",
+			"item_id",
+			"old_price",
+			"new_price",
+			"status",
+			"error",
+			"updated_ts",
+		])
+		.map_err(csv_error)?;
+	for item in &items {
+		writer
+			.write_record([
+				item.line.to_string().as_str(),
+				item.item_id.as_str(),
+				price(item.old_price).as_str(),
+				price(item.new_price).as_str(),
+				item.status.as_str(),
+				item.error.as_deref().unwrap_or(""),
+				item.updated_ts
+					.map(|ts| ts.to_rfc3339())
+					.unwrap_or_default()
+					.as_str(),
+			])
+			.map_err(csv_error)?;
+	}
+	let csv_bytes = writer
+		.into_inner()
+		.map_err(|e| ApiError::Other(format!("CSV finish error: {}", e)))?;
+
+	Ok(HttpResponse::Ok()
+		.content_type("text/csv")
+		.append_header((
+			"Content-Disposition",
+			format!(
+				"attachment; filename=\"price_update_{}_{}.csv\"",
+				job.created_ts.format("%Y-%m-%d"),
+				job.job_id
+			),
+		))
+		.body(csv_bytes))
+}
@@ -0,0 +1,153 @@
+use crate::models::{ItemStatsBucket, ItemStatsDelta, ItemStatsTotals};
+use chrono::{Datelike, Duration, NaiveDate};
+use std::collections::{BTreeMap, HashMap};
+
+pub const STATS_PERIODS: [&str; 3] = ["day", "week", "month"];
+
+// First day of the day, ISO week or month that contains `date`
+pub fn period_start(date: NaiveDate, period: &str) -> NaiveDate {
+	match period {
+		"week" => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+		"month" => date.with_day(1).unwrap_or(date),
+		_ => date,
+	}
+}
+
+// Start of the period right before the one starting at `start`
+pub fn previous_period_start(start: NaiveDate, period: &str) -> NaiveDate {
+	period_start(start - Duration::days(1), period)
+}
+
+impl ItemStatsTotals {
+	fn add(&mut self, other: &ItemStatsTotals) {
+		self.impressions += other.impressions;
+		self.views += other.views;
+		self.contacts += other.contacts;
+		self.favorites += other.favorites;
+		self.spending += other.spending;
+	}
+
+	fn metrics(&self) -> [(&'static str, f64); 5] {
+		[
+			("impressions", self.impressions as f64),
+			("views", self.views as f64),
+			("contacts", self.contacts as f64),
+			("favorites", self.favorites as f64),
+			("spending", self.spending),
+		]
+	}
+}
+
+// Sum daily rows into periods per key and compare every period with the one before it.
+// `rows` should reach one period back from `from`, so the first period has a delta too.
+pub fn aggregate_item_stats(
+	rows: &[(NaiveDate, Option<String>, ItemStatsTotals)],
+	period: &str,
+	from: NaiveDate,
+	to: NaiveDate,
+) -> Vec<ItemStatsBucket> {
+	let mut totals: BTreeMap<(NaiveDate, Option<String>), ItemStatsTotals> = BTreeMap::new();
+	for (date, key, day) in rows {
+		totals
+			.entry((period_start(*date, period), key.clone()))
+			.or_default()
+			.add(day);
+	}
+
+	let first = period_start(from, period);
+	let lookup: HashMap<(NaiveDate, Option<String>), ItemStatsTotals> = totals
+		.iter()
+		.map(|(bucket, totals)| (bucket.clone(), *totals))
+		.collect();
+
+	totals
+		.into_iter()
+		.filter(|((start, _), _)| *start >= first && *start <= to)
+		.map(|((start, key), current)| {
+			let previous = lookup
+				.get(&(previous_period_start(start, period), key.clone()))
+				.copied()
+				.unwrap_or_default();
+
+			let deltas = current
+				.metrics()
+				.into_iter()
+				.zip(previous.metrics())
+				.map(|((name, current), (_, previous))| {
+					let change = current - previous;
+					(
+						name,
+						ItemStatsDelta {
+							previous,
+							change,
+							change_percent: (previous != 0.0)
+								.then(|| (change / previous * 10000.0).round() / 100.0),
+						},
+					)
+				})
+				.collect();
+
+			ItemStatsBucket {
+				period_start: start,
+				key,
+				totals: current,
+				deltas,
+			}
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn date(day: u32) -> NaiveDate {
+		NaiveDate::from_ymd_opt(2026, 10, day).unwrap()
+	}
+
+	fn views(views: i64) -> ItemStatsTotals {
+		ItemStatsTotals {
+			views,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_aggregate_item_stats() {
+		assert_eq!(period_start(date(18), "week"), date(12));
+		assert_eq!(period_start(date(18), "month"), date(1));
+		assert_eq!(
+			previous_period_start(date(1), "month"),
+			NaiveDate::from_ymd_opt(2026, 9, 1).unwrap()
+		);
+
+		let item = |id: &str| Some(id.to_string());
+		let rows = vec![
+			(date(6), item("1"), views(10)),
+			(date(8), item("1"), views(30)),
+			(date(13), item("1"), views(50)),
+			(date(14), item("1"), views(10)),
+			(date(14), item("2"), views(5)),
+		];
+
+		let buckets = aggregate_item_stats(&rows, "week", date(14), date(18));
+		assert_eq!(buckets.len(), 2);
+		assert_eq!(buckets[0].period_start, date(12));
+		assert_eq!(buckets[0].key, item("1"));
+		assert_eq!(buckets[0].totals.views, 60);
+		assert_eq!(
+			buckets[0].deltas["views"],
+			ItemStatsDelta {
+				previous: 40.0,
+				change: 20.0,
+				change_percent: Some(50.0),
+			}
+		);
+		assert_eq!(buckets[1].key, item("2"));
+		assert_eq!(buckets[1].deltas["views"].change_percent, None);
+
+		let days = aggregate_item_stats(&rows, "day", date(14), date(14));
+		assert_eq!(days.len(), 2);
+		assert_eq!(days[0].deltas["views"].previous, 50.0);
+	}
+}
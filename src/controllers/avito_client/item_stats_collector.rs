@@ -0,0 +1,177 @@
+use crate::controllers::avito_client::avito_api::AvitoApi;
+use crate::controllers::avito_client::items_sync_scheduler::load_connected_accounts;
+use crate::controllers::avito_client::token_manager::AvitoTokenManager;
+use crate::models::{
+	ApiError, AvitoItemAnalyticsRequest, AvitoItemAnalyticsResponse, UpsertAvitoItemStat,
+};
+use chrono::{DateTime, Duration as ChronoDuration, NaiveDate, Utc};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+use uuid::Uuid;
+
+// Metrics stored per item and day; the named ones also get a column of their own
+const COLLECTED_METRICS: [&str; 5] = [
+	"impressions",
+	"views",
+	"contacts",
+	"favorites",
+	"allSpending",
+];
+// Largest page Avito's statistics endpoint returns
+const STATS_PAGE_SIZE: i32 = 1000;
+const MAX_STATS_PAGES: i32 = 100;
+
+// Collect the statistics of every connected account each `interval_minutes`; 0 turns the job off
+pub async fn start_item_stats_scheduler(
+	db_pool: Pool<ConnectionManager<PgConnection>>,
+	tokens: AvitoTokenManager,
+	api: Arc<dyn AvitoApi>,
+	interval_minutes: u64,
+	lookback_days: u32,
+) {
+	if interval_minutes == 0 {
+		log::info!("Scheduled Avito item statistics collection is disabled");
+		return;
+	}
+
+	loop {
+		sleep(Duration::from_secs(interval_minutes * 60)).await;
+
+		let account_ids = match load_connected_accounts(&db_pool) {
+			Ok(account_ids) => account_ids,
+			Err(e) => {
+				log::error!("Failed to load Avito accounts for statistics: {}", e);
+				continue;
+			}
+		};
+
+		for account_id in account_ids {
+			if let Err(e) = collect_account_item_stats(
+				&db_pool,
+				&tokens,
+				api.as_ref(),
+				account_id,
+				lookback_days,
+			)
+			.await
+			{
+				log::warn!(
+					"Collecting item statistics of account {} failed: {}",
+					account_id,
+					e
+				);
+			}
+		}
+	}
+}
+
+// Fetch the daily statistics of the last `lookback_days` days and upsert them. Avito keeps
+// adjusting the recent days, so they are collected again on every run.
+pub async fn collect_account_item_stats(
+	db_pool: &Pool<ConnectionManager<PgConnection>>,
+	tokens: &AvitoTokenManager,
+	api: &dyn AvitoApi,
+	account_id: Uuid,
+	lookback_days: u32,
+) -> Result<usize, ApiError> {
+	let profile = tokens
+		.with_token(db_pool, account_id, |avito_token| async move {
+			api.get_user_profile(&avito_token).await
+		})
+		.await?;
+	let avito_user_id = profile.id.to_string();
+
+	let today = Utc::now().date_naive();
+	let date_from = today - ChronoDuration::days(lookback_days.max(1) as i64 - 1);
+	let collected_ts = Utc::now();
+	let mut rows = Vec::new();
+
+	for page in 0..MAX_STATS_PAGES {
+		let request = AvitoItemAnalyticsRequest {
+			date_from: date_from.format("%Y-%m-%d").to_string(),
+			date_to: today.format("%Y-%m-%d").to_string(),
+			grouping: "day".to_string(),
+			limit: STATS_PAGE_SIZE,
+			metrics: COLLECTED_METRICS.iter().map(|m| m.to_string()).collect(),
+			offset: page * STATS_PAGE_SIZE,
+		};
+		let (avito_user_id, request) = (&avito_user_id, &request);
+		let response = tokens
+			.with_token(db_pool, account_id, |avito_token| async move {
+				api.get_item_analytics(&avito_token, avito_user_id, request)
+					.await
+			})
+			.await?;
+
+		let last_page = response.result.items.len() < STATS_PAGE_SIZE as usize;
+		rows.extend(stats_rows(account_id, &response, collected_ts));
+		if last_page {
+			break;
+		}
+	}
+
+	let stored = rows.len();
+	let mut conn = db_pool.get().map_err(|e| ApiError::Other(e.to_string()))?;
+	conn.transaction(|conn| {
+		for row in &rows {
+			diesel::insert_into(crate::schema::avito_item_stats::table)
+				.values(row)
+				.on_conflict((
+					crate::schema::avito_item_stats::account_id,
+					crate::schema::avito_item_stats::item_id,
+					crate::schema::avito_item_stats::stat_date,
+				))
+				.do_update()
+				.set(row)
+				.execute(conn)?;
+		}
+		Ok::<_, diesel::result::Error>(())
+	})?;
+
+	log::info!(
+		"Stored {} daily item statistics of account {} since {}",
+		stored,
+		account_id,
+		date_from
+	);
+	Ok(stored)
+}
+
+// One row per item and day; points with an unreadable date are dropped
+pub fn stats_rows(
+	account_id: Uuid,
+	response: &AvitoItemAnalyticsResponse,
+	collected_ts: DateTime<Utc>,
+) -> Vec<UpsertAvitoItemStat> {
+	let count = |metrics: &std::collections::BTreeMap<String, serde_json::Value>, name: &str| {
+		metrics
+			.get(name)
+			.and_then(|value| value.as_f64())
+			.unwrap_or(0.0)
+	};
+
+	response
+		.result
+		.items
+		.iter()
+		.flat_map(|item| {
+			item.stats.iter().filter_map(move |point| {
+				let stat_date = NaiveDate::parse_from_str(&point.date, "%Y-%m-%d").ok()?;
+				Some(UpsertAvitoItemStat {
+					account_id,
+					item_id: item.item_id.to_string(),
+					stat_date,
+					impressions: count(&point.metrics, "impressions") as i64,
+					views: count(&point.metrics, "views") as i64,
+					contacts: count(&point.metrics, "contacts") as i64,
+					favorites: count(&point.metrics, "favorites") as i64,
+					spending: count(&point.metrics, "allSpending"),
+					metrics: serde_json::to_value(&point.metrics).unwrap_or_default(),
+					collected_ts,
+				})
+			})
+		})
+		.collect()
+}
@@ -0,0 +1,60 @@
+use crate::controllers::avito_client::avito_api::AvitoApi;
+use crate::controllers::avito_client::sync_avito_items::sync_account_items;
+use crate::controllers::avito_client::token_manager::AvitoTokenManager;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+use uuid::Uuid;
+
+// Sync the items of every connected account each `interval_minutes`; 0 turns the job off
+pub async fn start_items_sync_scheduler(
+	db_pool: Pool<ConnectionManager<PgConnection>>,
+	tokens: AvitoTokenManager,
+	api: Arc<dyn AvitoApi>,
+	interval_minutes: u64,
+) {
+	if interval_minutes == 0 {
+		log::info!("Scheduled Avito items sync is disabled");
+		return;
+	}
+
+	loop {
+		sleep(Duration::from_secs(interval_minutes * 60)).await;
+
+		let account_ids = match load_connected_accounts(&db_pool) {
+			Ok(account_ids) => account_ids,
+			Err(e) => {
+				log::error!("Failed to load Avito accounts to sync: {}", e);
+				continue;
+			}
+		};
+
+		// One account at a time; the request policy spreads the calls within an account
+		for account_id in account_ids {
+			if let Err(e) = sync_account_items(&db_pool, &tokens, api.as_ref(), account_id).await {
+				log::warn!(
+					"Scheduled items sync of account {} failed: {}",
+					account_id,
+					e
+				);
+			}
+		}
+	}
+}
+
+// Accounts whose credentials are not known to be broken
+pub fn load_connected_accounts(
+	db_pool: &Pool<ConnectionManager<PgConnection>>,
+) -> Result<Vec<Uuid>, String> {
+	let mut conn = db_pool.get().map_err(|e| e.to_string())?;
+	crate::schema::avito_accounts::table
+		.filter(
+			crate::schema::avito_accounts::is_connected
+				.is_null()
+				.or(crate::schema::avito_accounts::is_connected.eq(true)),
+		)
+		.select(crate::schema::avito_accounts::account_id)
+		.load(&mut conn)
+		.map_err(|e| e.to_string())
+}
@@ -1,12 +1,37 @@
+pub mod avito_api;
+pub mod balance_monitor;
+pub mod catalog_cache;
+pub mod category_schema;
+pub mod collect_item_stats;
 pub mod config;
+#[cfg(test)]
+pub mod fake_avito_api;
 pub mod get_avito_balance;
 pub mod get_avito_item_analytics;
 pub mod get_avito_items;
 pub mod get_avito_token;
 pub mod get_avito_user_profile;
+pub mod get_balance_alert;
+pub mod get_balance_history;
 pub mod get_categories_tree;
 pub mod get_category_fields;
+pub mod get_item_stats;
+pub mod get_item_syncs;
+pub mod get_price_update;
+pub mod get_price_update_report;
+pub mod item_stats_aggregation;
+pub mod item_stats_collector;
+pub mod items_sync_scheduler;
+pub mod preview_price_update;
+pub mod price_update_input;
+pub mod refresh_category_cache;
+pub mod request_policy;
+pub mod start_price_update;
+pub mod sync_avito_items;
+pub mod token_manager;
 pub mod update_avito_price;
+pub mod upload_price_update;
+pub mod upsert_balance_alert;
 
 use actix_web::web;
 
@@ -0,0 +1,267 @@
+use crate::controllers::avito_client::price_update_input::{parse_price_json, PriceUpdateLine};
+use crate::controllers::avito_client::sync_avito_items::fetch_all_items;
+use crate::controllers::avito_client::token_manager::authorize_account;
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::{
+	ApiError, AvitoItem, AvitoPriceUpdateItem, AvitoPriceUpdateJob, CreateAvitoPriceUpdateItem,
+	CreateAvitoPriceUpdateJob, PriceUpdateBody, PriceUpdateDiff,
+};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
+use actix_web::{post, web, HttpResponse, Result};
+use diesel::prelude::*;
+use serde_json::json;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+// Upper bound of a single price list, so one upload cannot occupy the account's rate limit for hours
+pub const MAX_PRICE_UPDATE_ITEMS: usize = 10000;
+
+// POST a JSON price list and get the diff against the current prices.
+// The job is stored as a preview and runs once started.
+#[post(
+	"/avito/accounts/{account_id}/price_updates",
+	wrap = "RequirePermission(Permission::UseAvitoApi)"
+)]
+pub async fn preview_price_update(
+	path: web::Path<Uuid>,
+	body: web::Json<PriceUpdateBody>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	let account_id = path.into_inner();
+	authorize_account(&data, account_id, user.user_id)?;
+
+	let lines = parse_price_json(&body.items);
+	create_price_update_preview(&data, user.user_id, account_id, lines).await
+}
+
+// Compare a validated price list with the live items and store it as a preview job
+pub async fn create_price_update_preview(
+	data: &AppState,
+	user_id: Uuid,
+	account_id: Uuid,
+	lines: Vec<PriceUpdateLine>,
+) -> Result<HttpResponse, ApiError> {
+	if lines.is_empty() {
+		return Ok(HttpResponse::BadRequest().json(json!({
+			"status": "error",
+			"message": "The price list is empty"
+		})));
+	}
+	if lines.len() > MAX_PRICE_UPDATE_ITEMS {
+		return Ok(HttpResponse::BadRequest().json(json!({
+			"status": "error",
+			"message": format!("A price list may contain at most {} items", MAX_PRICE_UPDATE_ITEMS)
+		})));
+	}
+
+	let items = fetch_all_items(
+		&data.db,
+		&data.avito_tokens,
+		data.avito_api.as_ref(),
+		account_id,
+	)
+	.await?;
+	let planned = plan_price_update(&lines, &items);
+
+	let mut conn = data.db.get().map_err(|e| ApiError::Other(e.to_string()))?;
+	let (job, items) = conn.transaction(|conn| {
+		let job: AvitoPriceUpdateJob =
+			diesel::insert_into(crate::schema::avito_price_update_jobs::table)
+				.values(CreateAvitoPriceUpdateJob {
+					account_id,
+					user_id,
+					status: "preview".to_string(),
+					items_total: planned.len() as i32,
+					items_skipped: planned
+						.iter()
+						.filter(|item| item.status != "pending")
+						.count() as i32,
+				})
+				.get_result(conn)?;
+
+		let rows: Vec<CreateAvitoPriceUpdateItem> = planned
+			.into_iter()
+			.map(|item| CreateAvitoPriceUpdateItem {
+				job_id: job.job_id,
+				..item
+			})
+			.collect();
+		// Postgres caps a statement at 65535 bind parameters
+		let mut items: Vec<AvitoPriceUpdateItem> = Vec::with_capacity(rows.len());
+		for chunk in rows.chunks(5000) {
+			items.extend(
+				diesel::insert_into(crate::schema::avito_price_update_items::table)
+					.values(chunk)
+					.get_results::<AvitoPriceUpdateItem>(conn)?,
+			);
+		}
+		items.sort_by_key(|item| item.line);
+
+		Ok::<_, diesel::result::Error>((job, items))
+	})?;
+
+	Ok(HttpResponse::Ok().json(price_update_response(job, items)))
+}
+
+// Match every line to the live item: invalid lines and unknown items are not sent to Avito,
+// neither are prices that are already set
+pub fn plan_price_update(
+	lines: &[PriceUpdateLine],
+	items: &[AvitoItem],
+) -> Vec<CreateAvitoPriceUpdateItem> {
+	let current: HashMap<String, Option<f64>> = items
+		.iter()
+		.map(|item| (item.id.to_string(), item.price))
+		.collect();
+
+	lines
+		.iter()
+		.map(|line| {
+			let old_price = current.get(&line.item_id).copied().flatten();
+			let (status, error) = match (&line.error, current.contains_key(&line.item_id)) {
+				(Some(error), _) => ("invalid", Some(error.clone())),
+				(None, false) => (
+					"invalid",
+					Some(format!(
+						"Item {} is not listed in the account",
+						line.item_id
+					)),
+				),
+				(None, true) if old_price == line.price => ("unchanged", None),
+				(None, true) => ("pending", None),
+			};
+
+			CreateAvitoPriceUpdateItem {
+				job_id: Uuid::nil(),
+				line: line.line,
+				item_id: line.item_id.clone(),
+				old_price,
+				new_price: line.price,
+				status: status.to_string(),
+				error,
+			}
+		})
+		.collect()
+}
+
+pub fn price_update_response(
+	job: AvitoPriceUpdateJob,
+	items: Vec<AvitoPriceUpdateItem>,
+) -> serde_json::Value {
+	let items: Vec<PriceUpdateDiff> = items.into_iter().map(PriceUpdateDiff::from).collect();
+	json!({
+		"status": "success",
+		"data": {
+			"job": job,
+			"items": items
+		}
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::controllers::avito_client::fake_avito_api::{
+		extract_fake_user, fake_app_state, FakeAvitoApi,
+	};
+	use actix_web::http::StatusCode;
+	use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+	use actix_web::App;
+	use actix_web_grants::GrantsMiddleware;
+	use std::sync::Arc;
+
+	fn item(id: i64, price: Option<f64>) -> AvitoItem {
+		AvitoItem {
+			id,
+			title: None,
+			price,
+			status: Some("active".to_string()),
+			url: None,
+			address: None,
+			category: None,
+		}
+	}
+
+	fn line(line: i32, item_id: &str, price: Option<f64>, error: Option<&str>) -> PriceUpdateLine {
+		PriceUpdateLine {
+			line,
+			item_id: item_id.to_string(),
+			price,
+			error: error.map(str::to_string),
+		}
+	}
+
+	#[test]
+	fn test_plan_price_update() {
+		let planned = plan_price_update(
+			&[
+				line(1, "101", Some(150.0), None),
+				line(2, "102", Some(200.0), None),
+				line(3, "103", Some(300.0), None),
+				line(4, "10x", None, Some("Invalid item_id \"10x\"")),
+				line(5, "104", Some(400.0), None),
+			],
+			&[
+				item(101, Some(100.0)),
+				item(102, Some(200.0)),
+				item(104, None),
+			],
+		);
+
+		let statuses: Vec<(&str, &str, Option<f64>)> = planned
+			.iter()
+			.map(|item| (item.item_id.as_str(), item.status.as_str(), item.old_price))
+			.collect();
+		assert_eq!(
+			statuses,
+			vec![
+				("101", "pending", Some(100.0)),
+				("102", "unchanged", Some(200.0)),
+				("103", "invalid", None),
+				("10x", "invalid", None),
+				("104", "pending", None),
+			]
+		);
+		assert_eq!(
+			planned[2].error.as_deref(),
+			Some("Item 103 is not listed in the account")
+		);
+	}
+
+	// Lists refused before anything is fetched or stored, so no database is involved
+	#[actix_web::test]
+	async fn test_preview_price_update_refuses_bad_lists() {
+		let (data, account_id) = fake_app_state(Arc::new(FakeAvitoApi::new())).await;
+		let app = init_service(
+			App::new()
+				.app_data(data)
+				.wrap(GrantsMiddleware::with_extractor(extract_fake_user))
+				.service(preview_price_update),
+		)
+		.await;
+
+		let preview = |account_id: Uuid, items: Vec<serde_json::Value>| {
+			TestRequest::post()
+				.uri(&format!("/avito/accounts/{}/price_updates", account_id))
+				.set_json(json!({ "items": items }))
+				.to_request()
+		};
+
+		let response = call_service(&app, preview(account_id, vec![])).await;
+		assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+		let body: serde_json::Value = read_body_json(response).await;
+		assert_eq!(body["message"], "The price list is empty");
+
+		let items = (0..=MAX_PRICE_UPDATE_ITEMS)
+			.map(|id| json!({ "item_id": id, "price": 100 }))
+			.collect();
+		let response = call_service(&app, preview(account_id, items)).await;
+		assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+		let items = vec![json!({ "item_id": 1, "price": 100 })];
+		let response = call_service(&app, preview(Uuid::new_v4(), items)).await;
+		assert_eq!(response.status(), StatusCode::NOT_FOUND);
+	}
+}
@@ -0,0 +1,219 @@
+use crate::models::PriceUpdateInputRow;
+use std::collections::HashSet;
+
+// Column names accepted in the header of a price list, compared in lower case
+const ITEM_ID_COLUMNS: [&str; 4] = ["item_id", "id", "avito_id", "avitoid"];
+const PRICE_COLUMNS: [&str; 2] = ["price", "цена"];
+
+// A validated row of a price list; rows with an error are kept so the preview can show them
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceUpdateLine {
+	// 1-based row of the input, header excluded
+	pub line: i32,
+	pub item_id: String,
+	pub price: Option<f64>,
+	pub error: Option<String>,
+}
+
+// Prices come from spreadsheets: "1 500,50", "1500.5" and "1500 ₽" are all the same kind of value
+pub fn parse_price(value: &str) -> Option<f64> {
+	let cleaned: String = value
+		.trim()
+		.trim_end_matches('₽')
+		.trim_end_matches("руб.")
+		.chars()
+		.filter(|c| !c.is_whitespace())
+		.map(|c| if c == ',' { '.' } else { c })
+		.collect();
+	cleaned
+		.parse::<f64>()
+		.ok()
+		.filter(|price| price.is_finite())
+}
+
+fn validate_line(
+	line: i32,
+	item_id: &str,
+	price: &str,
+	seen: &mut HashSet<String>,
+) -> PriceUpdateLine {
+	let item_id = item_id.trim().to_string();
+	let parsed_price = parse_price(price);
+
+	let error = if item_id.is_empty() {
+		Some("item_id is required".to_string())
+	} else if !item_id.chars().all(|c| c.is_ascii_digit()) {
+		Some(format!("Invalid item_id \"{}\"", item_id))
+	} else if price.trim().is_empty() {
+		Some("price is required".to_string())
+	} else if parsed_price.is_none() {
+		Some(format!("Invalid price \"{}\"", price.trim()))
+	} else if parsed_price.is_some_and(|price| price <= 0.0) {
+		Some("price must be greater than zero".to_string())
+	} else if !seen.insert(item_id.clone()) {
+		Some(format!("Duplicate item_id {}", item_id))
+	} else {
+		None
+	};
+
+	PriceUpdateLine {
+		line,
+		item_id,
+		price: if error.is_none() { parsed_price } else { None },
+		error,
+	}
+}
+
+// Values of a JSON row as text, so they are validated the same way as CSV cells
+fn json_cell(value: &serde_json::Value) -> String {
+	match value {
+		serde_json::Value::String(s) => s.clone(),
+		serde_json::Value::Null => String::new(),
+		value => value.to_string(),
+	}
+}
+
+pub fn parse_price_json(rows: &[PriceUpdateInputRow]) -> Vec<PriceUpdateLine> {
+	let mut seen = HashSet::new();
+	rows.iter()
+		.enumerate()
+		.map(|(index, row)| {
+			validate_line(
+				index as i32 + 1,
+				&json_cell(&row.item_id),
+				&json_cell(&row.price),
+				&mut seen,
+			)
+		})
+		.collect()
+}
+
+// Excel saves CSV with ';' in locales that use the decimal comma, so the delimiter is
+// taken from the first line
+fn detect_delimiter(content: &str) -> u8 {
+	let first_line = content.lines().next().unwrap_or_default();
+	[b';', b'\t', b',']
+		.into_iter()
+		.max_by_key(|delimiter| first_line.matches(*delimiter as char).count())
+		.filter(|delimiter| first_line.contains(*delimiter as char))
+		.unwrap_or(b',')
+}
+
+// Parse a CSV price list. The header is optional; without one the first column is the
+// item id and the second the price.
+pub fn parse_price_csv(bytes: &[u8]) -> Result<Vec<PriceUpdateLine>, String> {
+	let content = std::str::from_utf8(bytes)
+		.map_err(|_| "The file must be UTF-8 encoded".to_string())?
+		.trim_start_matches('\u{feff}');
+
+	let mut reader = csv::ReaderBuilder::new()
+		.delimiter(detect_delimiter(content))
+		.has_headers(false)
+		.flexible(true)
+		.from_reader(content.as_bytes());
+
+	let mut records = Vec::new();
+	for record in reader.records() {
+		let record = record.map_err(|e| format!("Invalid CSV: {}", e))?;
+		if record.iter().all(|cell| cell.trim().is_empty()) {
+			continue;
+		}
+		records.push(record);
+	}
+
+	let mut columns = (0, 1);
+	if let Some(first) = records.first() {
+		let header: Vec<String> = first
+			.iter()
+			.map(|cell| cell.trim().to_lowercase())
+			.collect();
+		let item_id_column = header
+			.iter()
+			.position(|cell| ITEM_ID_COLUMNS.contains(&cell.as_str()));
+		let price_column = header
+			.iter()
+			.position(|cell| PRICE_COLUMNS.contains(&cell.as_str()));
+
+		match (item_id_column, price_column) {
+			(Some(item_id), Some(price)) => {
+				columns = (item_id, price);
+				records.remove(0);
+			}
+			(None, None) => {}
+			_ => {
+				return Err(format!(
+					"The header must name both the item id ({}) and the price ({}) columns",
+					ITEM_ID_COLUMNS.join(", "),
+					PRICE_COLUMNS.join(", ")
+				))
+			}
+		}
+	}
+
+	let mut seen = HashSet::new();
+	Ok(records
+		.iter()
+		.enumerate()
+		.map(|(index, record)| {
+			validate_line(
+				index as i32 + 1,
+				record.get(columns.0).unwrap_or_default(),
+				record.get(columns.1).unwrap_or_default(),
+				&mut seen,
+			)
+		})
+		.collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	fn valid(line: i32, item_id: &str, price: f64) -> PriceUpdateLine {
+		PriceUpdateLine {
+			line,
+			item_id: item_id.to_string(),
+			price: Some(price),
+			error: None,
+		}
+	}
+
+	#[test]
+	fn test_parse_price_lists() {
+		assert_eq!(parse_price("1 500,50"), Some(1500.5));
+		assert_eq!(parse_price("1500 ₽"), Some(1500.0));
+		assert_eq!(parse_price("NaN"), None);
+
+		let lines = parse_price_csv(
+			"\u{feff}Название;ID;Цена\nШины;101;\"12 000,50\"\nДиски;102;9000\n\n;103;abc\n"
+				.as_bytes(),
+		)
+		.unwrap();
+		assert_eq!(lines[0], valid(1, "101", 12000.5));
+		assert_eq!(lines[1], valid(2, "102", 9000.0));
+		assert_eq!(lines[2].line, 3);
+		assert_eq!(lines[2].error.as_deref(), Some("Invalid price \"abc\""));
+
+		let lines = parse_price_csv(b"101,100\n102,-5\n101,200\n").unwrap();
+		assert_eq!(lines[0], valid(1, "101", 100.0));
+		assert_eq!(
+			lines[1].error.as_deref(),
+			Some("price must be greater than zero")
+		);
+		assert_eq!(lines[2].error.as_deref(), Some("Duplicate item_id 101"));
+
+		assert!(parse_price_csv(b"id,cost\n101,100\n").is_err());
+
+		let rows: Vec<PriceUpdateInputRow> = serde_json::from_value(json!([
+			{ "item_id": 101, "price": 100 },
+			{ "item_id": "10x", "price": "100" },
+			{ "item_id": "102", "price": null }
+		]))
+		.unwrap();
+		let lines = parse_price_json(&rows);
+		assert_eq!(lines[0], valid(1, "101", 100.0));
+		assert_eq!(lines[1].error.as_deref(), Some("Invalid item_id \"10x\""));
+		assert_eq!(lines[2].error.as_deref(), Some("price is required"));
+	}
+}
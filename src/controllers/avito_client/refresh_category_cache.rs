@@ -0,0 +1,81 @@
+use crate::controllers::avito_client::catalog_cache::{cached_category_slugs, CacheMode};
+use crate::controllers::avito_client::get_categories_tree::fetch_categories_tree;
+use crate::controllers::avito_client::get_category_fields::fetch_category_fields;
+use crate::controllers::avito_client::token_manager::authorize_account;
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::{ApiError, RefreshCategoryCacheParams};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
+use actix_web::{post, web, HttpResponse, Result};
+use futures::StreamExt;
+use serde_json::json;
+
+/// Reload the category tree and node fields from Avito, ignoring the cache TTLs
+///
+/// Expected request body:
+/// {
+///     "account_id": "uuid-of-the-avito-account",
+///     "avito_slugs": ["zapchasti"]   // optional, every cached category by default
+/// }
+///
+/// A category Avito fails to return keeps its previous cached copy and is reported with its error.
+#[post(
+	"/avito/category_cache/refresh",
+	wrap = "RequirePermission(Permission::UseAvitoApi)"
+)]
+pub async fn refresh_category_cache(
+	opts: web::Json<RefreshCategoryCacheParams>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	authorize_account(&data, opts.account_id, user.user_id)?;
+
+	let avito_slugs = match &opts.avito_slugs {
+		Some(avito_slugs) => avito_slugs.clone(),
+		None => {
+			let mut conn = data.db.get().map_err(|e| ApiError::Other(e.to_string()))?;
+			cached_category_slugs(&mut conn)?
+		}
+	};
+
+	let tree_error = fetch_categories_tree(&data, opts.account_id, CacheMode::Refresh)
+		.await
+		.err()
+		.map(|e| e.to_string());
+
+	// One category at a time: each one already loads its dictionaries with
+	// avito_catalog_fetch_concurrency requests in flight
+	let categories: Vec<serde_json::Value> = futures::stream::iter(avito_slugs)
+		.then(|avito_slug| {
+			let data = &data;
+			let account_id = opts.account_id;
+			async move {
+				let result =
+					fetch_category_fields(data, account_id, &avito_slug, CacheMode::Refresh).await;
+				match result {
+					Ok(_) => json!({ "avito_slug": avito_slug, "refreshed": true }),
+					Err(e) => {
+						log::warn!("Failed to refresh fields of {}: {}", avito_slug, e);
+						json!({
+							"avito_slug": avito_slug,
+							"refreshed": false,
+							"error": e.to_string()
+						})
+					}
+				}
+			}
+		})
+		.collect()
+		.await;
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": {
+			"tree": {
+				"refreshed": tree_error.is_none(),
+				"error": tree_error
+			},
+			"categories": categories
+		}
+	})))
+}
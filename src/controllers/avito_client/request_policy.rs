@@ -0,0 +1,400 @@
+use crate::config::Config;
+use crate::models::{ApiError, AvitoRetryReport};
+use rand_core::{OsRng, RngCore};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::time::{sleep, Duration, Instant};
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct RequestPolicySettings {
+	// Retries after the first attempt; 0 disables retrying
+	pub max_retries: u32,
+	pub base_delay: Duration,
+	// Upper bound of a single backoff; a longer Retry-After gives up instead of waiting
+	pub max_delay: Duration,
+	// Requests per second of one account; 0 disables the limiter
+	pub rate_per_sec: f64,
+	pub burst: u32,
+	// Consecutive outages (5xx, network errors) that open the circuit
+	pub failure_threshold: u32,
+	pub cooldown: Duration,
+}
+
+impl RequestPolicySettings {
+	pub fn from_config(config: &Config) -> Self {
+		Self {
+			max_retries: config.avito_max_retries,
+			base_delay: Duration::from_millis(config.avito_retry_base_delay_ms),
+			max_delay: Duration::from_millis(config.avito_retry_max_delay_ms),
+			rate_per_sec: config.avito_rate_limit_per_sec,
+			burst: config.avito_rate_limit_burst.max(1),
+			failure_threshold: config.avito_circuit_failure_threshold.max(1),
+			cooldown: Duration::from_secs(config.avito_circuit_cooldown_secs),
+		}
+	}
+}
+
+// GCRA bucket: `tat` is when the bucket would be full again at the configured rate
+struct Bucket {
+	tat: Instant,
+	paused_until: Option<Instant>,
+}
+
+impl Bucket {
+	// Reserve the next slot, possibly one in the future, and return how long to wait for it
+	fn reserve(&mut self, now: Instant, interval: Duration, burst: u32) -> Duration {
+		let from = self
+			.paused_until
+			.filter(|until| *until > now)
+			.unwrap_or(now);
+		let tat = self.tat.max(from) + interval;
+		let allowed_at = tat.checked_sub(interval * burst).unwrap_or(from).max(from);
+		self.tat = tat;
+		allowed_at.saturating_duration_since(now)
+	}
+}
+
+enum Circuit {
+	Closed { failures: u32 },
+	Open { until: Instant },
+	// Cooldown is over and one probe request is on its way; another probe is sent if it
+	// hasn't reported back within a cooldown, e.g. because the caller dropped it
+	HalfOpen { probe_sent: Instant },
+}
+
+enum Failure {
+	// Avito or the network is down; counts towards the circuit breaker
+	Outage,
+	RateLimited,
+	// Anything a retry won't fix: validation errors, 401/403/404 and so on
+	Permanent,
+}
+
+fn classify(error: &ApiError) -> Failure {
+	match error {
+		ApiError::AvitoApiError(429, _) | ApiError::AvitoRetryAfter(429, _, _) => {
+			Failure::RateLimited
+		}
+		ApiError::AvitoApiError(500 | 502 | 503 | 504, _)
+		| ApiError::AvitoRetryAfter(500 | 502 | 503 | 504, _, _)
+		| ApiError::ReqwestError(_) => Failure::Outage,
+		_ => Failure::Permanent,
+	}
+}
+
+fn avito_status(error: &ApiError) -> Option<u16> {
+	match error {
+		ApiError::AvitoApiError(status, _) | ApiError::AvitoRetryAfter(status, _, _) => {
+			Some(*status)
+		}
+		_ => None,
+	}
+}
+
+// Outbound policy shared by all Avito calls: per-account rate limit, retries with
+// exponential backoff and jitter, Retry-After and a circuit breaker over the whole API
+#[derive(Clone)]
+pub struct AvitoRequestPolicy {
+	settings: Arc<RequestPolicySettings>,
+	buckets: Arc<Mutex<HashMap<Uuid, Bucket>>>,
+	circuit: Arc<Mutex<Circuit>>,
+}
+
+impl AvitoRequestPolicy {
+	pub fn new(settings: RequestPolicySettings) -> Self {
+		Self {
+			settings: Arc::new(settings),
+			buckets: Arc::default(),
+			circuit: Arc::new(Mutex::new(Circuit::Closed { failures: 0 })),
+		}
+	}
+
+	pub fn from_config(config: &Config) -> Self {
+		Self::new(RequestPolicySettings::from_config(config))
+	}
+
+	fn interval(&self) -> Duration {
+		if self.settings.rate_per_sec > 0.0 {
+			Duration::from_secs_f64(1.0 / self.settings.rate_per_sec)
+		} else {
+			Duration::ZERO
+		}
+	}
+
+	// Wait for the account's next request slot; returns the time spent waiting
+	async fn acquire(&self, account_id: Uuid) -> Duration {
+		let wait = {
+			let now = Instant::now();
+			let mut buckets = self.buckets.lock().unwrap();
+			let bucket = buckets.entry(account_id).or_insert(Bucket {
+				tat: now,
+				paused_until: None,
+			});
+			bucket.reserve(now, self.interval(), self.settings.burst)
+		};
+
+		if !wait.is_zero() {
+			log::info!(
+				"Throttling Avito request of account {} for {} ms",
+				account_id,
+				wait.as_millis()
+			);
+			sleep(wait).await;
+		}
+		wait
+	}
+
+	// Hold back every request of the account until Avito's Retry-After has passed
+	fn pause(&self, account_id: Uuid, retry_after: Duration) {
+		let until = Instant::now() + retry_after;
+		let mut buckets = self.buckets.lock().unwrap();
+		let bucket = buckets.entry(account_id).or_insert(Bucket {
+			tat: Instant::now(),
+			paused_until: None,
+		});
+		bucket.paused_until = Some(bucket.paused_until.map_or(until, |p| p.max(until)));
+	}
+
+	// Fail fast while the circuit is open; after the cooldown a single probe is let through
+	fn check_circuit(&self) -> Result<(), ApiError> {
+		let mut circuit = self.circuit.lock().unwrap();
+		let now = Instant::now();
+		let retry_at = match *circuit {
+			Circuit::Closed { .. } => return Ok(()),
+			Circuit::Open { until } => until,
+			Circuit::HalfOpen { probe_sent } => probe_sent + self.settings.cooldown,
+		};
+
+		if now >= retry_at {
+			log::info!("Avito circuit half-open, sending a probe request");
+			*circuit = Circuit::HalfOpen { probe_sent: now };
+			Ok(())
+		} else {
+			let remaining = retry_at.saturating_duration_since(now).as_secs_f64().ceil();
+			Err(ApiError::AvitoCircuitOpen(remaining as u64))
+		}
+	}
+
+	fn record_outcome<T>(&self, result: &Result<T, ApiError>) {
+		let outage = matches!(result, Err(error) if matches!(classify(error), Failure::Outage));
+		let mut circuit = self.circuit.lock().unwrap();
+
+		*circuit = match (&*circuit, outage) {
+			(Circuit::Closed { .. }, false) => Circuit::Closed { failures: 0 },
+			(_, false) => {
+				log::info!("Avito responded again, circuit closed");
+				Circuit::Closed { failures: 0 }
+			}
+			(Circuit::Closed { failures }, true)
+				if failures + 1 < self.settings.failure_threshold =>
+			{
+				Circuit::Closed {
+					failures: failures + 1,
+				}
+			}
+			(Circuit::Open { until }, true) => Circuit::Open { until: *until },
+			(_, true) => {
+				log::error!(
+					"Avito looks unavailable, circuit opened for {} s",
+					self.settings.cooldown.as_secs()
+				);
+				Circuit::Open {
+					until: Instant::now() + self.settings.cooldown,
+				}
+			}
+		};
+	}
+
+	// Exponential backoff with equal jitter: half of the delay is fixed, half random
+	fn backoff(&self, retry: u32) -> Duration {
+		let exponential = self
+			.settings
+			.base_delay
+			.saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+			.min(self.settings.max_delay);
+		let half = exponential / 2;
+		let jitter_ms = OsRng.next_u64() % (half.as_millis() as u64 + 1);
+		half + Duration::from_millis(jitter_ms)
+	}
+
+	// Run one Avito request of the account under the policy. Retryable errors that outlast
+	// the retries come back as AvitoRetriesExhausted with a report of what was tried.
+	pub async fn run<T, F, Fut>(&self, account_id: Uuid, mut request: F) -> Result<T, ApiError>
+	where
+		F: FnMut() -> Fut,
+		Fut: Future<Output = Result<T, ApiError>>,
+	{
+		let mut report = AvitoRetryReport::default();
+
+		loop {
+			self.check_circuit()?;
+			report.throttled_ms += self.acquire(account_id).await.as_millis() as u64;
+			report.attempts += 1;
+
+			let result = request().await;
+			self.record_outcome(&result);
+			let error = match result {
+				Ok(value) => return Ok(value),
+				Err(error) => error,
+			};
+
+			let retry_after = match &error {
+				ApiError::AvitoRetryAfter(_, secs, _) => Some(Duration::from_secs(*secs)),
+				_ => None,
+			};
+			report.avito_status = avito_status(&error);
+			report.retry_after_secs = retry_after.map(|delay| delay.as_secs());
+
+			let delay = match classify(&error) {
+				Failure::Permanent => return Err(error),
+				Failure::RateLimited | Failure::Outage => {
+					retry_after.unwrap_or_else(|| self.backoff(report.attempts))
+				}
+			};
+
+			if report.attempts > self.settings.max_retries || delay > self.settings.max_delay {
+				log::error!(
+					"Avito request of account {} failed after {} attempts: {}",
+					account_id,
+					report.attempts,
+					error
+				);
+				return Err(ApiError::AvitoRetriesExhausted(report, Box::new(error)));
+			}
+
+			log::warn!(
+				"Avito request of account {} failed ({}), retry {}/{} in {} ms",
+				account_id,
+				error,
+				report.attempts,
+				self.settings.max_retries,
+				delay.as_millis()
+			);
+
+			if retry_after.is_some() {
+				// The wait is taken by acquire(), so other requests of the account hold back too
+				self.pause(account_id, delay);
+			} else {
+				report.backoff_ms += delay.as_millis() as u64;
+				sleep(delay).await;
+			}
+		}
+	}
+}
+
+// Policy that sends every request once and right away, for tests
+#[cfg(test)]
+pub fn no_retry_policy() -> AvitoRequestPolicy {
+	AvitoRequestPolicy::new(RequestPolicySettings {
+		max_retries: 0,
+		base_delay: Duration::from_millis(1),
+		max_delay: Duration::from_millis(1),
+		rate_per_sec: 0.0,
+		burst: 1,
+		failure_threshold: 3,
+		cooldown: Duration::from_secs(60),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::atomic::{AtomicU32, Ordering};
+
+	fn settings() -> RequestPolicySettings {
+		RequestPolicySettings {
+			max_retries: 2,
+			base_delay: Duration::from_millis(1),
+			max_delay: Duration::from_millis(50),
+			rate_per_sec: 0.0,
+			burst: 1,
+			failure_threshold: 3,
+			cooldown: Duration::from_secs(60),
+		}
+	}
+
+	#[test]
+	fn test_bucket_reserve() {
+		let now = Instant::now();
+		let interval = Duration::from_millis(100);
+		let mut bucket = Bucket {
+			tat: now,
+			paused_until: None,
+		};
+
+		// A burst of two passes at once, the third waits for the next slot
+		assert_eq!(bucket.reserve(now, interval, 2), Duration::ZERO);
+		assert_eq!(bucket.reserve(now, interval, 2), Duration::ZERO);
+		assert_eq!(bucket.reserve(now, interval, 2), interval);
+
+		bucket.paused_until = Some(now + Duration::from_secs(5));
+		assert!(bucket.reserve(now, interval, 2) >= Duration::from_secs(5));
+	}
+
+	#[actix_web::test]
+	async fn test_retry_and_circuit_breaker() {
+		let policy = AvitoRequestPolicy::new(settings());
+		let account_id = Uuid::new_v4();
+		let calls = AtomicU32::new(0);
+
+		// Two outages, then Avito answers
+		let result = policy
+			.run(account_id, || async {
+				match calls.fetch_add(1, Ordering::SeqCst) {
+					0 | 1 => Err(ApiError::AvitoApiError(503, "unavailable".to_string())),
+					_ => Ok("ok"),
+				}
+			})
+			.await;
+		assert_eq!(result.unwrap(), "ok");
+		assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+		// Permanent errors are returned at once
+		calls.store(0, Ordering::SeqCst);
+		let result: Result<(), ApiError> = policy
+			.run(account_id, || async {
+				calls.fetch_add(1, Ordering::SeqCst);
+				Err(ApiError::AvitoApiError(404, "not found".to_string()))
+			})
+			.await;
+		assert!(matches!(result, Err(ApiError::AvitoApiError(404, _))));
+		assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+		// A Retry-After beyond max_delay gives up with a report
+		let result: Result<(), ApiError> = policy
+			.run(account_id, || async {
+				Err(ApiError::AvitoRetryAfter(429, 120, "slow down".to_string()))
+			})
+			.await;
+		match result {
+			Err(ApiError::AvitoRetriesExhausted(report, _)) => {
+				assert_eq!(report.attempts, 1);
+				assert_eq!(report.avito_status, Some(429));
+				assert_eq!(report.retry_after_secs, Some(120));
+			}
+			other => panic!("unexpected result: {:?}", other.err()),
+		}
+
+		// Three outages in a row open the circuit, later requests fail without calling Avito
+		calls.store(0, Ordering::SeqCst);
+		let result: Result<(), ApiError> = policy
+			.run(account_id, || async {
+				calls.fetch_add(1, Ordering::SeqCst);
+				Err(ApiError::ReqwestError("connection refused".to_string()))
+			})
+			.await;
+		assert!(matches!(
+			result,
+			Err(ApiError::AvitoRetriesExhausted(
+				AvitoRetryReport { attempts: 3, .. },
+				_
+			))
+		));
+		assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+		let result: Result<(), ApiError> = policy.run(account_id, || async { Ok(()) }).await;
+		assert!(matches!(result, Err(ApiError::AvitoCircuitOpen(_))));
+	}
+}
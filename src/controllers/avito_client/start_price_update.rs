@@ -0,0 +1,309 @@
+use crate::controllers::avito_client::avito_api::AvitoApi;
+use crate::controllers::avito_client::token_manager::AvitoTokenManager;
+use crate::controllers::websocket::WebSocketConnections;
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::{ApiError, AvitoPriceUpdateItem, AvitoPriceUpdateJob};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
+use actix_web::{post, web, HttpResponse, Result};
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use futures::StreamExt;
+use serde::Serialize;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+use uuid::Uuid;
+
+// How often a running job refreshes its heartbeat
+const HEARTBEAT_INTERVAL_SECS: u64 = 30;
+// A running job without a heartbeat for this long lost its server
+const STALE_HEARTBEAT_SECS: i64 = 5 * 60;
+
+// Progress message sent to the user's WebSocket connections while prices are updated
+#[derive(Serialize, Debug)]
+pub struct PriceUpdateProgress {
+	#[serde(rename = "type")]
+	pub kind: &'static str,
+	// running, completed or failed
+	pub stage: &'static str,
+	pub job_id: Uuid,
+	pub items_total: i32,
+	pub items_processed: i32,
+	pub items_updated: i32,
+	pub items_failed: i32,
+	pub error: Option<String>,
+}
+
+// POST start a previewed price update; the prices are sent to Avito in the background
+#[post(
+	"/avito/price_updates/{job_id}/start",
+	wrap = "RequirePermission(Permission::UseAvitoApi)"
+)]
+pub async fn start_price_update(
+	path: web::Path<Uuid>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	use crate::schema::avito_price_update_jobs::dsl;
+
+	let job_id = path.into_inner();
+	let mut conn = data.db.get().map_err(|e| ApiError::Other(e.to_string()))?;
+
+	let job = find_user_price_update(&mut conn, job_id, user.user_id)?;
+	// Only the preview may be started, and only once even if the button is hit twice
+	let job: AvitoPriceUpdateJob = diesel::update(
+		dsl::avito_price_update_jobs
+			.find(job.job_id)
+			.filter(dsl::status.eq("preview")),
+	)
+	.set((
+		dsl::status.eq("running"),
+		dsl::started_ts.eq(Utc::now()),
+		dsl::heartbeat_ts.eq(Utc::now()),
+	))
+	.get_result(&mut conn)
+	.optional()?
+	.ok_or_else(|| {
+		ApiError::Conflict(format!("Price update {} has already been started", job_id))
+	})?;
+
+	let pool = data.db.clone();
+	let tokens = data.avito_tokens.clone();
+	let api = data.avito_api.clone();
+	let ws_server = data.ws_server.clone();
+	let concurrency = data.env.avito_bulk_price_concurrency.max(1);
+	let running_job = job.clone();
+	tokio::spawn(async move {
+		run_price_update(pool, tokens, api, ws_server, concurrency, running_job).await
+	});
+
+	Ok(HttpResponse::Accepted().json(json!({
+		"status": "success",
+		"data": job
+	})))
+}
+
+// The job, if it was created by the user; other users' jobs are reported as missing
+pub fn find_user_price_update(
+	conn: &mut PgConnection,
+	job_id: Uuid,
+	user_id: Uuid,
+) -> Result<AvitoPriceUpdateJob, ApiError> {
+	crate::schema::avito_price_update_jobs::table
+		.find(job_id)
+		.filter(crate::schema::avito_price_update_jobs::user_id.eq(user_id))
+		.first::<AvitoPriceUpdateJob>(conn)
+		.optional()?
+		.ok_or_else(|| ApiError::NotFound(format!("Price update {} not found", job_id)))
+}
+
+// Send the pending prices to Avito, a few at a time. The account's request policy keeps
+// the pace within Avito's rate limit; every item is recorded as soon as Avito answers.
+pub async fn run_price_update(
+	pool: Pool<ConnectionManager<PgConnection>>,
+	tokens: AvitoTokenManager,
+	api: Arc<dyn AvitoApi>,
+	ws_server: WebSocketConnections,
+	concurrency: usize,
+	job: AvitoPriceUpdateJob,
+) {
+	let mut progress = PriceUpdateProgress {
+		kind: "price_update_progress",
+		stage: "running",
+		job_id: job.job_id,
+		items_total: job.items_total,
+		items_processed: job.items_skipped,
+		items_updated: 0,
+		items_failed: 0,
+		error: None,
+	};
+
+	// Another server may be sweeping for interrupted jobs, this one shows it is still alive
+	let heartbeat = tokio::spawn(beat_heartbeat(pool.clone(), job.job_id));
+
+	let result = async {
+		let pending: Vec<AvitoPriceUpdateItem> = {
+			let mut conn = pool.get().map_err(|e| ApiError::Other(e.to_string()))?;
+			crate::schema::avito_price_update_items::table
+				.filter(crate::schema::avito_price_update_items::job_id.eq(job.job_id))
+				.filter(crate::schema::avito_price_update_items::status.eq("pending"))
+				.order(crate::schema::avito_price_update_items::line.asc())
+				.load(&mut conn)?
+		};
+
+		let (pool, tokens, api) = (&pool, &tokens, api.as_ref());
+		let mut updates = futures::stream::iter(pending)
+			.map(|item| async move {
+				let price = item.new_price.unwrap_or_default();
+				let result = tokens
+					.with_token(pool, job.account_id, |avito_token| {
+						let item_id = item.item_id.clone();
+						async move { api.update_price(&avito_token, &item_id, price).await }
+					})
+					.await;
+				(item, result)
+			})
+			.buffer_unordered(concurrency);
+
+		while let Some((item, result)) = updates.next().await {
+			let (status, error) = match result {
+				Ok(response) if response.result.success => ("updated", None),
+				Ok(_) => ("failed", Some("Avito did not accept the price".to_string())),
+				Err(e) => ("failed", Some(e.to_string())),
+			};
+			if status == "updated" {
+				progress.items_updated += 1;
+			} else {
+				progress.items_failed += 1;
+			}
+			progress.items_processed += 1;
+
+			record_item_result(pool, &item, status, error, &progress)?;
+			send_progress(&ws_server, job.user_id, &progress).await;
+		}
+
+		Ok::<_, ApiError>(())
+	}
+	.await;
+	heartbeat.abort();
+
+	let (status, error) = match result {
+		Ok(()) => ("completed", None),
+		Err(e) => ("failed", Some(e.to_string())),
+	};
+	if let Ok(mut conn) = pool.get() {
+		use crate::schema::avito_price_update_jobs::dsl;
+		let finished = diesel::update(dsl::avito_price_update_jobs.find(job.job_id))
+			.set((
+				dsl::status.eq(status),
+				dsl::error.eq(&error),
+				dsl::finished_ts.eq(Utc::now()),
+			))
+			.execute(&mut conn);
+		if let Err(e) = finished {
+			log::error!("Failed to finish price update {}: {}", job.job_id, e);
+		}
+	}
+
+	match &error {
+		None => log::info!(
+			"Price update {} of account {} completed: {} updated, {} failed",
+			job.job_id,
+			job.account_id,
+			progress.items_updated,
+			progress.items_failed
+		),
+		Some(e) => log::error!("Price update {} failed: {}", job.job_id, e),
+	}
+
+	progress.stage = status;
+	progress.error = error;
+	send_progress(&ws_server, job.user_id, &progress).await;
+}
+
+fn record_item_result(
+	pool: &Pool<ConnectionManager<PgConnection>>,
+	item: &AvitoPriceUpdateItem,
+	status: &str,
+	error: Option<String>,
+	progress: &PriceUpdateProgress,
+) -> Result<(), ApiError> {
+	use crate::schema::{avito_price_update_items, avito_price_update_jobs};
+
+	let mut conn = pool.get().map_err(|e| ApiError::Other(e.to_string()))?;
+	conn.transaction(|conn| {
+		diesel::update(avito_price_update_items::table.find(item.job_item_id))
+			.set((
+				avito_price_update_items::status.eq(status),
+				avito_price_update_items::error.eq(error),
+				avito_price_update_items::updated_ts.eq(Utc::now()),
+			))
+			.execute(conn)?;
+		diesel::update(avito_price_update_jobs::table.find(item.job_id))
+			.set((
+				avito_price_update_jobs::items_updated.eq(progress.items_updated),
+				avito_price_update_jobs::items_failed.eq(progress.items_failed),
+			))
+			.execute(conn)
+	})?;
+	Ok(())
+}
+
+async fn send_progress(
+	ws_server: &WebSocketConnections,
+	user_id: Uuid,
+	progress: &PriceUpdateProgress,
+) {
+	if let Ok(message) = serde_json::to_string(progress) {
+		ws_server
+			.broadcast_message_to_user(&user_id.to_string(), &message)
+			.await;
+	}
+}
+
+async fn beat_heartbeat(pool: Pool<ConnectionManager<PgConnection>>, job_id: Uuid) {
+	use crate::schema::avito_price_update_jobs::dsl;
+
+	loop {
+		sleep(Duration::from_secs(HEARTBEAT_INTERVAL_SECS)).await;
+
+		let beat = pool.get().map_err(|e| e.to_string()).and_then(|mut conn| {
+			diesel::update(dsl::avito_price_update_jobs.find(job_id))
+				.set(dsl::heartbeat_ts.eq(Utc::now()))
+				.execute(&mut conn)
+				.map_err(|e| e.to_string())
+		});
+		if let Err(e) = beat {
+			log::error!(
+				"Failed to record the heartbeat of price update {}: {}",
+				job_id,
+				e
+			);
+		}
+	}
+}
+
+// Jobs marked running whose heartbeat stopped were cut off with the server that ran them.
+// Jobs of other servers that are still running keep their heartbeat fresh and are left alone.
+pub fn fail_interrupted_price_updates(pool: &Pool<ConnectionManager<PgConnection>>) {
+	use crate::schema::avito_price_update_jobs::dsl;
+
+	let Ok(mut conn) = pool.get() else {
+		return;
+	};
+	let stale_before = Utc::now() - chrono::Duration::seconds(STALE_HEARTBEAT_SECS);
+	let result = diesel::update(
+		dsl::avito_price_update_jobs
+			.filter(dsl::status.eq("running"))
+			.filter(
+				dsl::heartbeat_ts
+					.is_null()
+					.or(dsl::heartbeat_ts.lt(stale_before)),
+			),
+	)
+	.set((
+		dsl::status.eq("failed"),
+		dsl::error.eq("Interrupted by a server restart"),
+		dsl::finished_ts.eq(Utc::now()),
+	))
+	.execute(&mut conn);
+
+	match result {
+		Ok(0) => {}
+		Ok(count) => log::warn!("Marked {} interrupted price updates as failed", count),
+		Err(e) => log::error!("Failed to mark interrupted price updates: {}", e),
+	}
+}
+
+// Look for interrupted jobs now and then; the heartbeat of a job cut off by a restart
+// only goes stale a while after this server is back
+pub async fn start_interrupted_price_update_check(
+	pool: Pool<ConnectionManager<PgConnection>>,
+) -> ! {
+	loop {
+		fail_interrupted_price_updates(&pool);
+		sleep(Duration::from_secs(STALE_HEARTBEAT_SECS as u64)).await;
+	}
+}
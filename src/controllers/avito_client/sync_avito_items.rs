@@ -0,0 +1,542 @@
+use crate::controllers::avito_client::avito_api::AvitoApi;
+use crate::controllers::avito_client::token_manager::{authorize_account, AvitoTokenManager};
+use crate::controllers::avito_feeds::export_avito_xml::load_ads_field_values;
+use crate::controllers::avito_feeds::import_avito_xml::{
+	insert_xml_ad_fields, replace_ad_field_values, ImportClock,
+};
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::{
+	AdFieldValues, ApiError, AvitoAd, AvitoFeed, AvitoItem, AvitoItemSync, CreateAvitoFeed,
+	CreateAvitoItemSync, FinishAvitoItemSync,
+};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
+use actix_web::{post, web, HttpResponse, Result};
+use chrono::{Duration as ChronoDuration, Utc};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+// Category of the per-account feed that receives live items without a local ad
+pub const ITEMS_SYNC_FEED_CATEGORY: &str = "avito_items";
+// Status of a local ad whose item is no longer listed on Avito
+pub const REMOVED_ITEM_STATUS: &str = "removed";
+// Everything Avito still lists in the account; removed items are left out on purpose
+const LISTED_ITEM_STATUSES: &str = "active,old,blocked,rejected";
+const ITEMS_PAGE_SIZE: i32 = 100;
+// Stops the paging loop should Avito keep returning full pages; the sync fails then
+const MAX_ITEM_PAGES: i32 = 1000;
+// A sync still marked running after this long was interrupted, e.g. by a restart
+const STALE_SYNC_MINUTES: i64 = 60;
+// Tags written by the sync; other fields of an ad are left as they are
+const SYNCED_TAGS: [&str; 4] = ["AvitoId", "Price", "Title", "Url"];
+
+// Synced fields of a local ad, matched to the Avito item by avito_ad_id
+#[derive(Debug, Clone)]
+pub struct LocalAd {
+	pub ad_id: Uuid,
+	pub status: Option<String>,
+	pub fields: AdFieldValues,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedAd {
+	// None for items that get a new ad in the sync feed
+	pub ad_id: Option<Uuid>,
+	pub avito_ad_id: String,
+	pub status: String,
+	pub fields: AdFieldValues,
+}
+
+#[derive(Debug, Default)]
+pub struct ItemsSyncPlan {
+	pub items_seen: usize,
+	pub added: Vec<PlannedAd>,
+	pub updated: Vec<PlannedAd>,
+	pub unchanged: usize,
+	pub removed: Vec<Uuid>,
+}
+
+// POST sync the live items of the account into avito_ads
+#[post(
+	"/avito/accounts/{account_id}/sync_items",
+	wrap = "RequirePermission(Permission::UseAvitoApi)"
+)]
+pub async fn sync_avito_items(
+	path: web::Path<Uuid>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	let account_id = path.into_inner();
+	authorize_account(&data, account_id, user.user_id)?;
+
+	let sync = sync_account_items(
+		&data.db,
+		&data.avito_tokens,
+		data.avito_api.as_ref(),
+		account_id,
+	)
+	.await?;
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": sync
+	})))
+}
+
+// Page through all items of the account, store them and record the run in avito_item_syncs.
+// A failed run is recorded too and its error returned.
+pub async fn sync_account_items(
+	db_pool: &Pool<ConnectionManager<PgConnection>>,
+	tokens: &AvitoTokenManager,
+	api: &dyn AvitoApi,
+	account_id: Uuid,
+) -> Result<AvitoItemSync, ApiError> {
+	let sync = start_sync_record(db_pool, account_id)?;
+
+	let result = async {
+		let items = fetch_all_items(db_pool, tokens, api, account_id).await?;
+		let pool = db_pool.clone();
+		web::block(move || {
+			let mut conn = pool.get().map_err(|e| ApiError::Other(e.to_string()))?;
+			conn.transaction(|conn| store_items(conn, account_id, &items))
+				.map_err(ApiError::from)
+		})
+		.await
+		.map_err(|e| ApiError::Other(e.to_string()))?
+	}
+	.await;
+
+	let finish = match &result {
+		Ok(plan) => FinishAvitoItemSync {
+			status: "success".to_string(),
+			items_seen: plan.items_seen as i32,
+			ads_added: plan.added.len() as i32,
+			ads_updated: plan.updated.len() as i32,
+			ads_unchanged: plan.unchanged as i32,
+			ads_removed: plan.removed.len() as i32,
+			error: None,
+			finished_ts: Some(Utc::now()),
+		},
+		Err(e) => FinishAvitoItemSync {
+			status: "failed".to_string(),
+			error: Some(e.to_string()),
+			finished_ts: Some(Utc::now()),
+			..Default::default()
+		},
+	};
+
+	let mut conn = db_pool.get().map_err(|e| ApiError::Other(e.to_string()))?;
+	let sync: AvitoItemSync =
+		diesel::update(crate::schema::avito_item_syncs::table.find(sync.sync_id))
+			.set(&finish)
+			.get_result(&mut conn)?;
+
+	match result {
+		Ok(_) => {
+			log::info!(
+				"Synced Avito items of account {}: {} seen, {} added, {} updated, {} removed",
+				account_id,
+				sync.items_seen,
+				sync.ads_added,
+				sync.ads_updated,
+				sync.ads_removed
+			);
+			Ok(sync)
+		}
+		Err(e) => {
+			log::error!("Avito items sync of account {} failed: {}", account_id, e);
+			Err(e)
+		}
+	}
+}
+
+// Record a running sync; only one may run per account at a time
+fn start_sync_record(
+	db_pool: &Pool<ConnectionManager<PgConnection>>,
+	account_id: Uuid,
+) -> Result<AvitoItemSync, ApiError> {
+	use crate::schema::avito_item_syncs::dsl;
+
+	let mut conn = db_pool.get().map_err(|e| ApiError::Other(e.to_string()))?;
+
+	diesel::update(
+		dsl::avito_item_syncs
+			.filter(dsl::account_id.eq(account_id))
+			.filter(dsl::status.eq("running"))
+			.filter(dsl::started_ts.lt(Utc::now() - ChronoDuration::minutes(STALE_SYNC_MINUTES))),
+	)
+	.set((
+		dsl::status.eq("failed"),
+		dsl::error.eq("Interrupted"),
+		dsl::finished_ts.eq(Utc::now()),
+	))
+	.execute(&mut conn)?;
+
+	diesel::insert_into(dsl::avito_item_syncs)
+		.values(CreateAvitoItemSync {
+			account_id,
+			status: "running".to_string(),
+		})
+		.get_result(&mut conn)
+		.map_err(|e| match e {
+			diesel::result::Error::DatabaseError(
+				diesel::result::DatabaseErrorKind::UniqueViolation,
+				_,
+			) => ApiError::Conflict(format!(
+				"Items of account {} are already being synced",
+				account_id
+			)),
+			e => ApiError::from(e),
+		})
+}
+
+// Every item Avito lists in the account, paged through in full
+pub async fn fetch_all_items(
+	db_pool: &Pool<ConnectionManager<PgConnection>>,
+	tokens: &AvitoTokenManager,
+	api: &dyn AvitoApi,
+	account_id: Uuid,
+) -> Result<Vec<AvitoItem>, ApiError> {
+	fetch_item_pages(db_pool, tokens, api, account_id, MAX_ITEM_PAGES).await
+}
+
+// Fails once `max_pages` pages were read without reaching the last one. A partial list
+// would have every local ad missing from it marked as removed.
+async fn fetch_item_pages(
+	db_pool: &Pool<ConnectionManager<PgConnection>>,
+	tokens: &AvitoTokenManager,
+	api: &dyn AvitoApi,
+	account_id: Uuid,
+	max_pages: i32,
+) -> Result<Vec<AvitoItem>, ApiError> {
+	let mut items = Vec::new();
+
+	for page in 1..=max_pages {
+		let response = tokens
+			.with_token(db_pool, account_id, |avito_token| async move {
+				api.get_items(
+					&avito_token,
+					page,
+					ITEMS_PAGE_SIZE,
+					Some(LISTED_ITEM_STATUSES),
+				)
+				.await
+			})
+			.await?;
+
+		let last_page = response.resources.len() < ITEMS_PAGE_SIZE as usize;
+		items.extend(response.resources);
+		if last_page {
+			return Ok(items);
+		}
+	}
+
+	Err(ApiError::Other(format!(
+		"Avito listed more than {} pages of items in account {}, nothing was synced",
+		max_pages, account_id
+	)))
+}
+
+// Synced tags of an item, sorted by tag like the filtered local fields
+pub fn item_field_values(item: &AvitoItem) -> AdFieldValues {
+	let mut fields = vec![("AvitoId".to_string(), vec![item.id.to_string()])];
+	if let Some(price) = item.price {
+		fields.push(("Price".to_string(), vec![price.to_string()]));
+	}
+	if let Some(title) = &item.title {
+		fields.push(("Title".to_string(), vec![title.clone()]));
+	}
+	if let Some(url) = &item.url {
+		fields.push(("Url".to_string(), vec![url.clone()]));
+	}
+	fields
+}
+
+// Compare the live items with the local ads. Items Avito listed twice, as pages
+// may shift while paging, are taken once.
+pub fn plan_items_sync(local: &HashMap<String, LocalAd>, items: &[AvitoItem]) -> ItemsSyncPlan {
+	let mut plan = ItemsSyncPlan::default();
+	let mut seen_ids = HashSet::new();
+
+	for item in items {
+		let avito_ad_id = item.id.to_string();
+		if !seen_ids.insert(avito_ad_id.clone()) {
+			continue;
+		}
+		plan.items_seen += 1;
+
+		let planned = PlannedAd {
+			ad_id: None,
+			avito_ad_id,
+			status: item.status.clone().unwrap_or_else(|| "active".to_string()),
+			fields: item_field_values(item),
+		};
+
+		match local.get(&planned.avito_ad_id) {
+			None => plan.added.push(planned),
+			Some(ad)
+				if ad.status.as_deref() == Some(planned.status.as_str())
+					&& ad.fields == planned.fields =>
+			{
+				plan.unchanged += 1
+			}
+			Some(ad) => plan.updated.push(PlannedAd {
+				ad_id: Some(ad.ad_id),
+				..planned
+			}),
+		}
+	}
+
+	let mut removed: Vec<(&String, Uuid)> = local
+		.iter()
+		.filter(|(avito_ad_id, ad)| {
+			!seen_ids.contains(*avito_ad_id) && ad.status.as_deref() != Some(REMOVED_ITEM_STATUS)
+		})
+		.map(|(avito_ad_id, ad)| (avito_ad_id, ad.ad_id))
+		.collect();
+	removed.sort();
+	plan.removed = removed.into_iter().map(|(_, ad_id)| ad_id).collect();
+
+	plan
+}
+
+// Local ads of the account that are linked to an Avito item, the oldest one per item
+fn load_local_ads(
+	conn: &mut PgConnection,
+	account_id: Uuid,
+) -> Result<HashMap<String, LocalAd>, diesel::result::Error> {
+	let ads = crate::schema::avito_ads::table
+		.inner_join(crate::schema::avito_feeds::table)
+		.filter(crate::schema::avito_feeds::account_id.eq(account_id))
+		.filter(crate::schema::avito_ads::avito_ad_id.is_not_null())
+		.order(crate::schema::avito_ads::created_ts.asc())
+		.select(AvitoAd::as_select())
+		.load::<AvitoAd>(conn)?;
+
+	let mut local = HashMap::new();
+	for (ad, fields) in load_ads_field_values(conn, ads)? {
+		let Some(avito_ad_id) = ad.avito_ad_id else {
+			continue;
+		};
+		let mut fields: AdFieldValues = fields
+			.into_iter()
+			.filter(|(tag, _)| SYNCED_TAGS.contains(&tag.as_str()))
+			.collect();
+		fields.sort_by(|a, b| a.0.cmp(&b.0));
+
+		local.entry(avito_ad_id).or_insert(LocalAd {
+			ad_id: ad.ad_id,
+			status: ad.status,
+			fields,
+		});
+	}
+
+	Ok(local)
+}
+
+// The account's feed for items that have no ad in any other feed, created on first use
+fn sync_feed_id(conn: &mut PgConnection, account_id: Uuid) -> Result<Uuid, diesel::result::Error> {
+	let existing = crate::schema::avito_feeds::table
+		.filter(crate::schema::avito_feeds::account_id.eq(account_id))
+		.filter(crate::schema::avito_feeds::category.eq(ITEMS_SYNC_FEED_CATEGORY))
+		.order(crate::schema::avito_feeds::created_ts.asc())
+		.first::<AvitoFeed>(conn)
+		.optional()?;
+
+	match existing {
+		Some(feed) => Ok(feed.feed_id),
+		None => diesel::insert_into(crate::schema::avito_feeds::table)
+			.values(CreateAvitoFeed {
+				account_id,
+				category: ITEMS_SYNC_FEED_CATEGORY.to_string(),
+			})
+			.returning(crate::schema::avito_feeds::feed_id)
+			.get_result(conn),
+	}
+}
+
+// Write the planned changes; the caller runs this inside a transaction
+fn store_items(
+	conn: &mut PgConnection,
+	account_id: Uuid,
+	items: &[AvitoItem],
+) -> Result<ItemsSyncPlan, diesel::result::Error> {
+	let local = load_local_ads(conn, account_id)?;
+	let plan = plan_items_sync(&local, items);
+	let mut clock = ImportClock::new();
+
+	if !plan.added.is_empty() {
+		let feed_id = sync_feed_id(conn, account_id)?;
+		for ad in &plan.added {
+			let ad_id: Uuid = diesel::insert_into(crate::schema::avito_ads::table)
+				.values((
+					crate::schema::avito_ads::feed_id.eq(feed_id),
+					crate::schema::avito_ads::avito_ad_id.eq(&ad.avito_ad_id),
+					crate::schema::avito_ads::status.eq(&ad.status),
+					crate::schema::avito_ads::created_ts.eq(clock.tick().naive_utc()),
+				))
+				.returning(crate::schema::avito_ads::ad_id)
+				.get_result(conn)?;
+			insert_xml_ad_fields(conn, ad_id, ad.fields.clone(), &mut clock)?;
+		}
+	}
+
+	for ad in &plan.updated {
+		let Some(ad_id) = ad.ad_id else {
+			continue;
+		};
+
+		replace_ad_field_values(conn, ad_id, &SYNCED_TAGS, ad.fields.clone(), &mut clock)?;
+		diesel::update(crate::schema::avito_ads::table.find(ad_id))
+			.set(crate::schema::avito_ads::status.eq(&ad.status))
+			.execute(conn)?;
+	}
+
+	diesel::update(
+		crate::schema::avito_ads::table
+			.filter(crate::schema::avito_ads::ad_id.eq_any(&plan.removed)),
+	)
+	.set(crate::schema::avito_ads::status.eq(REMOVED_ITEM_STATUS))
+	.execute(conn)?;
+
+	Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::controllers::avito_client::fake_avito_api::FakeAvitoApi;
+	use crate::controllers::avito_client::request_policy::no_retry_policy;
+	use crate::controllers::avito_client::token_manager::AccountCredentials;
+	use std::sync::Arc;
+
+	fn item(id: i64, status: &str, price: f64) -> AvitoItem {
+		AvitoItem {
+			id,
+			title: Some(format!("Item {}", id)),
+			price: Some(price),
+			status: Some(status.to_string()),
+			url: Some(format!("https://www.avito.ru/items/{}", id)),
+			address: None,
+			category: None,
+		}
+	}
+
+	fn local(item: &AvitoItem, status: &str) -> LocalAd {
+		LocalAd {
+			ad_id: Uuid::new_v4(),
+			status: Some(status.to_string()),
+			fields: item_field_values(item),
+		}
+	}
+
+	#[test]
+	fn test_plan_items_sync() {
+		let same = item(1, "active", 100.0);
+		let repriced = item(2, "active", 250.0);
+		let paused = item(3, "old", 300.0);
+		let new = item(4, "active", 400.0);
+
+		let mut local_ads = HashMap::new();
+		local_ads.insert("1".to_string(), local(&same, "active"));
+		local_ads.insert("2".to_string(), local(&item(2, "active", 200.0), "active"));
+		local_ads.insert("3".to_string(), local(&paused, "active"));
+		let gone = local(&item(5, "active", 500.0), "active");
+		let gone_id = gone.ad_id;
+		local_ads.insert("5".to_string(), gone);
+		local_ads.insert("6".to_string(), local(&item(6, "active", 600.0), "removed"));
+
+		let plan = plan_items_sync(&local_ads, &[same.clone(), repriced, paused, new, same]);
+
+		assert_eq!(plan.items_seen, 4);
+		assert_eq!(plan.unchanged, 1);
+		assert_eq!(plan.removed, vec![gone_id]);
+		assert_eq!(
+			plan.added
+				.iter()
+				.map(|ad| (ad.avito_ad_id.as_str(), ad.ad_id))
+				.collect::<Vec<_>>(),
+			vec![("4", None)]
+		);
+
+		let updated: Vec<(&str, &str)> = plan
+			.updated
+			.iter()
+			.map(|ad| (ad.avito_ad_id.as_str(), ad.status.as_str()))
+			.collect();
+		assert_eq!(updated, vec![("2", "active"), ("3", "old")]);
+		assert!(plan.updated[0]
+			.fields
+			.contains(&("Price".to_string(), vec!["250".to_string()])));
+	}
+
+	// Never connected to: with the account's token cached, paging reads nothing from it
+	fn unconnected_pool() -> Pool<ConnectionManager<PgConnection>> {
+		Pool::builder()
+			.min_idle(Some(0))
+			.build_unchecked(ConnectionManager::new("postgres://localhost/unused"))
+	}
+
+	async fn fake_account(items: Vec<AvitoItem>) -> (Arc<FakeAvitoApi>, AvitoTokenManager, Uuid) {
+		let api = Arc::new(FakeAvitoApi::new().with_account("client", "secret"));
+		api.state.lock().unwrap().items = items;
+		let tokens = AvitoTokenManager::new(api.clone(), no_retry_policy());
+		let account_id = Uuid::new_v4();
+		tokens
+			.access_token_from(account_id, || {
+				Ok(AccountCredentials {
+					client_id: "client".to_string(),
+					client_secret: "secret".to_string(),
+				})
+			})
+			.await
+			.unwrap();
+		(api, tokens, account_id)
+	}
+
+	#[actix_web::test]
+	async fn test_sync_against_fake_avito() {
+		// Three pages of listed items, and one Avito no longer lists
+		let mut items: Vec<AvitoItem> = (1..=250).map(|id| item(id, "active", id as f64)).collect();
+		items.push(item(251, "removed", 1.0));
+		let (api, tokens, account_id) = fake_account(items).await;
+
+		let fetched = fetch_all_items(&unconnected_pool(), &tokens, api.as_ref(), account_id)
+			.await
+			.unwrap();
+		assert_eq!(fetched.len(), 250);
+
+		let mut local_ads = HashMap::new();
+		local_ads.insert("7".to_string(), local(&item(7, "active", 7.0), "active"));
+		let gone = local(&item(251, "active", 1.0), "active");
+		let gone_id = gone.ad_id;
+		local_ads.insert("251".to_string(), gone);
+
+		let plan = plan_items_sync(&local_ads, &fetched);
+		assert_eq!(plan.items_seen, 250);
+		assert_eq!(plan.unchanged, 1);
+		assert_eq!(plan.added.len(), 249);
+		assert_eq!(plan.removed, vec![gone_id]);
+	}
+
+	#[actix_web::test]
+	async fn test_sync_fails_when_the_page_limit_is_hit() {
+		let items = (1..=250).map(|id| item(id, "active", 1.0)).collect();
+		let (api, tokens, account_id) = fake_account(items).await;
+
+		// Two full pages and more behind them: nothing is returned rather than a partial list
+		let result =
+			fetch_item_pages(&unconnected_pool(), &tokens, api.as_ref(), account_id, 2).await;
+		assert!(
+			matches!(result, Err(ApiError::Other(message)) if message.contains("more than 2 pages"))
+		);
+
+		let fetched = fetch_item_pages(&unconnected_pool(), &tokens, api.as_ref(), account_id, 3)
+			.await
+			.unwrap();
+		assert_eq!(fetched.len(), 250);
+	}
+}
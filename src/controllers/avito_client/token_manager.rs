@@ -0,0 +1,335 @@
+use crate::controllers::avito_accounts::rotate_encryption_keys::upgrade_account_secrets;
+use crate::controllers::avito_client::avito_api::AvitoApi;
+use crate::controllers::avito_client::request_policy::AvitoRequestPolicy;
+use crate::models::{ApiError, AvitoAccount};
+use crate::utils::encryption::decrypt_avito_credentials;
+use crate::AppState;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::time::{Duration, Instant};
+use uuid::Uuid;
+
+// Tokens are renewed this long before Avito's expiry, so a request never starts with a dying token
+const TOKEN_EXPIRY_MARGIN_SECS: u64 = 60;
+// Avito issues client-credentials tokens for 24 hours; used when the response omits expires_in
+const DEFAULT_TOKEN_LIFETIME_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Clone)]
+pub struct CachedToken {
+	pub access_token: String,
+	pub expires_at: Instant,
+}
+
+type TokenSlot = Arc<tokio::sync::Mutex<Option<CachedToken>>>;
+
+// Client-credentials tokens of the Avito accounts, kept in memory until they expire.
+// Every account has its own lock, so concurrent requests share a single token request.
+// All requests, token requests included, go through the outbound request policy.
+#[derive(Clone)]
+pub struct AvitoTokenManager {
+	api: Arc<dyn AvitoApi>,
+	policy: AvitoRequestPolicy,
+	tokens: Arc<Mutex<HashMap<Uuid, TokenSlot>>>,
+}
+
+impl AvitoTokenManager {
+	pub fn new(api: Arc<dyn AvitoApi>, policy: AvitoRequestPolicy) -> Self {
+		Self {
+			api,
+			policy,
+			tokens: Arc::default(),
+		}
+	}
+
+	fn slot(&self, account_id: Uuid) -> TokenSlot {
+		self.tokens
+			.lock()
+			.unwrap()
+			.entry(account_id)
+			.or_default()
+			.clone()
+	}
+
+	// A valid token of the account, requested from Avito when none is cached
+	pub async fn access_token(
+		&self,
+		db_pool: &Pool<ConnectionManager<PgConnection>>,
+		account_id: Uuid,
+	) -> Result<CachedToken, ApiError> {
+		self.access_token_from(account_id, || load_account_credentials(db_pool, account_id))
+			.await
+	}
+
+	// Same as access_token, with the account's credentials taken from `credentials`
+	pub(crate) async fn access_token_from(
+		&self,
+		account_id: Uuid,
+		credentials: impl FnOnce() -> Result<AccountCredentials, ApiError>,
+	) -> Result<CachedToken, ApiError> {
+		let slot = self.slot(account_id);
+		let mut cached = slot.lock().await;
+
+		if let Some(token) = cached.as_ref() {
+			if token.expires_at > Instant::now() {
+				return Ok(token.clone());
+			}
+		}
+
+		let token =
+			request_account_token(self.api.as_ref(), &self.policy, account_id, credentials()?)
+				.await?;
+		*cached = Some(token.clone());
+		Ok(token)
+	}
+
+	// Drop and re-request the token of the account
+	pub async fn refresh_token(
+		&self,
+		db_pool: &Pool<ConnectionManager<PgConnection>>,
+		account_id: Uuid,
+	) -> Result<CachedToken, ApiError> {
+		self.forget(account_id);
+		self.access_token(db_pool, account_id).await
+	}
+
+	// Forget the cached token, e.g. after the account's credentials changed
+	pub fn forget(&self, account_id: Uuid) {
+		self.tokens.lock().unwrap().remove(&account_id);
+	}
+
+	// Drop the token only if it is still the one Avito rejected; a concurrent request
+	// may already have replaced it
+	async fn invalidate(&self, account_id: Uuid, rejected_token: &str) {
+		let slot = self.slot(account_id);
+		let mut cached = slot.lock().await;
+		if cached
+			.as_ref()
+			.is_some_and(|token| token.access_token == rejected_token)
+		{
+			*cached = None;
+		}
+	}
+
+	// Run an Avito request with the account's token. When Avito answers 401 the token
+	// is renewed and the request is repeated once.
+	pub async fn with_token<T, F, Fut>(
+		&self,
+		db_pool: &Pool<ConnectionManager<PgConnection>>,
+		account_id: Uuid,
+		request: F,
+	) -> Result<T, ApiError>
+	where
+		F: FnMut(String) -> Fut,
+		Fut: Future<Output = Result<T, ApiError>>,
+	{
+		self.with_token_from(
+			account_id,
+			|| load_account_credentials(db_pool, account_id),
+			request,
+		)
+		.await
+	}
+
+	async fn with_token_from<T, F, Fut>(
+		&self,
+		account_id: Uuid,
+		credentials: impl Fn() -> Result<AccountCredentials, ApiError>,
+		mut request: F,
+	) -> Result<T, ApiError>
+	where
+		F: FnMut(String) -> Fut,
+		Fut: Future<Output = Result<T, ApiError>>,
+	{
+		let token = self.access_token_from(account_id, &credentials).await?;
+		let result = self
+			.policy
+			.run(account_id, || request(token.access_token.clone()))
+			.await;
+
+		match result {
+			Err(ApiError::AvitoApiError(401, message)) => {
+				log::warn!(
+					"Avito rejected the token of account {}, requesting a new one: {}",
+					account_id,
+					message
+				);
+				self.invalidate(account_id, &token.access_token).await;
+				let token = self.access_token_from(account_id, &credentials).await?;
+				self.policy
+					.run(account_id, || request(token.access_token.clone()))
+					.await
+			}
+			result => result,
+		}
+	}
+}
+
+// The account, if it belongs to the user; other users' accounts are reported as missing
+pub fn find_user_account(
+	conn: &mut PgConnection,
+	account_id: Uuid,
+	user_id: Uuid,
+) -> Result<AvitoAccount, ApiError> {
+	crate::schema::avito_accounts::table
+		.find(account_id)
+		.filter(crate::schema::avito_accounts::user_id.eq(user_id))
+		.first::<AvitoAccount>(conn)
+		.optional()?
+		.ok_or_else(|| ApiError::NotFound(format!("Avito account {} not found", account_id)))
+}
+
+// Where handlers look up the accounts of a user; tests swap in a fixed list of accounts
+pub trait AvitoAccounts: Send + Sync {
+	fn find_user_account(&self, account_id: Uuid, user_id: Uuid) -> Result<AvitoAccount, ApiError>;
+}
+
+pub struct DbAvitoAccounts {
+	db_pool: Pool<ConnectionManager<PgConnection>>,
+}
+
+impl DbAvitoAccounts {
+	pub fn new(db_pool: Pool<ConnectionManager<PgConnection>>) -> Self {
+		Self { db_pool }
+	}
+}
+
+impl AvitoAccounts for DbAvitoAccounts {
+	fn find_user_account(&self, account_id: Uuid, user_id: Uuid) -> Result<AvitoAccount, ApiError> {
+		let mut conn = self
+			.db_pool
+			.get()
+			.map_err(|e| ApiError::Other(e.to_string()))?;
+		find_user_account(&mut conn, account_id, user_id)
+	}
+}
+
+// Check that the user may act on the account before using its token
+pub fn authorize_account(
+	data: &AppState,
+	account_id: Uuid,
+	user_id: Uuid,
+) -> Result<AvitoAccount, ApiError> {
+	data.avito_accounts.find_user_account(account_id, user_id)
+}
+
+// Decrypted client credentials of an account
+pub struct AccountCredentials {
+	pub client_id: String,
+	pub client_secret: String,
+}
+
+// Decrypt the account's stored credentials
+fn load_account_credentials(
+	db_pool: &Pool<ConnectionManager<PgConnection>>,
+	account_id: Uuid,
+) -> Result<AccountCredentials, ApiError> {
+	let mut conn = db_pool.get().map_err(|e| ApiError::Other(e.to_string()))?;
+	let account = crate::schema::avito_accounts::table
+		.find(account_id)
+		.first::<AvitoAccount>(&mut conn)
+		.optional()?
+		.ok_or_else(|| ApiError::NotFound(format!("Avito account {} not found", account_id)))?;
+
+	let (client_secret, client_id) =
+		decrypt_avito_credentials(&account.avito_client_secret, &account.avito_client_id).map_err(
+			|e| {
+				ApiError::Other(format!(
+					"Failed to decrypt credentials of account {}: {}",
+					account_id, e
+				))
+			},
+		)?;
+	upgrade_account_secrets(&mut conn, &account, &client_secret, &client_id);
+
+	Ok(AccountCredentials {
+		client_id,
+		client_secret,
+	})
+}
+
+// Exchange the account's credentials for a client-credentials token
+async fn request_account_token(
+	api: &dyn AvitoApi,
+	policy: &AvitoRequestPolicy,
+	account_id: Uuid,
+	credentials: AccountCredentials,
+) -> Result<CachedToken, ApiError> {
+	let AccountCredentials {
+		client_id,
+		client_secret,
+	} = credentials;
+
+	let token_response = policy
+		.run(account_id, || api.request_token(&client_id, &client_secret))
+		.await?;
+	let expires_in = token_response
+		.expires_in
+		.unwrap_or(DEFAULT_TOKEN_LIFETIME_SECS);
+
+	log::info!("Issued Avito token for account {}", account_id);
+
+	Ok(CachedToken {
+		access_token: token_response.access_token,
+		expires_at: Instant::now()
+			+ Duration::from_secs(expires_in.saturating_sub(TOKEN_EXPIRY_MARGIN_SECS)),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::controllers::avito_client::fake_avito_api::FakeAvitoApi;
+	use crate::controllers::avito_client::request_policy::no_retry_policy;
+
+	fn credentials(client_id: &str, client_secret: &str) -> AccountCredentials {
+		AccountCredentials {
+			client_id: client_id.to_string(),
+			client_secret: client_secret.to_string(),
+		}
+	}
+
+	#[actix_web::test]
+	async fn test_with_token_renews_rejected_tokens() {
+		let api = Arc::new(FakeAvitoApi::new().with_account("client", "secret"));
+		api.state.lock().unwrap().balance = 150.0;
+		let tokens = AvitoTokenManager::new(api.clone(), no_retry_policy());
+		let account_id = Uuid::new_v4();
+
+		let get_balance = || {
+			tokens.with_token_from(
+				account_id,
+				|| Ok(credentials("client", "secret")),
+				|token| {
+					let api = api.clone();
+					async move { api.get_balance(&token).await.map(|b| b.balance) }
+				},
+			)
+		};
+
+		assert_eq!(get_balance().await.unwrap(), 150.0);
+		// The token is cached
+		assert_eq!(get_balance().await.unwrap(), 150.0);
+		assert_eq!(api.state.lock().unwrap().token_requests, 1);
+
+		// Avito no longer accepts the token: it is renewed and the request repeated
+		api.expire_tokens();
+		assert_eq!(get_balance().await.unwrap(), 150.0);
+		assert_eq!(api.state.lock().unwrap().token_requests, 2);
+
+		// Credentials Avito refuses fail the request without a retry loop
+		let refused = tokens
+			.with_token_from(
+				Uuid::new_v4(),
+				|| Ok(credentials("client", "wrong")),
+				|token| {
+					let api = api.clone();
+					async move { api.get_balance(&token).await }
+				},
+			)
+			.await;
+		assert!(matches!(refused, Err(ApiError::AvitoApiError(400, _))));
+	}
+}
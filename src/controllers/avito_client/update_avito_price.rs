@@ -1,67 +1,91 @@
+use crate::controllers::avito_client::token_manager::authorize_account;
 use crate::jwt_auth::JwtMiddleware;
 use crate::models::{ApiError, UpdatePriceBody};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
 use actix_web::{post, web, HttpResponse, Result};
-use reqwest::{
-	header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE, USER_AGENT},
-	Client,
-};
 use serde_json::json;
-use std::env;
 
-#[post("/avito/update_price")]
+#[post(
+	"/avito/update_price",
+	wrap = "RequirePermission(Permission::UseAvitoApi)"
+)]
 pub async fn update_avito_price(
 	opts: web::Json<UpdatePriceBody>,
-	_: JwtMiddleware,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
 ) -> Result<HttpResponse, ApiError> {
-	let avito_token = opts.avito_token.clone();
-	let item_id = opts.item_id.clone();
+	authorize_account(&data, opts.account_id, user.user_id)?;
 
-	let url = env::var("AVITO_BASE_URL")
-		.map_err(|_| ApiError::Other("AVITO_BASE_URL not set".to_string()))?;
+	let api = data.avito_api.as_ref();
+	let opts = &opts;
+	let update_price_data = data
+		.avito_tokens
+		.with_token(&data.db, opts.account_id, |avito_token| async move {
+			api.update_price(&avito_token, &opts.item_id, opts.price)
+				.await
+		})
+		.await?;
 
-	// Build headers
-	let mut headers = HeaderMap::new();
-	headers.insert(
-		AUTHORIZATION,
-		format!("Bearer {}", avito_token).parse().unwrap(),
-	);
-	headers.insert(USER_AGENT, HeaderValue::from_static("YourApp/1.0"));
-	headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-	headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": update_price_data.result
+	})))
+}
 
-	// Build request body
-	let request_body = json!({
-		"price": opts.price
-	});
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::controllers::avito_client::fake_avito_api::{
+		extract_fake_user, fake_app_state, FakeAvitoApi,
+	};
+	use crate::models::AvitoItem;
+	use actix_web::http::StatusCode;
+	use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+	use actix_web::App;
+	use actix_web_grants::GrantsMiddleware;
+	use std::sync::Arc;
 
-	// Build URL
-	let api_url = format!("{}/core/v1/items/{}/update_price", url, item_id);
+	#[actix_web::test]
+	async fn test_update_avito_price() {
+		let api = Arc::new(FakeAvitoApi::new());
+		api.state.lock().unwrap().items = vec![AvitoItem {
+			id: 42,
+			title: Some("Шины R17".to_string()),
+			price: Some(24000.0),
+			status: Some("active".to_string()),
+			url: None,
+			address: None,
+			category: None,
+		}];
+		let (data, account_id) = fake_app_state(api.clone()).await;
+		let app = init_service(
+			App::new()
+				.app_data(data)
+				.wrap(GrantsMiddleware::with_extractor(extract_fake_user))
+				.service(update_avito_price),
+		)
+		.await;
 
-	// Make request
-	let response = Client::builder()
-		.danger_accept_invalid_certs(true)
-		.build()?
-		.post(&api_url)
-		.headers(headers)
-		.json(&request_body)
-		.send()
-		.await?;
+		let update = |item_id: &str| {
+			TestRequest::post()
+				.uri("/avito/update_price")
+				.set_json(json!({ "account_id": account_id, "item_id": item_id, "price": 21500.0 }))
+				.to_request()
+		};
 
-	// Check response status
-	if !response.status().is_success() {
-		let status_code = response.status().as_u16();
-		let error_body = response.text().await?;
-		return Err(ApiError::AvitoApiError(status_code, error_body));
-	}
+		let response = call_service(&app, update("42")).await;
+		assert_eq!(response.status(), StatusCode::OK);
+		let body: serde_json::Value = read_body_json(response).await;
+		assert_eq!(body["data"]["success"], true);
+		assert_eq!(
+			api.state.lock().unwrap().price_updates,
+			vec![("42".to_string(), 21500.0)]
+		);
 
-	// Parse response
-	let response_text: String = response.text().await?;
-
-	let update_price_data: serde_json::Value = serde_json::from_str(&response_text)
-		.map_err(|e| ApiError::JsonParseError(e.to_string()))?;
-
-	Ok(HttpResponse::Ok().json(json!({
-		"status": "success",
-		"data": &update_price_data["result"]
-	})))
+		// Avito's errors reach the caller
+		let response = call_service(&app, update("7")).await;
+		assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+		assert_eq!(api.state.lock().unwrap().price_updates.len(), 1);
+	}
 }
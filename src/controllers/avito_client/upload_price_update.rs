@@ -0,0 +1,131 @@
+use crate::controllers::avito_client::preview_price_update::create_price_update_preview;
+use crate::controllers::avito_client::price_update_input::parse_price_csv;
+use crate::controllers::avito_client::token_manager::authorize_account;
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::ApiError;
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
+use actix_multipart::Multipart;
+use actix_web::{post, web, HttpResponse, Result};
+use futures::StreamExt;
+use serde_json::json;
+use uuid::Uuid;
+
+// A price list of MAX_PRICE_UPDATE_ITEMS rows stays well below this
+const MAX_PRICE_FILE_SIZE: usize = 5 * 1024 * 1024;
+
+// POST multipart form with a CSV price list in the "file" field; answers like price_updates
+#[post(
+	"/avito/accounts/{account_id}/price_updates/upload",
+	wrap = "RequirePermission(Permission::UseAvitoApi)"
+)]
+pub async fn upload_price_update(
+	path: web::Path<Uuid>,
+	mut payload: Multipart,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	let account_id = path.into_inner();
+	authorize_account(&data, account_id, user.user_id)?;
+
+	let mut file: Option<Vec<u8>> = None;
+	// Counted over the whole form, so extra fields can't stretch the limit
+	let mut received = 0;
+
+	while let Some(field) = payload.next().await {
+		let mut field = field.map_err(|e| ApiError::Other(e.to_string()))?;
+		let is_file = field.name() == Some("file");
+
+		let mut bytes = Vec::new();
+		while let Some(chunk) = field.next().await {
+			let chunk = chunk.map_err(|e| ApiError::Other(e.to_string()))?;
+			received += chunk.len();
+			if received > MAX_PRICE_FILE_SIZE {
+				return Ok(HttpResponse::PayloadTooLarge().json(json!({
+					"status": "error",
+					"message": format!("Uploaded file exceeds {} bytes", MAX_PRICE_FILE_SIZE)
+				})));
+			}
+			// Other fields are read through but not kept
+			if is_file {
+				bytes.extend_from_slice(&chunk);
+			}
+		}
+
+		if is_file {
+			file = Some(bytes);
+		}
+	}
+
+	let Some(file) = file.filter(|file| !file.is_empty()) else {
+		return Ok(HttpResponse::BadRequest().json(json!({
+			"status": "error",
+			"message": "CSV file is required"
+		})));
+	};
+
+	let lines = match parse_price_csv(&file) {
+		Ok(lines) => lines,
+		Err(message) => {
+			return Ok(HttpResponse::BadRequest().json(json!({
+				"status": "error",
+				"message": message
+			})))
+		}
+	};
+
+	create_price_update_preview(&data, user.user_id, account_id, lines).await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::controllers::avito_client::fake_avito_api::{
+		extract_fake_user, fake_app_state, FakeAvitoApi,
+	};
+	use actix_web::http::StatusCode;
+	use actix_web::test::{call_service, init_service, TestRequest};
+	use actix_web::App;
+	use actix_web_grants::GrantsMiddleware;
+	use std::sync::Arc;
+
+	fn form_field(name: &str, content: &str) -> String {
+		format!(
+			"--boundary\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+			name, content
+		)
+	}
+
+	#[actix_web::test]
+	async fn test_upload_limit_covers_every_field() {
+		let (data, account_id) = fake_app_state(Arc::new(FakeAvitoApi::new())).await;
+		let app = init_service(
+			App::new()
+				.app_data(data)
+				.wrap(GrantsMiddleware::with_extractor(extract_fake_user))
+				.service(upload_price_update),
+		)
+		.await;
+
+		// Each field is below the limit, together they are not
+		let half = "x".repeat(MAX_PRICE_FILE_SIZE / 2 + 1);
+		let body = format!(
+			"{}{}--boundary--\r\n",
+			form_field("note", &half),
+			form_field("file", &half)
+		);
+		let response = call_service(
+			&app,
+			TestRequest::post()
+				.uri(&format!(
+					"/avito/accounts/{}/price_updates/upload",
+					account_id
+				))
+				.insert_header(("content-type", "multipart/form-data; boundary=boundary"))
+				.set_payload(body)
+				.to_request(),
+		)
+		.await;
+		assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+	}
+}
@@ -0,0 +1,70 @@
+use crate::controllers::avito_client::token_manager::authorize_account;
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::{
+	ApiError, AvitoBalanceAlert, AvitoBalanceAlertRequest, UpsertAvitoBalanceAlert,
+};
+use crate::permissions::{Permission, RequirePermission};
+use crate::utils::webhook::resolve_webhook_url;
+use crate::AppState;
+use actix_web::{put, web, HttpResponse, Result};
+use chrono::Utc;
+use diesel::prelude::*;
+use serde_json::json;
+use uuid::Uuid;
+
+// Create or replace the low-balance alert of an account
+#[put(
+	"/avito/accounts/{account_id}/balance_alert",
+	wrap = "RequirePermission(Permission::UseAvitoApi)"
+)]
+pub async fn upsert_balance_alert(
+	path: web::Path<Uuid>,
+	body: web::Json<AvitoBalanceAlertRequest>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	let account_id = path.into_inner();
+	let body = body.into_inner();
+	authorize_account(&data, account_id, user.user_id)?;
+
+	let webhook_url = body
+		.webhook_url
+		.map(|url| url.trim().to_string())
+		.filter(|url| !url.is_empty());
+
+	let invalid = if !(body.threshold.is_finite() && body.threshold >= 0.0) {
+		Some("threshold must not be negative".to_string())
+	} else if let Some(url) = &webhook_url {
+		resolve_webhook_url(url).await.err()
+	} else {
+		None
+	};
+	if let Some(message) = invalid {
+		return Ok(HttpResponse::BadRequest().json(json!({
+			"status": "fail",
+			"message": message
+		})));
+	}
+
+	let alert = UpsertAvitoBalanceAlert {
+		account_id,
+		threshold: body.threshold,
+		webhook_url,
+		enabled: body.enabled.unwrap_or(true),
+		below_threshold: false,
+		updated_ts: Utc::now(),
+	};
+
+	let mut conn = data.db.get().map_err(|e| ApiError::Other(e.to_string()))?;
+	let alert = diesel::insert_into(crate::schema::avito_balance_alerts::table)
+		.values(&alert)
+		.on_conflict(crate::schema::avito_balance_alerts::account_id)
+		.do_update()
+		.set(&alert)
+		.get_result::<AvitoBalanceAlert>(&mut conn)?;
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": { "alert": alert }
+	})))
+}
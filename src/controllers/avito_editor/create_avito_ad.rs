@@ -1,4 +1,5 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{models::AvitoAd, AppState};
 use actix_web::{web, HttpResponse, Result};
 use chrono::Utc;
@@ -20,7 +21,7 @@ struct AvitoAdFieldWithValue {
 	value: Option<String>,
 }
 
-#[actix_web::post("/avito/ads/create")]
+#[actix_web::post("/avito/ads/create", wrap = "RequirePermission(Permission::ManageAds)")]
 pub async fn create_avito_ad(
 	user: JwtMiddleware,
 	mut payload: Payload,
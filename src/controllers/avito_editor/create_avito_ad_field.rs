@@ -1,4 +1,5 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	models::{AvitoAdField, CreateAvitoAdField},
 	AppState,
@@ -8,7 +9,7 @@ use chrono::Utc;
 use diesel::prelude::*;
 use serde_json::json;
 
-#[actix_web::post("/avito/ad_fields")]
+#[actix_web::post("/avito/ad_fields", wrap = "RequirePermission(Permission::ManageAds)")]
 pub async fn create_avito_ad_field(
 	user: JwtMiddleware,
 	body: web::Json<CreateAvitoAdField>,
@@ -1,4 +1,5 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	models::{AvitoAdFieldValue, CreateAvitoAdFieldValue},
 	AppState,
@@ -8,7 +9,10 @@ use chrono::Utc;
 use diesel::prelude::*;
 use serde_json::json;
 
-#[actix_web::post("/avito/ad_field_values")]
+#[actix_web::post(
+	"/avito/ad_field_values",
+	wrap = "RequirePermission(Permission::ManageAds)"
+)]
 pub async fn create_avito_ad_field_value(
 	user: JwtMiddleware,
 	body: web::Json<CreateAvitoAdFieldValue>,
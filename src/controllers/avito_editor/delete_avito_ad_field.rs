@@ -1,11 +1,15 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{models::AvitoAdField, AppState};
 use actix_web::{web, HttpResponse, Result};
 use diesel::prelude::*;
 use serde_json::json;
 use uuid::Uuid;
 
-#[actix_web::delete("/avito/ad_fields/{id}")]
+#[actix_web::delete(
+	"/avito/ad_fields/{id}",
+	wrap = "RequirePermission(Permission::ManageAds)"
+)]
 pub async fn delete_avito_ad_field(
 	user: JwtMiddleware,
 	path: web::Path<String>,
@@ -1,11 +1,15 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{models::AvitoAdFieldValue, AppState};
 use actix_web::{web, HttpResponse, Result};
 use diesel::prelude::*;
 use serde_json::json;
 use uuid::Uuid;
 
-#[actix_web::delete("/avito/ad_field_values/{id}")]
+#[actix_web::delete(
+	"/avito/ad_field_values/{id}",
+	wrap = "RequirePermission(Permission::ManageAds)"
+)]
 pub async fn delete_avito_ad_field_value(
 	user: JwtMiddleware,
 	path: web::Path<String>,
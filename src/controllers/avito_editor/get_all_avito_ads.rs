@@ -1,4 +1,5 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	models::{AvitoAd, PaginationParams, PaginationResponse, ResponseWithPagination},
 	AppState,
@@ -9,7 +10,7 @@ use serde_json::json;
 
 use super::models::{AvitoAdFieldWithValues, AvitoAdWithFields};
 
-#[actix_web::get("/avito_ads")]
+#[actix_web::get("/avito_ads", wrap = "RequirePermission(Permission::ManageAds)")]
 pub async fn get_all_avito_ads(
 	user: JwtMiddleware,
 	pagination: web::Query<PaginationParams>,
@@ -1,4 +1,5 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{models::AvitoAd, AppState};
 use actix_web::{web, HttpResponse, Result};
 use diesel::prelude::*;
@@ -7,7 +8,7 @@ use uuid::Uuid;
 
 use super::models::{AvitoAdFieldWithValues, AvitoAdWithFields, AvitoAdWithFieldsResponse};
 
-#[actix_web::get("/avito_ads/{id}")]
+#[actix_web::get("/avito_ads/{id}", wrap = "RequirePermission(Permission::ManageAds)")]
 pub async fn get_avito_ad_by_id(
 	user: JwtMiddleware,
 	path: web::Path<Uuid>,
@@ -1,4 +1,5 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{models::AvitoAd, AppState};
 use actix_web::{web, HttpResponse, Result};
 use diesel::prelude::*;
@@ -47,7 +48,7 @@ struct AvitoAdFieldValueToCreate {
 	value: Option<String>,
 }
 
-#[actix_web::put("/avito_ads/{id}")]
+#[actix_web::put("/avito_ads/{id}", wrap = "RequirePermission(Permission::ManageAds)")]
 pub async fn update_avito_ad(
 	user: JwtMiddleware,
 	path: web::Path<Uuid>,
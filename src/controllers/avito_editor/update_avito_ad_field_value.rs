@@ -1,4 +1,5 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	models::{AvitoAdFieldValue, UpdateAvitoAdFieldValue},
 	AppState,
@@ -8,7 +9,10 @@ use diesel::prelude::*;
 use serde_json::json;
 use uuid::Uuid;
 
-#[actix_web::patch("/avito/ad_field_values/{id}")]
+#[actix_web::patch(
+	"/avito/ad_field_values/{id}",
+	wrap = "RequirePermission(Permission::ManageAds)"
+)]
 pub async fn update_avito_ad_field_value(
 	user: JwtMiddleware,
 	path: web::Path<String>,
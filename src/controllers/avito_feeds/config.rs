@@ -1,6 +1,8 @@
 use crate::controllers::avito_feeds::{
-	create_avito_feed, delete_avito_feed, get_all_avito_feeds, get_avito_feed_by_id,
-	get_avito_feeds_by_account, import_avito_xml, update_avito_feed,
+	create_avito_feed, delete_avito_feed, delete_feed_import_schedule, export_avito_xml,
+	get_all_avito_feeds, get_avito_feed_by_id, get_avito_feeds_by_account,
+	get_feed_import_schedule, get_feed_imports, import_avito_xml, update_avito_feed,
+	upload_avito_xml, upsert_feed_import_schedule, validate_avito_ad, validate_avito_feed,
 };
 use actix_web::web;
 
@@ -11,5 +13,13 @@ pub fn avito_feed_routes(cfg: &mut web::ServiceConfig) {
 		.service(update_avito_feed::update_avito_feed)
 		.service(delete_avito_feed::delete_avito_feed)
 		.service(get_all_avito_feeds::get_all_avito_feeds)
-		.service(import_avito_xml::import_avito_xml);
+		.service(import_avito_xml::import_avito_xml)
+		.service(upload_avito_xml::upload_avito_xml)
+		.service(export_avito_xml::export_avito_xml)
+		.service(get_feed_import_schedule::get_feed_import_schedule)
+		.service(upsert_feed_import_schedule::upsert_feed_import_schedule)
+		.service(delete_feed_import_schedule::delete_feed_import_schedule)
+		.service(get_feed_imports::get_feed_imports)
+		.service(validate_avito_feed::validate_avito_feed)
+		.service(validate_avito_ad::validate_avito_ad);
 }
@@ -1,3 +1,4 @@
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	jwt_auth::JwtMiddleware,
 	models::{AvitoFeed, AvitoFeedResponse, CreateAvitoFeed},
@@ -15,7 +16,10 @@ pub struct CreateAvitoFeedRequest {
 }
 
 // Create avito feed
-#[actix_web::post("/avito/feeds/create")]
+#[actix_web::post(
+	"/avito/feeds/create",
+	wrap = "RequirePermission(Permission::ManageFeeds)"
+)]
 pub async fn create_avito_feed(
 	data: web::Data<AppState>,
 	new_feed: web::Json<CreateAvitoFeedRequest>,
@@ -1,10 +1,14 @@
+use crate::permissions::{Permission, RequirePermission};
 use crate::{models::AvitoFeed, AppState};
 use actix_web::{web, HttpResponse, Result};
 use diesel::prelude::*;
 use serde_json::json;
 use uuid::Uuid;
 
-#[actix_web::delete("/avito/feeds/{id}")]
+#[actix_web::delete(
+	"/avito/feeds/{id}",
+	wrap = "RequirePermission(Permission::ManageFeeds)"
+)]
 pub async fn delete_avito_feed(
 	path: web::Path<Uuid>,
 	data: web::Data<AppState>,
@@ -0,0 +1,316 @@
+use crate::controllers::avito_feeds::feed_access::{feed_access_error, find_user_feed};
+use crate::permissions::{Permission, RequirePermission};
+use crate::{
+	jwt_auth::JwtMiddleware,
+	models::{AdFieldValues, AvitoAd, AvitoAdField, AvitoAdFieldValue},
+	AppState,
+};
+use actix_web::{web, HttpResponse, Result};
+use diesel::prelude::*;
+use quick_xml::events::{BytesCData, BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::Writer;
+use serde_json::json;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+// GET avito feed rendered as an Avito Autoload XML document
+#[actix_web::get(
+	"/avito/feeds/{feed_id}/xml",
+	wrap = "RequirePermission(Permission::ManageFeeds)"
+)]
+pub async fn export_avito_xml(
+	path: web::Path<Uuid>,
+	data: web::Data<AppState>,
+	user: JwtMiddleware,
+) -> Result<HttpResponse> {
+	let feed_id = path.into_inner();
+	let mut conn = data.db.get().unwrap();
+
+	// Make sure the feed belongs to one of the user's accounts
+	if let Err(e) = find_user_feed(&mut conn, feed_id, user.user_id) {
+		return Ok(feed_access_error(feed_id, e));
+	}
+
+	let xml = match load_feed_field_values(&mut conn, feed_id, FeedAdStatus::Active)
+		.map_err(|e| format!("Database error: {}", e))
+		.and_then(|ads| render_ads_xml(&ads))
+	{
+		Ok(xml) => xml,
+		Err(e) => {
+			log::error!("Failed to export avito feed {}: {}", feed_id, e);
+			return Ok(HttpResponse::InternalServerError().json(json!({
+				"status": "error",
+				"message": "Failed to export avito feed"
+			})));
+		}
+	};
+
+	Ok(HttpResponse::Ok()
+		.content_type("application/xml; charset=utf-8")
+		.body(xml))
+}
+
+// Which ads of a feed to load
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedAdStatus {
+	// The ads that go into the exported document. Inactive ads are left out so that Avito
+	// removes them on the next autoload.
+	Active,
+	// Every ad, inactive ones included
+	Any,
+}
+
+// Load the ads of a feed with their fields and values, in insertion order
+pub fn load_feed_field_values(
+	conn: &mut PgConnection,
+	feed_id: Uuid,
+	status: FeedAdStatus,
+) -> Result<Vec<AdFieldValues>, diesel::result::Error> {
+	Ok(load_feed_ads(conn, feed_id, status)?
+		.into_iter()
+		.map(|(_, fields)| fields)
+		.collect())
+}
+
+// The ads of a feed, together with their fields
+pub fn load_feed_ads(
+	conn: &mut PgConnection,
+	feed_id: Uuid,
+	status: FeedAdStatus,
+) -> Result<Vec<(AvitoAd, AdFieldValues)>, diesel::result::Error> {
+	let mut query = crate::schema::avito_ads::table
+		.filter(crate::schema::avito_ads::feed_id.eq(feed_id))
+		.order(crate::schema::avito_ads::created_ts.asc())
+		.into_boxed();
+	if status == FeedAdStatus::Active {
+		query = query.filter(
+			crate::schema::avito_ads::status
+				.is_null()
+				.or(crate::schema::avito_ads::status.ne("inactive")),
+		);
+	}
+	let ads = query.load::<AvitoAd>(conn)?;
+
+	load_ads_field_values(conn, ads)
+}
+
+// Attach the stored fields and values to each ad, keeping the order of `ads`
+pub fn load_ads_field_values(
+	conn: &mut PgConnection,
+	ads: Vec<AvitoAd>,
+) -> Result<Vec<(AvitoAd, AdFieldValues)>, diesel::result::Error> {
+	let ad_ids: Vec<Uuid> = ads.iter().map(|ad| ad.ad_id).collect();
+	let fields = crate::schema::avito_ad_fields::table
+		.filter(crate::schema::avito_ad_fields::ad_id.eq_any(&ad_ids))
+		.order(crate::schema::avito_ad_fields::created_ts.asc())
+		.load::<AvitoAdField>(conn)?;
+
+	let field_ids: Vec<Uuid> = fields.iter().map(|field| field.field_id).collect();
+	let values = crate::schema::avito_ad_field_values::table
+		.filter(crate::schema::avito_ad_field_values::field_id.eq_any(&field_ids))
+		.order(crate::schema::avito_ad_field_values::created_ts.asc())
+		.load::<AvitoAdFieldValue>(conn)?;
+
+	let field_values = rows_to_field_values(&ads, &fields, &values);
+	Ok(ads.into_iter().zip(field_values).collect())
+}
+
+// Group ad, field and value rows into (tag, values) pairs per ad, keeping the row order
+pub fn rows_to_field_values(
+	ads: &[AvitoAd],
+	fields: &[AvitoAdField],
+	values: &[AvitoAdFieldValue],
+) -> Vec<AdFieldValues> {
+	let mut values_by_field: HashMap<Uuid, Vec<String>> = HashMap::new();
+	for value in values {
+		if let (Some(field_id), Some(value)) = (value.field_id, &value.value) {
+			values_by_field
+				.entry(field_id)
+				.or_default()
+				.push(value.clone());
+		}
+	}
+
+	let mut fields_by_ad: HashMap<Uuid, AdFieldValues> = HashMap::new();
+	for field in fields {
+		if let Some(tag) = field.tag.as_ref().filter(|tag| !tag.is_empty()) {
+			fields_by_ad.entry(field.ad_id).or_default().push((
+				tag.clone(),
+				values_by_field.remove(&field.field_id).unwrap_or_default(),
+			));
+		}
+	}
+
+	ads.iter()
+		.map(|ad| fields_by_ad.remove(&ad.ad_id).unwrap_or_default())
+		.collect()
+}
+
+// Render ads as an Avito Autoload document, the inverse of parse_xml_ads
+pub fn render_ads_xml(ads: &[AdFieldValues]) -> Result<String, String> {
+	let mut writer = Writer::new_with_indent(Vec::new(), b'\t', 1);
+
+	write_ads(&mut writer, ads).map_err(|e| format!("XML write error: {}", e))?;
+
+	String::from_utf8(writer.into_inner()).map_err(|e| format!("UTF-8 error: {}", e))
+}
+
+fn write_ads(writer: &mut Writer<Vec<u8>>, ads: &[AdFieldValues]) -> std::io::Result<()> {
+	writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+	writer.write_event(Event::Start(
+		BytesStart::new("Ads").with_attributes([("formatVersion", "3"), ("target", "Avito.ru")]),
+	))?;
+
+	for ad in ads {
+		writer.write_event(Event::Start(BytesStart::new("Ad")))?;
+
+		for (tag, values) in ad {
+			let values: Vec<&String> = values.iter().filter(|v| !v.trim().is_empty()).collect();
+			if values.is_empty() {
+				continue;
+			}
+
+			writer.write_event(Event::Start(BytesStart::new(tag.as_str())))?;
+			match tag.as_str() {
+				"Images" => {
+					for url in values {
+						writer.write_event(Event::Empty(
+							BytesStart::new("Image").with_attributes([("url", url.as_str())]),
+						))?;
+					}
+				}
+				"Delivery" => {
+					for option in values {
+						writer.write_event(Event::Start(BytesStart::new("Option")))?;
+						writer.write_event(Event::Text(BytesText::new(option)))?;
+						writer.write_event(Event::End(BytesEnd::new("Option")))?;
+					}
+				}
+				_ => {
+					let value = values.first().unwrap();
+					// Descriptions may carry HTML markup, which Avito expects inside CDATA
+					if tag == "Description" && !value.contains("]]>") {
+						writer.write_event(Event::CData(BytesCData::new(value.as_str())))?;
+					} else {
+						writer.write_event(Event::Text(BytesText::new(value)))?;
+					}
+				}
+			}
+			writer.write_event(Event::End(BytesEnd::new(tag.as_str())))?;
+		}
+
+		writer.write_event(Event::End(BytesEnd::new("Ad")))?;
+	}
+
+	writer.write_event(Event::End(BytesEnd::new("Ads")))?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::controllers::avito_feeds::import_avito_xml::parse_xml_ads;
+	use chrono::Utc;
+
+	const FEED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Ads formatVersion="3" target="Avito.ru">
+	<Ad>
+		<Id>tyre-001</Id>
+		<Title>Шины &amp; диски R17</Title>
+		<Description><![CDATA[<p>Комплект <b>летних</b> шин</p>]]></Description>
+		<Price>24000</Price>
+		<Images>
+			<Image url="https://example.com/1.jpg?w=640&amp;h=480"/>
+			<Image url="https://example.com/2.jpg"/>
+			<Image url="https://example.com/3.jpg?crop=0,0,640,480"/>
+		</Images>
+		<Delivery>
+			<Option>ПВЗ</Option>
+			<Option>Курьер</Option>
+		</Delivery>
+	</Ad>
+	<Ad>
+		<Id>tyre-002</Id>
+		<Title>Диски R16</Title>
+		<Price>12000</Price>
+	</Ad>
+</Ads>"#;
+
+	// Build the rows that importing the parsed ads would write to the database
+	fn store(
+		ads: &[crate::models::XmlAd],
+	) -> (Vec<AvitoAd>, Vec<AvitoAdField>, Vec<AvitoAdFieldValue>) {
+		let (mut ad_rows, mut field_rows, mut value_rows) = (Vec::new(), Vec::new(), Vec::new());
+		for ad in ads {
+			let ad_id = Uuid::new_v4();
+			ad_rows.push(AvitoAd {
+				ad_id,
+				feed_id: Uuid::nil(),
+				avito_ad_id: None,
+				parsed_id: Some(ad.id.clone()),
+				status: None,
+				created_ts: Utc::now().naive_utc(),
+			});
+			for (tag, values) in ad.field_values() {
+				let field_id = Uuid::new_v4();
+				field_rows.push(AvitoAdField {
+					field_id,
+					ad_id,
+					tag: Some(tag),
+					data_type: None,
+					field_type: None,
+					created_ts: Utc::now(),
+				});
+				for value in values {
+					value_rows.push(AvitoAdFieldValue {
+						field_value_id: Uuid::new_v4(),
+						field_id: Some(field_id),
+						value: Some(value),
+						created_ts: Utc::now(),
+					});
+				}
+			}
+		}
+		(ad_rows, field_rows, value_rows)
+	}
+
+	#[test]
+	fn test_xml_round_trip() {
+		let parsed = parse_xml_ads(FEED).unwrap();
+		assert_eq!(parsed.len(), 2);
+		assert_eq!(parsed[0].fields["Title"], "Шины & диски R17");
+
+		let (ads, fields, values) = store(&parsed);
+		let xml = render_ads_xml(&rows_to_field_values(&ads, &fields, &values)).unwrap();
+		assert!(xml.contains(r#"<Ads formatVersion="3" target="Avito.ru">"#));
+		assert!(xml.contains("<Option>Курьер</Option>"));
+
+		let reparsed = parse_xml_ads(&xml).unwrap();
+		assert_eq!(reparsed.len(), parsed.len());
+		for (original, exported) in parsed.iter().zip(reparsed.iter()) {
+			assert_eq!(original.id, exported.id);
+			assert_eq!(original.fields, exported.fields);
+			assert_eq!(original.lists, exported.lists);
+		}
+	}
+
+	#[test]
+	fn test_field_values_keeps_list_items() {
+		let parsed = parse_xml_ads(FEED).unwrap();
+		let field_values = parsed[0].field_values();
+
+		assert_eq!(field_values[0].0, "Id");
+		let images = field_values
+			.iter()
+			.find(|(tag, _)| tag == "Images")
+			.unwrap();
+		assert_eq!(
+			images.1,
+			vec![
+				"https://example.com/1.jpg?w=640&h=480".to_string(),
+				"https://example.com/2.jpg".to_string(),
+				"https://example.com/3.jpg?crop=0,0,640,480".to_string()
+			]
+		);
+	}
+}
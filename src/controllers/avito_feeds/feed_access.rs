@@ -0,0 +1,36 @@
+use crate::models::AvitoFeed;
+use actix_web::HttpResponse;
+use diesel::prelude::*;
+use serde_json::json;
+use uuid::Uuid;
+
+// Load a feed only if it belongs to one of the user's accounts
+pub fn find_user_feed(
+	conn: &mut PgConnection,
+	feed_id: Uuid,
+	user_id: Uuid,
+) -> Result<AvitoFeed, diesel::result::Error> {
+	crate::schema::avito_feeds::table
+		.inner_join(crate::schema::avito_accounts::table)
+		.filter(crate::schema::avito_feeds::feed_id.eq(feed_id))
+		.filter(crate::schema::avito_accounts::user_id.eq(user_id))
+		.select(crate::schema::avito_feeds::all_columns)
+		.first::<AvitoFeed>(conn)
+}
+
+// Error response for a failed find_user_feed
+pub fn feed_access_error(feed_id: Uuid, error: diesel::result::Error) -> HttpResponse {
+	match error {
+		diesel::result::Error::NotFound => HttpResponse::NotFound().json(json!({
+			"status": "fail",
+			"message": "Feed not found or you don't have permission to access it"
+		})),
+		e => {
+			log::error!("Failed to fetch avito feed {}: {:?}", feed_id, e);
+			HttpResponse::InternalServerError().json(json!({
+				"status": "error",
+				"message": "Failed to fetch avito feed"
+			}))
+		}
+	}
+}
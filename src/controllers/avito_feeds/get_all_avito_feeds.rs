@@ -1,3 +1,4 @@
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	jwt_auth::JwtMiddleware,
 	models::{AvitoFeed, PaginationParams, PaginationResponse, ResponseWithPagination},
@@ -8,7 +9,7 @@ use diesel::prelude::*;
 use serde_json::json;
 
 // GET all avito feeds
-#[actix_web::get("/avito/feeds")]
+#[actix_web::get("/avito/feeds", wrap = "RequirePermission(Permission::ManageFeeds)")]
 pub async fn get_all_avito_feeds(
 	data: web::Data<AppState>,
 	pagination: web::Query<PaginationParams>,
@@ -1,3 +1,4 @@
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	jwt_auth::JwtMiddleware,
 	models::{
@@ -25,7 +26,10 @@ pub struct FeedIdPath {
 	pub feed_id: Uuid,
 }
 
-#[get("/avito/feeds/{feed_id}")]
+#[get(
+	"/avito/feeds/{feed_id}",
+	wrap = "RequirePermission(Permission::ManageFeeds)"
+)]
 pub async fn get_avito_feed_by_id(
 	path: web::Path<FeedIdPath>,
 	pagination: web::Query<PaginationParams>,
@@ -1,3 +1,4 @@
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	jwt_auth::JwtMiddleware,
 	models::{AvitoFeed, PaginationParams, PaginationResponse, ResponseWithPagination},
@@ -15,7 +16,7 @@ pub struct AccountIdRequest {
 }
 
 // GET all avito feeds by specific account_id via POST request body
-#[actix_web::post("/avito/feeds")]
+#[actix_web::post("/avito/feeds", wrap = "RequirePermission(Permission::ManageFeeds)")]
 pub async fn get_avito_feeds_by_account(
 	data: web::Data<AppState>,
 	body: web::Json<AccountIdRequest>,
@@ -0,0 +1,50 @@
+use crate::controllers::avito_feeds::feed_access::{feed_access_error, find_user_feed};
+use crate::permissions::{Permission, RequirePermission};
+use crate::{jwt_auth::JwtMiddleware, models::AvitoFeedImportSchedule, AppState};
+use actix_web::{web, HttpResponse, Result};
+use diesel::prelude::*;
+use serde_json::json;
+use uuid::Uuid;
+
+#[actix_web::get(
+	"/avito/feeds/{feed_id}/import_schedule",
+	wrap = "RequirePermission(Permission::ManageFeeds)"
+)]
+pub async fn get_feed_import_schedule(
+	path: web::Path<Uuid>,
+	data: web::Data<AppState>,
+	user: JwtMiddleware,
+) -> Result<HttpResponse> {
+	let feed_id = path.into_inner();
+	let mut conn = data.db.get().unwrap();
+
+	if let Err(e) = find_user_feed(&mut conn, feed_id, user.user_id) {
+		return Ok(feed_access_error(feed_id, e));
+	}
+
+	match crate::schema::avito_feed_import_schedules::table
+		.filter(crate::schema::avito_feed_import_schedules::feed_id.eq(feed_id))
+		.first::<AvitoFeedImportSchedule>(&mut conn)
+		.optional()
+	{
+		Ok(Some(schedule)) => Ok(HttpResponse::Ok().json(json!({
+			"status": "success",
+			"data": { "schedule": schedule }
+		}))),
+		Ok(None) => Ok(HttpResponse::NotFound().json(json!({
+			"status": "fail",
+			"message": "Feed has no import schedule"
+		}))),
+		Err(e) => {
+			log::error!(
+				"Failed to fetch import schedule of feed {}: {:?}",
+				feed_id,
+				e
+			);
+			Ok(HttpResponse::InternalServerError().json(json!({
+				"status": "error",
+				"message": "Failed to fetch import schedule"
+			})))
+		}
+	}
+}
@@ -0,0 +1,72 @@
+use crate::controllers::avito_feeds::feed_access::{feed_access_error, find_user_feed};
+use crate::permissions::{Permission, RequirePermission};
+use crate::{
+	jwt_auth::JwtMiddleware,
+	models::{AvitoFeedImport, PaginationParams, PaginationResponse, ResponseWithPagination},
+	AppState,
+};
+use actix_web::{web, HttpResponse, Result};
+use diesel::prelude::*;
+use serde_json::json;
+use uuid::Uuid;
+
+// GET import history of a feed, newest run first
+#[actix_web::get(
+	"/avito/feeds/{feed_id}/imports",
+	wrap = "RequirePermission(Permission::ManageFeeds)"
+)]
+pub async fn get_feed_imports(
+	path: web::Path<Uuid>,
+	pagination: web::Query<PaginationParams>,
+	data: web::Data<AppState>,
+	user: JwtMiddleware,
+) -> Result<HttpResponse> {
+	let feed_id = path.into_inner();
+	let page = pagination.page.unwrap_or(1).max(1);
+	let limit = pagination.limit.unwrap_or(10).clamp(1, 100);
+	let offset = (page - 1) * limit;
+
+	let mut conn = data.db.get().unwrap();
+
+	if let Err(e) = find_user_feed(&mut conn, feed_id, user.user_id) {
+		return Ok(feed_access_error(feed_id, e));
+	}
+
+	let history = crate::schema::avito_feed_imports::table
+		.filter(crate::schema::avito_feed_imports::feed_id.eq(feed_id))
+		.count()
+		.get_result::<i64>(&mut conn)
+		.and_then(|total| {
+			crate::schema::avito_feed_imports::table
+				.filter(crate::schema::avito_feed_imports::feed_id.eq(feed_id))
+				.order(crate::schema::avito_feed_imports::started_ts.desc())
+				.limit(limit as i64)
+				.offset(offset as i64)
+				.load::<AvitoFeedImport>(&mut conn)
+				.map(|imports| (total, imports))
+		});
+
+	match history {
+		Ok((total, imports)) => Ok(HttpResponse::Ok().json(ResponseWithPagination {
+			status: "success".to_string(),
+			data: imports,
+			pagination: PaginationResponse {
+				page,
+				limit,
+				total,
+				pages: ((total as f64) / (limit as f64)).ceil() as u32,
+			},
+		})),
+		Err(e) => {
+			log::error!(
+				"Failed to fetch import history of feed {}: {:?}",
+				feed_id,
+				e
+			);
+			Ok(HttpResponse::InternalServerError().json(json!({
+				"status": "error",
+				"message": "Failed to fetch import history"
+			})))
+		}
+	}
+}
@@ -1,297 +1,835 @@
+use crate::config::Config;
+use crate::controllers::avito_feeds::feed_access::find_user_feed;
+use crate::controllers::avito_feeds::sync_avito_xml::{
+	apply_feed_sync, load_existing_ads, plan_deactivations, plan_sync_batch, SyncPlan,
+};
+use crate::controllers::avito_feeds::xml_stream::{
+	open_feed_source, stream_response, XmlAdReader, XmlLimits,
+};
+use crate::controllers::websocket::WebSocketConnections;
+use crate::permissions::{Permission, RequirePermission};
+use crate::utils::webhook::{resolve_public_url, WebhookTarget};
 use crate::{
 	jwt_auth::JwtMiddleware,
-	models::{AvitoFeed, CreateAvitoFeed, XmlAd},
+	models::{
+		AdFieldValues, AvitoAccount, AvitoAd, AvitoAdField, AvitoFeed, CreateAvitoFeed, XmlAd,
+	},
 	AppState,
 };
 use actix_web::{web, HttpResponse, Result};
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
-use quick_xml::events::Event;
-use quick_xml::Reader;
-use reqwest::Client;
-use serde::Deserialize;
-use std::collections::HashMap;
+use diesel::r2d2::{ConnectionManager, Pool};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{BufRead, Read};
 use std::time::Duration;
+use tokio::sync::mpsc;
 use uuid::Uuid;
 
+// Upper bound for downloading a whole feed, large supplier feeds take minutes
+pub const FEED_DOWNLOAD_TIMEOUT_SECS: u64 = 30 * 60;
+
 #[derive(Deserialize)]
 pub struct ImportAvitoXmlRequest {
 	pub account_id: Uuid,
 	pub xml_url: String,
+	// Sync into an existing feed instead of creating a new one, matching ads by Id
+	pub feed_id: Option<Uuid>,
+	// Only report what a sync would change, without writing anything
+	#[serde(default)]
+	pub dry_run: bool,
 }
 
-#[actix_web::post("/avito/feeds/import_xml")]
+#[actix_web::post(
+	"/avito/feeds/import_xml",
+	wrap = "RequirePermission(Permission::ManageFeeds)"
+)]
 pub async fn import_avito_xml(
 	body: web::Json<ImportAvitoXmlRequest>,
 	data: web::Data<AppState>,
-	_: JwtMiddleware,
+	user: JwtMiddleware,
 ) -> Result<HttpResponse> {
 	let xml_url = &body.xml_url;
-	let account_id = body.account_id;
+	let target = ImportTarget {
+		account_id: body.account_id,
+		feed_id: body.feed_id,
+		dry_run: body.dry_run,
+	};
+
+	// Nothing is downloaded for an account or feed the user doesn't own
+	if let Err(e) = authorize_import_target(&data, user.user_id, target.clone()).await {
+		return Ok(import_response(Err(e)));
+	}
 
-	// Fetch XML data
-	let client = match Client::builder().timeout(Duration::from_secs(30)).build() {
-		Ok(client) => client,
+	let feed_host = match resolve_public_url(xml_url, "xml_url").await {
+		Ok(feed_host) => feed_host,
 		Err(e) => {
-			log::error!("Failed to build HTTP client: {:?}", e);
-			return Ok(
-				actix_web::HttpResponse::InternalServerError().json(serde_json::json!({
-					"status": "error",
-					"message": format!("Failed to build HTTP client: {}", e)
-				})),
-			);
+			return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+				"status": "error",
+				"message": e
+			})));
 		}
 	};
 
-	let response = match client.get(xml_url).send().await {
+	let response = match fetch_feed_from(&feed_host, xml_url).await {
 		Ok(response) => response,
 		Err(e) => {
-			log::error!("Failed to fetch XML: {:?}", e);
-			return Ok(
-				actix_web::HttpResponse::InternalServerError().json(serde_json::json!({
-					"status": "error",
-					"message": format!("Failed to fetch XML: {}", e)
-				})),
-			);
+			log::error!("{}", e);
+			return Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+				"status": "error",
+				"message": e
+			})));
 		}
 	};
 
+	// The body is parsed while it downloads instead of being read into memory first
+	let source = Box::new(stream_response(response));
+
+	Ok(run_xml_import(&data, user.user_id, source, target).await)
+}
+
+// Request a feed URL, leaving the body unread so it can be streamed. The host must
+// resolve to public addresses.
+pub async fn fetch_feed(url: &str) -> Result<reqwest::Response, String> {
+	let feed_host = resolve_public_url(url, "source_url").await?;
+	fetch_feed_from(&feed_host, url).await
+}
+
+// Request a feed URL from the address it was checked against, without following redirects
+async fn fetch_feed_from(
+	feed_host: &WebhookTarget,
+	url: &str,
+) -> Result<reqwest::Response, String> {
+	let client = feed_host
+		.client(Duration::from_secs(FEED_DOWNLOAD_TIMEOUT_SECS))
+		.map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+
+	let response = client
+		.get(url)
+		.send()
+		.await
+		.map_err(|e| format!("Failed to fetch XML: {}", e))?;
+
 	if !response.status().is_success() {
-		log::error!("Failed to fetch XML: Status {}", response.status());
-		return Ok(
-			actix_web::HttpResponse::InternalServerError().json(serde_json::json!({
-				"status": "error",
-				"message": format!("Failed to fetch XML: Status {}", response.status())
-			})),
-		);
+		return Err(format!("Failed to fetch XML: Status {}", response.status()));
 	}
 
-	let xml_data = match response.text().await {
-		Ok(xml_data) => xml_data,
-		Err(e) => {
-			log::error!("Failed to read response: {:?}", e);
-			return Ok(
-				actix_web::HttpResponse::InternalServerError().json(serde_json::json!({
-					"status": "error",
-					"message": format!("Failed to read response: {}", e)
-				})),
-			);
+	Ok(response)
+}
+
+// Where the ads of an imported document go
+#[derive(Debug, Clone)]
+pub struct ImportTarget {
+	pub account_id: Uuid,
+	// Sync into this feed instead of creating a new one
+	pub feed_id: Option<Uuid>,
+	pub dry_run: bool,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct ImportSummary {
+	pub created: usize,
+	pub skipped: usize,
+	pub failed: usize,
+}
+
+#[derive(Debug)]
+pub enum ImportOutcome {
+	Created {
+		feed_id: Uuid,
+		ads_processed: usize,
+		summary: ImportSummary,
+		results: Vec<AdImportResult>,
+	},
+	Synced {
+		feed_id: Uuid,
+		dry_run: bool,
+		ads_processed: usize,
+		plan: SyncPlan,
+	},
+}
+
+impl ImportOutcome {
+	pub fn feed_id(&self) -> Uuid {
+		match self {
+			ImportOutcome::Created { feed_id, .. } | ImportOutcome::Synced { feed_id, .. } => {
+				*feed_id
+			}
 		}
-	};
+	}
+}
 
-	// Parse XML and extract ads
-	println!("Parsing XML data with length: {}", xml_data.len());
-	let ads = match parse_xml_ads(&xml_data) {
-		Ok(ads) => ads,
-		Err(e) => {
-			log::error!("Failed to parse XML: {:?}", e);
-			return Ok(
-				actix_web::HttpResponse::InternalServerError().json(serde_json::json!({
-					"status": "error",
-					"message": format!("Failed to parse XML: {}", e)
-				})),
-			);
+#[derive(Debug)]
+pub enum ImportError {
+	AccountNotFound,
+	FeedNotFound,
+	FeedAccountMismatch,
+	Connection(String),
+	// The document could not be read, decoded or parsed
+	Source(String),
+	Database(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for ImportError {
+	fn from(e: diesel::result::Error) -> Self {
+		ImportError::Database(e)
+	}
+}
+
+impl fmt::Display for ImportError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ImportError::AccountNotFound => write!(f, "Avito account not found"),
+			ImportError::FeedNotFound => write!(f, "Feed not found"),
+			ImportError::FeedAccountMismatch => write!(f, "Feed does not belong to this account"),
+			ImportError::Connection(e) => write!(f, "Database connection error: {}", e),
+			ImportError::Source(e) => write!(f, "Failed to read XML: {}", e),
+			ImportError::Database(e) => write!(f, "Failed to import feed: {}", e),
+		}
+	}
+}
+
+// Progress message sent to the user's WebSocket connections while a feed is imported
+#[derive(Serialize, Debug)]
+pub struct ImportProgress {
+	#[serde(rename = "type")]
+	pub kind: &'static str,
+	// parsing, completed or failed
+	pub stage: &'static str,
+	pub feed_id: Option<Uuid>,
+	pub ads_parsed: usize,
+	pub bytes_read: u64,
+	pub error: Option<String>,
+}
+
+impl ImportProgress {
+	pub fn new<R: BufRead>(
+		stage: &'static str,
+		feed_id: Option<Uuid>,
+		ads: &XmlAdReader<R>,
+	) -> Self {
+		Self {
+			kind: "xml_import_progress",
+			stage,
+			feed_id,
+			ads_parsed: ads.ads_read(),
+			bytes_read: ads.bytes_read(),
+			error: None,
+		}
+	}
+}
+
+// Parse and store a feed on the blocking thread pool and answer with the import report
+pub async fn run_xml_import(
+	data: &web::Data<AppState>,
+	user_id: Uuid,
+	source: Box<dyn Read + Send>,
+	target: ImportTarget,
+) -> HttpResponse {
+	import_response(
+		execute_xml_import(
+			data.db.clone(),
+			&data.env,
+			data.ws_server.clone(),
+			user_id,
+			source,
+			target,
+		)
+		.await,
+	)
+}
+
+// Parse and store a feed on the blocking thread pool, reporting progress to the user over WebSocket
+pub async fn execute_xml_import(
+	pool: Pool<ConnectionManager<PgConnection>>,
+	config: &Config,
+	ws_server: WebSocketConnections,
+	user_id: Uuid,
+	source: Box<dyn Read + Send>,
+	target: ImportTarget,
+) -> Result<ImportOutcome, ImportError> {
+	let limits = XmlLimits::from_config(config);
+	let batch_size = config.xml_import_batch_size.max(1);
+
+	let (progress_sender, mut progress_receiver) = mpsc::unbounded_channel::<ImportProgress>();
+	let forwarder = tokio::spawn(async move {
+		while let Some(progress) = progress_receiver.recv().await {
+			if let Ok(message) = serde_json::to_string(&progress) {
+				ws_server
+					.broadcast_message_to_user(&user_id.to_string(), &message)
+					.await;
+			}
 		}
+	});
+
+	let result = web::block(move || {
+		let mut conn = pool
+			.get()
+			.map_err(|e| ImportError::Connection(e.to_string()))?;
+		authorize_import(&mut conn, user_id, &target)?;
+		let source = open_feed_source(source, &limits).map_err(ImportError::Source)?;
+		let mut ads = XmlAdReader::new(source, limits);
+
+		let mut report = |progress: ImportProgress| {
+			let _ = progress_sender.send(progress);
+		};
+		let result = import_xml_stream(&mut conn, &mut ads, &target, batch_size, &mut report);
+
+		report(match &result {
+			Ok(outcome) => ImportProgress::new("completed", Some(outcome.feed_id()), &ads),
+			Err(e) => ImportProgress {
+				error: Some(e.to_string()),
+				..ImportProgress::new("failed", target.feed_id, &ads)
+			},
+		});
+		result
+	})
+	.await
+	.unwrap_or_else(|e| Err(ImportError::Connection(e.to_string())));
+
+	let _ = forwarder.await;
+
+	result
+}
+
+// Check the target before a document is read, so requests for other users' accounts and
+// feeds are turned away without transferring it
+pub async fn authorize_import_target(
+	data: &web::Data<AppState>,
+	user_id: Uuid,
+	target: ImportTarget,
+) -> Result<(), ImportError> {
+	let pool = data.db.clone();
+	web::block(move || {
+		let mut conn = pool
+			.get()
+			.map_err(|e| ImportError::Connection(e.to_string()))?;
+		authorize_import(&mut conn, user_id, &target)
+	})
+	.await
+	.unwrap_or_else(|e| Err(ImportError::Connection(e.to_string())))
+}
+
+// Load the account and feed the import writes to and check that they are the user's
+fn authorize_import(
+	conn: &mut PgConnection,
+	user_id: Uuid,
+	target: &ImportTarget,
+) -> Result<(), ImportError> {
+	let account = crate::schema::avito_accounts::table
+		.find(target.account_id)
+		.first::<AvitoAccount>(conn)
+		.optional()?;
+	let feed = match target.feed_id {
+		Some(feed_id) => find_user_feed(conn, feed_id, user_id).optional()?,
+		None => None,
 	};
-	println!("Parsed {} ads from XML", ads.len());
-
-	// Print debug information about the first few ads
-	for (i, ad) in ads.iter().take(3).enumerate() {
-		println!("Ad {}: id={}, fields={}", i, ad.id, ad.fields.len());
-		if let Some(images) = ad.fields.get("Images") {
-			println!(" Images field: {}", images);
-		} else {
-			println!("  No Images field found");
+	check_import_access(user_id, target, account.as_ref(), feed.as_ref())
+}
+
+// Other users' accounts and feeds are reported as missing, as everywhere else.
+// `feed` is the target feed as found among the user's feeds.
+pub fn check_import_access(
+	user_id: Uuid,
+	target: &ImportTarget,
+	account: Option<&AvitoAccount>,
+	feed: Option<&AvitoFeed>,
+) -> Result<(), ImportError> {
+	if account.is_none_or(|account| account.user_id != user_id) {
+		return Err(ImportError::AccountNotFound);
+	}
+	if target.feed_id.is_some() {
+		match feed {
+			None => return Err(ImportError::FeedNotFound),
+			Some(feed) if feed.account_id != target.account_id => {
+				return Err(ImportError::FeedAccountMismatch)
+			}
+			Some(_) => {}
 		}
 	}
+	Ok(())
+}
 
-	println!("Parsed {} ads from XML", ads.len());
+// Stream ads from the reader into the target feed, one batch at a time
+pub fn import_xml_stream<R: BufRead>(
+	conn: &mut PgConnection,
+	ads: &mut XmlAdReader<R>,
+	target: &ImportTarget,
+	batch_size: usize,
+	progress: &mut dyn FnMut(ImportProgress),
+) -> Result<ImportOutcome, ImportError> {
+	match target.feed_id {
+		Some(feed_id) => sync_feed_stream(
+			conn,
+			ads,
+			target.account_id,
+			feed_id,
+			target.dry_run,
+			batch_size,
+			progress,
+		),
+		None => create_feed_stream(conn, ads, target.account_id, batch_size, progress),
+	}
+}
 
-	let mut conn = data.db.get().unwrap();
+// Create the feed and its ads in one transaction so a failed import leaves nothing behind
+fn create_feed_stream<R: BufRead>(
+	conn: &mut PgConnection,
+	ads: &mut XmlAdReader<R>,
+	account_id: Uuid,
+	batch_size: usize,
+	progress: &mut dyn FnMut(ImportProgress),
+) -> Result<ImportOutcome, ImportError> {
+	conn.transaction::<_, ImportError, _>(|conn| {
+		let avito_feed: AvitoFeed = diesel::insert_into(crate::schema::avito_feeds::table)
+			.values(CreateAvitoFeed {
+				account_id,
+				category: "IMPORT".to_string(),
+			})
+			.get_result(conn)?;
 
-	// Create feed entry
-	let feed_id = Uuid::new_v4();
-	let new_feed = CreateAvitoFeed {
-		account_id,
-		category: "IMPORT".to_string(),
+		let mut seen_ids = HashSet::new();
+		let mut clock = ImportClock::new();
+		let mut results = Vec::new();
+
+		loop {
+			let batch = ads.next_batch(batch_size).map_err(ImportError::Source)?;
+			if batch.is_empty() {
+				break;
+			}
+
+			results.extend(persist_xml_ads(
+				conn,
+				avito_feed.feed_id,
+				&batch,
+				&mut seen_ids,
+				&mut clock,
+			));
+			progress(ImportProgress::new(
+				"parsing",
+				Some(avito_feed.feed_id),
+				ads,
+			));
+		}
+
+		let count = |status: AdImportStatus| results.iter().filter(|r| r.status == status).count();
+		let summary = ImportSummary {
+			created: count(AdImportStatus::Created),
+			skipped: count(AdImportStatus::Skipped),
+			failed: count(AdImportStatus::Failed),
+		};
+		log::info!(
+			"Imported {} ads into feed {}",
+			summary.created,
+			avito_feed.feed_id
+		);
+
+		Ok(ImportOutcome::Created {
+			feed_id: avito_feed.feed_id,
+			ads_processed: results.len(),
+			summary,
+			results,
+		})
+	})
+}
+
+// Incrementally update an existing feed from the streamed ads, matching them by Id
+fn sync_feed_stream<R: BufRead>(
+	conn: &mut PgConnection,
+	ads: &mut XmlAdReader<R>,
+	account_id: Uuid,
+	feed_id: Uuid,
+	dry_run: bool,
+	batch_size: usize,
+	progress: &mut dyn FnMut(ImportProgress),
+) -> Result<ImportOutcome, ImportError> {
+	let feed = match crate::schema::avito_feeds::table
+		.find(feed_id)
+		.first::<AvitoFeed>(conn)
+	{
+		Ok(feed) => feed,
+		Err(diesel::result::Error::NotFound) => return Err(ImportError::FeedNotFound),
+		Err(e) => return Err(e.into()),
 	};
+	if feed.account_id != account_id {
+		return Err(ImportError::FeedAccountMismatch);
+	}
 
-	let avito_feed_result: Result<AvitoFeed, diesel::result::Error> =
-		diesel::insert_into(crate::schema::avito_feeds::table)
-			.values(new_feed)
-			.get_result(&mut conn);
+	conn.transaction::<_, ImportError, _>(|conn| {
+		let existing = load_existing_ads(conn, feed_id)?;
+		let mut seen_ids = HashSet::new();
+		let mut clock = ImportClock::new();
+		let mut plan = SyncPlan::default();
+		let mut ads_processed = 0;
 
-	match avito_feed_result {
-		Ok(avito_feed) => Ok(HttpResponse::Ok().json(serde_json::json!({
+		loop {
+			let batch = ads.next_batch(batch_size).map_err(ImportError::Source)?;
+			if batch.is_empty() {
+				break;
+			}
+			ads_processed += batch.len();
+
+			let batch_plan = plan_sync_batch(&existing, &batch, &mut seen_ids);
+			if !dry_run {
+				apply_feed_sync(conn, feed_id, &batch_plan, &batch, &mut clock)?;
+			}
+			plan.append(batch_plan);
+			progress(ImportProgress::new("parsing", Some(feed_id), ads));
+		}
+
+		// Only once the whole document is read do we know which ads disappeared
+		let deactivations = plan_deactivations(&existing, &seen_ids);
+		if !dry_run {
+			apply_feed_sync(conn, feed_id, &deactivations, &[], &mut clock)?;
+			diesel::update(crate::schema::avito_feeds::table.find(feed_id))
+				.set(crate::schema::avito_feeds::updated_ts.eq(Some(Utc::now())))
+				.execute(conn)?;
+		}
+		plan.append(deactivations);
+
+		Ok(ImportOutcome::Synced {
+			feed_id,
+			dry_run,
+			ads_processed,
+			plan,
+		})
+	})
+}
+
+pub fn import_response(result: Result<ImportOutcome, ImportError>) -> HttpResponse {
+	match result {
+		Ok(ImportOutcome::Created {
+			feed_id,
+			ads_processed,
+			summary,
+			results,
+		}) => HttpResponse::Ok().json(serde_json::json!({
 			"status": "success",
 			"message": "Import completed successfully",
-			"feed_id": avito_feed.feed_id,
-			"ads_processed": ads.len()
-		}))),
+			"feed_id": feed_id,
+			"ads_processed": ads_processed,
+			"summary": summary,
+			"results": results
+		})),
+		Ok(ImportOutcome::Synced {
+			feed_id,
+			dry_run,
+			ads_processed,
+			plan,
+		}) => HttpResponse::Ok().json(serde_json::json!({
+			"status": "success",
+			"message": if dry_run { "Sync preview completed" } else { "Sync completed successfully" },
+			"feed_id": feed_id,
+			"dry_run": dry_run,
+			"ads_processed": ads_processed,
+			"summary": plan.summary,
+			"results": plan.entries
+		})),
+		Err(e @ (ImportError::AccountNotFound | ImportError::FeedNotFound)) => {
+			HttpResponse::NotFound().json(serde_json::json!({
+				"status": "fail",
+				"message": e.to_string()
+			}))
+		}
+		Err(e @ ImportError::FeedAccountMismatch) => {
+			HttpResponse::BadRequest().json(serde_json::json!({
+				"status": "fail",
+				"message": e.to_string()
+			}))
+		}
+		Err(e @ ImportError::Source(_)) => {
+			log::error!("{}", e);
+			HttpResponse::BadRequest().json(serde_json::json!({
+				"status": "error",
+				"message": e.to_string()
+			}))
+		}
 		Err(e) => {
-			log::error!("Failed to create feed: {:?}", e);
-			Ok(
-				actix_web::HttpResponse::InternalServerError().json(serde_json::json!({
-					"status": "error",
-					"message": format!("Failed to create feed: {}", e)
-				})),
-			)
+			log::error!("{}", e);
+			HttpResponse::InternalServerError().json(serde_json::json!({
+				"status": "error",
+				"message": e.to_string()
+			}))
 		}
 	}
 }
 
-pub fn parse_xml_ads(xml_data: &str) -> Result<Vec<XmlAd>, String> {
-	let mut reader = Reader::from_str(xml_data);
-	let mut ads = Vec::new();
-	let mut buf = Vec::new();
-	let mut current_ad: Option<XmlAd> = None;
-	let mut current_path = Vec::new();
-	let mut current_values = String::new();
-	let mut in_ad = false;
-	let mut delivery_buffer = Vec::new();
-	let mut images_buffer: Vec<String> = Vec::new();
-
-	loop {
-		match reader.read_event_into(&mut buf) {
-			Ok(Event::Start(e)) => {
-				let name = std::str::from_utf8(e.name().as_ref())
-					.map_err(|e| format!("UTF-8 error: {}", e))?
-					.to_string();
-
-				// println!("Start element: {}, current path: {:?}", name, current_path);
-				current_path.push(name.clone());
-
-				if name == "Ad" {
-					in_ad = true;
-					current_ad = Some(XmlAd {
-						id: String::new(),
-						fields: HashMap::new(),
-					});
-					delivery_buffer.clear();
-					images_buffer.clear();
-				}
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AdImportStatus {
+	Created,
+	Skipped,
+	Failed,
+}
 
-				current_values.clear();
-			}
-			Ok(Event::Text(e)) => {
-				// Extract text content directly from the bytes
-				let text = std::str::from_utf8(e.into_inner().as_ref())
-					.map_err(|e| format!("UTF-8 error: {}", e))?
-					.to_string();
-
-				if in_ad && !&text.trim().is_empty() {
-					current_values.push_str(&text);
-				}
-			}
-			Ok(Event::CData(e)) => {
-				// Handle CDATA content (for Description)
-				let text = std::str::from_utf8(e.as_ref())
-					.map_err(|e| format!("UTF-8 error: {}", e))?
-					.to_string();
-
-				if in_ad {
-					current_values.push_str(&text);
-				}
-			}
-			Ok(Event::Empty(e)) => {
-				let name = std::str::from_utf8(e.name().as_ref())
-					.map_err(|e| format!("UTF-8 error: {}", e))?
-					.to_string();
-
-				// Handle Image tags with attributes
-				if name == "Image" && current_path.contains(&"Images".to_string()) {
-					// Extract the url attribute directly and add to images_buffer
-					for attr_result in e.attributes() {
-						if let Ok(attr) = attr_result {
-							if attr.key.as_ref() == b"url" {
-								if let Ok(url) = std::str::from_utf8(&attr.value) {
-									images_buffer.push(url.to_string());
-								}
-							}
-						}
-					}
-				}
-				// Handle other empty elements (fallback)
-				else {
-					let text = std::str::from_utf8(e.as_ref())
-						.map_err(|e| format!("UTF-8 error: {}", e))?
-						.to_string();
-
-					if in_ad {
-						current_values.push_str(&text);
-					}
+#[derive(Serialize, Debug)]
+pub struct AdImportResult {
+	pub parsed_id: String,
+	pub status: AdImportStatus,
+	pub ad_id: Option<Uuid>,
+	pub reason: Option<String>,
+}
+
+// Write a batch of parsed ads with their fields and values to the feed. Each ad runs in its
+// own savepoint, so an ad that fails is rolled back on its own and reported with the reason.
+// `seen_ids` carries the Ids of earlier batches so duplicates are caught across the document.
+pub fn persist_xml_ads(
+	conn: &mut PgConnection,
+	feed_id: Uuid,
+	ads: &[XmlAd],
+	seen_ids: &mut HashSet<String>,
+	clock: &mut ImportClock,
+) -> Vec<AdImportResult> {
+	let mut results = Vec::with_capacity(ads.len());
+
+	for ad in ads {
+		if !seen_ids.insert(ad.id.clone()) {
+			results.push(AdImportResult {
+				parsed_id: ad.id.clone(),
+				status: AdImportStatus::Skipped,
+				ad_id: None,
+				reason: Some("Duplicate Id in feed".to_string()),
+			});
+			continue;
+		}
+
+		let result = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+			insert_xml_ad(conn, feed_id, ad, clock)
+		});
+
+		results.push(match result {
+			Ok(ad_id) => AdImportResult {
+				parsed_id: ad.id.clone(),
+				status: AdImportStatus::Created,
+				ad_id: Some(ad_id),
+				reason: None,
+			},
+			Err(e) => {
+				log::warn!("Failed to import ad {}: {:?}", ad.id, e);
+				AdImportResult {
+					parsed_id: ad.id.clone(),
+					status: AdImportStatus::Failed,
+					ad_id: None,
+					reason: Some(e.to_string()),
 				}
 			}
-			Ok(Event::End(e)) => {
-				let name = std::str::from_utf8(e.name().as_ref())
-					.map_err(|e| format!("UTF-8 error: {}", e))?
-					.to_string();
-
-				if let Some(ad) = &mut current_ad {
-					// Special handling for Delivery - store as comma-separated options
-					if name == "Delivery" && !delivery_buffer.is_empty() {
-						ad.fields
-							.insert("Delivery".to_string(), delivery_buffer.join(","));
-						delivery_buffer.clear();
-					}
-					// Special handling for Option elements inside Delivery
-					else if name == "Option" && current_path.contains(&"Delivery".to_string()) {
-						if !current_values.trim().is_empty() {
-							delivery_buffer.push(current_values.trim().to_string());
-						}
-					} else if name == "Images" {
-						// Store image URLs when closing Images tag
-						if !images_buffer.is_empty() {
-							ad.fields
-								.insert("Images".to_string(), images_buffer.join(","));
-							images_buffer.clear();
-						}
-					} else if name == "Image" && current_path.contains(&"Images".to_string()) {
-						// For non-empty Image tags, add their text content to images_buffer
-						if !current_values.trim().is_empty() {
-							images_buffer.push(current_values.trim().to_string());
-						}
-					}
-					// Store other field values if not empty
-					else if !current_values.trim().is_empty() && current_path.len() > 1 {
-						let field_name = current_path.last().unwrap().clone();
-						// Skip storing individual Image and Option elements as they're handled specially
-						if field_name != "Image" && field_name != "Option" {
-							ad.fields
-								.insert(field_name, current_values.trim().to_string());
-						}
-					}
-
-					// Special handling for Id field
-					if name == "Id" {
-						if let Some(id_value) = ad.fields.get("Id") {
-							ad.id = id_value.clone();
-						}
-					}
-				}
+		});
+	}
 
-				// If this is the end of an Ad element, add it to the list
-				if name == "Ad" {
-					in_ad = false;
-					if let Some(ad) = current_ad.take() {
-						if !ad.id.is_empty() {
-							ads.push(ad);
-						}
-					}
-				}
+	results
+}
 
-				// println!("End element: {}, current path: {:?}", name, current_path);
-				current_path.pop();
-				current_values.clear();
-			}
-			Ok(Event::Eof) => break,
-			Err(e) => return Err(format!("XML parse error: {}", e)),
-			_ => (),
-		}
+// Insert a single ad with one avito_ad_fields row per tag and one avito_ad_field_values row per value
+pub fn insert_xml_ad(
+	conn: &mut PgConnection,
+	feed_id: Uuid,
+	ad: &XmlAd,
+	clock: &mut ImportClock,
+) -> Result<Uuid, diesel::result::Error> {
+	let avito_ad: AvitoAd = diesel::insert_into(crate::schema::avito_ads::table)
+		.values((
+			crate::schema::avito_ads::feed_id.eq(feed_id),
+			crate::schema::avito_ads::avito_ad_id.eq(ad.fields.get("AvitoId")),
+			crate::schema::avito_ads::parsed_id.eq(&ad.id),
+			crate::schema::avito_ads::status.eq("active"),
+			crate::schema::avito_ads::created_ts.eq(clock.tick().naive_utc()),
+		))
+		.get_result(conn)?;
+
+	insert_xml_ad_fields(conn, avito_ad.ad_id, ad.field_values(), clock)?;
+
+	Ok(avito_ad.ad_id)
+}
 
-		// Clear the buffer to prevent re-processing the same event
-		buf.clear();
+// Insert (tag, values) pairs as fields of an existing ad
+pub fn insert_xml_ad_fields(
+	conn: &mut PgConnection,
+	ad_id: Uuid,
+	field_values: AdFieldValues,
+	clock: &mut ImportClock,
+) -> Result<(), diesel::result::Error> {
+	for (tag, values) in field_values {
+		let field: AvitoAdField = diesel::insert_into(crate::schema::avito_ad_fields::table)
+			.values((
+				crate::schema::avito_ad_fields::ad_id.eq(ad_id),
+				crate::schema::avito_ad_fields::tag.eq(tag),
+				crate::schema::avito_ad_fields::created_ts.eq(clock.tick()),
+			))
+			.get_result(conn)?;
+
+		let value_rows: Vec<_> = values
+			.into_iter()
+			.map(|value| {
+				(
+					crate::schema::avito_ad_field_values::field_id.eq(field.field_id),
+					crate::schema::avito_ad_field_values::value.eq(value),
+					crate::schema::avito_ad_field_values::created_ts.eq(clock.tick()),
+				)
+			})
+			.collect();
+
+		diesel::insert_into(crate::schema::avito_ad_field_values::table)
+			.values(&value_rows)
+			.execute(conn)?;
 	}
 
-	println!("Finished parsing {} ads", ads.len());
+	Ok(())
+}
+
+// Replace the ad's fields with the given tags by `fields`. A tag that is not in `fields`
+// is removed from the ad.
+pub fn replace_ad_field_values(
+	conn: &mut PgConnection,
+	ad_id: Uuid,
+	tags: &[&str],
+	fields: AdFieldValues,
+	clock: &mut ImportClock,
+) -> Result<(), diesel::result::Error> {
+	let stale_field_ids: Vec<Uuid> = crate::schema::avito_ad_fields::table
+		.filter(crate::schema::avito_ad_fields::ad_id.eq(ad_id))
+		.filter(crate::schema::avito_ad_fields::tag.eq_any(tags))
+		.select(crate::schema::avito_ad_fields::field_id)
+		.load(conn)?;
+
+	diesel::delete(
+		crate::schema::avito_ad_field_values::table
+			.filter(crate::schema::avito_ad_field_values::field_id.eq_any(&stale_field_ids)),
+	)
+	.execute(conn)?;
+	diesel::delete(
+		crate::schema::avito_ad_fields::table
+			.filter(crate::schema::avito_ad_fields::field_id.eq_any(&stale_field_ids)),
+	)
+	.execute(conn)?;
+
+	insert_xml_ad_fields(conn, ad_id, fields, clock)
+}
+
+// Hands out strictly increasing timestamps. Fields and values are read back ordered by
+// created_ts, so rows written in the same transaction must not share a timestamp.
+pub struct ImportClock {
+	next: DateTime<Utc>,
+}
+
+impl ImportClock {
+	pub fn new() -> Self {
+		Self { next: Utc::now() }
+	}
+
+	pub fn tick(&mut self) -> DateTime<Utc> {
+		let current = self.next;
+		self.next = current + chrono::Duration::microseconds(1);
+		current
+	}
+}
+
+// Parse a whole document that is already in memory
+#[cfg(test)]
+pub fn parse_xml_ads(xml_data: &str) -> Result<Vec<XmlAd>, String> {
+	let mut reader = XmlAdReader::new(xml_data.as_bytes(), XmlLimits::unlimited());
+	let mut ads = Vec::new();
+	while let Some(ad) = reader.next_ad()? {
+		ads.push(ad);
+	}
 	Ok(ads)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use actix_web::http::StatusCode;
+
+	fn account(user_id: Uuid) -> AvitoAccount {
+		AvitoAccount {
+			account_id: Uuid::new_v4(),
+			user_id,
+			client_id: "client".to_string(),
+			avito_client_secret: String::new(),
+			avito_client_id: String::new(),
+			is_connected: Some(true),
+			created_ts: Utc::now().naive_utc(),
+			updated_ts: Utc::now().naive_utc(),
+			last_check_ts: None,
+			last_check_error: None,
+		}
+	}
+
+	fn target(account: &AvitoAccount) -> ImportTarget {
+		ImportTarget {
+			account_id: account.account_id,
+			feed_id: None,
+			dry_run: false,
+		}
+	}
+
+	#[test]
+	fn test_imports_only_into_own_accounts() {
+		let (owner, other) = (Uuid::new_v4(), Uuid::new_v4());
+		let account = account(owner);
+
+		assert!(check_import_access(owner, &target(&account), Some(&account), None).is_ok());
+
+		let denied = check_import_access(other, &target(&account), Some(&account), None);
+		assert!(matches!(denied, Err(ImportError::AccountNotFound)));
+		assert_eq!(
+			import_response(denied.map(|_| unreachable!())).status(),
+			StatusCode::NOT_FOUND
+		);
+
+		assert!(matches!(
+			check_import_access(owner, &target(&account), None, None),
+			Err(ImportError::AccountNotFound)
+		));
+	}
+
+	#[test]
+	fn test_syncs_only_into_own_feeds() {
+		let (owner, other) = (Uuid::new_v4(), Uuid::new_v4());
+		let (owner_account, other_account) = (account(owner), account(other));
+		let feed = AvitoFeed {
+			feed_id: Uuid::new_v4(),
+			account_id: owner_account.account_id,
+			category: "IMPORT".to_string(),
+			created_ts: Utc::now(),
+			updated_ts: None,
+		};
+
+		let sync = |account: &AvitoAccount| ImportTarget {
+			feed_id: Some(feed.feed_id),
+			..target(account)
+		};
+		assert!(check_import_access(
+			owner,
+			&sync(&owner_account),
+			Some(&owner_account),
+			Some(&feed)
+		)
+		.is_ok());
+
+		// The other user syncing into the feed from their own account doesn't find it among
+		// their feeds
+		let denied = check_import_access(other, &sync(&other_account), Some(&other_account), None);
+		assert!(matches!(denied, Err(ImportError::FeedNotFound)));
+		assert_eq!(
+			import_response(denied.map(|_| unreachable!())).status(),
+			StatusCode::NOT_FOUND
+		);
+
+		// A feed of another of the user's own accounts
+		let second_account = AvitoAccount {
+			account_id: Uuid::new_v4(),
+			..owner_account.clone()
+		};
+		assert!(matches!(
+			check_import_access(
+				owner,
+				&sync(&second_account),
+				Some(&second_account),
+				Some(&feed)
+			),
+			Err(ImportError::FeedAccountMismatch)
+		));
+	}
+}
@@ -0,0 +1,325 @@
+use crate::config::Config;
+use crate::controllers::avito_feeds::import_avito_xml::{
+	execute_xml_import, fetch_feed, ImportOutcome, ImportTarget, FEED_DOWNLOAD_TIMEOUT_SECS,
+};
+use crate::controllers::avito_feeds::xml_stream::stream_response;
+use crate::controllers::websocket::WebSocketConnections;
+use crate::models::{
+	AvitoFeedImport, AvitoFeedImportSchedule, CreateAvitoFeedImport, FinishAvitoFeedImport,
+};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use cron::Schedule;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::time::{sleep, Duration};
+use uuid::Uuid;
+
+// How often the scheduler looks for due imports
+const SCHEDULER_TICK_SECS: u64 = 30;
+
+// Parse a cron expression; the classic five-field form is accepted and runs at second 0
+pub fn parse_cron_expression(expression: &str) -> Result<Schedule, String> {
+	let expression = expression.trim();
+	let expression = if expression.split_whitespace().count() == 5 {
+		format!("0 {}", expression)
+	} else {
+		expression.to_string()
+	};
+
+	Schedule::from_str(&expression).map_err(|e| format!("Invalid cron expression: {}", e))
+}
+
+// When a schedule should run next after `after`
+pub fn next_run_after(
+	interval_minutes: Option<i32>,
+	cron_expression: Option<&str>,
+	after: DateTime<Utc>,
+) -> Result<DateTime<Utc>, String> {
+	if let Some(expression) = cron_expression {
+		return parse_cron_expression(expression)?
+			.after(&after)
+			.next()
+			.ok_or_else(|| "Cron expression never fires".to_string());
+	}
+
+	match interval_minutes {
+		Some(minutes) if minutes > 0 => Ok(after + ChronoDuration::minutes(minutes as i64)),
+		Some(_) => Err("interval_minutes must be positive".to_string()),
+		None => Err("Either interval_minutes or cron_expression is required".to_string()),
+	}
+}
+
+pub async fn start_import_scheduler(
+	db_pool: Pool<ConnectionManager<PgConnection>>,
+	config: Config,
+	ws_server: WebSocketConnections,
+) -> ! {
+	// Runs go in the background so that one slow feed does not hold up the others
+	let running = Arc::new(Semaphore::new(config.feed_import_concurrency.max(1)));
+
+	loop {
+		match claim_due_schedules(&db_pool) {
+			Ok(schedules) => {
+				for schedule in schedules {
+					let (db_pool, config, ws_server) =
+						(db_pool.clone(), config.clone(), ws_server.clone());
+					// Waiting here also holds back claiming more runs while all slots are busy
+					let Ok(permit) = running.clone().acquire_owned().await else {
+						continue;
+					};
+					tokio::spawn(async move {
+						let _permit = permit;
+						run_scheduled_import(&db_pool, &config, &ws_server, schedule).await;
+					});
+				}
+			}
+			Err(e) => log::error!("Failed to load due feed imports: {}", e),
+		}
+
+		sleep(Duration::from_secs(SCHEDULER_TICK_SECS)).await;
+	}
+}
+
+// Pick the enabled schedules that are due and move them to their next run time right away,
+// so a slow import or a second server instance does not start the same run twice
+fn claim_due_schedules(
+	db_pool: &Pool<ConnectionManager<PgConnection>>,
+) -> Result<Vec<AvitoFeedImportSchedule>, String> {
+	let mut conn = db_pool.get().map_err(|e| e.to_string())?;
+	let now = Utc::now();
+
+	conn.transaction::<_, diesel::result::Error, _>(|conn| {
+		let due = crate::schema::avito_feed_import_schedules::table
+			.filter(crate::schema::avito_feed_import_schedules::enabled.eq(true))
+			.filter(
+				crate::schema::avito_feed_import_schedules::next_run_ts
+					.is_null()
+					.or(crate::schema::avito_feed_import_schedules::next_run_ts.le(now)),
+			)
+			.for_update()
+			.skip_locked()
+			.load::<AvitoFeedImportSchedule>(conn)?;
+
+		for schedule in &due {
+			let next_run_ts = match next_run_after(
+				schedule.interval_minutes,
+				schedule.cron_expression.as_deref(),
+				now,
+			) {
+				Ok(next_run_ts) => Some(next_run_ts),
+				Err(e) => {
+					log::warn!("Schedule {} has no next run: {}", schedule.schedule_id, e);
+					None
+				}
+			};
+
+			diesel::update(
+				crate::schema::avito_feed_import_schedules::table.find(schedule.schedule_id),
+			)
+			.set((
+				crate::schema::avito_feed_import_schedules::last_run_ts.eq(Some(now)),
+				crate::schema::avito_feed_import_schedules::next_run_ts.eq(next_run_ts),
+				// A schedule that can never fire again is switched off instead of running every tick
+				crate::schema::avito_feed_import_schedules::enabled.eq(next_run_ts.is_some()),
+			))
+			.execute(conn)?;
+		}
+
+		Ok(due)
+	})
+	.map_err(|e| e.to_string())
+}
+
+async fn run_scheduled_import(
+	db_pool: &Pool<ConnectionManager<PgConnection>>,
+	config: &Config,
+	ws_server: &WebSocketConnections,
+	schedule: AvitoFeedImportSchedule,
+) {
+	let started = match start_import_record(db_pool, &schedule) {
+		Ok(started) => started,
+		Err(e) => {
+			log::error!(
+				"Failed to start scheduled import of feed {}: {}",
+				schedule.feed_id,
+				e
+			);
+			return;
+		}
+	};
+	let (import, account_id, user_id) = started;
+
+	log::info!(
+		"Running scheduled import {} of feed {} from {}",
+		import.import_id,
+		schedule.feed_id,
+		schedule.source_url
+	);
+
+	let result = match fetch_feed(&schedule.source_url).await {
+		Ok(response) => execute_xml_import(
+			db_pool.clone(),
+			config,
+			ws_server.clone(),
+			user_id,
+			Box::new(stream_response(response)),
+			ImportTarget {
+				account_id,
+				feed_id: Some(schedule.feed_id),
+				dry_run: false,
+			},
+		)
+		.await
+		.map_err(|e| e.to_string()),
+		Err(e) => Err(e),
+	};
+
+	let finish = match result {
+		Ok(outcome) => finished_import(outcome),
+		Err(e) => {
+			log::error!("Scheduled import {} failed: {}", import.import_id, e);
+			FinishAvitoFeedImport {
+				status: "failed".to_string(),
+				error: Some(e),
+				finished_ts: Some(Utc::now()),
+				..Default::default()
+			}
+		}
+	};
+
+	let saved = db_pool
+		.get()
+		.map_err(|e| e.to_string())
+		.and_then(|mut conn| {
+			diesel::update(crate::schema::avito_feed_imports::table.find(import.import_id))
+				.set(&finish)
+				.execute(&mut conn)
+				.map_err(|e| e.to_string())
+		});
+	if let Err(e) = saved {
+		log::error!("Failed to record import {}: {}", import.import_id, e);
+	}
+}
+
+// Insert the history row of a run and look up the feed's account and its owner
+fn start_import_record(
+	db_pool: &Pool<ConnectionManager<PgConnection>>,
+	schedule: &AvitoFeedImportSchedule,
+) -> Result<(AvitoFeedImport, Uuid, Uuid), String> {
+	use crate::schema::avito_feed_imports::dsl;
+
+	let mut conn = db_pool.get().map_err(|e| e.to_string())?;
+
+	// A run still "running" past the download timeout was cut short by a restart
+	diesel::update(
+		dsl::avito_feed_imports
+			.filter(dsl::feed_id.eq(schedule.feed_id))
+			.filter(dsl::status.eq("running"))
+			.filter(
+				dsl::started_ts
+					.lt(Utc::now() - ChronoDuration::seconds(FEED_DOWNLOAD_TIMEOUT_SECS as i64)),
+			),
+	)
+	.set((
+		dsl::status.eq("failed"),
+		dsl::error.eq("Interrupted"),
+		dsl::finished_ts.eq(Utc::now()),
+	))
+	.execute(&mut conn)
+	.map_err(|e| e.to_string())?;
+
+	// The previous run of a feed may outlast its interval
+	let running = dsl::avito_feed_imports
+		.filter(dsl::feed_id.eq(schedule.feed_id))
+		.filter(dsl::status.eq("running"))
+		.count()
+		.get_result::<i64>(&mut conn)
+		.map_err(|e| e.to_string())?;
+	if running > 0 {
+		return Err("the previous import is still running".to_string());
+	}
+
+	let (account_id, user_id) = crate::schema::avito_feeds::table
+		.inner_join(crate::schema::avito_accounts::table)
+		.filter(crate::schema::avito_feeds::feed_id.eq(schedule.feed_id))
+		.select((
+			crate::schema::avito_accounts::account_id,
+			crate::schema::avito_accounts::user_id,
+		))
+		.first::<(Uuid, Uuid)>(&mut conn)
+		.map_err(|e| e.to_string())?;
+
+	let import = diesel::insert_into(dsl::avito_feed_imports)
+		.values(CreateAvitoFeedImport {
+			feed_id: schedule.feed_id,
+			schedule_id: Some(schedule.schedule_id),
+			source_url: schedule.source_url.clone(),
+			status: "running".to_string(),
+		})
+		.get_result::<AvitoFeedImport>(&mut conn)
+		.map_err(|e| e.to_string())?;
+
+	Ok((import, account_id, user_id))
+}
+
+fn finished_import(outcome: ImportOutcome) -> FinishAvitoFeedImport {
+	let finish = FinishAvitoFeedImport {
+		status: "success".to_string(),
+		finished_ts: Some(Utc::now()),
+		..Default::default()
+	};
+
+	match outcome {
+		ImportOutcome::Synced {
+			ads_processed,
+			plan,
+			..
+		} => FinishAvitoFeedImport {
+			ads_processed: ads_processed as i32,
+			ads_added: plan.summary.added as i32,
+			ads_updated: plan.summary.updated as i32,
+			ads_unchanged: plan.summary.unchanged as i32,
+			ads_deactivated: plan.summary.deactivated as i32,
+			..finish
+		},
+		ImportOutcome::Created {
+			ads_processed,
+			summary,
+			..
+		} => FinishAvitoFeedImport {
+			ads_processed: ads_processed as i32,
+			ads_added: summary.created as i32,
+			..finish
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use chrono::TimeZone;
+
+	#[test]
+	fn test_next_run_after() {
+		let now = Utc.with_ymd_and_hms(2026, 10, 18, 9, 15, 30).unwrap();
+
+		assert_eq!(
+			next_run_after(Some(90), None, now).unwrap(),
+			Utc.with_ymd_and_hms(2026, 10, 18, 10, 45, 30).unwrap()
+		);
+		assert_eq!(
+			next_run_after(Some(90), Some("0 6 * * *"), now).unwrap(),
+			Utc.with_ymd_and_hms(2026, 10, 19, 6, 0, 0).unwrap()
+		);
+		assert_eq!(
+			next_run_after(None, Some("0 */30 * * * *"), now).unwrap(),
+			Utc.with_ymd_and_hms(2026, 10, 18, 9, 30, 0).unwrap()
+		);
+		assert!(next_run_after(Some(0), None, now).is_err());
+		assert!(next_run_after(None, None, now).is_err());
+		assert!(next_run_after(None, Some("every morning"), now).is_err());
+	}
+}
@@ -1,11 +1,23 @@
 pub mod config;
 pub mod create_avito_feed;
 pub mod delete_avito_feed;
+pub mod delete_feed_import_schedule;
+pub mod export_avito_xml;
+pub mod feed_access;
 pub mod get_all_avito_feeds;
 pub mod get_avito_feed_by_id;
 pub mod get_avito_feeds_by_account;
+pub mod get_feed_import_schedule;
+pub mod get_feed_imports;
 pub mod import_avito_xml;
+pub mod import_scheduler;
+pub mod sync_avito_xml;
 pub mod update_avito_feed;
+pub mod upload_avito_xml;
+pub mod upsert_feed_import_schedule;
+pub mod validate_avito_ad;
+pub mod validate_avito_feed;
+pub mod xml_stream;
 
 use actix_web::web;
 
@@ -0,0 +1,355 @@
+use crate::controllers::avito_feeds::export_avito_xml::{load_feed_ads, FeedAdStatus};
+use crate::controllers::avito_feeds::import_avito_xml::{
+	insert_xml_ad, replace_ad_field_values, ImportClock,
+};
+use crate::models::{AdFieldValues, XmlAd};
+use diesel::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use uuid::Uuid;
+
+// Ad already stored in the target feed, keyed by its parsed_id
+#[derive(Debug, Clone)]
+pub struct ExistingAd {
+	pub ad_id: Uuid,
+	pub status: Option<String>,
+	pub fields: AdFieldValues,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum SyncAction {
+	Added,
+	Updated {
+		ad_id: Uuid,
+		added_tags: Vec<String>,
+		changed_tags: Vec<String>,
+		removed_tags: Vec<String>,
+		reactivated: bool,
+	},
+	Unchanged {
+		ad_id: Uuid,
+	},
+	Deactivated {
+		ad_id: Uuid,
+	},
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SyncEntry {
+	pub parsed_id: String,
+	#[serde(flatten)]
+	pub action: SyncAction,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct SyncSummary {
+	pub added: usize,
+	pub updated: usize,
+	pub unchanged: usize,
+	pub deactivated: usize,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct SyncPlan {
+	pub summary: SyncSummary,
+	pub entries: Vec<SyncEntry>,
+}
+
+impl SyncPlan {
+	pub fn append(&mut self, other: SyncPlan) {
+		self.summary.added += other.summary.added;
+		self.summary.updated += other.summary.updated;
+		self.summary.unchanged += other.summary.unchanged;
+		self.summary.deactivated += other.summary.deactivated;
+		self.entries.extend(other.entries);
+	}
+}
+
+// Load every ad of the feed that came from an import, together with its fields
+pub fn load_existing_ads(
+	conn: &mut PgConnection,
+	feed_id: Uuid,
+) -> Result<HashMap<String, ExistingAd>, diesel::result::Error> {
+	let mut existing = HashMap::new();
+	// Inactive ads are included so that an ad back in the feed is reactivated
+	for (ad, fields) in load_feed_ads(conn, feed_id, FeedAdStatus::Any)? {
+		if let Some(parsed_id) = ad.parsed_id {
+			// Keep the oldest ad when an earlier import stored the same Id twice
+			existing.entry(parsed_id).or_insert(ExistingAd {
+				ad_id: ad.ad_id,
+				status: ad.status,
+				fields,
+			});
+		}
+	}
+
+	Ok(existing)
+}
+
+// Compare a batch of incoming ads with the stored ones, matching them by Ad Id.
+// `seen_ids` carries the Ids of earlier batches of the same document.
+pub fn plan_sync_batch(
+	existing: &HashMap<String, ExistingAd>,
+	incoming: &[XmlAd],
+	seen_ids: &mut HashSet<String>,
+) -> SyncPlan {
+	let mut summary = SyncSummary::default();
+	let mut entries = Vec::new();
+
+	for ad in incoming {
+		if !seen_ids.insert(ad.id.clone()) {
+			continue;
+		}
+
+		let action = match existing.get(&ad.id) {
+			None => {
+				summary.added += 1;
+				SyncAction::Added
+			}
+			Some(stored) => {
+				let old: BTreeMap<&str, &Vec<String>> = stored
+					.fields
+					.iter()
+					.map(|(tag, values)| (tag.as_str(), values))
+					.collect();
+				let new_fields = ad.field_values();
+				let new: BTreeMap<&str, &Vec<String>> = new_fields
+					.iter()
+					.map(|(tag, values)| (tag.as_str(), values))
+					.collect();
+
+				let added_tags: Vec<String> = new
+					.keys()
+					.filter(|tag| !old.contains_key(*tag))
+					.map(|tag| tag.to_string())
+					.collect();
+				let changed_tags: Vec<String> = new
+					.iter()
+					.filter(|(tag, values)| old.get(*tag).is_some_and(|old| old != *values))
+					.map(|(tag, _)| tag.to_string())
+					.collect();
+				let removed_tags: Vec<String> = old
+					.keys()
+					.filter(|tag| !new.contains_key(*tag))
+					.map(|tag| tag.to_string())
+					.collect();
+				let reactivated = stored.status.as_deref() == Some("inactive");
+
+				if added_tags.is_empty()
+					&& changed_tags.is_empty()
+					&& removed_tags.is_empty()
+					&& !reactivated
+				{
+					summary.unchanged += 1;
+					SyncAction::Unchanged {
+						ad_id: stored.ad_id,
+					}
+				} else {
+					summary.updated += 1;
+					SyncAction::Updated {
+						ad_id: stored.ad_id,
+						added_tags,
+						changed_tags,
+						removed_tags,
+						reactivated,
+					}
+				}
+			}
+		};
+
+		entries.push(SyncEntry {
+			parsed_id: ad.id.clone(),
+			action,
+		});
+	}
+
+	SyncPlan { summary, entries }
+}
+
+// Ads missing from the XML are switched off, not deleted
+pub fn plan_deactivations(
+	existing: &HashMap<String, ExistingAd>,
+	seen_ids: &HashSet<String>,
+) -> SyncPlan {
+	let mut summary = SyncSummary::default();
+	let mut entries = Vec::new();
+
+	let mut missing: Vec<(&String, &ExistingAd)> = existing
+		.iter()
+		.filter(|(parsed_id, stored)| {
+			!seen_ids.contains(*parsed_id) && stored.status.as_deref() != Some("inactive")
+		})
+		.collect();
+	missing.sort_by_key(|(parsed_id, _)| parsed_id.as_str());
+
+	for (parsed_id, stored) in missing {
+		summary.deactivated += 1;
+		entries.push(SyncEntry {
+			parsed_id: parsed_id.clone(),
+			action: SyncAction::Deactivated {
+				ad_id: stored.ad_id,
+			},
+		});
+	}
+
+	SyncPlan { summary, entries }
+}
+
+// Write a planned sync to the feed; the caller is expected to run this inside a transaction
+pub fn apply_feed_sync(
+	conn: &mut PgConnection,
+	feed_id: Uuid,
+	plan: &SyncPlan,
+	incoming: &[XmlAd],
+	clock: &mut ImportClock,
+) -> Result<(), diesel::result::Error> {
+	let mut incoming_by_id: HashMap<&str, &XmlAd> = HashMap::new();
+	for ad in incoming {
+		incoming_by_id.entry(ad.id.as_str()).or_insert(ad);
+	}
+
+	for entry in &plan.entries {
+		match &entry.action {
+			SyncAction::Added => {
+				if let Some(ad) = incoming_by_id.get(entry.parsed_id.as_str()) {
+					insert_xml_ad(conn, feed_id, ad, clock)?;
+				}
+			}
+			SyncAction::Updated {
+				ad_id,
+				added_tags,
+				changed_tags,
+				removed_tags,
+				..
+			} => {
+				let Some(ad) = incoming_by_id.get(entry.parsed_id.as_str()) else {
+					continue;
+				};
+
+				// Changed tags are written anew, removed ones only deleted
+				let stale_tags: Vec<&str> = changed_tags
+					.iter()
+					.chain(removed_tags)
+					.map(String::as_str)
+					.collect();
+				let fresh_fields: AdFieldValues = ad
+					.field_values()
+					.into_iter()
+					.filter(|(tag, _)| added_tags.contains(tag) || changed_tags.contains(tag))
+					.collect();
+				replace_ad_field_values(conn, *ad_id, &stale_tags, fresh_fields, clock)?;
+
+				diesel::update(crate::schema::avito_ads::table.find(ad_id))
+					.set((
+						crate::schema::avito_ads::status.eq("active"),
+						crate::schema::avito_ads::avito_ad_id.eq(ad.fields.get("AvitoId")),
+					))
+					.execute(conn)?;
+			}
+			SyncAction::Unchanged { .. } => {}
+			SyncAction::Deactivated { ad_id } => {
+				diesel::update(crate::schema::avito_ads::table.find(ad_id))
+					.set(crate::schema::avito_ads::status.eq("inactive"))
+					.execute(conn)?;
+			}
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn xml_ad(id: &str, fields: &[(&str, &str)]) -> XmlAd {
+		let mut ad = XmlAd {
+			id: id.to_string(),
+			fields: HashMap::new(),
+			lists: HashMap::new(),
+		};
+		ad.fields.insert("Id".to_string(), id.to_string());
+		for (tag, value) in fields {
+			ad.fields.insert(tag.to_string(), value.to_string());
+		}
+		ad
+	}
+
+	fn plan_feed_sync(existing: &HashMap<String, ExistingAd>, incoming: &[XmlAd]) -> SyncPlan {
+		let mut seen_ids = HashSet::new();
+		let mut plan = plan_sync_batch(existing, incoming, &mut seen_ids);
+		plan.append(plan_deactivations(existing, &seen_ids));
+		plan
+	}
+
+	fn stored(ad: &XmlAd, status: Option<&str>) -> ExistingAd {
+		ExistingAd {
+			ad_id: Uuid::new_v4(),
+			status: status.map(|s| s.to_string()),
+			fields: ad.field_values(),
+		}
+	}
+
+	#[test]
+	fn test_plan_feed_sync() {
+		let same = xml_ad("1", &[("Price", "100")]);
+		let changed_before = xml_ad("2", &[("Price", "200"), ("Color", "red")]);
+		let changed_after = xml_ad("2", &[("Price", "250"), ("Title", "Wheel")]);
+		let gone = xml_ad("3", &[("Price", "300")]);
+		let revived = xml_ad("4", &[("Price", "400")]);
+		let new = xml_ad("5", &[("Price", "500")]);
+
+		let mut existing = HashMap::new();
+		existing.insert("1".to_string(), stored(&same, Some("active")));
+		existing.insert("2".to_string(), stored(&changed_before, Some("active")));
+		existing.insert("3".to_string(), stored(&gone, None));
+		existing.insert("4".to_string(), stored(&revived, Some("inactive")));
+
+		let plan = plan_feed_sync(&existing, &[same, changed_after, revived, new]);
+
+		assert_eq!(
+			plan.summary,
+			SyncSummary {
+				added: 1,
+				updated: 2,
+				unchanged: 1,
+				deactivated: 1,
+			}
+		);
+
+		let action = |id: &str| {
+			plan.entries
+				.iter()
+				.find(|entry| entry.parsed_id == id)
+				.map(|entry| entry.action.clone())
+				.unwrap()
+		};
+
+		assert!(matches!(action("1"), SyncAction::Unchanged { .. }));
+		assert!(matches!(action("3"), SyncAction::Deactivated { .. }));
+		assert_eq!(action("5"), SyncAction::Added);
+		match action("2") {
+			SyncAction::Updated {
+				added_tags,
+				changed_tags,
+				removed_tags,
+				reactivated,
+				..
+			} => {
+				assert_eq!(added_tags, vec!["Title".to_string()]);
+				assert_eq!(changed_tags, vec!["Price".to_string()]);
+				assert_eq!(removed_tags, vec!["Color".to_string()]);
+				assert!(!reactivated);
+			}
+			other => panic!("unexpected action {:?}", other),
+		}
+		assert!(matches!(
+			action("4"),
+			SyncAction::Updated {
+				reactivated: true,
+				..
+			}
+		));
+	}
+}
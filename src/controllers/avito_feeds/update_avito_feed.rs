@@ -1,3 +1,4 @@
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	models::{AvitoFeed, AvitoFeedData, AvitoFeedResponse, UpdateAvitoFeed},
 	AppState,
@@ -8,7 +9,10 @@ use diesel::prelude::*;
 use serde_json::json;
 use uuid::Uuid;
 
-#[actix_web::patch("/avito/feeds/{id}")]
+#[actix_web::patch(
+	"/avito/feeds/{id}",
+	wrap = "RequirePermission(Permission::ManageFeeds)"
+)]
 pub async fn update_avito_feed(
 	path: web::Path<Uuid>,
 	data: web::Data<AppState>,
@@ -0,0 +1,176 @@
+use crate::controllers::avito_feeds::import_avito_xml::{
+	authorize_import_target, import_response, run_xml_import, ImportTarget,
+};
+use crate::controllers::avito_feeds::xml_stream::body_channel;
+use crate::permissions::{Permission, RequirePermission};
+use crate::{jwt_auth::JwtMiddleware, AppState};
+use actix_multipart::{Field, Multipart};
+use actix_web::web::Bytes;
+use actix_web::{web, HttpResponse, Result};
+use futures::StreamExt;
+use serde_json::json;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+// Upper bound for the form fields other than the file
+const MAX_FORM_VALUE_SIZE: usize = 1024;
+
+// POST multipart form with an XML feed file (plain, .gz or .zip)
+// Form fields: account_id, file, optional feed_id and dry_run, same as import_xml.
+// The file is imported while it is received, so it has to be the last field.
+#[actix_web::post(
+	"/avito/feeds/import_xml/upload",
+	wrap = "RequirePermission(Permission::ManageFeeds)"
+)]
+pub async fn upload_avito_xml(
+	mut payload: Multipart,
+	data: web::Data<AppState>,
+	user: JwtMiddleware,
+) -> Result<HttpResponse> {
+	// Uploaded files are bounded by the same limit as the uncompressed document
+	let max_upload_size = data.env.xml_max_document_size;
+
+	let mut account_id: Option<Uuid> = None;
+	let mut feed_id: Option<Uuid> = None;
+	let mut dry_run = false;
+	let mut file: Option<Field> = None;
+
+	while let Some(field) = payload.next().await {
+		let mut field = field?;
+		let name = field.name().unwrap_or_default().to_string();
+		if name == "file" {
+			file = Some(field);
+			break;
+		}
+
+		let mut bytes = Vec::new();
+		while let Some(chunk) = field.next().await {
+			let chunk = chunk?;
+			if bytes.len() + chunk.len() > MAX_FORM_VALUE_SIZE {
+				return Ok(HttpResponse::BadRequest().json(json!({
+					"status": "error",
+					"message": format!("{} is too long", name)
+				})));
+			}
+			bytes.extend_from_slice(&chunk);
+		}
+
+		match name.as_str() {
+			"account_id" | "feed_id" => {
+				let value = String::from_utf8_lossy(&bytes).trim().to_string();
+				if value.is_empty() {
+					continue;
+				}
+				let Ok(uuid) = Uuid::parse_str(&value) else {
+					return Ok(HttpResponse::BadRequest().json(json!({
+						"status": "error",
+						"message": format!("Invalid {} format", name)
+					})));
+				};
+				if name == "account_id" {
+					account_id = Some(uuid);
+				} else {
+					feed_id = Some(uuid);
+				}
+			}
+			"dry_run" => {
+				let value = String::from_utf8_lossy(&bytes).trim().to_lowercase();
+				dry_run = matches!(value.as_str(), "true" | "1" | "on");
+			}
+			_ => {}
+		}
+	}
+
+	let Some(account_id) = account_id else {
+		return Ok(HttpResponse::BadRequest().json(json!({
+			"status": "error",
+			"message": "account_id is required"
+		})));
+	};
+
+	let file_required = || {
+		HttpResponse::BadRequest().json(json!({
+			"status": "error",
+			"message": "XML file is required"
+		}))
+	};
+	let Some(mut file) = file else {
+		return Ok(file_required());
+	};
+
+	let target = ImportTarget {
+		account_id,
+		feed_id,
+		dry_run,
+	};
+	if let Err(e) = authorize_import_target(&data, user.user_id, target.clone()).await {
+		return Ok(import_response(Err(e)));
+	}
+
+	// An empty file is refused before the import starts
+	let first_chunk = loop {
+		match file.next().await {
+			Some(chunk) => {
+				let chunk = chunk?;
+				if !chunk.is_empty() {
+					break chunk;
+				}
+			}
+			None => return Ok(file_required()),
+		}
+	};
+
+	let (sender, reader) = body_channel();
+	let (response, too_large) = futures::join!(
+		run_xml_import(&data, user.user_id, Box::new(reader), target),
+		forward_upload(first_chunk, file, sender, max_upload_size),
+	);
+
+	if too_large {
+		return Ok(HttpResponse::PayloadTooLarge().json(json!({
+			"status": "error",
+			"message": format!("Uploaded file exceeds {} bytes", max_upload_size)
+		})));
+	}
+	Ok(response)
+}
+
+// Hand the file to the import chunk by chunk as it is received. Returns whether the file
+// outgrew the limit, in which case the import is failed with a read error.
+async fn forward_upload(
+	first_chunk: Bytes,
+	mut file: Field,
+	sender: mpsc::Sender<Result<Bytes, String>>,
+	max_size: u64,
+) -> bool {
+	let mut received = 0u64;
+	let mut next = Some(Ok(first_chunk));
+
+	while let Some(chunk) = next {
+		let chunk = match chunk {
+			Ok(chunk) => chunk,
+			Err(e) => {
+				let _ = sender
+					.send(Err(format!("Failed to read upload: {}", e)))
+					.await;
+				return false;
+			}
+		};
+
+		received += chunk.len() as u64;
+		if received > max_size {
+			let _ = sender
+				.send(Err(format!("Uploaded file exceeds {} bytes", max_size)))
+				.await;
+			return true;
+		}
+		// The import stopped early, no need to receive the rest
+		if sender.send(Ok(chunk)).await.is_err() {
+			return false;
+		}
+
+		next = file.next().await;
+	}
+
+	false
+}
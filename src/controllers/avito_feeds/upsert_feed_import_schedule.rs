@@ -0,0 +1,94 @@
+use crate::controllers::avito_feeds::feed_access::{feed_access_error, find_user_feed};
+use crate::controllers::avito_feeds::import_scheduler::next_run_after;
+use crate::permissions::{Permission, RequirePermission};
+use crate::utils::webhook::resolve_public_url;
+use crate::{
+	jwt_auth::JwtMiddleware,
+	models::{
+		AvitoFeedImportSchedule, AvitoFeedImportScheduleRequest, UpsertAvitoFeedImportSchedule,
+	},
+	AppState,
+};
+use actix_web::{web, HttpResponse, Result};
+use chrono::Utc;
+use diesel::prelude::*;
+use serde_json::json;
+use uuid::Uuid;
+
+// Create or replace the import schedule of a feed
+#[actix_web::put(
+	"/avito/feeds/{feed_id}/import_schedule",
+	wrap = "RequirePermission(Permission::ManageFeeds)"
+)]
+pub async fn upsert_feed_import_schedule(
+	path: web::Path<Uuid>,
+	body: web::Json<AvitoFeedImportScheduleRequest>,
+	data: web::Data<AppState>,
+	user: JwtMiddleware,
+) -> Result<HttpResponse> {
+	let feed_id = path.into_inner();
+	let body = body.into_inner();
+	let mut conn = data.db.get().unwrap();
+
+	if let Err(e) = find_user_feed(&mut conn, feed_id, user.user_id) {
+		return Ok(feed_access_error(feed_id, e));
+	}
+
+	// Checked again before every run, the host may resolve elsewhere later
+	if let Err(e) = resolve_public_url(&body.source_url, "source_url").await {
+		return Ok(HttpResponse::BadRequest().json(json!({
+			"status": "fail",
+			"message": e
+		})));
+	}
+
+	let cron_expression = body
+		.cron_expression
+		.map(|expression| expression.trim().to_string())
+		.filter(|expression| !expression.is_empty());
+
+	let now = Utc::now();
+	let next_run_ts = match next_run_after(body.interval_minutes, cron_expression.as_deref(), now) {
+		Ok(next_run_ts) => next_run_ts,
+		Err(e) => {
+			return Ok(HttpResponse::BadRequest().json(json!({
+				"status": "fail",
+				"message": e
+			})));
+		}
+	};
+
+	let schedule = UpsertAvitoFeedImportSchedule {
+		feed_id,
+		source_url: body.source_url,
+		interval_minutes: body.interval_minutes,
+		cron_expression,
+		enabled: body.enabled.unwrap_or(true),
+		next_run_ts: Some(next_run_ts),
+		updated_ts: now,
+	};
+
+	match diesel::insert_into(crate::schema::avito_feed_import_schedules::table)
+		.values(&schedule)
+		.on_conflict(crate::schema::avito_feed_import_schedules::feed_id)
+		.do_update()
+		.set(&schedule)
+		.get_result::<AvitoFeedImportSchedule>(&mut conn)
+	{
+		Ok(schedule) => Ok(HttpResponse::Ok().json(json!({
+			"status": "success",
+			"data": { "schedule": schedule }
+		}))),
+		Err(e) => {
+			log::error!(
+				"Failed to save import schedule of feed {}: {:?}",
+				feed_id,
+				e
+			);
+			Ok(HttpResponse::InternalServerError().json(json!({
+				"status": "error",
+				"message": "Failed to save import schedule"
+			})))
+		}
+	}
+}
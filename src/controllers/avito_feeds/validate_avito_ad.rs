@@ -0,0 +1,75 @@
+use crate::controllers::avito_client::catalog_cache::CacheMode;
+use crate::controllers::avito_client::category_schema::CategorySchema;
+use crate::controllers::avito_client::get_category_fields::fetch_category_fields;
+use crate::controllers::avito_feeds::export_avito_xml::load_ads_field_values;
+use crate::controllers::avito_feeds::validate_avito_feed::{
+	validate_ads, validation_slug, ValidateAvitoAdsRequest,
+};
+use crate::permissions::{Permission, RequirePermission};
+use crate::{
+	jwt_auth::JwtMiddleware,
+	models::{ApiError, AvitoAd, AvitoFeed},
+	AppState,
+};
+use actix_web::{web, HttpResponse};
+use diesel::prelude::*;
+use serde_json::json;
+use uuid::Uuid;
+
+// POST check a single ad against the Avito category field schema
+#[actix_web::post(
+	"/avito/ads/{ad_id}/validate",
+	wrap = "RequirePermission(Permission::ManageFeeds)"
+)]
+pub async fn validate_avito_ad(
+	path: web::Path<Uuid>,
+	body: web::Json<ValidateAvitoAdsRequest>,
+	data: web::Data<AppState>,
+	user: JwtMiddleware,
+) -> Result<HttpResponse, ApiError> {
+	let ad_id = path.into_inner();
+	let mut conn = data.db.get().unwrap();
+
+	// Make sure the ad's feed belongs to one of the user's accounts
+	let found = crate::schema::avito_ads::table
+		.inner_join(
+			crate::schema::avito_feeds::table.inner_join(crate::schema::avito_accounts::table),
+		)
+		.filter(crate::schema::avito_ads::ad_id.eq(ad_id))
+		.filter(crate::schema::avito_accounts::user_id.eq(user.user_id))
+		.select((
+			crate::schema::avito_ads::all_columns,
+			crate::schema::avito_feeds::all_columns,
+		))
+		.first::<(AvitoAd, AvitoFeed)>(&mut conn)
+		.optional()?;
+
+	let Some((ad, feed)) = found else {
+		return Ok(HttpResponse::NotFound().json(json!({
+			"status": "fail",
+			"message": "Ad not found or you don't have permission to access it"
+		})));
+	};
+	// The feed's own account supplies the Avito token
+	let account_id = feed.account_id;
+	let avito_slug =
+		match validation_slug(&data, account_id, body.avito_slug.clone(), feed.category).await? {
+			Ok(avito_slug) => avito_slug,
+			Err(response) => return Ok(response),
+		};
+
+	let ads = load_ads_field_values(&mut conn, vec![ad])?;
+	let node_fields =
+		fetch_category_fields(&data, account_id, &avito_slug, CacheMode::PreferCache).await?;
+	let schema = CategorySchema::from_fields_json(&node_fields);
+
+	let result = validate_ads(&schema, ads).pop();
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": {
+			"avito_slug": avito_slug,
+			"ad": result
+		}
+	})))
+}
@@ -0,0 +1,156 @@
+use crate::controllers::avito_client::catalog_cache::CacheMode;
+use crate::controllers::avito_client::category_schema::{AdValidationReport, CategorySchema};
+use crate::controllers::avito_client::get_categories_tree::fetch_categories_tree;
+use crate::controllers::avito_client::get_category_fields::fetch_category_fields;
+use crate::controllers::avito_feeds::export_avito_xml::{load_feed_ads, FeedAdStatus};
+use crate::controllers::avito_feeds::feed_access::{feed_access_error, find_user_feed};
+use crate::permissions::{Permission, RequirePermission};
+use crate::{
+	jwt_auth::JwtMiddleware,
+	models::{AdFieldValues, ApiError, AvitoAd},
+	AppState,
+};
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct ValidateAvitoAdsRequest {
+	// Category node slug; defaults to the category of the feed when that is an Avito category
+	pub avito_slug: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AdValidationResult {
+	pub ad_id: Uuid,
+	pub parsed_id: Option<String>,
+	#[serde(flatten)]
+	pub report: AdValidationReport,
+}
+
+pub fn validate_ads(
+	schema: &CategorySchema,
+	ads: Vec<(AvitoAd, AdFieldValues)>,
+) -> Vec<AdValidationResult> {
+	ads.into_iter()
+		.map(|(ad, fields)| AdValidationResult {
+			ad_id: ad.ad_id,
+			parsed_id: ad.parsed_id,
+			report: schema.validate_ad(&fields),
+		})
+		.collect()
+}
+
+// Whether any node of the Avito category tree has the slug
+pub fn category_tree_has_slug(tree: &serde_json::Value, slug: &str) -> bool {
+	match tree {
+		serde_json::Value::Object(node) => {
+			node.get("slug").and_then(|s| s.as_str()) == Some(slug)
+				|| node
+					.values()
+					.any(|value| category_tree_has_slug(value, slug))
+		}
+		serde_json::Value::Array(nodes) => {
+			nodes.iter().any(|node| category_tree_has_slug(node, slug))
+		}
+		_ => false,
+	}
+}
+
+// The requested slug, or the feed category when it is one of the Avito catalog. Imported
+// and synced feeds carry categories of their own, such as "IMPORT", that Avito doesn't know.
+pub async fn validation_slug(
+	data: &AppState,
+	account_id: Uuid,
+	requested: Option<String>,
+	feed_category: String,
+) -> Result<Result<String, HttpResponse>, ApiError> {
+	if let Some(avito_slug) = requested {
+		return Ok(Ok(avito_slug));
+	}
+
+	let tree = fetch_categories_tree(data, account_id, CacheMode::PreferCache).await?;
+	if category_tree_has_slug(&tree, &feed_category) {
+		return Ok(Ok(feed_category));
+	}
+
+	Ok(Err(HttpResponse::BadRequest().json(json!({
+		"status": "fail",
+		"message": format!(
+			"Feed category \"{}\" is not an Avito category, avito_slug is required",
+			feed_category
+		)
+	}))))
+}
+
+// POST check every ad that would be exported against the Avito category field schema
+#[actix_web::post(
+	"/avito/feeds/{feed_id}/validate",
+	wrap = "RequirePermission(Permission::ManageFeeds)"
+)]
+pub async fn validate_avito_feed(
+	path: web::Path<Uuid>,
+	body: web::Json<ValidateAvitoAdsRequest>,
+	data: web::Data<AppState>,
+	user: JwtMiddleware,
+) -> Result<HttpResponse, ApiError> {
+	let feed_id = path.into_inner();
+	let mut conn = data.db.get().unwrap();
+
+	let feed = match find_user_feed(&mut conn, feed_id, user.user_id) {
+		Ok(feed) => feed,
+		Err(e) => return Ok(feed_access_error(feed_id, e)),
+	};
+	// The feed's own account supplies the Avito token
+	let account_id = feed.account_id;
+	let avito_slug =
+		match validation_slug(&data, account_id, body.avito_slug.clone(), feed.category).await? {
+			Ok(avito_slug) => avito_slug,
+			Err(response) => return Ok(response),
+		};
+
+	let ads = load_feed_ads(&mut conn, feed_id, FeedAdStatus::Active)?;
+	let node_fields =
+		fetch_category_fields(&data, account_id, &avito_slug, CacheMode::PreferCache).await?;
+	let schema = CategorySchema::from_fields_json(&node_fields);
+
+	let results = validate_ads(&schema, ads);
+	let invalid = results.iter().filter(|r| !r.report.valid).count();
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": {
+			"feed_id": feed_id,
+			"avito_slug": avito_slug,
+			"summary": {
+				"ads_checked": results.len(),
+				"valid": results.len() - invalid,
+				"invalid": invalid,
+			},
+			"ads": results
+		}
+	})))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_category_tree_has_slug() {
+		let tree = json!({
+			"categories": [{
+				"id": 1,
+				"name": "Транспорт",
+				"slug": "transport",
+				"nested": [{ "id": 9, "name": "Шины, диски и колёса", "slug": "shiny_diski_i_kolesa" }]
+			}]
+		});
+
+		assert!(category_tree_has_slug(&tree, "transport"));
+		assert!(category_tree_has_slug(&tree, "shiny_diski_i_kolesa"));
+		assert!(!category_tree_has_slug(&tree, "IMPORT"));
+		assert!(!category_tree_has_slug(&tree, "avito_items"));
+	}
+}
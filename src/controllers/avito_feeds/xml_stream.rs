@@ -0,0 +1,520 @@
+use crate::config::Config;
+use crate::models::XmlAd;
+use actix_web::web::Bytes;
+use flate2::read::GzDecoder;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use tokio::sync::mpsc;
+use zip::ZipArchive;
+
+// Number of received chunks buffered between the network and the parser
+const BODY_CHANNEL_CAPACITY: usize = 8;
+
+#[derive(Debug, Clone, Copy)]
+pub struct XmlLimits {
+	// Maximum size of the uncompressed XML document, in bytes
+	pub max_document_size: u64,
+	// Maximum size of a single <Ad> element, in bytes
+	pub max_ad_size: u64,
+}
+
+impl XmlLimits {
+	pub fn from_config(config: &Config) -> Self {
+		Self {
+			max_document_size: config.xml_max_document_size,
+			max_ad_size: config.xml_max_ad_size,
+		}
+	}
+
+	#[cfg(test)]
+	pub fn unlimited() -> Self {
+		Self {
+			max_document_size: u64::MAX,
+			max_ad_size: u64::MAX,
+		}
+	}
+}
+
+// Blocking reader over chunks of a body that is received on the async runtime
+pub struct ChannelReader {
+	receiver: mpsc::Receiver<Result<Bytes, String>>,
+	chunk: Bytes,
+}
+
+impl Read for ChannelReader {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		while self.chunk.is_empty() {
+			match self.receiver.blocking_recv() {
+				Some(Ok(chunk)) => self.chunk = chunk,
+				Some(Err(e)) => return Err(io::Error::other(e)),
+				None => return Ok(0),
+			}
+		}
+
+		let len = buf.len().min(self.chunk.len());
+		buf[..len].copy_from_slice(&self.chunk[..len]);
+		self.chunk = self.chunk.slice(len..);
+		Ok(len)
+	}
+}
+
+// Chunks sent on the returned sender come out of the reader. The channel is bounded, so
+// sending waits while the parser is busy, and fails once the parser stopped.
+pub fn body_channel() -> (mpsc::Sender<Result<Bytes, String>>, ChannelReader) {
+	let (sender, receiver) = mpsc::channel(BODY_CHANNEL_CAPACITY);
+	(
+		sender,
+		ChannelReader {
+			receiver,
+			chunk: Bytes::new(),
+		},
+	)
+}
+
+// Download the response body in the background, handing chunks to the returned reader
+pub fn stream_response(mut response: reqwest::Response) -> ChannelReader {
+	let (sender, reader) = body_channel();
+
+	tokio::spawn(async move {
+		loop {
+			match response.chunk().await {
+				Ok(Some(chunk)) => {
+					// The parser stopped early, no need to download the rest
+					if sender.send(Ok(chunk)).await.is_err() {
+						break;
+					}
+				}
+				Ok(None) => break,
+				Err(e) => {
+					let _ = sender
+						.send(Err(format!("Failed to read response: {}", e)))
+						.await;
+					break;
+				}
+			}
+		}
+	});
+
+	reader
+}
+
+// Fails the read once more than `limit` bytes went through
+pub struct LimitedReader<R> {
+	inner: R,
+	limit: u64,
+	read: u64,
+}
+
+impl<R: Read> LimitedReader<R> {
+	pub fn new(inner: R, limit: u64) -> Self {
+		Self {
+			inner,
+			limit,
+			read: 0,
+		}
+	}
+}
+
+impl<R: Read> Read for LimitedReader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let len = self.inner.read(buf)?;
+		self.read += len as u64;
+		if self.read > self.limit {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("XML document exceeds the {} byte limit", self.limit),
+			));
+		}
+		Ok(len)
+	}
+}
+
+// Detect gzip and zip archives by their magic bytes and return a reader over the XML inside.
+// Plain and gzip feeds are decoded on the fly; a zip archive needs random access, so the
+// archive and its XML entry are buffered, both bounded by the document size limit.
+pub fn open_feed_source(
+	source: Box<dyn Read + Send>,
+	limits: &XmlLimits,
+) -> Result<Box<dyn BufRead + Send>, String> {
+	let mut source = source;
+	let mut magic = Vec::with_capacity(4);
+	(&mut source)
+		.take(4)
+		.read_to_end(&mut magic)
+		.map_err(|e| format!("Failed to read feed: {}", e))?;
+	let source = Cursor::new(magic.clone()).chain(source);
+
+	if magic.starts_with(&[0x1f, 0x8b]) {
+		return Ok(Box::new(BufReader::new(LimitedReader::new(
+			GzDecoder::new(source),
+			limits.max_document_size,
+		))));
+	}
+
+	if magic.starts_with(b"PK\x03\x04") {
+		let mut archive_bytes = Vec::new();
+		LimitedReader::new(source, limits.max_document_size)
+			.read_to_end(&mut archive_bytes)
+			.map_err(|e| format!("Failed to read zip archive: {}", e))?;
+
+		let mut archive = ZipArchive::new(Cursor::new(archive_bytes))
+			.map_err(|e| format!("Invalid zip archive: {}", e))?;
+
+		// Prefer the first .xml entry, fall back to the first file in the archive
+		let index = (0..archive.len())
+			.find(|&i| {
+				archive
+					.name_for_index(i)
+					.is_some_and(|name| name.to_lowercase().ends_with(".xml"))
+			})
+			.or_else(|| {
+				(0..archive.len()).find(|&i| {
+					archive
+						.name_for_index(i)
+						.is_some_and(|name| !name.ends_with('/'))
+				})
+			})
+			.ok_or_else(|| "Zip archive contains no files".to_string())?;
+
+		let mut xml = Vec::new();
+		let entry = archive
+			.by_index(index)
+			.map_err(|e| format!("Invalid zip entry: {}", e))?;
+		LimitedReader::new(entry, limits.max_document_size)
+			.read_to_end(&mut xml)
+			.map_err(|e| format!("Failed to unpack zip entry: {}", e))?;
+
+		return Ok(Box::new(Cursor::new(xml)));
+	}
+
+	Ok(Box::new(BufReader::new(LimitedReader::new(
+		source,
+		limits.max_document_size,
+	))))
+}
+
+// Pull parser that yields one <Ad> at a time, so only the ad being read is kept in memory
+pub struct XmlAdReader<R: BufRead> {
+	reader: Reader<R>,
+	limits: XmlLimits,
+	buf: Vec<u8>,
+	current_ad: Option<XmlAd>,
+	current_path: Vec<String>,
+	current_values: String,
+	in_ad: bool,
+	ad_start: u64,
+	delivery_buffer: Vec<String>,
+	images_buffer: Vec<String>,
+	ads_read: usize,
+}
+
+impl<R: BufRead> XmlAdReader<R> {
+	pub fn new(source: R, limits: XmlLimits) -> Self {
+		Self {
+			reader: Reader::from_reader(source),
+			limits,
+			buf: Vec::new(),
+			current_ad: None,
+			current_path: Vec::new(),
+			current_values: String::new(),
+			in_ad: false,
+			ad_start: 0,
+			delivery_buffer: Vec::new(),
+			images_buffer: Vec::new(),
+			ads_read: 0,
+		}
+	}
+
+	pub fn ads_read(&self) -> usize {
+		self.ads_read
+	}
+
+	// Bytes of uncompressed XML consumed so far
+	pub fn bytes_read(&self) -> u64 {
+		self.reader.buffer_position()
+	}
+
+	// Read up to `size` ads; an empty batch means the document is finished
+	pub fn next_batch(&mut self, size: usize) -> Result<Vec<XmlAd>, String> {
+		let mut batch = Vec::with_capacity(size);
+		while batch.len() < size {
+			match self.next_ad()? {
+				Some(ad) => batch.push(ad),
+				None => break,
+			}
+		}
+		Ok(batch)
+	}
+
+	pub fn next_ad(&mut self) -> Result<Option<XmlAd>, String> {
+		loop {
+			self.buf.clear();
+			let event = self
+				.reader
+				.read_event_into(&mut self.buf)
+				.map_err(|e| format!("XML parse error: {}", e))?;
+
+			if self.in_ad && self.reader.buffer_position() - self.ad_start > self.limits.max_ad_size
+			{
+				return Err(format!(
+					"Ad #{} exceeds the {} byte limit",
+					self.ads_read + 1,
+					self.limits.max_ad_size
+				));
+			}
+
+			match event {
+				Event::Start(e) => {
+					let name = std::str::from_utf8(e.name().as_ref())
+						.map_err(|e| format!("UTF-8 error: {}", e))?
+						.to_string();
+
+					self.current_path.push(name.clone());
+
+					if name == "Ad" {
+						self.in_ad = true;
+						self.ad_start = self.reader.buffer_position();
+						self.current_ad = Some(XmlAd {
+							id: String::new(),
+							fields: HashMap::new(),
+							lists: HashMap::new(),
+						});
+						self.delivery_buffer.clear();
+						self.images_buffer.clear();
+					}
+
+					self.current_values.clear();
+				}
+				Event::Text(e) => {
+					// Extract text content and resolve entities such as &amp;
+					let text = e
+						.unescape()
+						.map_err(|e| format!("XML unescape error: {}", e))?;
+
+					if self.in_ad && !text.trim().is_empty() {
+						self.current_values.push_str(&text);
+					}
+				}
+				Event::CData(e) => {
+					// Handle CDATA content (for Description)
+					let text = std::str::from_utf8(e.as_ref())
+						.map_err(|e| format!("UTF-8 error: {}", e))?;
+
+					if self.in_ad {
+						self.current_values.push_str(text);
+					}
+				}
+				Event::Empty(e) => {
+					let name = std::str::from_utf8(e.name().as_ref())
+						.map_err(|e| format!("UTF-8 error: {}", e))?
+						.to_string();
+
+					// Handle Image tags with attributes
+					if name == "Image" && self.current_path.iter().any(|tag| tag == "Images") {
+						// Extract the url attribute directly and add to images_buffer
+						for attr in e.attributes().flatten() {
+							if attr.key.as_ref() == b"url" {
+								if let Ok(url) = attr.unescape_value() {
+									self.images_buffer.push(url.to_string());
+								}
+							}
+						}
+					}
+					// Handle other empty elements (fallback)
+					else {
+						let text = std::str::from_utf8(e.as_ref())
+							.map_err(|e| format!("UTF-8 error: {}", e))?;
+
+						if self.in_ad {
+							self.current_values.push_str(text);
+						}
+					}
+				}
+				Event::End(e) => {
+					let name = std::str::from_utf8(e.name().as_ref())
+						.map_err(|e| format!("UTF-8 error: {}", e))?
+						.to_string();
+
+					if let Some(ad) = self.current_ad.as_mut() {
+						let values = self.current_values.trim();
+
+						// Special handling for Delivery - store the options as a list
+						if name == "Delivery" && !self.delivery_buffer.is_empty() {
+							ad.lists.insert(
+								"Delivery".to_string(),
+								std::mem::take(&mut self.delivery_buffer),
+							);
+						}
+						// Special handling for Option elements inside Delivery
+						else if name == "Option"
+							&& self.current_path.iter().any(|tag| tag == "Delivery")
+						{
+							if !values.is_empty() {
+								self.delivery_buffer.push(values.to_string());
+							}
+						} else if name == "Images" {
+							// Store image URLs when closing Images tag
+							if !self.images_buffer.is_empty() {
+								ad.lists.insert(
+									"Images".to_string(),
+									std::mem::take(&mut self.images_buffer),
+								);
+							}
+						} else if name == "Image"
+							&& self.current_path.iter().any(|tag| tag == "Images")
+						{
+							// For non-empty Image tags, add their text content to images_buffer
+							if !values.is_empty() {
+								self.images_buffer.push(values.to_string());
+							}
+						}
+						// Store other field values if not empty
+						else if !values.is_empty() && self.current_path.len() > 1 {
+							let field_name = self.current_path.last().unwrap().clone();
+							// Skip storing individual Image and Option elements as they're handled specially
+							if field_name != "Image" && field_name != "Option" {
+								ad.fields.insert(field_name, values.to_string());
+							}
+						}
+
+						// Special handling for Id field
+						if name == "Id" {
+							if let Some(id_value) = ad.fields.get("Id") {
+								ad.id = id_value.clone();
+							}
+						}
+					}
+
+					self.current_path.pop();
+					self.current_values.clear();
+
+					// If this is the end of an Ad element, hand it to the caller
+					if name == "Ad" {
+						self.in_ad = false;
+						if let Some(ad) = self.current_ad.take() {
+							self.ads_read += 1;
+							if !ad.id.is_empty() {
+								return Ok(Some(ad));
+							}
+							log::debug!("Skipping ad #{} without Id", self.ads_read);
+						}
+					}
+				}
+				Event::Eof => return Ok(None),
+				_ => (),
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use flate2::write::GzEncoder;
+	use flate2::Compression;
+	use std::io::Write;
+
+	const FEED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Ads formatVersion="3" target="Avito.ru">
+	<Ad>
+		<Id>tyre-001</Id>
+		<Title>Шины R17</Title>
+	</Ad>
+	<Ad>
+		<Title>Без Id</Title>
+	</Ad>
+	<Ad>
+		<Id>tyre-002</Id>
+		<Description><![CDATA[<p>Диски R16, комплект из четырёх штук</p>]]></Description>
+	</Ad>
+</Ads>"#;
+
+	fn read_ids(source: Box<dyn Read + Send>, limits: XmlLimits) -> Result<Vec<String>, String> {
+		let source = open_feed_source(source, &limits)?;
+		let mut reader = XmlAdReader::new(source, limits);
+		let mut ids = Vec::new();
+		loop {
+			let batch = reader.next_batch(1)?;
+			if batch.is_empty() {
+				break;
+			}
+			ids.extend(batch.into_iter().map(|ad| ad.id));
+		}
+		Ok(ids)
+	}
+
+	#[test]
+	fn test_open_feed_source_formats() {
+		let expected = vec!["tyre-001".to_string(), "tyre-002".to_string()];
+
+		let plain = Box::new(Cursor::new(FEED.as_bytes().to_vec()));
+		assert_eq!(read_ids(plain, XmlLimits::unlimited()).unwrap(), expected);
+
+		let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+		gzip.write_all(FEED.as_bytes()).unwrap();
+		let gzip = Box::new(Cursor::new(gzip.finish().unwrap()));
+		assert_eq!(read_ids(gzip, XmlLimits::unlimited()).unwrap(), expected);
+
+		let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+		let options = zip::write::SimpleFileOptions::default();
+		zip.start_file("readme.txt", options).unwrap();
+		zip.write_all(b"not a feed").unwrap();
+		zip.start_file("feed.xml", options).unwrap();
+		zip.write_all(FEED.as_bytes()).unwrap();
+		let zip = Box::new(Cursor::new(zip.finish().unwrap().into_inner()));
+		assert_eq!(read_ids(zip, XmlLimits::unlimited()).unwrap(), expected);
+
+		let broken = Box::new(Cursor::new(vec![0x1f, 0x8b, 0x00]));
+		assert!(read_ids(broken, XmlLimits::unlimited()).is_err());
+	}
+
+	#[test]
+	fn test_body_channel() {
+		// Chunks that split the document at arbitrary points
+		let (sender, reader) = body_channel();
+		let feeder = std::thread::spawn(move || {
+			for chunk in FEED.as_bytes().chunks(7) {
+				sender
+					.blocking_send(Ok(Bytes::copy_from_slice(chunk)))
+					.unwrap();
+			}
+		});
+		assert_eq!(
+			read_ids(Box::new(reader), XmlLimits::unlimited()).unwrap(),
+			vec!["tyre-001".to_string(), "tyre-002".to_string()]
+		);
+		feeder.join().unwrap();
+
+		// An error on the sending side fails the import
+		let (sender, reader) = body_channel();
+		let feeder = std::thread::spawn(move || {
+			let half = FEED.len() / 2;
+			let _ = sender.blocking_send(Ok(Bytes::copy_from_slice(&FEED.as_bytes()[..half])));
+			let _ = sender.blocking_send(Err("Uploaded file exceeds 10 bytes".to_string()));
+		});
+		let error = read_ids(Box::new(reader), XmlLimits::unlimited()).unwrap_err();
+		assert!(error.contains("Uploaded file exceeds"), "{}", error);
+		feeder.join().unwrap();
+	}
+
+	#[test]
+	fn test_xml_limits() {
+		let document_limit = XmlLimits {
+			max_document_size: 200,
+			max_ad_size: u64::MAX,
+		};
+		let plain = Box::new(Cursor::new(FEED.as_bytes().to_vec()));
+		let error = read_ids(plain, document_limit).unwrap_err();
+		assert!(error.contains("200 byte limit"), "{}", error);
+
+		let ad_limit = XmlLimits {
+			max_document_size: u64::MAX,
+			max_ad_size: 100,
+		};
+		let plain = Box::new(Cursor::new(FEED.as_bytes().to_vec()));
+		let error = read_ids(plain, ad_limit).unwrap_err();
+		assert!(error.starts_with("Ad #3 exceeds"), "{}", error);
+	}
+}
@@ -0,0 +1,61 @@
+use crate::controllers::avito_repricing::repricing_engine::{
+	apply_repricing_decision, find_user_decision,
+};
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::{ApiError, AvitoRepricingDecision};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
+use actix_web::{post, web, HttpResponse, Result};
+use chrono::Utc;
+use diesel::prelude::*;
+use serde_json::json;
+use uuid::Uuid;
+
+// POST approve a pending price change and send it to Avito
+#[post(
+	"/avito/repricing_decisions/{decision_id}/approve",
+	wrap = "RequirePermission(Permission::ManageRepricing)"
+)]
+pub async fn approve_repricing_decision(
+	path: web::Path<Uuid>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	use crate::schema::avito_repricing_decisions::dsl;
+
+	let decision_id = path.into_inner();
+	let decision = {
+		let mut conn = data.db.get().map_err(|e| ApiError::Other(e.to_string()))?;
+		find_user_decision(&mut conn, decision_id, user.user_id)?;
+
+		// Only a pending decision is applied, and only once
+		diesel::update(
+			dsl::avito_repricing_decisions
+				.find(decision_id)
+				.filter(dsl::status.eq("pending")),
+		)
+		.set((
+			dsl::status.eq("approved"),
+			dsl::decided_by.eq(user.user_id),
+			dsl::decided_ts.eq(Utc::now()),
+		))
+		.get_result::<AvitoRepricingDecision>(&mut conn)
+		.optional()?
+		.ok_or_else(|| {
+			ApiError::Conflict(format!("Repricing decision {} is not pending", decision_id))
+		})?
+	};
+
+	let decision = apply_repricing_decision(
+		&data.db,
+		&data.avito_tokens,
+		data.avito_api.as_ref(),
+		decision,
+	)
+	.await?;
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": { "decision": decision }
+	})))
+}
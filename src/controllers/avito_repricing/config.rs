@@ -0,0 +1,15 @@
+use crate::controllers::avito_repricing::{
+	approve_repricing_decision, delete_repricing_rule, get_repricing_decisions, get_repricing_rule,
+	reject_repricing_decision, run_repricing_rule_now, upsert_repricing_rule,
+};
+use actix_web::web;
+
+pub fn avito_repricing_routes(cfg: &mut web::ServiceConfig) {
+	cfg.service(run_repricing_rule_now::run_repricing_rule_now)
+		.service(upsert_repricing_rule::upsert_repricing_rule)
+		.service(get_repricing_rule::get_repricing_rule)
+		.service(delete_repricing_rule::delete_repricing_rule)
+		.service(get_repricing_decisions::get_repricing_decisions)
+		.service(approve_repricing_decision::approve_repricing_decision)
+		.service(reject_repricing_decision::reject_repricing_decision);
+}
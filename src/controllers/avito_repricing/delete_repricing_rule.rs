@@ -0,0 +1,38 @@
+use crate::controllers::avito_repricing::repricing_engine::find_user_ad;
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::ApiError;
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
+use actix_web::{delete, web, HttpResponse, Result};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+// Delete the repricing rule of an ad together with its decision history
+#[delete(
+	"/avito/ads/{ad_id}/repricing_rule",
+	wrap = "RequirePermission(Permission::ManageRepricing)"
+)]
+pub async fn delete_repricing_rule(
+	path: web::Path<Uuid>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	let ad_id = path.into_inner();
+	let mut conn = data.db.get().map_err(|e| ApiError::Other(e.to_string()))?;
+
+	find_user_ad(&mut conn, ad_id, user.user_id)?;
+
+	let deleted = diesel::delete(
+		crate::schema::avito_repricing_rules::table
+			.filter(crate::schema::avito_repricing_rules::ad_id.eq(ad_id)),
+	)
+	.execute(&mut conn)?;
+	if deleted == 0 {
+		return Err(ApiError::NotFound(format!(
+			"Ad {} has no repricing rule",
+			ad_id
+		)));
+	}
+
+	Ok(HttpResponse::NoContent().finish())
+}
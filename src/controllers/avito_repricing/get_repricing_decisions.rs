@@ -0,0 +1,64 @@
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::{
+	ApiError, AvitoRepricingDecision, GetRepricingDecisionsParams, PaginationResponse,
+	ResponseWithPagination,
+};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
+use actix_web::{get, web, HttpResponse, Result};
+use diesel::prelude::*;
+
+// GET repricing decisions on the user's ads, newest first; filter by status=pending
+// to get the approval queue, or by ad_id for the history of one ad
+#[get(
+	"/avito/repricing_decisions",
+	wrap = "RequirePermission(Permission::ManageRepricing)"
+)]
+pub async fn get_repricing_decisions(
+	params: web::Query<GetRepricingDecisionsParams>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	use crate::schema::{avito_accounts, avito_ads, avito_feeds, avito_repricing_decisions};
+
+	let params = params.into_inner();
+	let page = params.page.unwrap_or(1).max(1);
+	let limit = params.limit.unwrap_or(10).clamp(1, 100);
+	let offset = (page - 1) * limit;
+
+	let query = || {
+		let mut query = avito_repricing_decisions::table
+			.inner_join(
+				avito_ads::table.inner_join(avito_feeds::table.inner_join(avito_accounts::table)),
+			)
+			.filter(avito_accounts::user_id.eq(user.user_id))
+			.into_boxed();
+		if let Some(status) = &params.status {
+			query = query.filter(avito_repricing_decisions::status.eq(status.clone()));
+		}
+		if let Some(ad_id) = params.ad_id {
+			query = query.filter(avito_repricing_decisions::ad_id.eq(ad_id));
+		}
+		query
+	};
+
+	let mut conn = data.db.get().map_err(|e| ApiError::Other(e.to_string()))?;
+	let total = query().count().get_result::<i64>(&mut conn)?;
+	let decisions = query()
+		.order(avito_repricing_decisions::created_ts.desc())
+		.limit(limit as i64)
+		.offset(offset as i64)
+		.select(AvitoRepricingDecision::as_select())
+		.load::<AvitoRepricingDecision>(&mut conn)?;
+
+	Ok(HttpResponse::Ok().json(ResponseWithPagination {
+		status: "success".to_string(),
+		data: decisions,
+		pagination: PaginationResponse {
+			page,
+			limit,
+			total,
+			pages: ((total as f64) / (limit as f64)).ceil() as u32,
+		},
+	}))
+}
@@ -0,0 +1,35 @@
+use crate::controllers::avito_repricing::repricing_engine::find_user_ad;
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::{ApiError, AvitoRepricingRule};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
+use actix_web::{get, web, HttpResponse, Result};
+use diesel::prelude::*;
+use serde_json::json;
+use uuid::Uuid;
+
+#[get(
+	"/avito/ads/{ad_id}/repricing_rule",
+	wrap = "RequirePermission(Permission::ManageRepricing)"
+)]
+pub async fn get_repricing_rule(
+	path: web::Path<Uuid>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	let ad_id = path.into_inner();
+	let mut conn = data.db.get().map_err(|e| ApiError::Other(e.to_string()))?;
+
+	find_user_ad(&mut conn, ad_id, user.user_id)?;
+
+	let rule = crate::schema::avito_repricing_rules::table
+		.filter(crate::schema::avito_repricing_rules::ad_id.eq(ad_id))
+		.first::<AvitoRepricingRule>(&mut conn)
+		.optional()?
+		.ok_or_else(|| ApiError::NotFound(format!("Ad {} has no repricing rule", ad_id)))?;
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": { "rule": rule }
+	})))
+}
@@ -0,0 +1,17 @@
+pub mod approve_repricing_decision;
+pub mod config;
+pub mod delete_repricing_rule;
+pub mod get_repricing_decisions;
+pub mod get_repricing_rule;
+pub mod reject_repricing_decision;
+pub mod repricing_engine;
+pub mod repricing_rules;
+pub mod repricing_scheduler;
+pub mod run_repricing_rule_now;
+pub mod upsert_repricing_rule;
+
+use actix_web::web;
+
+pub fn avito_repricing_config(cfg: &mut web::ServiceConfig) {
+	cfg.configure(config::avito_repricing_routes);
+}
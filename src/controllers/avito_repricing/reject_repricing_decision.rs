@@ -0,0 +1,48 @@
+use crate::controllers::avito_repricing::repricing_engine::find_user_decision;
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::{ApiError, AvitoRepricingDecision};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
+use actix_web::{post, web, HttpResponse, Result};
+use chrono::Utc;
+use diesel::prelude::*;
+use serde_json::json;
+use uuid::Uuid;
+
+// POST reject a pending price change; the price stays as it is
+#[post(
+	"/avito/repricing_decisions/{decision_id}/reject",
+	wrap = "RequirePermission(Permission::ManageRepricing)"
+)]
+pub async fn reject_repricing_decision(
+	path: web::Path<Uuid>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	use crate::schema::avito_repricing_decisions::dsl;
+
+	let decision_id = path.into_inner();
+	let mut conn = data.db.get().map_err(|e| ApiError::Other(e.to_string()))?;
+	find_user_decision(&mut conn, decision_id, user.user_id)?;
+
+	let decision = diesel::update(
+		dsl::avito_repricing_decisions
+			.find(decision_id)
+			.filter(dsl::status.eq("pending")),
+	)
+	.set((
+		dsl::status.eq("rejected"),
+		dsl::decided_by.eq(user.user_id),
+		dsl::decided_ts.eq(Utc::now()),
+	))
+	.get_result::<AvitoRepricingDecision>(&mut conn)
+	.optional()?
+	.ok_or_else(|| {
+		ApiError::Conflict(format!("Repricing decision {} is not pending", decision_id))
+	})?;
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": { "decision": decision }
+	})))
+}
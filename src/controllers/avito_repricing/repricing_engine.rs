@@ -0,0 +1,242 @@
+use crate::controllers::avito_client::avito_api::AvitoApi;
+use crate::controllers::avito_client::price_update_input::parse_price;
+use crate::controllers::avito_client::token_manager::AvitoTokenManager;
+use crate::controllers::avito_feeds::export_avito_xml::load_ads_field_values;
+use crate::controllers::avito_feeds::import_avito_xml::{replace_ad_field_values, ImportClock};
+use crate::controllers::avito_repricing::repricing_rules::{
+	competitor_prices, evaluate_rule, RepricingOutcome,
+};
+use crate::models::{
+	ApiError, AvitoAd, AvitoAnalyticsAd, AvitoRepricingDecision, AvitoRepricingRule,
+	CreateAvitoRepricingDecision,
+};
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use uuid::Uuid;
+
+// The ad and its account, if the ad is in one of the user's feeds
+pub fn find_user_ad(
+	conn: &mut PgConnection,
+	ad_id: Uuid,
+	user_id: Uuid,
+) -> Result<(AvitoAd, Uuid), ApiError> {
+	crate::schema::avito_ads::table
+		.inner_join(
+			crate::schema::avito_feeds::table.inner_join(crate::schema::avito_accounts::table),
+		)
+		.filter(crate::schema::avito_ads::ad_id.eq(ad_id))
+		.filter(crate::schema::avito_accounts::user_id.eq(user_id))
+		.select((
+			AvitoAd::as_select(),
+			crate::schema::avito_accounts::account_id,
+		))
+		.first::<(AvitoAd, Uuid)>(conn)
+		.optional()?
+		.ok_or_else(|| ApiError::NotFound(format!("Ad {} not found", ad_id)))
+}
+
+// The decision, if it was made for one of the user's ads
+pub fn find_user_decision(
+	conn: &mut PgConnection,
+	decision_id: Uuid,
+	user_id: Uuid,
+) -> Result<AvitoRepricingDecision, ApiError> {
+	crate::schema::avito_repricing_decisions::table
+		.inner_join(crate::schema::avito_ads::table.inner_join(
+			crate::schema::avito_feeds::table.inner_join(crate::schema::avito_accounts::table),
+		))
+		.filter(crate::schema::avito_repricing_decisions::decision_id.eq(decision_id))
+		.filter(crate::schema::avito_accounts::user_id.eq(user_id))
+		.select(AvitoRepricingDecision::as_select())
+		.first(conn)
+		.optional()?
+		.ok_or_else(|| ApiError::NotFound(format!("Repricing decision {} not found", decision_id)))
+}
+
+// Ads found by one run of a search request; the scraper stamps them over the course of the run
+const SEARCH_RUN_WINDOW_HOURS: i64 = 1;
+
+// The ads of the latest run of a search request, or every ad when none has a run date
+pub fn load_latest_run(
+	conn: &mut PgConnection,
+	avito_request_id: Uuid,
+) -> Result<Vec<AvitoAnalyticsAd>, diesel::result::Error> {
+	use crate::schema::avito_analytics_ads::dsl;
+
+	let latest_run = dsl::avito_analytics_ads
+		.filter(dsl::avito_request_id.eq(avito_request_id))
+		.select(diesel::dsl::max(dsl::run_date))
+		.first::<Option<chrono::DateTime<Utc>>>(conn)?;
+
+	let mut query = dsl::avito_analytics_ads
+		.filter(dsl::avito_request_id.eq(avito_request_id))
+		.into_boxed();
+	if let Some(latest_run) = latest_run {
+		query =
+			query.filter(dsl::run_date.ge(latest_run - Duration::hours(SEARCH_RUN_WINDOW_HOURS)));
+	}
+	query.load(conn)
+}
+
+// Evaluate a rule against the latest competitor prices and record the decision.
+// Without approval the new price is sent to Avito right away.
+pub async fn run_repricing_rule(
+	db_pool: &Pool<ConnectionManager<PgConnection>>,
+	tokens: &AvitoTokenManager,
+	api: &dyn AvitoApi,
+	rule: &AvitoRepricingRule,
+) -> Result<AvitoRepricingDecision, ApiError> {
+	let mut conn = db_pool.get().map_err(|e| ApiError::Other(e.to_string()))?;
+
+	let ad = crate::schema::avito_ads::table
+		.find(rule.ad_id)
+		.select(AvitoAd::as_select())
+		.first::<AvitoAd>(&mut conn)?;
+	let avito_item_id = ad.avito_ad_id.clone();
+	let current_price = ad_price(&mut conn, ad)?;
+
+	let competitors = load_latest_run(&mut conn, rule.avito_request_id)?;
+	let prices = competitor_prices(
+		&competitors,
+		avito_item_id.as_deref(),
+		rule.exclude_promoted,
+	);
+	let evaluation = evaluate_rule(rule, current_price, &prices);
+
+	let outcome = match (&avito_item_id, evaluation.outcome) {
+		(None, _) => RepricingOutcome::Skip("The ad is not published on Avito".to_string()),
+		(Some(_), outcome) => outcome,
+	};
+	let (status, proposed_price, reason) = match outcome {
+		RepricingOutcome::Skip(reason) => ("skipped", None, Some(reason)),
+		RepricingOutcome::Propose(price) if rule.require_approval => ("pending", Some(price), None),
+		RepricingOutcome::Propose(price) => ("approved", Some(price), None),
+	};
+
+	let decision = conn.transaction(|conn| {
+		// A newer proposal replaces the one still waiting for approval
+		if status == "pending" {
+			diesel::update(
+				crate::schema::avito_repricing_decisions::table
+					.filter(crate::schema::avito_repricing_decisions::rule_id.eq(rule.rule_id))
+					.filter(crate::schema::avito_repricing_decisions::status.eq("pending")),
+			)
+			.set((
+				crate::schema::avito_repricing_decisions::status.eq("superseded"),
+				crate::schema::avito_repricing_decisions::decided_ts.eq(Utc::now()),
+			))
+			.execute(conn)?;
+		}
+
+		diesel::insert_into(crate::schema::avito_repricing_decisions::table)
+			.values(CreateAvitoRepricingDecision {
+				rule_id: rule.rule_id,
+				ad_id: rule.ad_id,
+				avito_item_id,
+				competitors_count: evaluation.competitors_count as i32,
+				reference_price: evaluation.reference_price,
+				current_price,
+				proposed_price,
+				status: status.to_string(),
+				reason,
+				decided_ts: (status != "pending").then(Utc::now),
+			})
+			.get_result::<AvitoRepricingDecision>(conn)
+	})?;
+	drop(conn);
+
+	if decision.status == "approved" {
+		return apply_repricing_decision(db_pool, tokens, api, decision).await;
+	}
+	Ok(decision)
+}
+
+// Send an approved price to Avito and store it on the local ad. A failure from Avito is
+// recorded on the decision, not returned.
+pub async fn apply_repricing_decision(
+	db_pool: &Pool<ConnectionManager<PgConnection>>,
+	tokens: &AvitoTokenManager,
+	api: &dyn AvitoApi,
+	decision: AvitoRepricingDecision,
+) -> Result<AvitoRepricingDecision, ApiError> {
+	use crate::schema::avito_repricing_decisions::dsl;
+
+	let (Some(item_id), Some(price)) = (decision.avito_item_id.clone(), decision.proposed_price)
+	else {
+		return Err(ApiError::Other(format!(
+			"Repricing decision {} has no price to apply",
+			decision.decision_id
+		)));
+	};
+
+	let account_id = {
+		let mut conn = db_pool.get().map_err(|e| ApiError::Other(e.to_string()))?;
+		crate::schema::avito_ads::table
+			.inner_join(crate::schema::avito_feeds::table)
+			.filter(crate::schema::avito_ads::ad_id.eq(decision.ad_id))
+			.select(crate::schema::avito_feeds::account_id)
+			.first::<Uuid>(&mut conn)?
+	};
+
+	let item_id = item_id.as_str();
+	let result = tokens
+		.with_token(db_pool, account_id, |avito_token| async move {
+			api.update_price(&avito_token, item_id, price).await
+		})
+		.await;
+
+	let mut conn = db_pool.get().map_err(|e| ApiError::Other(e.to_string()))?;
+	let decision = match result {
+		Ok(response) if response.result.success => conn.transaction(|conn| {
+			set_ad_price(conn, decision.ad_id, price)?;
+			diesel::update(dsl::avito_repricing_decisions.find(decision.decision_id))
+				.set((dsl::status.eq("applied"), dsl::decided_ts.eq(Utc::now())))
+				.get_result::<AvitoRepricingDecision>(conn)
+		})?,
+		result => {
+			let error = match result {
+				Err(e) => e.to_string(),
+				Ok(_) => "Avito did not accept the price".to_string(),
+			};
+			log::warn!(
+				"Failed to apply repricing decision {}: {}",
+				decision.decision_id,
+				error
+			);
+			diesel::update(dsl::avito_repricing_decisions.find(decision.decision_id))
+				.set((
+					dsl::status.eq("failed"),
+					dsl::reason.eq(error),
+					dsl::decided_ts.eq(Utc::now()),
+				))
+				.get_result::<AvitoRepricingDecision>(&mut conn)?
+		}
+	};
+
+	Ok(decision)
+}
+
+// Current price of the ad, as last synced or imported
+fn ad_price(conn: &mut PgConnection, ad: AvitoAd) -> Result<Option<f64>, diesel::result::Error> {
+	Ok(load_ads_field_values(conn, vec![ad])?
+		.into_iter()
+		.flat_map(|(_, fields)| fields)
+		.find(|(tag, _)| tag == "Price")
+		.and_then(|(_, values)| values.first().and_then(|value| parse_price(value))))
+}
+
+// Replace the Price field, so the next run starts from the price Avito now shows
+fn set_ad_price(
+	conn: &mut PgConnection,
+	ad_id: Uuid,
+	price: f64,
+) -> Result<(), diesel::result::Error> {
+	replace_ad_field_values(
+		conn,
+		ad_id,
+		&["Price"],
+		vec![("Price".to_string(), vec![price.to_string()])],
+		&mut ImportClock::new(),
+	)
+}
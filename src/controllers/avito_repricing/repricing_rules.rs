@@ -0,0 +1,226 @@
+use crate::controllers::avito_client::price_update_input::parse_price;
+use crate::models::{AvitoAnalyticsAd, AvitoRepricingRule};
+
+pub const REPRICING_STRATEGIES: [&str; 3] = ["min", "median", "average"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RepricingOutcome {
+	Propose(f64),
+	Skip(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepricingEvaluation {
+	pub competitors_count: usize,
+	pub reference_price: Option<f64>,
+	pub outcome: RepricingOutcome,
+}
+
+// The scraper fills my_ad for the user's own listings; anything but an explicit "no" counts
+fn is_own_ad(my_ad: Option<&str>) -> bool {
+	my_ad
+		.map(|value| value.trim().to_lowercase())
+		.is_some_and(|value| !matches!(value.as_str(), "" | "0" | "false" | "no" | "нет"))
+}
+
+// Prices of the competitors among the ads of a search run. The ad itself, the user's
+// other ads and, if asked, promoted ads are left out.
+pub fn competitor_prices(
+	ads: &[AvitoAnalyticsAd],
+	avito_item_id: Option<&str>,
+	exclude_promoted: bool,
+) -> Vec<f64> {
+	ads.iter()
+		.filter(|ad| Some(ad.avito_ad_id.as_str()) != avito_item_id)
+		.filter(|ad| !is_own_ad(ad.my_ad.as_deref()))
+		.filter(|ad| {
+			!exclude_promoted
+				|| ad
+					.promotion
+					.as_deref()
+					.is_none_or(|promotion| promotion.trim().is_empty())
+		})
+		.filter_map(|ad| ad.price.as_deref().and_then(parse_price))
+		.filter(|price| *price > 0.0)
+		.collect()
+}
+
+pub fn reference_price(strategy: &str, prices: &[f64]) -> Option<f64> {
+	if prices.is_empty() {
+		return None;
+	}
+
+	let mut sorted = prices.to_vec();
+	sorted.sort_by(|a, b| a.total_cmp(b));
+	match strategy {
+		"min" => sorted.first().copied(),
+		"average" => Some(sorted.iter().sum::<f64>() / sorted.len() as f64),
+		_ => {
+			let middle = sorted.len() / 2;
+			Some(if sorted.len().is_multiple_of(2) {
+				(sorted[middle - 1] + sorted[middle]) / 2.0
+			} else {
+				sorted[middle]
+			})
+		}
+	}
+}
+
+// Work out the price the rule asks for. The offset is applied to the reference price, then
+// the step is limited and the result kept within floor and ceiling, in whole rubles.
+pub fn evaluate_rule(
+	rule: &AvitoRepricingRule,
+	current_price: Option<f64>,
+	prices: &[f64],
+) -> RepricingEvaluation {
+	let competitors_count = prices.len();
+	let reference = reference_price(&rule.strategy, prices);
+
+	let skip = |reason: String| RepricingEvaluation {
+		competitors_count,
+		reference_price: reference,
+		outcome: RepricingOutcome::Skip(reason),
+	};
+
+	if competitors_count < rule.min_competitors.max(1) as usize {
+		return skip(format!(
+			"Found {} competitor prices, the rule needs at least {}",
+			competitors_count,
+			rule.min_competitors.max(1)
+		));
+	}
+	let Some(reference) = reference else {
+		return skip("No competitor prices".to_string());
+	};
+
+	let mut target = reference * (1.0 + rule.offset_percent / 100.0) + rule.offset_amount;
+	if let (Some(current), Some(max_change)) = (current_price, rule.max_change_percent) {
+		let step = current * max_change.abs() / 100.0;
+		target = target.clamp(current - step, current + step);
+	}
+	let mut target = target.round();
+	if let Some(ceiling) = rule.ceiling_price {
+		target = target.min(ceiling.floor());
+	}
+	target = target.max(rule.floor_price.ceil());
+
+	if current_price == Some(target) {
+		return skip(format!("The price is already {}", target));
+	}
+
+	RepricingEvaluation {
+		competitors_count,
+		reference_price: Some(reference),
+		outcome: RepricingOutcome::Propose(target),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use chrono::{TimeZone, Utc};
+	use uuid::Uuid;
+
+	fn rule(strategy: &str, offset_percent: f64, floor_price: f64) -> AvitoRepricingRule {
+		AvitoRepricingRule {
+			rule_id: Uuid::nil(),
+			ad_id: Uuid::nil(),
+			avito_request_id: Uuid::nil(),
+			strategy: strategy.to_string(),
+			offset_percent,
+			offset_amount: 0.0,
+			floor_price,
+			ceiling_price: None,
+			max_change_percent: None,
+			min_competitors: 3,
+			exclude_promoted: true,
+			require_approval: true,
+			interval_minutes: 60,
+			enabled: true,
+			last_run_ts: None,
+			next_run_ts: None,
+			created_ts: Utc::now(),
+			updated_ts: Utc::now(),
+		}
+	}
+
+	fn ad(avito_ad_id: &str, price: &str, promotion: &str) -> AvitoAnalyticsAd {
+		AvitoAnalyticsAd {
+			ad_id: Uuid::new_v4(),
+			my_ad: None,
+			run_date: Some(Utc.with_ymd_and_hms(2026, 10, 18, 9, 0, 0).unwrap()),
+			city_query: None,
+			search_query: None,
+			position: None,
+			views: None,
+			views_today: None,
+			promotion: Some(promotion.to_string()),
+			delivery: None,
+			ad_date: None,
+			avito_ad_id: avito_ad_id.to_string(),
+			title: None,
+			price: Some(price.to_string()),
+			link: None,
+			categories: None,
+			seller_id: None,
+			seller_name: None,
+			seller_type: None,
+			register_date: None,
+			answer_time: None,
+			rating: None,
+			reviews_count: None,
+			ads_count: None,
+			closed_ads_count: None,
+			photo_count: None,
+			address: None,
+			description: None,
+			avito_request_id: None,
+			created_ts: None,
+		}
+	}
+
+	#[test]
+	fn test_evaluate_rule() {
+		let mut own = ad("5", "1 000 ₽", "");
+		own.my_ad = Some("да".to_string());
+		let ads = vec![
+			ad("1", "10 000 ₽", ""),
+			ad("2", "12 000 ₽", ""),
+			ad("3", "9 000 ₽", "XL"),
+			ad("4", "11 000 ₽", ""),
+			ad("42", "15 000 ₽", ""),
+			own,
+		];
+		let prices = competitor_prices(&ads, Some("42"), true);
+		assert_eq!(prices, vec![10000.0, 12000.0, 11000.0]);
+		assert_eq!(reference_price("min", &prices), Some(10000.0));
+		assert_eq!(reference_price("average", &prices), Some(11000.0));
+		assert_eq!(reference_price("median", &[1.0, 4.0, 2.0, 3.0]), Some(2.5));
+
+		// 3% below the median of 11 000
+		let evaluation = evaluate_rule(&rule("median", -3.0, 5000.0), Some(15000.0), &prices);
+		assert_eq!(evaluation.reference_price, Some(11000.0));
+		assert_eq!(evaluation.outcome, RepricingOutcome::Propose(10670.0));
+
+		// Never below the floor
+		let evaluation = evaluate_rule(&rule("median", -3.0, 10800.0), Some(15000.0), &prices);
+		assert_eq!(evaluation.outcome, RepricingOutcome::Propose(10800.0));
+
+		// The step from the current price is limited
+		let mut limited = rule("median", -3.0, 5000.0);
+		limited.max_change_percent = Some(10.0);
+		let evaluation = evaluate_rule(&limited, Some(15000.0), &prices);
+		assert_eq!(evaluation.outcome, RepricingOutcome::Propose(13500.0));
+
+		let evaluation = evaluate_rule(&rule("median", -3.0, 5000.0), Some(10670.0), &prices);
+		assert!(matches!(evaluation.outcome, RepricingOutcome::Skip(_)));
+
+		let evaluation = evaluate_rule(&rule("median", 0.0, 5000.0), None, &prices[..2]);
+		assert_eq!(
+			evaluation.outcome,
+			RepricingOutcome::Skip(
+				"Found 2 competitor prices, the rule needs at least 3".to_string()
+			)
+		);
+	}
+}
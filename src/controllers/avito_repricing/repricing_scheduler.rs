@@ -0,0 +1,73 @@
+use crate::controllers::avito_client::avito_api::AvitoApi;
+use crate::controllers::avito_client::token_manager::AvitoTokenManager;
+use crate::controllers::avito_repricing::repricing_engine::run_repricing_rule;
+use crate::models::AvitoRepricingRule;
+use chrono::{Duration as ChronoDuration, Utc};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+
+// How often the scheduler looks for due repricing rules
+const SCHEDULER_TICK_SECS: u64 = 60;
+
+pub async fn start_repricing_scheduler(
+	db_pool: Pool<ConnectionManager<PgConnection>>,
+	tokens: AvitoTokenManager,
+	api: Arc<dyn AvitoApi>,
+) -> ! {
+	loop {
+		match claim_due_rules(&db_pool) {
+			Ok(rules) => {
+				for rule in rules {
+					match run_repricing_rule(&db_pool, &tokens, api.as_ref(), &rule).await {
+						Ok(decision) => log::info!(
+							"Repricing rule {} of ad {}: {}",
+							rule.rule_id,
+							rule.ad_id,
+							decision.status
+						),
+						Err(e) => log::error!("Repricing rule {} failed: {}", rule.rule_id, e),
+					}
+				}
+			}
+			Err(e) => log::error!("Failed to load due repricing rules: {}", e),
+		}
+
+		sleep(Duration::from_secs(SCHEDULER_TICK_SECS)).await;
+	}
+}
+
+// Pick the enabled rules that are due and move them to their next run right away,
+// so a second server instance does not evaluate the same rule twice
+fn claim_due_rules(
+	db_pool: &Pool<ConnectionManager<PgConnection>>,
+) -> Result<Vec<AvitoRepricingRule>, String> {
+	use crate::schema::avito_repricing_rules::dsl;
+
+	let mut conn = db_pool.get().map_err(|e| e.to_string())?;
+	let now = Utc::now();
+
+	conn.transaction::<_, diesel::result::Error, _>(|conn| {
+		let due = dsl::avito_repricing_rules
+			.filter(dsl::enabled.eq(true))
+			.filter(dsl::next_run_ts.is_null().or(dsl::next_run_ts.le(now)))
+			.for_update()
+			.skip_locked()
+			.load::<AvitoRepricingRule>(conn)?;
+
+		for rule in &due {
+			diesel::update(dsl::avito_repricing_rules.find(rule.rule_id))
+				.set((
+					dsl::last_run_ts.eq(Some(now)),
+					dsl::next_run_ts.eq(Some(
+						now + ChronoDuration::minutes(rule.interval_minutes.max(1) as i64),
+					)),
+				))
+				.execute(conn)?;
+		}
+
+		Ok(due)
+	})
+	.map_err(|e| e.to_string())
+}
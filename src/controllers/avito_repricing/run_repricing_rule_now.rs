@@ -0,0 +1,39 @@
+use crate::controllers::avito_repricing::repricing_engine::{find_user_ad, run_repricing_rule};
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::{ApiError, AvitoRepricingRule};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
+use actix_web::{post, web, HttpResponse, Result};
+use diesel::prelude::*;
+use serde_json::json;
+use uuid::Uuid;
+
+// POST evaluate the ad's repricing rule now instead of waiting for the scheduler
+#[post(
+	"/avito/ads/{ad_id}/repricing_rule/run",
+	wrap = "RequirePermission(Permission::ManageRepricing)"
+)]
+pub async fn run_repricing_rule_now(
+	path: web::Path<Uuid>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	let ad_id = path.into_inner();
+	let rule = {
+		let mut conn = data.db.get().map_err(|e| ApiError::Other(e.to_string()))?;
+		find_user_ad(&mut conn, ad_id, user.user_id)?;
+		crate::schema::avito_repricing_rules::table
+			.filter(crate::schema::avito_repricing_rules::ad_id.eq(ad_id))
+			.first::<AvitoRepricingRule>(&mut conn)
+			.optional()?
+			.ok_or_else(|| ApiError::NotFound(format!("Ad {} has no repricing rule", ad_id)))?
+	};
+
+	let decision =
+		run_repricing_rule(&data.db, &data.avito_tokens, data.avito_api.as_ref(), &rule).await?;
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": { "decision": decision }
+	})))
+}
@@ -0,0 +1,109 @@
+use crate::controllers::avito_repricing::repricing_engine::find_user_ad;
+use crate::controllers::avito_repricing::repricing_rules::REPRICING_STRATEGIES;
+use crate::jwt_auth::JwtMiddleware;
+use crate::models::{
+	ApiError, AvitoRepricingRule, AvitoRepricingRuleRequest, UpsertAvitoRepricingRule,
+};
+use crate::permissions::{Permission, RequirePermission};
+use crate::AppState;
+use actix_web::{put, web, HttpResponse, Result};
+use chrono::Utc;
+use diesel::prelude::*;
+use serde_json::json;
+use uuid::Uuid;
+
+// Create or replace the repricing rule of an ad; the rule first runs on the next scheduler tick
+#[put(
+	"/avito/ads/{ad_id}/repricing_rule",
+	wrap = "RequirePermission(Permission::ManageRepricing)"
+)]
+pub async fn upsert_repricing_rule(
+	path: web::Path<Uuid>,
+	body: web::Json<AvitoRepricingRuleRequest>,
+	user: JwtMiddleware,
+	data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+	let ad_id = path.into_inner();
+	let body = body.into_inner();
+	let mut conn = data.db.get().map_err(|e| ApiError::Other(e.to_string()))?;
+
+	find_user_ad(&mut conn, ad_id, user.user_id)?;
+
+	let request_found = crate::schema::avito_requests::table
+		.find(body.avito_request_id)
+		.filter(crate::schema::avito_requests::user_id.eq(user.user_id))
+		.count()
+		.get_result::<i64>(&mut conn)?
+		> 0;
+	if !request_found {
+		return Err(ApiError::NotFound(format!(
+			"Avito request {} not found",
+			body.avito_request_id
+		)));
+	}
+
+	let strategy = body.strategy.unwrap_or_else(|| "median".to_string());
+	let interval_minutes = body.interval_minutes.unwrap_or(60);
+	let min_competitors = body.min_competitors.unwrap_or(3);
+
+	let invalid = if !REPRICING_STRATEGIES.contains(&strategy.as_str()) {
+		Some(format!(
+			"strategy must be one of {}",
+			REPRICING_STRATEGIES.join(", ")
+		))
+	} else if !(body.floor_price.is_finite() && body.floor_price > 0.0) {
+		Some("floor_price must be greater than zero".to_string())
+	} else if body
+		.ceiling_price
+		.is_some_and(|ceiling| !ceiling.is_finite() || ceiling < body.floor_price)
+	{
+		Some("ceiling_price must not be below floor_price".to_string())
+	} else if body
+		.max_change_percent
+		.is_some_and(|percent| !percent.is_finite() || percent <= 0.0)
+	{
+		Some("max_change_percent must be greater than zero".to_string())
+	} else if interval_minutes < 1 {
+		Some("interval_minutes must be positive".to_string())
+	} else if min_competitors < 1 {
+		Some("min_competitors must be positive".to_string())
+	} else {
+		None
+	};
+	if let Some(message) = invalid {
+		return Ok(HttpResponse::BadRequest().json(json!({
+			"status": "fail",
+			"message": message
+		})));
+	}
+
+	let rule = UpsertAvitoRepricingRule {
+		ad_id,
+		avito_request_id: body.avito_request_id,
+		strategy,
+		offset_percent: body.offset_percent.unwrap_or(0.0),
+		offset_amount: body.offset_amount.unwrap_or(0.0),
+		floor_price: body.floor_price,
+		ceiling_price: body.ceiling_price,
+		max_change_percent: body.max_change_percent,
+		min_competitors,
+		exclude_promoted: body.exclude_promoted.unwrap_or(true),
+		require_approval: body.require_approval.unwrap_or(true),
+		interval_minutes,
+		enabled: body.enabled.unwrap_or(true),
+		next_run_ts: None,
+		updated_ts: Utc::now(),
+	};
+
+	let rule = diesel::insert_into(crate::schema::avito_repricing_rules::table)
+		.values(&rule)
+		.on_conflict(crate::schema::avito_repricing_rules::ad_id)
+		.do_update()
+		.set(&rule)
+		.get_result::<AvitoRepricingRule>(&mut conn)?;
+
+	Ok(HttpResponse::Ok().json(json!({
+		"status": "success",
+		"data": { "rule": rule }
+	})))
+}
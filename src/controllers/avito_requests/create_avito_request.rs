@@ -1,5 +1,6 @@
 use crate::controllers::rabbitmq_publisher::publisher::publish_avito_request;
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	models::{AvitoRequest, CreateAvitoRequestJson, CreateAvitoRequestWithUserId},
 	AppState,
@@ -26,7 +27,10 @@ fn filter_add_avito_request_record(avito_request: &AvitoRequest) -> serde_json::
 }
 
 // Create avito request
-#[actix_web::post("/avito_requests")]
+#[actix_web::post(
+	"/avito_requests",
+	wrap = "RequirePermission(Permission::ManageRequests)"
+)]
 pub async fn create_avito_request(
 	data: web::Data<AppState>,
 	new_request: web::Json<CreateAvitoRequestJson>,
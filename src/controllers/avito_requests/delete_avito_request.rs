@@ -1,10 +1,14 @@
+use crate::permissions::{Permission, RequirePermission};
 use crate::{models::AvitoRequest, AppState};
 use actix_web::{web, HttpResponse, Result};
 use diesel::prelude::*;
 use serde_json::json;
 use uuid::Uuid;
 
-#[actix_web::delete("/avito_requests/{id}")]
+#[actix_web::delete(
+	"/avito_requests/{id}",
+	wrap = "RequirePermission(Permission::ManageRequests)"
+)]
 pub async fn delete_avito_request(
 	path: web::Path<Uuid>,
 	data: web::Data<AppState>,
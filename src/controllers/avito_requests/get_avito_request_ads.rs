@@ -1,4 +1,5 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	models::{AvitoAnalyticsAd, PaginationParams, PaginationResponse, ResponseWithPagination},
 	AppState,
@@ -10,7 +11,10 @@ use serde_json::json;
 use uuid::Uuid;
 
 // GET avito request with ads
-#[actix_web::get("/avito_requests/{avito_request_id}/ads")]
+#[actix_web::get(
+	"/avito_requests/{avito_request_id}/ads",
+	wrap = "RequirePermission(Permission::ManageRequests)"
+)]
 pub async fn get_avito_request_ads(
 	path: web::Path<Uuid>,
 	pagination: web::Query<PaginationParams>,
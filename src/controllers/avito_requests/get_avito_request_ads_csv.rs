@@ -1,4 +1,5 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::utils::transliterate::Translit;
 use crate::{models::AvitoAnalyticsAd, AppState};
 use actix_web::{web, HttpResponse, Result};
@@ -8,7 +9,10 @@ use std::io::Cursor;
 use uuid::Uuid;
 
 // GET avito request with ads in a csv file
-#[actix_web::get("/avito_requests/{avito_request_id}/ads/csv")]
+#[actix_web::get(
+	"/avito_requests/{avito_request_id}/ads/csv",
+	wrap = "RequirePermission(Permission::ManageRequests)"
+)]
 pub async fn get_avito_request_ads_csv(
 	path: web::Path<Uuid>,
 	data: web::Data<AppState>,
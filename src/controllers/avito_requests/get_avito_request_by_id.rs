@@ -1,4 +1,5 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	models::{AvitoRequest, AvitoRequestData, AvitoRequestResponse},
 	AppState,
@@ -9,7 +10,10 @@ use serde_json::json;
 use uuid::Uuid;
 
 // Get my account avito requests
-#[actix_web::get("/avito_requests/{id}")]
+#[actix_web::get(
+	"/avito_requests/{id}",
+	wrap = "RequirePermission(Permission::ManageRequests)"
+)]
 pub async fn get_avito_request_by_id(
 	path: web::Path<Uuid>,
 	data: web::Data<AppState>,
@@ -1,4 +1,5 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	models::{AvitoRequest, PaginationParams, PaginationResponse, ResponseWithPagination},
 	AppState,
@@ -9,7 +10,10 @@ use diesel::query_dsl::methods::{LimitDsl, OffsetDsl};
 use serde_json::json;
 
 // GET all avito requests by specific user_id
-#[actix_web::get("/avito_requests")]
+#[actix_web::get(
+	"/avito_requests",
+	wrap = "RequirePermission(Permission::ManageRequests)"
+)]
 pub async fn get_avito_requests_by_user(
 	data: web::Data<AppState>,
 	pagination: web::Query<PaginationParams>,
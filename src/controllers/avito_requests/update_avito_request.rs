@@ -1,3 +1,4 @@
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	models::{AvitoRequest, AvitoRequestData, AvitoRequestResponse, UpdateAvitoRequest},
 	AppState,
@@ -7,7 +8,10 @@ use diesel::prelude::*;
 use serde_json::json;
 use uuid::Uuid;
 
-#[actix_web::patch("/avito_requests/{id}")]
+#[actix_web::patch(
+	"/avito_requests/{id}",
+	wrap = "RequirePermission(Permission::ManageRequests)"
+)]
 pub async fn update_avito_request(
 	path: web::Path<Uuid>,
 	data: web::Data<AppState>,
@@ -2,11 +2,14 @@ use crate::controllers::auth;
 use crate::controllers::avito_accounts;
 use crate::controllers::avito_ads;
 use crate::controllers::avito_ai_processing;
+use crate::controllers::avito_autocatalog;
 use crate::controllers::avito_client;
 use crate::controllers::avito_editor;
 use crate::controllers::avito_feeds;
+use crate::controllers::avito_repricing;
 use crate::controllers::avito_requests;
 use crate::controllers::users;
+use crate::controllers::websocket;
 use actix_web::web;
 
 pub fn config(conf: &mut web::ServiceConfig) {
@@ -19,7 +22,10 @@ pub fn config(conf: &mut web::ServiceConfig) {
 		.configure(avito_feeds::avito_feeds_config)
 		.configure(avito_requests::avito_requests_config)
 		.configure(avito_client::avito_client_config)
-		.configure(avito_editor::avito_editor_config);
+		.configure(avito_repricing::avito_repricing_config)
+		.configure(avito_autocatalog::avito_autocatalog_config)
+		.configure(avito_editor::avito_editor_config)
+		.configure(websocket::websocket_config);
 
 	conf.service(scope);
 }
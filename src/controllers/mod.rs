@@ -2,9 +2,11 @@ pub mod auth;
 pub mod avito_accounts;
 pub mod avito_ads;
 pub mod avito_ai_processing;
+pub mod avito_autocatalog;
 pub mod avito_client;
 pub mod avito_editor;
 pub mod avito_feeds;
+pub mod avito_repricing;
 pub mod avito_requests;
 pub mod config;
 pub mod rabbitmq_consumer;
@@ -1,10 +1,11 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::AppState;
 use actix_web::{web, HttpResponse, Result};
 use diesel::prelude::*;
 use serde_json::json;
 
-#[actix_web::delete("/users/me")]
+#[actix_web::delete("/users/me", wrap = "RequirePermission(Permission::EditProfile)")]
 pub async fn delete_me(user: JwtMiddleware, data: web::Data<AppState>) -> Result<HttpResponse> {
 	let mut conn = data.db.get().unwrap();
 
@@ -1,3 +1,4 @@
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	models::{PaginationParams, PaginationResponse, ResponseWithPagination, User},
 	AppState,
@@ -6,7 +7,7 @@ use actix_web::{web, HttpResponse, Result};
 use diesel::prelude::*;
 use serde_json::json;
 
-#[actix_web::get("/users")]
+#[actix_web::get("/users", wrap = "RequirePermission(Permission::ListUsers)")]
 pub async fn get_all_users(
 	data: web::Data<AppState>,
 	pagination: web::Query<PaginationParams>,
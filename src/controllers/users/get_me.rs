@@ -1,4 +1,5 @@
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	models::{User, UserData, UserResponse},
 	AppState,
@@ -7,7 +8,7 @@ use actix_web::{web, HttpResponse, Result};
 use diesel::prelude::*;
 use serde_json::json;
 
-#[actix_web::get("/users/me")]
+#[actix_web::get("/users/me", wrap = "RequirePermission(Permission::ReadProfile)")]
 pub async fn get_me(user: JwtMiddleware, data: web::Data<AppState>) -> Result<HttpResponse> {
 	let mut conn = data.db.get().unwrap();
 
@@ -1,3 +1,4 @@
+use crate::permissions::{Permission, RequirePermission};
 use crate::{
 	models::{User, UserData, UserResponse},
 	AppState,
@@ -6,7 +7,7 @@ use actix_web::{web, HttpResponse, Result};
 use diesel::prelude::*;
 use serde_json::json;
 
-#[actix_web::get("/users/{id}")]
+#[actix_web::get("/users/{id}", wrap = "RequirePermission(Permission::ListUsers)")]
 pub async fn get_user_by_id(
 	path: web::Path<uuid::Uuid>,
 	data: web::Data<AppState>,
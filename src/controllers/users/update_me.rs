@@ -1,10 +1,12 @@
+use crate::controllers::auth::verify_email::send_verification_email;
 use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use crate::{models::UpdateUser, AppState};
 use actix_web::{web, HttpResponse, Result};
 use diesel::prelude::*;
 use serde_json::json;
 
-#[actix_web::patch("/users/me")]
+#[actix_web::patch("/users/me", wrap = "RequirePermission(Permission::EditProfile)")]
 pub async fn update_me(
 	user: JwtMiddleware,
 	body: web::Json<UpdateUser>,
@@ -13,34 +15,95 @@ pub async fn update_me(
 	let mut conn = data.db.get().unwrap();
 
 	// Check if user exists
-	match crate::schema::users::table
+	let current_user = match crate::schema::users::table
 		.find(user.user_id)
 		.first::<crate::models::User>(&mut conn)
 	{
-		Ok(_) => {}
+		Ok(current_user) => current_user,
 		Err(_) => {
 			return Ok(HttpResponse::NotFound().json(json!({
 				"status": "fail",
 				"message": "User not found"
 			})));
 		}
+	};
+
+	let new_email = changed_email(&current_user.email, body.email.as_deref());
+	if let Some(email) = &new_email {
+		let taken = crate::schema::users::table
+			.filter(crate::schema::users::email.eq(email))
+			.filter(crate::schema::users::id.ne(user.user_id))
+			.first::<crate::models::User>(&mut conn)
+			.optional()
+			.expect("Error checking the email");
+		if taken.is_some() {
+			return Ok(HttpResponse::BadRequest().json(json!({
+				"status": "fail",
+				"message": "User with this email already exists"
+			})));
+		}
 	}
 
-	// Update user
+	// Update user; the role is changed by admins only, through POST /auth/role.
+	// A new email has to be verified again.
+	let verified = match new_email {
+		Some(_) => Some(false),
+		None => current_user.verified,
+	};
 	let updated_user: crate::models::User =
 		diesel::update(crate::schema::users::table.find(user.user_id))
 			.set((
 				crate::schema::users::name.eq(body.name.clone()),
-				crate::schema::users::email.eq(body.email.clone().unwrap_or_default()),
-				crate::schema::users::role.eq(body.role.clone()),
+				crate::schema::users::email.eq(new_email.as_ref().unwrap_or(&current_user.email)),
+				crate::schema::users::verified.eq(verified),
 				crate::schema::users::photo.eq(body.photo.clone()),
 				crate::schema::users::updated_at.eq(Some(chrono::Utc::now().naive_utc())),
 			))
 			.get_result(&mut conn)
 			.expect("Error updating user");
 
+	// Links mailed to the old address stop working once a new one is issued
+	if new_email.is_some() {
+		if let Err(e) =
+			send_verification_email(&mut conn, data.mailer.as_ref(), &data.env, &updated_user).await
+		{
+			log::error!(
+				"Failed to send the verification email to user {}: {}",
+				updated_user.id,
+				e
+			);
+		}
+	}
+
 	Ok(HttpResponse::Ok().json(crate::models::UserResponse {
 		status: "success".to_string(),
 		data: crate::models::UserData { user: updated_user },
 	}))
 }
+
+// The requested email if it differs from the current one; a missing or blank email keeps it
+fn changed_email(current: &str, requested: Option<&str>) -> Option<String> {
+	requested
+		.map(str::trim)
+		.filter(|email| !email.is_empty() && *email != current)
+		.map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_changed_email() {
+		assert_eq!(
+			changed_email("old@example.com", Some(" new@example.com ")),
+			Some("new@example.com".to_string())
+		);
+		assert_eq!(
+			changed_email("old@example.com", Some("old@example.com")),
+			None
+		);
+		assert_eq!(changed_email("old@example.com", Some("")), None);
+		assert_eq!(changed_email("old@example.com", None), None);
+	}
+}
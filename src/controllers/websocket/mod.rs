@@ -1,2 +1,8 @@
 pub mod websocket;
 pub use self::websocket::*;
+
+use actix_web::web;
+
+pub fn websocket_config(cfg: &mut web::ServiceConfig) {
+	cfg.service(websocket_route);
+}
@@ -1,3 +1,5 @@
+use crate::jwt_auth::JwtMiddleware;
+use crate::permissions::{Permission, RequirePermission};
 use actix_web::{web, HttpRequest, Responder};
 use actix_ws::{handle, Message};
 use futures::StreamExt;
@@ -158,10 +160,23 @@ impl WebSocketConnections {
 }
 
 // WebSocket handler function
+// GET open a WebSocket for the logged in user; messages for the user, and for the request
+// named by `?request_id=`, are pushed to it
+#[actix_web::get("/ws", wrap = "RequirePermission(Permission::ReadProfile)")]
+pub async fn websocket_route(
+	req: HttpRequest,
+	body: web::Payload,
+	connections: web::Data<WebSocketConnections>,
+	user: JwtMiddleware,
+) -> actix_web::Result<impl Responder> {
+	websocket_handler(req, body, connections, user.user_id).await
+}
+
 pub async fn websocket_handler(
 	req: HttpRequest,
 	body: web::Payload,
 	connections: web::Data<WebSocketConnections>,
+	user_id: uuid::Uuid,
 ) -> actix_web::Result<impl Responder> {
 	println!(
 		"WebSocket connection attempt from: {}",
@@ -171,12 +186,8 @@ pub async fn websocket_handler(
 	// Log the request URI to debug
 	println!("WebSocket request URI: {}", req.uri());
 
-	// Extract user_id from query parameters
-	let user_id = extract_user_id_from_request(&req).await.unwrap_or_else(|| {
-		// Generate a placeholder user_id if not found
-		println!("No user_id found in request, using placeholder");
-		uuid::Uuid::nil().to_string()
-	});
+	// The user comes from the access token, never from the query
+	let user_id = user_id.to_string();
 
 	// Extract request_id from query parameters
 	let request_id = extract_request_id_from_request(&req).await;
@@ -302,18 +313,6 @@ pub async fn websocket_handler(
 	Ok(response)
 }
 
-// Helper function to extract user_id from request
-async fn extract_user_id_from_request(req: &HttpRequest) -> Option<String> {
-	if let Some(query) = req.uri().query() {
-		let params: std::collections::HashMap<String, String> =
-			form_urlencoded::parse(query.as_bytes())
-				.into_owned()
-				.collect();
-		return params.get("user_id").cloned();
-	}
-	None
-}
-
 // Helper function to extract request_id from request
 async fn extract_request_id_from_request(req: &HttpRequest) -> Option<String> {
 	if let Some(query) = req.uri().query() {
@@ -1,12 +1,14 @@
 use core::fmt;
 use std::future::{ready, Ready};
 
-use actix_web::error::ErrorUnauthorized;
+use actix_web::error::{ErrorInternalServerError, ErrorUnauthorized};
 use actix_web::{dev::Payload, Error as ActixWebError};
-use actix_web::{http, web, FromRequest, HttpMessage, HttpRequest};
+use actix_web::{http, web, FromRequest, HttpMessage, HttpRequest, HttpResponse};
 use jsonwebtoken::{decode, DecodingKey, Validation};
 use serde::Serialize;
 
+use crate::config::Config;
+use crate::controllers::auth::sessions::session_is_active;
 use crate::models::TokenClaims;
 use crate::AppState;
 
@@ -22,91 +24,142 @@ impl fmt::Display for ErrorResponse {
 	}
 }
 
+// Why a request could not be authenticated
+#[derive(Debug, Clone, Copy)]
+pub enum AuthError {
+	Unauthorized(&'static str),
+	Internal(&'static str),
+}
+
+impl AuthError {
+	pub fn into_error(self) -> ActixWebError {
+		match self {
+			AuthError::Unauthorized(message) => ErrorUnauthorized(ErrorResponse {
+				status: String::from("fail"),
+				message: String::from(message),
+			}),
+			AuthError::Internal(message) => ErrorInternalServerError(ErrorResponse {
+				status: String::from("error"),
+				message: String::from(message),
+			}),
+		}
+	}
+
+	pub fn into_response(self) -> HttpResponse {
+		self.into_error().error_response()
+	}
+}
+
+// Outcome of authenticating a request, kept in its extensions so the token and session
+// are only checked once however many extractors and middlewares ask
+#[derive(Clone, Copy)]
+pub struct Authentication(pub Result<JwtMiddleware, AuthError>);
+
+#[derive(Debug, Clone, Copy)]
 pub struct JwtMiddleware {
 	pub user_id: uuid::Uuid,
+	pub session_id: uuid::Uuid,
 }
 
 impl FromRequest for JwtMiddleware {
 	type Error = ActixWebError;
 	type Future = Ready<Result<Self, Self::Error>>;
 	fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
-		let data = req.app_data::<web::Data<AppState>>().unwrap();
-
-		let token = req
-			.cookie("token")
-			.map(|c| c.value().to_string())
-			.or_else(|| {
-				req.headers()
-					.get(http::header::AUTHORIZATION)
-					.map(|h| h.to_str().unwrap().split_at(7).1.to_string())
-			});
-
-		let status = String::from("fail");
-
-		if token.is_none() {
-			let json_error = ErrorResponse {
-				status,
-				message: String::from("You are not logged in, please log in"),
-			};
-			return ready(Err(ErrorUnauthorized(json_error)));
-		}
-
-		let claims = match decode::<TokenClaims>(
-			&token.unwrap(),
-			&DecodingKey::from_secret(data.env.jwt_secret.as_ref()),
-			&Validation::default(),
-		) {
-			Ok(c) => c.claims,
-			Err(_) => {
-				let json_error = ErrorResponse {
-					status,
-					message: String::from("Authentication error"),
-				};
-				return ready(Err(ErrorUnauthorized(json_error)));
-			}
+		let user = match authenticate(req) {
+			Ok(user) => user,
+			Err(e) => return ready(Err(e.into_error())),
 		};
 
-		// Check if token is expired
-		let current_timestamp = chrono::Utc::now().timestamp() as usize;
-		if claims.exp < current_timestamp {
-			let json_error = ErrorResponse {
-				status,
-				message: String::from("Token has expired"),
-			};
-			return ready(Err(ErrorUnauthorized(json_error)));
-		}
+		req.extensions_mut()
+			.insert::<uuid::Uuid>(user.user_id.to_owned());
 
-		let user_id = match uuid::Uuid::parse_str(claims.sub.as_str()) {
-			Ok(id) => id,
-			Err(_) => {
-				let json_error = ErrorResponse {
-					status,
-					message: String::from("Invalid token payload"),
-				};
-				return ready(Err(ErrorUnauthorized(json_error)));
-			}
-		};
+		ready(Ok(user))
+	}
+}
 
-		req.extensions_mut()
-			.insert::<uuid::Uuid>(user_id.to_owned());
+// Authenticate the request by its access token, reusing an earlier outcome
+pub fn authenticate(req: &HttpRequest) -> Result<JwtMiddleware, AuthError> {
+	if let Some(Authentication(outcome)) = req.extensions().get::<Authentication>() {
+		return *outcome;
+	}
+
+	let outcome = authenticate_token(req);
+	req.extensions_mut().insert(Authentication(outcome));
+	outcome
+}
+
+fn authenticate_token(req: &HttpRequest) -> Result<JwtMiddleware, AuthError> {
+	let data = req.app_data::<web::Data<AppState>>().unwrap();
+
+	let token = req
+		.cookie("token")
+		.map(|c| c.value().to_string())
+		.or_else(|| {
+			req.headers()
+				.get(http::header::AUTHORIZATION)
+				.and_then(|h| h.to_str().ok())
+				.and_then(|h| h.strip_prefix("Bearer "))
+				.map(|token| token.to_string())
+		})
+		.ok_or(AuthError::Unauthorized(
+			"You are not logged in, please log in",
+		))?;
+
+	let claims = decode::<TokenClaims>(
+		&token,
+		&DecodingKey::from_secret(data.env.jwt_secret.as_ref()),
+		&Validation::default(),
+	)
+	.map_err(|_| AuthError::Unauthorized("Authentication error"))?
+	.claims;
 
-		ready(Ok(JwtMiddleware { user_id }))
+	// Check if token is expired
+	let current_timestamp = chrono::Utc::now().timestamp() as usize;
+	if claims.exp < current_timestamp {
+		return Err(AuthError::Unauthorized("Token has expired"));
+	}
+
+	let user_id = uuid::Uuid::parse_str(claims.sub.as_str())
+		.map_err(|_| AuthError::Unauthorized("Invalid token payload"))?;
+	let session_id = uuid::Uuid::parse_str(claims.sid.as_str())
+		.map_err(|_| AuthError::Unauthorized("Invalid token payload"))?;
+
+	// Tokens of a logged out or revoked session are rejected before they expire
+	let session_active = data
+		.db
+		.get()
+		.map_err(|e| e.to_string())
+		.and_then(|mut conn| session_is_active(&mut conn, session_id).map_err(|e| e.to_string()));
+	match session_active {
+		Ok(true) => Ok(JwtMiddleware {
+			user_id,
+			session_id,
+		}),
+		Ok(false) => Err(AuthError::Unauthorized(
+			"Session has been revoked, please log in",
+		)),
+		Err(e) => {
+			log::error!("Failed to check session {}: {}", session_id, e);
+			Err(AuthError::Internal("Failed to check the session"))
+		}
 	}
 }
 
-// Function to generate a new token
+// Function to generate a new access token of a session
 pub fn generate_token(
 	user_id: uuid::Uuid,
-	jwt_secret: &str,
+	session_id: uuid::Uuid,
+	config: &Config,
 ) -> Result<String, jsonwebtoken::errors::Error> {
 	let expiration = chrono::Utc::now()
-		.checked_add_signed(chrono::Duration::hours(24))
+		.checked_add_signed(chrono::Duration::minutes(config.access_token_ttl_minutes))
 		.unwrap()
 		.timestamp() as usize;
 	let iat = chrono::Utc::now().timestamp() as usize;
 
 	let claims = TokenClaims {
 		sub: user_id.to_string(),
+		sid: session_id.to_string(),
 		exp: expiration,
 		iat,
 	};
@@ -114,6 +167,6 @@ pub fn generate_token(
 	jsonwebtoken::encode(
 		&jsonwebtoken::Header::default(),
 		&claims,
-		&jsonwebtoken::EncodingKey::from_secret(jwt_secret.as_ref()),
+		&jsonwebtoken::EncodingKey::from_secret(config.jwt_secret.as_ref()),
 	)
 }
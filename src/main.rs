@@ -3,28 +3,55 @@ mod config;
 mod controllers;
 mod jwt_auth;
 mod models;
+mod permissions;
+mod rate_limit;
 mod schema;
 mod utils;
 
+use crate::controllers::avito_accounts::account_health::start_account_check_scheduler;
+use crate::controllers::avito_client::avito_api::{AvitoApi, AvitoHttpApi};
+use crate::controllers::avito_client::balance_monitor::BalanceMonitor;
+use crate::controllers::avito_client::item_stats_collector::start_item_stats_scheduler;
+use crate::controllers::avito_client::items_sync_scheduler::start_items_sync_scheduler;
+use crate::controllers::avito_client::request_policy::AvitoRequestPolicy;
+use crate::controllers::avito_client::start_price_update::start_interrupted_price_update_check;
+use crate::controllers::avito_client::token_manager::{
+	AvitoAccounts, AvitoTokenManager, DbAvitoAccounts,
+};
+use crate::controllers::avito_feeds::import_scheduler::start_import_scheduler;
+use crate::controllers::avito_repricing::repricing_scheduler::start_repricing_scheduler;
 use crate::controllers::rabbitmq_consumer::{
 	start_ai_processing_consumer, start_rabbitmq_consumer,
 };
 use crate::controllers::rabbitmq_publisher::publisher::establish_rabbitmq_connection;
-use crate::controllers::websocket::{websocket_handler, WebSocketConnections};
+use crate::controllers::websocket::WebSocketConnections;
+use crate::permissions::extract_permissions;
+use crate::rate_limit::{start_rate_limit_cleanup, RateLimit};
+use crate::utils::encryption::{install_keyring, Keyring};
+use crate::utils::mailer::{mailer_from_config, Mailer};
+use crate::utils::password::{PasswordHashing, PasswordPolicy};
 use actix_cors::Cors;
 use actix_web::middleware::Logger;
-use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use actix_web::{web, App, HttpResponse, HttpServer};
+use actix_web_grants::GrantsMiddleware;
 use config::Config;
 use diesel::prelude::*;
 use diesel::r2d2::{self, ConnectionManager};
 use dotenv::dotenv;
 use lapin::Channel;
+use std::sync::Arc;
 
 pub struct AppState {
 	db: r2d2::Pool<ConnectionManager<diesel::PgConnection>>,
 	env: Config,
 	pub rabbitmq_channel: Option<Channel>,
 	ws_server: WebSocketConnections,
+	avito_tokens: AvitoTokenManager,
+	avito_api: Arc<dyn AvitoApi>,
+	avito_accounts: Arc<dyn AvitoAccounts>,
+	mailer: Arc<dyn Mailer>,
+	password_hashing: PasswordHashing,
+	password_policy: Arc<PasswordPolicy>,
 }
 
 #[actix_web::main]
@@ -37,6 +64,32 @@ async fn main() -> std::io::Result<()> {
 
 	let config = Config::init();
 
+	let keyring = Keyring::parse(
+		&config.encryption_keys,
+		config.encryption_active_key_id.as_deref(),
+	)
+	.unwrap_or_else(|e| panic!("Invalid encryption keys: {}", e));
+	println!(
+		"✅ Encryption keys loaded, active key: {}",
+		keyring.active_key_id()
+	);
+	install_keyring(keyring);
+
+	let mailer =
+		mailer_from_config(&config).unwrap_or_else(|e| panic!("Invalid mail configuration: {}", e));
+	println!("✅ Mail transport: {}", config.mail_transport);
+
+	let password_hashing = PasswordHashing::from_config(&config)
+		.unwrap_or_else(|e| panic!("Invalid password hashing configuration: {}", e));
+	let password_policy = Arc::new(
+		PasswordPolicy::from_config(&config)
+			.unwrap_or_else(|e| panic!("Invalid password policy: {}", e)),
+	);
+	println!(
+		"✅ Password policy loaded, {} breached passwords",
+		password_policy.breached_count()
+	);
+
 	let manager = ConnectionManager::<diesel::PgConnection>::new(&config.database_url);
 	let pool = r2d2::Pool::builder()
 		.max_size(10)
@@ -78,6 +131,13 @@ async fn main() -> std::io::Result<()> {
 	let ws_server = WebSocketConnections::new();
 	let ws_server_data = web::Data::new(ws_server.clone());
 
+	// One Avito client, token cache and request policy for all workers, so connections are
+	// pooled, each account asks Avito for one token at a time and rate limits hold across workers
+	let avito_api: Arc<dyn AvitoApi> = Arc::new(AvitoHttpApi::new(&config));
+	let avito_tokens =
+		AvitoTokenManager::new(avito_api.clone(), AvitoRequestPolicy::from_config(&config));
+	let avito_accounts: Arc<dyn AvitoAccounts> = Arc::new(DbAvitoAccounts::new(pool.clone()));
+
 	// Start RabbitMQ consumer with WebSocket server
 	let ws_server_clone = ws_server.clone();
 	let pool_clone = pool.clone();
@@ -87,6 +147,101 @@ async fn main() -> std::io::Result<()> {
 	let ws_server_clone_ai = ws_server.clone();
 	tokio::spawn(async move { start_ai_processing_consumer(ws_server_clone_ai).await });
 
+	// Start scheduled re-import of supplier XML feeds
+	let ws_server_clone_imports = ws_server.clone();
+	let pool_clone_imports = pool.clone();
+	let config_clone_imports = config.clone();
+	tokio::spawn(async move {
+		start_import_scheduler(
+			pool_clone_imports,
+			config_clone_imports,
+			ws_server_clone_imports,
+		)
+		.await
+	});
+
+	// Bulk price updates run in the process that started them, so none survives a restart
+	let pool_clone_price_updates = pool.clone();
+	tokio::spawn(
+		async move { start_interrupted_price_update_check(pool_clone_price_updates).await },
+	);
+
+	// Start scheduled sync of live Avito items into avito_ads
+	let pool_clone_items = pool.clone();
+	let avito_tokens_clone_items = avito_tokens.clone();
+	let avito_api_clone_items = avito_api.clone();
+	let items_sync_interval = config.avito_items_sync_interval_minutes;
+	tokio::spawn(async move {
+		start_items_sync_scheduler(
+			pool_clone_items,
+			avito_tokens_clone_items,
+			avito_api_clone_items,
+			items_sync_interval,
+		)
+		.await
+	});
+
+	// Start scheduled collection of daily item statistics
+	let pool_clone_stats = pool.clone();
+	let avito_tokens_clone_stats = avito_tokens.clone();
+	let avito_api_clone_stats = avito_api.clone();
+	let item_stats_interval = config.avito_item_stats_interval_minutes;
+	let item_stats_lookback = config.avito_item_stats_lookback_days;
+	tokio::spawn(async move {
+		start_item_stats_scheduler(
+			pool_clone_stats,
+			avito_tokens_clone_stats,
+			avito_api_clone_stats,
+			item_stats_interval,
+			item_stats_lookback,
+		)
+		.await
+	});
+
+	// Start scheduled checks of the Avito credentials
+	let pool_clone_account_checks = pool.clone();
+	let avito_tokens_clone_account_checks = avito_tokens.clone();
+	let account_check_interval = config.avito_account_check_interval_minutes;
+	tokio::spawn(async move {
+		start_account_check_scheduler(
+			pool_clone_account_checks,
+			avito_tokens_clone_account_checks,
+			account_check_interval,
+		)
+		.await
+	});
+
+	// Start balance polling with low-balance alerts
+	let balance_monitor = BalanceMonitor::new(
+		pool.clone(),
+		avito_tokens.clone(),
+		avito_api.clone(),
+		ws_server.clone(),
+		config.balance_alert_webhook_url.clone(),
+	);
+	let balance_poll_interval = config.avito_balance_poll_interval_minutes;
+	tokio::spawn(async move { balance_monitor.run(balance_poll_interval).await });
+
+	// Start scheduled evaluation of repricing rules
+	let pool_clone_repricing = pool.clone();
+	let avito_tokens_clone_repricing = avito_tokens.clone();
+	let avito_api_clone_repricing = avito_api.clone();
+	tokio::spawn(async move {
+		start_repricing_scheduler(
+			pool_clone_repricing,
+			avito_tokens_clone_repricing,
+			avito_api_clone_repricing,
+		)
+		.await
+	});
+
+	// Start cleanup of rate limit counters and failed logins
+	let pool_clone_rate_limits = pool.clone();
+	let config_clone_rate_limits = config.clone();
+	tokio::spawn(async move {
+		start_rate_limit_cleanup(pool_clone_rate_limits, config_clone_rate_limits).await
+	});
+
 	println!("✅ Server started successfully on http://0.0.0.0:8081");
 
 	HttpServer::new(move || {
@@ -96,14 +251,19 @@ async fn main() -> std::io::Result<()> {
 				env: config.clone(),
 				rabbitmq_channel: rabbitmq_channel.clone(),
 				ws_server: ws_server.clone(),
+				avito_tokens: avito_tokens.clone(),
+				avito_api: avito_api.clone(),
+				avito_accounts: avito_accounts.clone(),
+				mailer: mailer.clone(),
+				password_hashing: password_hashing.clone(),
+				password_policy: password_policy.clone(),
 			}))
 			.app_data(ws_server_data.clone())
-			.service(web::resource("/api/ws").route(web::get().to(
-				|req: HttpRequest, body: web::Payload, data: web::Data<WebSocketConnections>| async move {
-					websocket_handler(req, body, data).await
-				},
-			)))
 			.configure(controllers::config::config)
+			// Runs after the permissions extractor, so limits of the API are per user
+			.wrap(RateLimit)
+			// Permissions of the caller, checked by the `RequirePermission` of each route
+			.wrap(GrantsMiddleware::with_extractor(extract_permissions))
 			.wrap(Cors::permissive())
 			.wrap(Logger::default())
 			.route(
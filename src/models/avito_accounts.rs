@@ -1,5 +1,5 @@
 use crate::schema::avito_accounts;
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -16,6 +16,9 @@ pub struct AvitoAccount {
 	pub is_connected: Option<bool>,
 	pub created_ts: NaiveDateTime,
 	pub updated_ts: NaiveDateTime,
+	pub last_check_ts: Option<DateTime<Utc>>,
+	// Why the last credentials check failed; None after a successful one
+	pub last_check_error: Option<String>,
 }
 
 #[derive(Insertable, AsChangeset, Deserialize)]
@@ -0,0 +1,109 @@
+use crate::schema::{
+	avito_car_generations, avito_car_makes, avito_car_models, avito_car_modifications,
+};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = avito_car_makes)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AvitoCarMake {
+	pub make_id: Uuid,
+	pub avito_id: Option<String>,
+	pub name: String,
+	pub created_ts: DateTime<Utc>,
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = avito_car_models)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AvitoCarModel {
+	pub model_id: Uuid,
+	pub make_id: Uuid,
+	pub avito_id: Option<String>,
+	pub name: String,
+	pub created_ts: DateTime<Utc>,
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = avito_car_generations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AvitoCarGeneration {
+	pub generation_id: Uuid,
+	pub model_id: Uuid,
+	pub avito_id: Option<String>,
+	pub name: String,
+	pub created_ts: DateTime<Utc>,
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = avito_car_modifications)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AvitoCarModification {
+	pub modification_id: Uuid,
+	pub generation_id: Uuid,
+	pub avito_id: Option<String>,
+	pub name: String,
+	pub body_type: Option<String>,
+	pub doors: Option<String>,
+	pub created_ts: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct AutocatalogModelsQuery {
+	pub make: String,
+}
+
+#[derive(Deserialize)]
+pub struct AutocatalogGenerationsQuery {
+	pub make: String,
+	pub model: String,
+}
+
+#[derive(Deserialize)]
+pub struct AutocatalogModificationsQuery {
+	pub make: String,
+	pub model: String,
+	pub generation: String,
+}
+
+#[derive(Deserialize)]
+pub struct ImportAutocatalogRequest {
+	// Defaults to AUTOCATALOG_URL, then to the public Avito catalog
+	pub source_url: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = avito_car_makes)]
+pub struct NewAvitoCarMake<'a> {
+	pub avito_id: Option<&'a str>,
+	pub name: &'a str,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = avito_car_models)]
+pub struct NewAvitoCarModel<'a> {
+	pub make_id: Uuid,
+	pub avito_id: Option<&'a str>,
+	pub name: &'a str,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = avito_car_generations)]
+pub struct NewAvitoCarGeneration<'a> {
+	pub model_id: Uuid,
+	pub avito_id: Option<&'a str>,
+	pub name: &'a str,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = avito_car_modifications)]
+pub struct NewAvitoCarModification<'a> {
+	pub generation_id: Uuid,
+	pub avito_id: Option<&'a str>,
+	pub name: &'a str,
+	pub body_type: Option<&'a str>,
+	pub doors: Option<&'a str>,
+}
@@ -0,0 +1,75 @@
+use crate::schema::{avito_balance_alerts, avito_balance_history};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = avito_balance_history)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AvitoBalanceHistory {
+	pub history_id: Uuid,
+	pub account_id: Uuid,
+	pub balance: f64,
+	pub recorded_ts: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = avito_balance_history)]
+pub struct CreateAvitoBalanceHistory {
+	pub account_id: Uuid,
+	pub balance: f64,
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = avito_balance_alerts)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AvitoBalanceAlert {
+	pub account_id: Uuid,
+	pub threshold: f64,
+	pub webhook_url: Option<String>,
+	pub enabled: bool,
+	pub below_threshold: bool,
+	pub last_alert_ts: Option<DateTime<Utc>>,
+	pub created_ts: DateTime<Utc>,
+	pub updated_ts: DateTime<Utc>,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = avito_balance_alerts)]
+#[diesel(treat_none_as_null = true)]
+pub struct UpsertAvitoBalanceAlert {
+	pub account_id: Uuid,
+	pub threshold: f64,
+	pub webhook_url: Option<String>,
+	pub enabled: bool,
+	// Reset on every change, so a balance already under the new threshold is alerted
+	pub below_threshold: bool,
+	pub updated_ts: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct AvitoBalanceAlertRequest {
+	pub threshold: f64,
+	pub webhook_url: Option<String>,
+	pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetBalanceHistoryParams {
+	// Last 30 days when omitted
+	pub from: Option<DateTime<Utc>>,
+	pub to: Option<DateTime<Utc>>,
+}
+
+// Sent to the user's WebSocket sessions and the alert webhook
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BalanceAlertMessage {
+	#[serde(rename = "type")]
+	pub kind: &'static str,
+	pub account_id: Uuid,
+	pub client_id: String,
+	pub balance: f64,
+	pub threshold: f64,
+	pub recorded_ts: DateTime<Utc>,
+}
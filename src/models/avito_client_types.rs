@@ -1,46 +1,48 @@
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 #[derive(Debug, Deserialize)]
-pub struct AvitoTokenCredentials {
-	pub client_id: String,
-	pub client_secret: String,
-	pub grant_type: String,
+pub struct GetAvitoTokenParams {
+	pub account_id: Uuid,
+	// Request a new token even if the cached one is still valid
+	pub refresh: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct AvitoTokenParams {
-	pub avito_token: String,
+pub struct AvitoAccountParams {
+	pub account_id: Uuid,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct GetAvitoItemsParams {
-	pub avito_token: String,
+	pub account_id: Uuid,
 	pub page: Option<i32>,
 	pub per_page: Option<i32>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct GetCategoriesTreeParams {
-	pub avito_token: String,
+	pub account_id: Uuid,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct AvitoEditorCategoryFieldsParams {
-	pub avito_token: String,
+	pub account_id: Uuid,
 	pub avito_slug: String,
 }
 
-// Define a temporary struct for car marks since the table may not exist
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct AvitoCarMark {
-	pub car_mark_id: i32,
-	pub value: String,
+#[derive(Debug, Deserialize)]
+pub struct RefreshCategoryCacheParams {
+	pub account_id: Uuid,
+	// Categories to refresh; every cached category when omitted
+	pub avito_slugs: Option<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct GetItemAnalyticsBody {
-	pub avito_token: String,
-	pub account_id: String,
+	pub account_id: Uuid,
+	// Avito profile id the statistics belong to
+	pub avito_user_id: String,
 	pub date_from: String,
 	pub date_to: String,
 	pub grouping: String,
@@ -51,25 +53,122 @@ pub struct GetItemAnalyticsBody {
 
 #[derive(Debug, Deserialize)]
 pub struct UpdatePriceBody {
-	pub avito_token: String,
+	pub account_id: Uuid,
 	pub item_id: String,
 	pub price: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AvitoTokenResponse {
+	pub access_token: String,
+	pub token_type: Option<String>,
+	pub expires_in: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AvitoItemsMeta {
+	pub page: i32,
+	pub per_page: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AvitoItemCategory {
+	pub id: i64,
+	pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AvitoItem {
+	pub id: i64,
+	pub title: Option<String>,
+	pub price: Option<f64>,
+	pub status: Option<String>,
+	pub url: Option<String>,
+	pub address: Option<String>,
+	pub category: Option<AvitoItemCategory>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct AvitoGetItemsApiResponse {
-	pub meta: serde_json::Value,
-	pub resources: Vec<serde_json::Value>,
+	#[serde(default)]
+	pub meta: AvitoItemsMeta,
+	#[serde(default)]
+	pub resources: Vec<AvitoItem>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct AvitoGetBalanceApiResponse {
-	pub balance: serde_json::Value,
+	pub balance: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AvitoUserProfile {
+	pub id: i64,
+	pub name: Option<String>,
+	pub email: Option<String>,
+	pub phone: Option<String>,
+	pub phones: Option<Vec<String>>,
+	pub profile_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AvitoItemAnalyticsRequest {
+	pub date_from: String,
+	pub date_to: String,
+	pub grouping: String,
+	pub limit: i32,
+	pub metrics: Vec<String>,
+	pub offset: i32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// One day (or week, month) of statistics; metric names depend on the requested metrics
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AvitoItemStatsPoint {
+	pub date: String,
+	#[serde(flatten)]
+	pub metrics: std::collections::BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AvitoItemAnalytics {
+	#[serde(rename = "itemId")]
+	pub item_id: i64,
+	#[serde(default)]
+	pub stats: Vec<AvitoItemStatsPoint>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AvitoItemAnalyticsResult {
+	#[serde(default)]
+	pub items: Vec<AvitoItemAnalytics>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct AvitoItemAnalyticsResponse {
-	pub result: serde_json::Value,
+	#[serde(default)]
+	pub result: AvitoItemAnalyticsResult,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AvitoUpdatePriceResult {
+	pub success: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AvitoUpdatePriceResponse {
+	pub result: AvitoUpdatePriceResult,
+}
+
+// What the outbound policy went through before giving up on an Avito request
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct AvitoRetryReport {
+	pub attempts: u32,
+	// Time spent waiting for the account's rate limit and Avito's Retry-After
+	pub throttled_ms: u64,
+	pub backoff_ms: u64,
+	pub avito_status: Option<u16>,
+	pub retry_after_secs: Option<u64>,
 }
 
 #[derive(Debug)]
@@ -78,6 +177,13 @@ pub enum ApiError {
 	DieselError(diesel::result::Error),
 	JsonParseError(String),
 	AvitoApiError(u16, String),
+	// Avito status, Retry-After seconds and message
+	AvitoRetryAfter(u16, u64, String),
+	AvitoRetriesExhausted(AvitoRetryReport, Box<ApiError>),
+	// Seconds until the next request is let through to Avito
+	AvitoCircuitOpen(u64),
+	NotFound(String),
+	Conflict(String),
 	Other(String),
 }
 
@@ -90,6 +196,23 @@ impl std::fmt::Display for ApiError {
 			ApiError::AvitoApiError(status, message) => {
 				write!(f, "Avito API error {}: {}", status, message)
 			}
+			ApiError::AvitoRetryAfter(status, retry_after, message) => write!(
+				f,
+				"Avito API error {} (retry after {} s): {}",
+				status, retry_after, message
+			),
+			ApiError::AvitoRetriesExhausted(report, error) => write!(
+				f,
+				"Avito request failed after {} attempts: {}",
+				report.attempts, error
+			),
+			ApiError::AvitoCircuitOpen(retry_after) => write!(
+				f,
+				"Avito API is unavailable, requests are paused for {} s",
+				retry_after
+			),
+			ApiError::NotFound(message) => write!(f, "Not found: {}", message),
+			ApiError::Conflict(message) => write!(f, "Conflict: {}", message),
 			ApiError::Other(s) => write!(f, "Other error: {}", s),
 		}
 	}
@@ -117,6 +240,7 @@ impl From<diesel::result::Error> for ApiError {
 
 impl actix_web::ResponseError for ApiError {
 	fn error_response(&self) -> actix_web::HttpResponse {
+		use actix_web::http::header::RETRY_AFTER;
 		use actix_web::HttpResponse;
 		use serde_json::json;
 
@@ -137,6 +261,50 @@ impl actix_web::ResponseError for ApiError {
 				"status": "error",
 				"message": format!("Avito API error {}: {}", status, message)
 			})),
+			ApiError::AvitoRetryAfter(status, retry_after, message) => {
+				let mut response = if *status == 429 {
+					HttpResponse::TooManyRequests()
+				} else {
+					HttpResponse::BadRequest()
+				};
+				response
+					.insert_header((RETRY_AFTER, retry_after.to_string()))
+					.json(json!({
+						"status": "error",
+						"message": format!("Avito API error {}: {}", status, message),
+						"retry_after_secs": retry_after
+					}))
+			}
+			ApiError::AvitoRetriesExhausted(report, _) => {
+				let mut response = if report.avito_status == Some(429) {
+					HttpResponse::TooManyRequests()
+				} else {
+					HttpResponse::ServiceUnavailable()
+				};
+				if let Some(retry_after) = report.retry_after_secs {
+					response.insert_header((RETRY_AFTER, retry_after.to_string()));
+				}
+				response.json(json!({
+					"status": "error",
+					"message": self.to_string(),
+					"retry": report
+				}))
+			}
+			ApiError::AvitoCircuitOpen(retry_after) => HttpResponse::ServiceUnavailable()
+				.insert_header((RETRY_AFTER, retry_after.to_string()))
+				.json(json!({
+					"status": "error",
+					"message": self.to_string(),
+					"retry_after_secs": retry_after
+				})),
+			ApiError::NotFound(message) => HttpResponse::NotFound().json(json!({
+				"status": "fail",
+				"message": message
+			})),
+			ApiError::Conflict(message) => HttpResponse::Conflict().json(json!({
+				"status": "fail",
+				"message": message
+			})),
 			ApiError::Other(message) => HttpResponse::InternalServerError().json(json!({
 				"status": "error",
 				"message": message
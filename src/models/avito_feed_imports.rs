@@ -0,0 +1,85 @@
+use crate::schema::{avito_feed_import_schedules, avito_feed_imports};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = avito_feed_import_schedules)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AvitoFeedImportSchedule {
+	pub schedule_id: Uuid,
+	pub feed_id: Uuid,
+	pub source_url: String,
+	pub interval_minutes: Option<i32>,
+	pub cron_expression: Option<String>,
+	pub enabled: bool,
+	pub last_run_ts: Option<DateTime<Utc>>,
+	pub next_run_ts: Option<DateTime<Utc>>,
+	pub created_ts: DateTime<Utc>,
+	pub updated_ts: DateTime<Utc>,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = avito_feed_import_schedules)]
+#[diesel(treat_none_as_null = true)]
+pub struct UpsertAvitoFeedImportSchedule {
+	pub feed_id: Uuid,
+	pub source_url: String,
+	pub interval_minutes: Option<i32>,
+	pub cron_expression: Option<String>,
+	pub enabled: bool,
+	pub next_run_ts: Option<DateTime<Utc>>,
+	pub updated_ts: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct AvitoFeedImportScheduleRequest {
+	pub source_url: String,
+	// Either a fixed interval or a cron expression; the cron expression wins when both are set
+	pub interval_minutes: Option<i32>,
+	pub cron_expression: Option<String>,
+	pub enabled: Option<bool>,
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = avito_feed_imports)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AvitoFeedImport {
+	pub import_id: Uuid,
+	pub feed_id: Uuid,
+	pub schedule_id: Option<Uuid>,
+	pub source_url: String,
+	// running, success or failed
+	pub status: String,
+	pub ads_processed: i32,
+	pub ads_added: i32,
+	pub ads_updated: i32,
+	pub ads_unchanged: i32,
+	pub ads_deactivated: i32,
+	pub error: Option<String>,
+	pub started_ts: DateTime<Utc>,
+	pub finished_ts: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = avito_feed_imports)]
+pub struct CreateAvitoFeedImport {
+	pub feed_id: Uuid,
+	pub schedule_id: Option<Uuid>,
+	pub source_url: String,
+	pub status: String,
+}
+
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = avito_feed_imports)]
+pub struct FinishAvitoFeedImport {
+	pub status: String,
+	pub ads_processed: i32,
+	pub ads_added: i32,
+	pub ads_updated: i32,
+	pub ads_unchanged: i32,
+	pub ads_deactivated: i32,
+	pub error: Option<String>,
+	pub finished_ts: Option<DateTime<Utc>>,
+}
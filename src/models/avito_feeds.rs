@@ -10,6 +10,29 @@ use uuid::Uuid;
 pub struct XmlAd {
 	pub id: String,
 	pub fields: HashMap<String, String>,
+	// Tags that hold a list of nested elements, such as Images and Delivery, with their items
+	pub lists: HashMap<String, Vec<String>>,
+}
+
+// (tag, values) pairs of a single ad in the shape they are stored in the database
+pub type AdFieldValues = Vec<(String, Vec<String>)>;
+
+impl XmlAd {
+	// The parsed fields as storable (tag, values) pairs, Id first, the rest sorted by tag
+	pub fn field_values(&self) -> AdFieldValues {
+		let mut field_values: AdFieldValues = self
+			.fields
+			.iter()
+			.map(|(tag, value)| (tag.clone(), vec![value.clone()]))
+			.chain(
+				self.lists
+					.iter()
+					.map(|(tag, items)| (tag.clone(), items.clone())),
+			)
+			.collect();
+		field_values.sort_by(|(a, _), (b, _)| (a != "Id", a).cmp(&(b != "Id", b)));
+		field_values
+	}
 }
 
 #[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
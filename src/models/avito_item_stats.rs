@@ -0,0 +1,77 @@
+use crate::schema::avito_item_stats;
+use chrono::{DateTime, NaiveDate, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = avito_item_stats)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AvitoItemStat {
+	pub account_id: Uuid,
+	pub item_id: String,
+	pub stat_date: NaiveDate,
+	pub impressions: i64,
+	pub views: i64,
+	pub contacts: i64,
+	pub favorites: i64,
+	pub spending: f64,
+	pub metrics: serde_json::Value,
+	pub collected_ts: DateTime<Utc>,
+}
+
+#[derive(Insertable, AsChangeset, Debug, Clone, PartialEq)]
+#[diesel(table_name = avito_item_stats)]
+pub struct UpsertAvitoItemStat {
+	pub account_id: Uuid,
+	pub item_id: String,
+	pub stat_date: NaiveDate,
+	pub impressions: i64,
+	pub views: i64,
+	pub contacts: i64,
+	pub favorites: i64,
+	pub spending: f64,
+	pub metrics: serde_json::Value,
+	pub collected_ts: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetItemStatsParams {
+	// YYYY-MM-DD, both inclusive; the last 30 days when omitted
+	pub date_from: Option<NaiveDate>,
+	pub date_to: Option<NaiveDate>,
+	// day, week or month
+	pub period: Option<String>,
+	// account, ad or feed
+	pub group_by: Option<String>,
+	pub item_id: Option<String>,
+	pub feed_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq)]
+pub struct ItemStatsTotals {
+	pub impressions: i64,
+	pub views: i64,
+	pub contacts: i64,
+	pub favorites: i64,
+	pub spending: f64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ItemStatsDelta {
+	pub previous: f64,
+	pub change: f64,
+	// None when the previous period had nothing to compare with
+	pub change_percent: Option<f64>,
+}
+
+// Totals of one period for one item, feed or the whole account, compared with the period before
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ItemStatsBucket {
+	pub period_start: NaiveDate,
+	// Item or feed id, depending on group_by; None for the account and for unlinked items
+	pub key: Option<String>,
+	#[serde(flatten)]
+	pub totals: ItemStatsTotals,
+	pub deltas: std::collections::BTreeMap<&'static str, ItemStatsDelta>,
+}
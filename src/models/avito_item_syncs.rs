@@ -0,0 +1,43 @@
+use crate::schema::avito_item_syncs;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = avito_item_syncs)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AvitoItemSync {
+	pub sync_id: Uuid,
+	pub account_id: Uuid,
+	// running, success or failed
+	pub status: String,
+	pub items_seen: i32,
+	pub ads_added: i32,
+	pub ads_updated: i32,
+	pub ads_unchanged: i32,
+	pub ads_removed: i32,
+	pub error: Option<String>,
+	pub started_ts: DateTime<Utc>,
+	pub finished_ts: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = avito_item_syncs)]
+pub struct CreateAvitoItemSync {
+	pub account_id: Uuid,
+	pub status: String,
+}
+
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = avito_item_syncs)]
+pub struct FinishAvitoItemSync {
+	pub status: String,
+	pub items_seen: i32,
+	pub ads_added: i32,
+	pub ads_updated: i32,
+	pub ads_unchanged: i32,
+	pub ads_removed: i32,
+	pub error: Option<String>,
+	pub finished_ts: Option<DateTime<Utc>>,
+}
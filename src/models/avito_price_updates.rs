@@ -0,0 +1,114 @@
+use crate::schema::{avito_price_update_items, avito_price_update_jobs};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = avito_price_update_jobs)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AvitoPriceUpdateJob {
+	pub job_id: Uuid,
+	pub account_id: Uuid,
+	pub user_id: Uuid,
+	// preview, running, completed or failed
+	pub status: String,
+	pub items_total: i32,
+	pub items_updated: i32,
+	pub items_failed: i32,
+	pub items_skipped: i32,
+	pub error: Option<String>,
+	pub created_ts: DateTime<Utc>,
+	pub started_ts: Option<DateTime<Utc>>,
+	pub finished_ts: Option<DateTime<Utc>>,
+	// Refreshed by the server running the job
+	pub heartbeat_ts: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = avito_price_update_jobs)]
+pub struct CreateAvitoPriceUpdateJob {
+	pub account_id: Uuid,
+	pub user_id: Uuid,
+	pub status: String,
+	pub items_total: i32,
+	pub items_skipped: i32,
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = avito_price_update_items)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AvitoPriceUpdateItem {
+	pub job_item_id: Uuid,
+	pub job_id: Uuid,
+	pub line: i32,
+	pub item_id: String,
+	pub old_price: Option<f64>,
+	pub new_price: Option<f64>,
+	// invalid, pending, unchanged, updated or failed
+	pub status: String,
+	pub error: Option<String>,
+	pub updated_ts: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable, Debug, Clone, PartialEq)]
+#[diesel(table_name = avito_price_update_items)]
+pub struct CreateAvitoPriceUpdateItem {
+	pub job_id: Uuid,
+	pub line: i32,
+	pub item_id: String,
+	pub old_price: Option<f64>,
+	pub new_price: Option<f64>,
+	pub status: String,
+	pub error: Option<String>,
+}
+
+// One row of a JSON price list; ids and prices may come as numbers or strings
+#[derive(Debug, Deserialize)]
+pub struct PriceUpdateInputRow {
+	pub item_id: serde_json::Value,
+	pub price: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PriceUpdateBody {
+	pub items: Vec<PriceUpdateInputRow>,
+}
+
+// Item of a price update as shown in the preview and the job status
+#[derive(Debug, Serialize)]
+pub struct PriceUpdateDiff {
+	pub line: i32,
+	pub item_id: String,
+	pub old_price: Option<f64>,
+	pub new_price: Option<f64>,
+	pub change: Option<f64>,
+	pub change_percent: Option<f64>,
+	pub status: String,
+	pub error: Option<String>,
+}
+
+impl From<AvitoPriceUpdateItem> for PriceUpdateDiff {
+	fn from(item: AvitoPriceUpdateItem) -> Self {
+		let change = item
+			.old_price
+			.zip(item.new_price)
+			.map(|(old, new)| new - old);
+		let change_percent = item
+			.old_price
+			.zip(change)
+			.filter(|(old, _)| *old != 0.0)
+			.map(|(old, change)| (change / old * 10000.0).round() / 100.0);
+
+		Self {
+			line: item.line,
+			item_id: item.item_id,
+			old_price: item.old_price,
+			new_price: item.new_price,
+			change,
+			change_percent,
+			status: item.status,
+			error: item.error,
+		}
+	}
+}
@@ -0,0 +1,111 @@
+use crate::schema::{avito_repricing_decisions, avito_repricing_rules};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = avito_repricing_rules)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AvitoRepricingRule {
+	pub rule_id: Uuid,
+	pub ad_id: Uuid,
+	pub avito_request_id: Uuid,
+	// min, median or average
+	pub strategy: String,
+	pub offset_percent: f64,
+	pub offset_amount: f64,
+	pub floor_price: f64,
+	pub ceiling_price: Option<f64>,
+	pub max_change_percent: Option<f64>,
+	pub min_competitors: i32,
+	pub exclude_promoted: bool,
+	pub require_approval: bool,
+	pub interval_minutes: i32,
+	pub enabled: bool,
+	pub last_run_ts: Option<DateTime<Utc>>,
+	pub next_run_ts: Option<DateTime<Utc>>,
+	pub created_ts: DateTime<Utc>,
+	pub updated_ts: DateTime<Utc>,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = avito_repricing_rules)]
+#[diesel(treat_none_as_null = true)]
+pub struct UpsertAvitoRepricingRule {
+	pub ad_id: Uuid,
+	pub avito_request_id: Uuid,
+	pub strategy: String,
+	pub offset_percent: f64,
+	pub offset_amount: f64,
+	pub floor_price: f64,
+	pub ceiling_price: Option<f64>,
+	pub max_change_percent: Option<f64>,
+	pub min_competitors: i32,
+	pub exclude_promoted: bool,
+	pub require_approval: bool,
+	pub interval_minutes: i32,
+	pub enabled: bool,
+	pub next_run_ts: Option<DateTime<Utc>>,
+	pub updated_ts: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct AvitoRepricingRuleRequest {
+	pub avito_request_id: Uuid,
+	pub strategy: Option<String>,
+	pub offset_percent: Option<f64>,
+	pub offset_amount: Option<f64>,
+	pub floor_price: f64,
+	pub ceiling_price: Option<f64>,
+	pub max_change_percent: Option<f64>,
+	pub min_competitors: Option<i32>,
+	pub exclude_promoted: Option<bool>,
+	// Proposed prices wait for approval unless this is false
+	pub require_approval: Option<bool>,
+	pub interval_minutes: Option<i32>,
+	pub enabled: Option<bool>,
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = avito_repricing_decisions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AvitoRepricingDecision {
+	pub decision_id: Uuid,
+	pub rule_id: Uuid,
+	pub ad_id: Uuid,
+	pub avito_item_id: Option<String>,
+	pub competitors_count: i32,
+	pub reference_price: Option<f64>,
+	pub current_price: Option<f64>,
+	pub proposed_price: Option<f64>,
+	// skipped, pending, approved, applied, failed, rejected or superseded
+	pub status: String,
+	pub reason: Option<String>,
+	pub decided_by: Option<Uuid>,
+	pub created_ts: DateTime<Utc>,
+	pub decided_ts: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = avito_repricing_decisions)]
+pub struct CreateAvitoRepricingDecision {
+	pub rule_id: Uuid,
+	pub ad_id: Uuid,
+	pub avito_item_id: Option<String>,
+	pub competitors_count: i32,
+	pub reference_price: Option<f64>,
+	pub current_price: Option<f64>,
+	pub proposed_price: Option<f64>,
+	pub status: String,
+	pub reason: Option<String>,
+	pub decided_ts: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetRepricingDecisionsParams {
+	pub page: Option<u32>,
+	pub limit: Option<u32>,
+	pub status: Option<String>,
+	pub ad_id: Option<Uuid>,
+}
@@ -0,0 +1,13 @@
+use crate::schema::login_attempts;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+
+#[derive(Queryable, Selectable, Insertable, AsChangeset, Debug, Clone)]
+#[diesel(table_name = login_attempts)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct LoginAttempt {
+	pub email: String,
+	pub failed_count: i32,
+	pub last_failed_ts: DateTime<Utc>,
+	pub locked_until: Option<DateTime<Utc>>,
+}
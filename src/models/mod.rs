@@ -3,12 +3,22 @@ pub mod avito_ad_field_values;
 pub mod avito_ad_fields;
 pub mod avito_ads;
 pub mod avito_analytics_ads;
+pub mod avito_autocatalog;
+pub mod avito_balance;
 pub mod avito_client_types;
+pub mod avito_feed_imports;
 pub mod avito_feed_responses;
 pub mod avito_feeds;
+pub mod avito_item_stats;
+pub mod avito_item_syncs;
+pub mod avito_price_updates;
+pub mod avito_repricing;
 pub mod avito_request_progress;
 pub mod avito_requests;
+pub mod login_attempts;
 pub mod pagination;
+pub mod user_sessions;
+pub mod user_tokens;
 pub mod users;
 
 pub use self::avito_accounts::*;
@@ -16,10 +26,20 @@ pub use self::avito_ad_field_values::*;
 pub use self::avito_ad_fields::*;
 pub use self::avito_ads::*;
 pub use self::avito_analytics_ads::*;
+pub use self::avito_autocatalog::*;
+pub use self::avito_balance::*;
 pub use self::avito_client_types::*;
+pub use self::avito_feed_imports::*;
 pub use self::avito_feed_responses::*;
 pub use self::avito_feeds::*;
+pub use self::avito_item_stats::*;
+pub use self::avito_item_syncs::*;
+pub use self::avito_price_updates::*;
+pub use self::avito_repricing::*;
 pub use self::avito_request_progress::*;
 pub use self::avito_requests::*;
+pub use self::login_attempts::*;
 pub use self::pagination::*;
+pub use self::user_sessions::*;
+pub use self::user_tokens::*;
 pub use self::users::*;
@@ -0,0 +1,31 @@
+use crate::schema::user_sessions;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = user_sessions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct UserSession {
+	pub session_id: Uuid,
+	pub user_id: Uuid,
+	#[serde(skip_serializing)]
+	pub refresh_token_hash: String,
+	pub created_ts: DateTime<Utc>,
+	pub refreshed_ts: Option<DateTime<Utc>>,
+	pub expires_ts: DateTime<Utc>,
+	pub revoked_ts: Option<DateTime<Utc>>,
+	pub revoke_reason: Option<String>,
+	// Hash of the token the last refresh replaced
+	#[serde(skip_serializing)]
+	pub previous_token_hash: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = user_sessions)]
+pub struct CreateUserSession {
+	pub user_id: Uuid,
+	pub refresh_token_hash: String,
+	pub expires_ts: DateTime<Utc>,
+}
@@ -0,0 +1,13 @@
+use crate::schema::user_tokens;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+#[derive(Insertable)]
+#[diesel(table_name = user_tokens)]
+pub struct CreateUserToken {
+	pub user_id: Uuid,
+	pub purpose: String,
+	pub token_hash: String,
+	pub expires_ts: DateTime<Utc>,
+}
@@ -1,4 +1,5 @@
 use crate::schema::users;
+use crate::utils::password::PasswordPolicy;
 use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
@@ -39,13 +40,14 @@ pub struct CreateUser {
 pub struct UpdateUser {
 	pub name: Option<String>,
 	pub email: Option<String>,
-	pub role: Option<String>,
 	pub photo: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TokenClaims {
 	pub sub: String,
+	// Session the token was issued for, so revoking the session rejects the token
+	pub sid: String,
 	pub exp: usize,
 	pub iat: usize,
 }
@@ -64,6 +66,32 @@ pub struct RegisterRequest {
 	pub password_confirm: String,
 }
 
+impl RegisterRequest {
+	pub fn validate(&self, policy: &PasswordPolicy) -> Result<(), String> {
+		if self.email.trim().is_empty() {
+			return Err("Email is required".to_string());
+		}
+		policy.check(&self.password, &self.password_confirm)
+	}
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EmailRequest {
+	pub email: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ConfirmTokenRequest {
+	pub token: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ResetPasswordRequest {
+	pub token: String,
+	pub password: String,
+	pub password_confirm: String,
+}
+
 #[derive(Serialize)]
 pub struct AuthResponse {
 	pub status: String,
@@ -0,0 +1,593 @@
+use std::collections::HashSet;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::ErrorInternalServerError;
+use actix_web::{web, Error, HttpMessage, HttpResponse};
+use actix_web_grants::authorities::{AuthDetails, AuthoritiesCheck};
+use diesel::prelude::*;
+use futures::future::LocalBoxFuture;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::jwt_auth::authenticate;
+use crate::AppState;
+
+// Values of `users.role`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+	User,
+	Admin,
+}
+
+impl Role {
+	pub const ALL: [Role; 2] = [Role::User, Role::Admin];
+
+	pub fn parse(role: &str) -> Option<Role> {
+		match role {
+			"user" => Some(Role::User),
+			"admin" => Some(Role::Admin),
+			_ => None,
+		}
+	}
+
+	// Users created before roles were enforced may have no role; they are plain users
+	pub fn from_db(role: Option<&str>) -> Option<Role> {
+		role.map_or(Some(Role::User), Role::parse)
+	}
+
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Role::User => "user",
+			Role::Admin => "admin",
+		}
+	}
+
+	pub fn permissions(&self) -> &'static [Permission] {
+		use Permission::*;
+
+		match self {
+			Role::User => &[
+				ReadProfile,
+				EditProfile,
+				ManageAvitoAccounts,
+				UseAvitoApi,
+				ManageFeeds,
+				ManageAds,
+				ManageRequests,
+				ManageRepricing,
+				ReadAutocatalog,
+				UseAiProcessing,
+			],
+			Role::Admin => &[
+				ReadProfile,
+				EditProfile,
+				ManageAvitoAccounts,
+				UseAvitoApi,
+				ManageFeeds,
+				ManageAds,
+				ManageRequests,
+				ManageRepricing,
+				ReadAutocatalog,
+				UseAiProcessing,
+				ListUsers,
+				ManageRoles,
+				ReadAllRequests,
+				ImportAutocatalog,
+				RotateEncryptionKeys,
+			],
+		}
+	}
+}
+
+// What a route requires; every protected route declares one with `RequirePermission`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+	ReadProfile,
+	EditProfile,
+	ManageAvitoAccounts,
+	UseAvitoApi,
+	ManageFeeds,
+	ManageAds,
+	ManageRequests,
+	ManageRepricing,
+	ReadAutocatalog,
+	UseAiProcessing,
+	// Admin only
+	ListUsers,
+	ManageRoles,
+	ReadAllRequests,
+	ImportAutocatalog,
+	RotateEncryptionKeys,
+}
+
+// Permissions of the caller for `GrantsMiddleware`, from the role of the user behind the
+// access token. Requests without a valid token get none; public routes do not need any.
+pub async fn extract_permissions(req: &ServiceRequest) -> Result<HashSet<Permission>, Error> {
+	let Ok(user) = authenticate(req.request()) else {
+		return Ok(HashSet::new());
+	};
+
+	let data = req
+		.app_data::<web::Data<AppState>>()
+		.ok_or_else(|| ErrorInternalServerError("Application state is not configured"))?;
+	let mut conn = data.db.get().map_err(ErrorInternalServerError)?;
+	let role = crate::schema::users::table
+		.find(user.user_id)
+		.select(crate::schema::users::role)
+		.first::<Option<String>>(&mut conn)
+		.optional()
+		.map_err(ErrorInternalServerError)?;
+
+	Ok(role
+		.and_then(|role| Role::from_db(role.as_deref()))
+		.map(|role| role.permissions().iter().copied().collect())
+		.unwrap_or_default())
+}
+
+// Route middleware that lets a request through when its caller holds the permission.
+// It runs before the handler's extractors, so a denied request never reaches them.
+pub struct RequirePermission(pub Permission);
+
+impl<S, B> Transform<S, ServiceRequest> for RequirePermission
+where
+	S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+	B: 'static,
+{
+	type Response = ServiceResponse<EitherBody<B>>;
+	type Error = Error;
+	type Transform = RequirePermissionMiddleware<S>;
+	type InitError = ();
+	type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+	fn new_transform(&self, service: S) -> Self::Future {
+		ready(Ok(RequirePermissionMiddleware {
+			service: Rc::new(service),
+			permission: self.0,
+		}))
+	}
+}
+
+pub struct RequirePermissionMiddleware<S> {
+	service: Rc<S>,
+	permission: Permission,
+}
+
+impl<S, B> Service<ServiceRequest> for RequirePermissionMiddleware<S>
+where
+	S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+	B: 'static,
+{
+	type Response = ServiceResponse<EitherBody<B>>;
+	type Error = Error;
+	type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+	forward_ready!(service);
+
+	fn call(&self, req: ServiceRequest) -> Self::Future {
+		let denied = match authenticate(req.request()) {
+			Err(e) => Some(e.into_response()),
+			Ok(_) => {
+				let allowed = req
+					.extensions()
+					.get::<AuthDetails<Permission>>()
+					.is_some_and(|details| details.has_authority(&self.permission));
+				(!allowed).then(|| access_denied(self.permission))
+			}
+		};
+
+		if let Some(response) = denied {
+			return Box::pin(async move { Ok(req.into_response(response).map_into_right_body()) });
+		}
+
+		let service = self.service.clone();
+		Box::pin(async move {
+			service
+				.call(req)
+				.await
+				.map(ServiceResponse::map_into_left_body)
+		})
+	}
+}
+
+fn access_denied(permission: Permission) -> HttpResponse {
+	HttpResponse::Forbidden().json(json!({
+		"status": "fail",
+		"message": "You don't have permission to access this resource",
+		"required_permission": permission
+	}))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::jwt_auth::{AuthError, Authentication, JwtMiddleware};
+	use actix_web::http::{Method, StatusCode};
+	use actix_web::test::{call_service, init_service, TestRequest};
+	use actix_web::App;
+	use actix_web_grants::GrantsMiddleware;
+	use uuid::Uuid;
+
+	use Permission::*;
+
+	// Stands in for `extract_permissions`: the role comes from a header, no database involved
+	async fn extract_test_permissions(req: &ServiceRequest) -> Result<HashSet<Permission>, Error> {
+		let role = req
+			.headers()
+			.get("x-test-role")
+			.and_then(|role| role.to_str().ok())
+			.and_then(Role::parse);
+
+		let Some(role) = role else {
+			req.extensions_mut()
+				.insert(Authentication(Err(AuthError::Unauthorized(
+					"You are not logged in, please log in",
+				))));
+			return Ok(HashSet::new());
+		};
+
+		req.extensions_mut()
+			.insert(Authentication(Ok(JwtMiddleware {
+				user_id: Uuid::nil(),
+				session_id: Uuid::nil(),
+			})));
+		Ok(role.permissions().iter().copied().collect())
+	}
+
+	const ID: &str = "00000000-0000-0000-0000-000000000001";
+
+	// Every route under /api with the permission it requires; None for public routes
+	fn routes() -> Vec<(Method, String, Option<Permission>)> {
+		let routes: &[(Method, &str, Option<Permission>)] = &[
+			(Method::POST, "/auth/login", None),
+			(Method::POST, "/auth/register", None),
+			(Method::POST, "/auth/refresh", None),
+			(Method::POST, "/auth/logout", None),
+			(Method::POST, "/auth/verify_email/request", None),
+			(Method::POST, "/auth/verify_email/confirm", None),
+			(Method::POST, "/auth/password_reset/request", None),
+			(Method::POST, "/auth/password_reset/confirm", None),
+			(Method::GET, "/auth/role", Some(ReadProfile)),
+			(Method::POST, "/auth/role", Some(ManageRoles)),
+			(Method::GET, "/users/me", Some(ReadProfile)),
+			(Method::PATCH, "/users/me", Some(EditProfile)),
+			(Method::DELETE, "/users/me", Some(EditProfile)),
+			(Method::GET, "/users", Some(ListUsers)),
+			(Method::GET, "/users/{id}", Some(ListUsers)),
+			(Method::GET, "/avito/accounts", Some(ManageAvitoAccounts)),
+			(Method::POST, "/avito/accounts", Some(ManageAvitoAccounts)),
+			(
+				Method::GET,
+				"/avito/accounts/{id}",
+				Some(ManageAvitoAccounts),
+			),
+			(
+				Method::PUT,
+				"/avito/accounts/{id}",
+				Some(ManageAvitoAccounts),
+			),
+			(
+				Method::DELETE,
+				"/avito/accounts/{id}",
+				Some(ManageAvitoAccounts),
+			),
+			(
+				Method::POST,
+				"/avito/accounts/{id}/check",
+				Some(ManageAvitoAccounts),
+			),
+			(
+				Method::POST,
+				"/avito/accounts/encryption/rotate",
+				Some(RotateEncryptionKeys),
+			),
+			(
+				Method::GET,
+				"/avito/accounts/{id}/balance_alert",
+				Some(UseAvitoApi),
+			),
+			(
+				Method::PUT,
+				"/avito/accounts/{id}/balance_alert",
+				Some(UseAvitoApi),
+			),
+			(
+				Method::GET,
+				"/avito/accounts/{id}/balance_history",
+				Some(UseAvitoApi),
+			),
+			(
+				Method::GET,
+				"/avito/accounts/{id}/item_stats",
+				Some(UseAvitoApi),
+			),
+			(
+				Method::POST,
+				"/avito/accounts/{id}/item_stats/collect",
+				Some(UseAvitoApi),
+			),
+			(
+				Method::GET,
+				"/avito/accounts/{id}/item_syncs",
+				Some(UseAvitoApi),
+			),
+			(
+				Method::POST,
+				"/avito/accounts/{id}/sync_items",
+				Some(UseAvitoApi),
+			),
+			(
+				Method::POST,
+				"/avito/accounts/{id}/price_updates",
+				Some(UseAvitoApi),
+			),
+			(
+				Method::POST,
+				"/avito/accounts/{id}/price_updates/upload",
+				Some(UseAvitoApi),
+			),
+			(Method::GET, "/avito/price_updates/{id}", Some(UseAvitoApi)),
+			(
+				Method::GET,
+				"/avito/price_updates/{id}/report",
+				Some(UseAvitoApi),
+			),
+			(
+				Method::POST,
+				"/avito/price_updates/{id}/start",
+				Some(UseAvitoApi),
+			),
+			(Method::POST, "/avito/get_token", Some(UseAvitoApi)),
+			(Method::POST, "/avito/get_balance", Some(UseAvitoApi)),
+			(
+				Method::POST,
+				"/avito/get_categories_tree",
+				Some(UseAvitoApi),
+			),
+			(
+				Method::POST,
+				"/avito/get_category_fields",
+				Some(UseAvitoApi),
+			),
+			(Method::POST, "/avito/get_item_analytics", Some(UseAvitoApi)),
+			(Method::POST, "/avito/get_items", Some(UseAvitoApi)),
+			(Method::POST, "/avito/get_user_profile", Some(UseAvitoApi)),
+			(Method::POST, "/avito/update_price", Some(UseAvitoApi)),
+			(
+				Method::POST,
+				"/avito/category_cache/refresh",
+				Some(UseAvitoApi),
+			),
+			(Method::POST, "/avito/feeds", Some(ManageFeeds)),
+			(Method::GET, "/avito/feeds", Some(ManageFeeds)),
+			(Method::POST, "/avito/feeds/create", Some(ManageFeeds)),
+			(Method::GET, "/avito/feeds/{id}", Some(ManageFeeds)),
+			(Method::PATCH, "/avito/feeds/{id}", Some(ManageFeeds)),
+			(Method::DELETE, "/avito/feeds/{id}", Some(ManageFeeds)),
+			(Method::GET, "/avito/feeds/{id}/xml", Some(ManageFeeds)),
+			(
+				Method::POST,
+				"/avito/feeds/{id}/validate",
+				Some(ManageFeeds),
+			),
+			(Method::GET, "/avito/feeds/{id}/imports", Some(ManageFeeds)),
+			(
+				Method::GET,
+				"/avito/feeds/{id}/import_schedule",
+				Some(ManageFeeds),
+			),
+			(
+				Method::PUT,
+				"/avito/feeds/{id}/import_schedule",
+				Some(ManageFeeds),
+			),
+			(
+				Method::DELETE,
+				"/avito/feeds/{id}/import_schedule",
+				Some(ManageFeeds),
+			),
+			(Method::POST, "/avito/feeds/import_xml", Some(ManageFeeds)),
+			(
+				Method::POST,
+				"/avito/feeds/import_xml/upload",
+				Some(ManageFeeds),
+			),
+			(Method::POST, "/avito/ads/{id}/validate", Some(ManageFeeds)),
+			(Method::GET, "/avito_ads", Some(ManageAds)),
+			(Method::POST, "/avito_ads", Some(ManageAds)),
+			(Method::GET, "/avito_ads/{id}", Some(ManageAds)),
+			(Method::PUT, "/avito_ads/{id}", Some(ManageAds)),
+			(Method::PATCH, "/avito_ads/{id}", Some(ManageAds)),
+			(Method::DELETE, "/avito_ads/{id}", Some(ManageAds)),
+			(Method::POST, "/avito/ads/create", Some(ManageAds)),
+			(Method::POST, "/avito/ad_fields", Some(ManageAds)),
+			(Method::GET, "/avito/ad_fields/{id}", Some(ManageAds)),
+			(Method::PATCH, "/avito/ad_fields/{id}", Some(ManageAds)),
+			(Method::DELETE, "/avito/ad_fields/{id}", Some(ManageAds)),
+			(Method::POST, "/avito/ad_field_values", Some(ManageAds)),
+			(Method::GET, "/avito/ad_field_values/{id}", Some(ManageAds)),
+			(
+				Method::PATCH,
+				"/avito/ad_field_values/{id}",
+				Some(ManageAds),
+			),
+			(
+				Method::DELETE,
+				"/avito/ad_field_values/{id}",
+				Some(ManageAds),
+			),
+			(Method::GET, "/avito_requests", Some(ManageRequests)),
+			(Method::POST, "/avito_requests", Some(ManageRequests)),
+			(Method::GET, "/avito_requests/all", Some(ReadAllRequests)),
+			(Method::GET, "/avito_requests/{id}", Some(ManageRequests)),
+			(Method::PATCH, "/avito_requests/{id}", Some(ManageRequests)),
+			(Method::DELETE, "/avito_requests/{id}", Some(ManageRequests)),
+			(
+				Method::GET,
+				"/avito_requests/{id}/ads",
+				Some(ManageRequests),
+			),
+			(
+				Method::GET,
+				"/avito_requests/{id}/ads/csv",
+				Some(ManageRequests),
+			),
+			(
+				Method::GET,
+				"/avito/ads/{id}/repricing_rule",
+				Some(ManageRepricing),
+			),
+			(
+				Method::PUT,
+				"/avito/ads/{id}/repricing_rule",
+				Some(ManageRepricing),
+			),
+			(
+				Method::DELETE,
+				"/avito/ads/{id}/repricing_rule",
+				Some(ManageRepricing),
+			),
+			(
+				Method::POST,
+				"/avito/ads/{id}/repricing_rule/run",
+				Some(ManageRepricing),
+			),
+			(
+				Method::GET,
+				"/avito/repricing_decisions",
+				Some(ManageRepricing),
+			),
+			(
+				Method::POST,
+				"/avito/repricing_decisions/{id}/approve",
+				Some(ManageRepricing),
+			),
+			(
+				Method::POST,
+				"/avito/repricing_decisions/{id}/reject",
+				Some(ManageRepricing),
+			),
+			(
+				Method::GET,
+				"/avito/autocatalog/makes",
+				Some(ReadAutocatalog),
+			),
+			(
+				Method::GET,
+				"/avito/autocatalog/models",
+				Some(ReadAutocatalog),
+			),
+			(
+				Method::GET,
+				"/avito/autocatalog/generations",
+				Some(ReadAutocatalog),
+			),
+			(
+				Method::GET,
+				"/avito/autocatalog/modifications",
+				Some(ReadAutocatalog),
+			),
+			(
+				Method::POST,
+				"/avito/autocatalog/import",
+				Some(ImportAutocatalog),
+			),
+			(
+				Method::POST,
+				"/avito/autocatalog/import/upload",
+				Some(ImportAutocatalog),
+			),
+			(Method::GET, "/ws", Some(ReadProfile)),
+			(Method::POST, "/ai_title_processing", Some(UseAiProcessing)),
+			(
+				Method::POST,
+				"/ai_description_processing",
+				Some(UseAiProcessing),
+			),
+		];
+
+		routes
+			.iter()
+			.map(|(method, path, permission)| {
+				(
+					method.clone(),
+					format!("/api{}", path.replace("{id}", ID)),
+					*permission,
+				)
+			})
+			.collect()
+	}
+
+	#[actix_web::test]
+	async fn test_route_access_matrix() {
+		let app = init_service(
+			App::new()
+				.wrap(GrantsMiddleware::with_extractor(extract_test_permissions))
+				.configure(crate::controllers::config::config),
+		)
+		.await;
+
+		for (method, path, permission) in routes() {
+			for role in [None, Some(Role::User), Some(Role::Admin)] {
+				let mut req = TestRequest::default().method(method.clone()).uri(&path);
+				if let Some(role) = role {
+					req = req.insert_header(("x-test-role", role.as_str()));
+				}
+				let status = call_service(&app, req.to_request()).await.status();
+
+				let expected_denial = match (permission, role) {
+					(None, _) => None,
+					(Some(_), None) => Some(StatusCode::UNAUTHORIZED),
+					(Some(permission), Some(role)) => {
+						(!role.permissions().contains(&permission)).then_some(StatusCode::FORBIDDEN)
+					}
+				};
+				match expected_denial {
+					Some(expected) => {
+						assert_eq!(status, expected, "{} {} as {:?}", method, path, role)
+					}
+					// Let through to the handler, whatever it makes of the empty request
+					None => assert!(
+						status != StatusCode::UNAUTHORIZED
+							&& status != StatusCode::FORBIDDEN
+							&& status != StatusCode::NOT_FOUND,
+						"{} {} as {:?} got {}",
+						method,
+						path,
+						role,
+						status
+					),
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn test_role_permissions() {
+		assert_eq!(Role::from_db(None), Some(Role::User));
+		assert_eq!(Role::from_db(Some("admin")), Some(Role::Admin));
+		assert_eq!(Role::from_db(Some("superuser")), None);
+
+		for role in Role::ALL {
+			assert_eq!(Role::parse(role.as_str()), Some(role));
+		}
+
+		// Admins can do everything users can
+		for permission in Role::User.permissions() {
+			assert!(Role::Admin.permissions().contains(permission));
+		}
+		for permission in [
+			ListUsers,
+			ManageRoles,
+			ReadAllRequests,
+			ImportAutocatalog,
+			RotateEncryptionKeys,
+		] {
+			assert!(!Role::User.permissions().contains(&permission));
+			assert!(Role::Admin.permissions().contains(&permission));
+		}
+	}
+}
@@ -0,0 +1,356 @@
+use std::future::{ready, Ready};
+use std::rc::Rc;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sql_types::{Integer, Text, Timestamptz};
+use futures::future::LocalBoxFuture;
+use serde_json::json;
+use tokio::time::sleep;
+
+use crate::config::Config;
+use crate::jwt_auth::authenticate;
+use crate::AppState;
+
+// Routes that share a limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteGroup {
+	// Login, registration and the other /api/auth endpoints, limited per client address
+	Auth,
+	// Everything else under /api, limited per user, or per address without a valid token
+	Api,
+}
+
+impl RouteGroup {
+	pub fn for_path(path: &str) -> Option<RouteGroup> {
+		if path.starts_with("/api/auth/") {
+			Some(RouteGroup::Auth)
+		} else if path.starts_with("/api/") {
+			Some(RouteGroup::Api)
+		} else {
+			None
+		}
+	}
+
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			RouteGroup::Auth => "auth",
+			RouteGroup::Api => "api",
+		}
+	}
+
+	pub fn window(&self, config: &Config) -> Option<FixedWindow> {
+		let (requests, window_secs) = match self {
+			RouteGroup::Auth => (
+				config.rate_limit_auth_requests,
+				config.rate_limit_auth_window_secs,
+			),
+			RouteGroup::Api => (
+				config.rate_limit_api_requests,
+				config.rate_limit_api_window_secs,
+			),
+		};
+		(requests > 0 && window_secs > 0).then_some(FixedWindow {
+			requests,
+			window_secs: window_secs as i64,
+		})
+	}
+}
+
+// At most `requests` per client in each window, the windows being aligned to the epoch so
+// every instance agrees on them
+#[derive(Debug, Clone, Copy)]
+pub struct FixedWindow {
+	pub requests: u32,
+	pub window_secs: i64,
+}
+
+impl FixedWindow {
+	pub fn window_start(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+		let secs = now.timestamp();
+		Utc.timestamp_opt(secs - secs.rem_euclid(self.window_secs), 0)
+			.unwrap()
+	}
+
+	pub fn retry_after(&self, window_start: DateTime<Utc>, now: DateTime<Utc>) -> Duration {
+		window_start + Duration::seconds(self.window_secs) - now
+	}
+}
+
+#[derive(QueryableByName)]
+struct Counter {
+	#[diesel(sql_type = Integer)]
+	hits: i32,
+}
+
+// Count a request in the window and return the requests seen in it so far. One statement,
+// so concurrent requests, from any instance, all get counted.
+fn count_request(
+	conn: &mut PgConnection,
+	bucket_key: &str,
+	window_start: DateTime<Utc>,
+) -> QueryResult<i32> {
+	diesel::sql_query(
+		"INSERT INTO rate_limit_counters (bucket_key, window_start, hits) VALUES ($1, $2, 1)
+		ON CONFLICT (bucket_key) DO UPDATE SET
+			hits = CASE WHEN rate_limit_counters.window_start = EXCLUDED.window_start
+				THEN rate_limit_counters.hits + 1 ELSE 1 END,
+			window_start = EXCLUDED.window_start
+		RETURNING hits",
+	)
+	.bind::<Text, _>(bucket_key)
+	.bind::<Timestamptz, _>(window_start)
+	.get_result::<Counter>(conn)
+	.map(|counter| counter.hits)
+}
+
+// Whole seconds to wait, as sent in Retry-After
+pub fn retry_after_secs(wait: Duration) -> i64 {
+	let millis = wait.num_milliseconds().max(0);
+	((millis + 999) / 1000).max(1)
+}
+
+pub fn too_many_requests(wait: Duration, message: &str) -> HttpResponse {
+	let retry_after = retry_after_secs(wait);
+	HttpResponse::TooManyRequests()
+		.insert_header((RETRY_AFTER, retry_after.to_string()))
+		.json(json!({
+			"status": "fail",
+			"message": message,
+			"retry_after_secs": retry_after
+		}))
+}
+
+pub fn client_ip(req: &HttpRequest, trust_forwarded_for: bool) -> String {
+	let info = req.connection_info();
+	let addr = if trust_forwarded_for {
+		info.realip_remote_addr()
+	} else {
+		info.peer_addr()
+	};
+	addr.unwrap_or("unknown").to_string()
+}
+
+// App middleware limiting requests per route group and client with counters in Postgres.
+// When the database can't be reached requests are let through rather than failed.
+pub struct RateLimit;
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+	S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+	B: 'static,
+{
+	type Response = ServiceResponse<EitherBody<B>>;
+	type Error = Error;
+	type Transform = RateLimitMiddleware<S>;
+	type InitError = ();
+	type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+	fn new_transform(&self, service: S) -> Self::Future {
+		ready(Ok(RateLimitMiddleware {
+			service: Rc::new(service),
+		}))
+	}
+}
+
+pub struct RateLimitMiddleware<S> {
+	service: Rc<S>,
+}
+
+impl<S> RateLimitMiddleware<S> {
+	// How long the request has to wait, or None to let it through
+	fn check(req: &ServiceRequest) -> Option<Duration> {
+		let group = RouteGroup::for_path(req.path())?;
+		let data = req.app_data::<web::Data<AppState>>()?;
+		let window = group.window(&data.env)?;
+
+		let client = match group {
+			RouteGroup::Api => authenticate(req.request())
+				.ok()
+				.map(|user| format!("user:{}", user.user_id)),
+			RouteGroup::Auth => None,
+		}
+		.unwrap_or_else(|| {
+			format!(
+				"ip:{}",
+				client_ip(req.request(), data.env.trust_forwarded_for)
+			)
+		});
+		let bucket_key = format!("{}:{}", group.as_str(), client);
+
+		let now = Utc::now();
+		let window_start = window.window_start(now);
+		let hits = data
+			.db
+			.get()
+			.map_err(|e| e.to_string())
+			.and_then(|mut conn| {
+				count_request(&mut conn, &bucket_key, window_start).map_err(|e| e.to_string())
+			});
+
+		match hits {
+			Ok(hits) if hits > window.requests as i32 => {
+				Some(window.retry_after(window_start, now))
+			}
+			Ok(_) => None,
+			Err(e) => {
+				log::error!("Failed to count a request of {}: {}", bucket_key, e);
+				None
+			}
+		}
+	}
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+	S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+	B: 'static,
+{
+	type Response = ServiceResponse<EitherBody<B>>;
+	type Error = Error;
+	type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+	forward_ready!(service);
+
+	fn call(&self, req: ServiceRequest) -> Self::Future {
+		if let Some(wait) = Self::check(&req) {
+			let response = too_many_requests(wait, "Too many requests, please try again later");
+			return Box::pin(async move { Ok(req.into_response(response).map_into_right_body()) });
+		}
+
+		let service = self.service.clone();
+		Box::pin(async move {
+			service
+				.call(req)
+				.await
+				.map(ServiceResponse::map_into_left_body)
+		})
+	}
+}
+
+// Drop counters of past windows and failed logins that no longer count, every hour
+pub async fn start_rate_limit_cleanup(
+	db_pool: Pool<ConnectionManager<PgConnection>>,
+	config: Config,
+) {
+	let counter_retention = Duration::seconds(
+		config
+			.rate_limit_auth_window_secs
+			.max(config.rate_limit_api_window_secs) as i64,
+	);
+	let attempt_retention = Duration::minutes(config.login_lockout_minutes);
+
+	loop {
+		sleep(std::time::Duration::from_secs(60 * 60)).await;
+
+		let now = Utc::now();
+		let deleted = db_pool
+			.get()
+			.map_err(|e| e.to_string())
+			.and_then(|mut conn| {
+				let counters = diesel::delete(crate::schema::rate_limit_counters::table.filter(
+					crate::schema::rate_limit_counters::window_start.lt(now - counter_retention),
+				))
+				.execute(&mut conn)
+				.map_err(|e| e.to_string())?;
+
+				let attempts = diesel::delete(
+					crate::schema::login_attempts::table
+						.filter(
+							crate::schema::login_attempts::last_failed_ts
+								.lt(now - attempt_retention),
+						)
+						.filter(
+							crate::schema::login_attempts::locked_until
+								.is_null()
+								.or(crate::schema::login_attempts::locked_until.lt(now)),
+						),
+				)
+				.execute(&mut conn)
+				.map_err(|e| e.to_string())?;
+
+				Ok((counters, attempts))
+			});
+
+		match deleted {
+			Ok((counters, attempts)) => log::debug!(
+				"Rate limit cleanup removed {} counters and {} login attempts",
+				counters,
+				attempts
+			),
+			Err(e) => log::error!("Rate limit cleanup failed: {}", e),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use actix_web::test::TestRequest;
+
+	#[test]
+	fn test_route_groups() {
+		assert_eq!(
+			RouteGroup::for_path("/api/auth/login"),
+			Some(RouteGroup::Auth)
+		);
+		assert_eq!(
+			RouteGroup::for_path("/api/auth/password_reset/request"),
+			Some(RouteGroup::Auth)
+		);
+		assert_eq!(
+			RouteGroup::for_path("/api/avito/accounts"),
+			Some(RouteGroup::Api)
+		);
+		assert_eq!(RouteGroup::for_path("/api/authors"), Some(RouteGroup::Api));
+		assert_eq!(RouteGroup::for_path("/"), None);
+	}
+
+	#[test]
+	fn test_fixed_window() {
+		let window = FixedWindow {
+			requests: 10,
+			window_secs: 60,
+		};
+		let now = Utc.timestamp_opt(1_700_000_030, 500_000_000).unwrap();
+
+		let start = window.window_start(now);
+		assert_eq!(start, Utc.timestamp_opt(1_699_999_980, 0).unwrap());
+		assert!(start <= now && now < start + Duration::seconds(60));
+		assert_eq!(window.window_start(start), start);
+
+		let wait = window.retry_after(start, now);
+		assert_eq!(now + wait, start + Duration::seconds(60));
+	}
+
+	#[test]
+	fn test_retry_after_rounds_up() {
+		assert_eq!(retry_after_secs(Duration::milliseconds(1)), 1);
+		assert_eq!(retry_after_secs(Duration::milliseconds(1500)), 2);
+		assert_eq!(retry_after_secs(Duration::seconds(30)), 30);
+		assert_eq!(retry_after_secs(Duration::seconds(-5)), 1);
+
+		let response = too_many_requests(Duration::milliseconds(2500), "Slow down");
+		assert_eq!(
+			response.status(),
+			actix_web::http::StatusCode::TOO_MANY_REQUESTS
+		);
+		assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "3");
+	}
+
+	#[test]
+	fn test_client_ip() {
+		let req = TestRequest::default()
+			.peer_addr("10.0.0.1:4000".parse().unwrap())
+			.insert_header(("x-forwarded-for", "203.0.113.7"))
+			.to_http_request();
+
+		assert_eq!(client_ip(&req, false), "10.0.0.1");
+		assert_eq!(client_ip(&req, true), "203.0.113.7");
+	}
+}
@@ -25,6 +25,8 @@ diesel::table! {
 		is_connected -> Nullable<Bool>,
 		created_ts -> Timestamp,
 		updated_ts -> Timestamp,
+		last_check_ts -> Nullable<Timestamptz>,
+		last_check_error -> Nullable<Text>,
 	}
 }
 
@@ -39,11 +41,81 @@ diesel::table! {
 	}
 }
 
+diesel::table! {
+	user_sessions (session_id) {
+		session_id -> Uuid,
+		user_id -> Uuid,
+		refresh_token_hash -> Text,
+		created_ts -> Timestamptz,
+		refreshed_ts -> Nullable<Timestamptz>,
+		expires_ts -> Timestamptz,
+		revoked_ts -> Nullable<Timestamptz>,
+		revoke_reason -> Nullable<Varchar>,
+		previous_token_hash -> Nullable<Text>,
+	}
+}
+
+diesel::table! {
+	user_tokens (token_id) {
+		token_id -> Uuid,
+		user_id -> Uuid,
+		purpose -> Varchar,
+		token_hash -> Text,
+		created_ts -> Timestamptz,
+		expires_ts -> Timestamptz,
+		used_ts -> Nullable<Timestamptz>,
+	}
+}
+
+diesel::table! {
+	rate_limit_counters (bucket_key) {
+		bucket_key -> Text,
+		window_start -> Timestamptz,
+		hits -> Integer,
+	}
+}
+
+diesel::table! {
+	login_attempts (email) {
+		email -> Text,
+		failed_count -> Integer,
+		last_failed_ts -> Timestamptz,
+		locked_until -> Nullable<Timestamptz>,
+	}
+}
+
+diesel::joinable!(user_sessions -> users (user_id));
+diesel::joinable!(user_tokens -> users (user_id));
 diesel::joinable!(avito_accounts -> users (user_id));
 diesel::joinable!(avito_ads -> avito_feeds (feed_id));
 diesel::joinable!(avito_feeds -> avito_accounts (account_id));
 diesel::joinable!(avito_requests -> users (user_id));
 
+diesel::table! {
+	avito_balance_history (history_id) {
+		history_id -> Uuid,
+		account_id -> Uuid,
+		balance -> Float8,
+		recorded_ts -> Timestamptz,
+	}
+}
+
+diesel::table! {
+	avito_balance_alerts (account_id) {
+		account_id -> Uuid,
+		threshold -> Float8,
+		webhook_url -> Nullable<Text>,
+		enabled -> Bool,
+		below_threshold -> Bool,
+		last_alert_ts -> Nullable<Timestamptz>,
+		created_ts -> Timestamptz,
+		updated_ts -> Timestamptz,
+	}
+}
+
+diesel::joinable!(avito_balance_history -> avito_accounts (account_id));
+diesel::joinable!(avito_balance_alerts -> avito_accounts (account_id));
+
 diesel::table! {
 	avito_feeds (feed_id) {
 		feed_id -> Uuid,
@@ -54,6 +126,79 @@ diesel::table! {
 	}
 }
 
+diesel::table! {
+	avito_price_update_jobs (job_id) {
+		job_id -> Uuid,
+		account_id -> Uuid,
+		user_id -> Uuid,
+		status -> Varchar,
+		items_total -> Integer,
+		items_updated -> Integer,
+		items_failed -> Integer,
+		items_skipped -> Integer,
+		error -> Nullable<Text>,
+		created_ts -> Timestamptz,
+		started_ts -> Nullable<Timestamptz>,
+		finished_ts -> Nullable<Timestamptz>,
+		heartbeat_ts -> Nullable<Timestamptz>,
+	}
+}
+
+diesel::table! {
+	avito_price_update_items (job_item_id) {
+		job_item_id -> Uuid,
+		job_id -> Uuid,
+		line -> Integer,
+		item_id -> Varchar,
+		old_price -> Nullable<Float8>,
+		new_price -> Nullable<Float8>,
+		status -> Varchar,
+		error -> Nullable<Text>,
+		updated_ts -> Nullable<Timestamptz>,
+	}
+}
+
+diesel::table! {
+	avito_repricing_rules (rule_id) {
+		rule_id -> Uuid,
+		ad_id -> Uuid,
+		avito_request_id -> Uuid,
+		strategy -> Varchar,
+		offset_percent -> Float8,
+		offset_amount -> Float8,
+		floor_price -> Float8,
+		ceiling_price -> Nullable<Float8>,
+		max_change_percent -> Nullable<Float8>,
+		min_competitors -> Integer,
+		exclude_promoted -> Bool,
+		require_approval -> Bool,
+		interval_minutes -> Integer,
+		enabled -> Bool,
+		last_run_ts -> Nullable<Timestamptz>,
+		next_run_ts -> Nullable<Timestamptz>,
+		created_ts -> Timestamptz,
+		updated_ts -> Timestamptz,
+	}
+}
+
+diesel::table! {
+	avito_repricing_decisions (decision_id) {
+		decision_id -> Uuid,
+		rule_id -> Uuid,
+		ad_id -> Uuid,
+		avito_item_id -> Nullable<Varchar>,
+		competitors_count -> Integer,
+		reference_price -> Nullable<Float8>,
+		current_price -> Nullable<Float8>,
+		proposed_price -> Nullable<Float8>,
+		status -> Varchar,
+		reason -> Nullable<Text>,
+		decided_by -> Nullable<Uuid>,
+		created_ts -> Timestamptz,
+		decided_ts -> Nullable<Timestamptz>,
+	}
+}
+
 diesel::table! {
 	avito_requests (request_id) {
 		request_id -> Uuid,
@@ -136,18 +281,186 @@ diesel::table! {
 	}
 }
 
+diesel::table! {
+	avito_feed_import_schedules (schedule_id) {
+		schedule_id -> Uuid,
+		feed_id -> Uuid,
+		source_url -> Text,
+		interval_minutes -> Nullable<Integer>,
+		cron_expression -> Nullable<Varchar>,
+		enabled -> Bool,
+		last_run_ts -> Nullable<Timestamptz>,
+		next_run_ts -> Nullable<Timestamptz>,
+		created_ts -> Timestamptz,
+		updated_ts -> Timestamptz,
+	}
+}
+
+diesel::table! {
+	avito_item_stats (account_id, item_id, stat_date) {
+		account_id -> Uuid,
+		item_id -> Varchar,
+		stat_date -> Date,
+		impressions -> Int8,
+		views -> Int8,
+		contacts -> Int8,
+		favorites -> Int8,
+		spending -> Float8,
+		metrics -> Jsonb,
+		collected_ts -> Timestamptz,
+	}
+}
+
+diesel::table! {
+	avito_item_syncs (sync_id) {
+		sync_id -> Uuid,
+		account_id -> Uuid,
+		status -> Varchar,
+		items_seen -> Integer,
+		ads_added -> Integer,
+		ads_updated -> Integer,
+		ads_unchanged -> Integer,
+		ads_removed -> Integer,
+		error -> Nullable<Text>,
+		started_ts -> Timestamptz,
+		finished_ts -> Nullable<Timestamptz>,
+	}
+}
+
+diesel::table! {
+	avito_feed_imports (import_id) {
+		import_id -> Uuid,
+		feed_id -> Uuid,
+		schedule_id -> Nullable<Uuid>,
+		source_url -> Text,
+		status -> Varchar,
+		ads_processed -> Integer,
+		ads_added -> Integer,
+		ads_updated -> Integer,
+		ads_unchanged -> Integer,
+		ads_deactivated -> Integer,
+		error -> Nullable<Text>,
+		started_ts -> Timestamptz,
+		finished_ts -> Nullable<Timestamptz>,
+	}
+}
+
+diesel::table! {
+	avito_category_tree_cache (cache_key) {
+		cache_key -> Varchar,
+		data -> Jsonb,
+		fetched_ts -> Timestamptz,
+		expires_ts -> Timestamptz,
+	}
+}
+
+diesel::table! {
+	avito_category_fields_cache (avito_slug) {
+		avito_slug -> Varchar,
+		data -> Jsonb,
+		fetched_ts -> Timestamptz,
+		expires_ts -> Timestamptz,
+	}
+}
+
+diesel::table! {
+	avito_field_values_cache (values_link) {
+		values_link -> Text,
+		data -> Jsonb,
+		fetched_ts -> Timestamptz,
+		expires_ts -> Timestamptz,
+	}
+}
+
+diesel::table! {
+	avito_car_makes (make_id) {
+		make_id -> Uuid,
+		avito_id -> Nullable<Varchar>,
+		name -> Varchar,
+		created_ts -> Timestamptz,
+	}
+}
+
+diesel::table! {
+	avito_car_models (model_id) {
+		model_id -> Uuid,
+		make_id -> Uuid,
+		avito_id -> Nullable<Varchar>,
+		name -> Varchar,
+		created_ts -> Timestamptz,
+	}
+}
+
+diesel::table! {
+	avito_car_generations (generation_id) {
+		generation_id -> Uuid,
+		model_id -> Uuid,
+		avito_id -> Nullable<Varchar>,
+		name -> Varchar,
+		created_ts -> Timestamptz,
+	}
+}
+
+diesel::table! {
+	avito_car_modifications (modification_id) {
+		modification_id -> Uuid,
+		generation_id -> Uuid,
+		avito_id -> Nullable<Varchar>,
+		name -> Varchar,
+		body_type -> Nullable<Varchar>,
+		doors -> Nullable<Varchar>,
+		created_ts -> Timestamptz,
+	}
+}
+
 diesel::joinable!(avito_ad_field_values -> avito_ad_fields (field_id));
+diesel::joinable!(avito_feed_import_schedules -> avito_feeds (feed_id));
+diesel::joinable!(avito_feed_imports -> avito_feeds (feed_id));
+diesel::joinable!(avito_item_stats -> avito_accounts (account_id));
+diesel::joinable!(avito_item_syncs -> avito_accounts (account_id));
+diesel::joinable!(avito_price_update_jobs -> avito_accounts (account_id));
+diesel::joinable!(avito_price_update_jobs -> users (user_id));
+diesel::joinable!(avito_price_update_items -> avito_price_update_jobs (job_id));
+diesel::joinable!(avito_repricing_rules -> avito_ads (ad_id));
+diesel::joinable!(avito_repricing_rules -> avito_requests (avito_request_id));
+diesel::joinable!(avito_repricing_decisions -> avito_repricing_rules (rule_id));
+diesel::joinable!(avito_repricing_decisions -> avito_ads (ad_id));
+diesel::joinable!(avito_repricing_decisions -> users (decided_by));
 diesel::joinable!(avito_ad_fields -> avito_ads (ad_id));
+diesel::joinable!(avito_car_models -> avito_car_makes (make_id));
+diesel::joinable!(avito_car_generations -> avito_car_models (model_id));
+diesel::joinable!(avito_car_modifications -> avito_car_generations (generation_id));
 diesel::joinable!(avito_request_progress -> avito_requests (request_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
 	users,
+	user_sessions,
+	user_tokens,
+	rate_limit_counters,
+	login_attempts,
 	avito_accounts,
 	avito_ads,
 	avito_ad_fields,
 	avito_ad_field_values,
 	avito_analytics_ads,
+	avito_balance_alerts,
+	avito_balance_history,
+	avito_car_makes,
+	avito_car_models,
+	avito_car_generations,
+	avito_car_modifications,
+	avito_category_tree_cache,
+	avito_category_fields_cache,
+	avito_field_values_cache,
 	avito_feeds,
+	avito_feed_import_schedules,
+	avito_feed_imports,
+	avito_item_stats,
+	avito_item_syncs,
+	avito_price_update_jobs,
+	avito_price_update_items,
+	avito_repricing_rules,
+	avito_repricing_decisions,
 	avito_requests,
 	avito_request_progress,
 );
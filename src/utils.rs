@@ -1,5 +1,8 @@
 pub mod encryption;
+pub mod mailer;
+pub mod password;
 pub mod transliterate;
+pub mod webhook;
 
 use actix_web::{HttpResponse, Result};
 use serde::Serialize;
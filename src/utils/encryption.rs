@@ -1,10 +1,26 @@
 use aes::Aes256;
-use cbc::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
-use cbc::{Decryptor, Encryptor};
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use cbc::cipher::{BlockDecryptMut, KeyIvInit};
+use cbc::Decryptor;
 use hex;
 use rand_core::{OsRng, RngCore};
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
+use std::sync::OnceLock;
+
+// Key of the values written before keys came from config: `{IV}:{DATA}` without a key id.
+// Only used to read such values until they are rotated to a configured key.
+const LEGACY_KEY: [u8; 32] = [
+	1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26,
+	27, 28, 29, 30, 31, 32,
+];
+pub const LEGACY_KEY_ID: &str = "legacy";
+// Marks values encrypted with AES-256-GCM: `{KEY_ID}:gcm:{NONCE}:{DATA}`
+pub const GCM_MARKER: &str = "gcm";
+
+static KEYRING: OnceLock<Keyring> = OnceLock::new();
 
 // Custom error type to handle different error types
 #[derive(Debug)]
@@ -20,23 +36,65 @@ impl fmt::Display for EncryptionError {
 
 impl Error for EncryptionError {}
 
-pub fn encrypt_data(data: &str, key: &[u8; 32], iv: &[u8; 16]) -> String {
-	type Aes256CbcEnc = Encryptor<Aes256>;
+impl EncryptionError {
+	fn new(message: impl Into<String>) -> Self {
+		EncryptionError {
+			message: message.into(),
+		}
+	}
+}
 
-	let cipher = Aes256CbcEnc::new_from_slices(key, iv).expect("Invalid key or IV length");
-	let plaintext = data.as_bytes();
+// AES-256-GCM with the key id as associated data, so a value only opens under its own key id
+fn encrypt_gcm(data: &str, key_id: &str, key: &[u8; 32]) -> Result<String, EncryptionError> {
+	let cipher = Aes256Gcm::new(key.into());
+	let nonce = generate_nonce();
+	let ciphertext = cipher
+		.encrypt(
+			Nonce::from_slice(&nonce),
+			Payload {
+				msg: data.as_bytes(),
+				aad: key_id.as_bytes(),
+			},
+		)
+		.map_err(|_| EncryptionError::new("Encryption failed"))?;
 
-	// Pad the plaintext to a multiple of block size using PKCS7
-	let block_size = 16;
-	let padding_len = block_size - (plaintext.len() % block_size);
-	let mut padded_plaintext = plaintext.to_vec();
-	padded_plaintext.resize(plaintext.len() + padding_len, padding_len as u8);
+	Ok(format!(
+		"{}:{}:{}:{}",
+		key_id,
+		GCM_MARKER,
+		hex::encode(nonce),
+		hex::encode(ciphertext)
+	))
+}
 
-	let ciphertext =
-		cipher.encrypt_padded_vec_mut::<cipher::block_padding::Pkcs7>(&padded_plaintext);
-	hex::encode(ciphertext)
+fn decrypt_gcm(
+	nonce: &str,
+	encrypted_data: &str,
+	key_id: &str,
+	key: &[u8; 32],
+) -> Result<String, Box<dyn std::error::Error>> {
+	let nonce = hex::decode(nonce)?;
+	if nonce.len() != 12 {
+		return Err("Invalid nonce length".into());
+	}
+	let ciphertext = hex::decode(encrypted_data)?;
+
+	let cipher = Aes256Gcm::new(key.into());
+	let decrypted = cipher
+		.decrypt(
+			Nonce::from_slice(&nonce),
+			Payload {
+				msg: &ciphertext,
+				aad: key_id.as_bytes(),
+			},
+		)
+		.map_err(|_| EncryptionError::new("Decryption failed: value is corrupted or tampered"))?;
+
+	Ok(String::from_utf8(decrypted)?)
 }
 
+// Reads AES-256-CBC values of the old scheme. Their writer padded the plaintext by hand and
+// the cipher padded it again, so both layers of PKCS7 padding are stripped here.
 pub fn decrypt_data(
 	encrypted_data: &str,
 	key: &[u8; 32],
@@ -47,33 +105,30 @@ pub fn decrypt_data(
 	let ciphertext = hex::decode(encrypted_data)?;
 	let cipher = Aes256CbcDec::new_from_slices(key, iv).expect("Invalid key or IV length");
 
-	// Handle the decryption result properly
-	match cipher.decrypt_padded_vec_mut::<cipher::block_padding::Pkcs7>(&ciphertext) {
-		Ok(mut decrypted) => {
-			// Remove PKCS7 padding manually if needed
-			if !decrypted.is_empty() {
-				let padding_len = decrypted[decrypted.len() - 1] as usize;
-				if padding_len <= decrypted.len()
-					&& decrypted
-						.iter()
-						.skip(decrypted.len() - padding_len)
-						.all(|&x| x == padding_len as u8)
-				{
-					decrypted.truncate(decrypted.len() - padding_len);
-				}
-			}
-			Ok(String::from_utf8(decrypted)?)
-		}
-		Err(e) => Err(Box::new(EncryptionError {
-			message: format!("Decryption failed: {:?}", e),
-		})),
+	let mut decrypted = cipher
+		.decrypt_padded_vec_mut::<cipher::block_padding::Pkcs7>(&ciphertext)
+		.map_err(|e| EncryptionError::new(format!("Decryption failed: {:?}", e)))?;
+
+	let padding_len = decrypted.last().copied().unwrap_or_default() as usize;
+	if !(1..=16).contains(&padding_len)
+		|| padding_len > decrypted.len()
+		|| decrypted[decrypted.len() - padding_len..]
+			.iter()
+			.any(|&x| x as usize != padding_len)
+	{
+		return Err(Box::new(EncryptionError::new(
+			"Decryption failed: invalid padding",
+		)));
 	}
+	decrypted.truncate(decrypted.len() - padding_len);
+
+	Ok(String::from_utf8(decrypted)?)
 }
 
-pub fn generate_iv() -> [u8; 16] {
-	let mut iv = [0u8; 16];
-	OsRng.fill_bytes(&mut iv);
-	iv
+fn generate_nonce() -> [u8; 12] {
+	let mut nonce = [0u8; 12];
+	OsRng.fill_bytes(&mut nonce);
+	nonce
 }
 
 // Function to decrypt Avito credentials
@@ -81,26 +136,164 @@ pub fn decrypt_avito_credentials(
 	encrypted_secret: &str,
 	encrypted_client_id: &str,
 ) -> Result<(String, String), Box<dyn std::error::Error>> {
-	// Global key for encryption (in production, this should be stored securely)
-	static ENCRYPTION_KEY: [u8; 32] = [
-		1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25,
-		26, 27, 28, 29, 30, 31, 32,
-	];
+	let keyring = keyring()?;
+	let secret = keyring.decrypt(encrypted_secret)?;
+	let client_id = keyring.decrypt(encrypted_client_id)?;
 
-	// Split IV and encrypted data for secret
-	let (iv_secret, encrypted_secret_data) = split_iv_and_data(encrypted_secret)?;
-	let secret = decrypt_data(&encrypted_secret_data, &ENCRYPTION_KEY, &iv_secret)?;
+	Ok((secret, client_id))
+}
 
-	// Split IV and encrypted data for client_id
-	let (iv_client_id, encrypted_client_id_data) = split_iv_and_data(encrypted_client_id)?;
-	let client_id = decrypt_data(&encrypted_client_id_data, &ENCRYPTION_KEY, &iv_client_id)?;
+// Encrypt a value under the active key in the `{KEY_ID}:gcm:{NONCE}:{DATA}` format
+pub fn encrypt_secret(data: &str) -> Result<String, Box<dyn std::error::Error>> {
+	Ok(keyring()?.encrypt(data)?)
+}
 
-	Ok((secret, client_id))
+// Encryption keys by id; new values are written with the active one, stored values are read
+// with the key their id prefix names
+#[derive(Clone)]
+pub struct Keyring {
+	keys: HashMap<String, [u8; 32]>,
+	active_key_id: String,
+}
+
+impl Keyring {
+	// Keys come as `id:hex` entries, separated by commas or new lines; `#` starts a comment.
+	// Without an explicit active key id the last listed key is used for new values.
+	pub fn parse(spec: &str, active_key_id: Option<&str>) -> Result<Self, EncryptionError> {
+		let mut keys = HashMap::new();
+		let mut last_key_id = None;
+
+		for entry in spec
+			.lines()
+			.map(|line| line.split('#').next().unwrap_or_default())
+			.flat_map(|line| line.split(','))
+			.map(str::trim)
+			.filter(|entry| !entry.is_empty())
+		{
+			let (key_id, hex_key) = entry.split_once(':').ok_or_else(|| {
+				EncryptionError::new(format!("Key entry '{}' is not in the id:hex format", entry))
+			})?;
+			let key_id = key_id.trim();
+			if key_id.is_empty() || key_id == LEGACY_KEY_ID {
+				return Err(EncryptionError::new(format!(
+					"Invalid encryption key id '{}'",
+					key_id
+				)));
+			}
+
+			let key = hex::decode(hex_key.trim())
+				.ok()
+				.and_then(|key| <[u8; 32]>::try_from(key).ok())
+				.ok_or_else(|| {
+					EncryptionError::new(format!(
+						"Encryption key '{}' must be 32 bytes in hex",
+						key_id
+					))
+				})?;
+			if keys.insert(key_id.to_string(), key).is_some() {
+				return Err(EncryptionError::new(format!(
+					"Encryption key '{}' is listed twice",
+					key_id
+				)));
+			}
+			last_key_id = Some(key_id.to_string());
+		}
+
+		let active_key_id = match active_key_id {
+			Some(key_id) if !keys.contains_key(key_id) => {
+				return Err(EncryptionError::new(format!(
+					"Active encryption key '{}' is not configured",
+					key_id
+				)));
+			}
+			Some(key_id) => key_id.to_string(),
+			None => {
+				last_key_id.ok_or_else(|| EncryptionError::new("No encryption keys configured"))?
+			}
+		};
+
+		Ok(Keyring {
+			keys,
+			active_key_id,
+		})
+	}
+
+	pub fn active_key_id(&self) -> &str {
+		&self.active_key_id
+	}
+
+	pub fn encrypt(&self, data: &str) -> Result<String, EncryptionError> {
+		encrypt_gcm(data, &self.active_key_id, &self.keys[&self.active_key_id])
+	}
+
+	pub fn decrypt(&self, stored: &str) -> Result<String, Box<dyn std::error::Error>> {
+		let value = StoredValue::parse(stored)?;
+		let key = if value.key_id == LEGACY_KEY_ID {
+			&LEGACY_KEY
+		} else {
+			self.keys.get(value.key_id).ok_or_else(|| {
+				EncryptionError::new(format!("Unknown encryption key '{}'", value.key_id))
+			})?
+		};
+
+		if value.authenticated {
+			decrypt_gcm(value.iv, value.data, value.key_id, key)
+		} else {
+			let (iv, encrypted_data) = split_iv_and_data(&format!("{}:{}", value.iv, value.data))?;
+			decrypt_data(&encrypted_data, key, &iv)
+		}
+	}
+
+	// Whether the value should be re-encrypted: it is on another key or on the old CBC scheme
+	pub fn needs_rotation(&self, stored: &str) -> bool {
+		match StoredValue::parse(stored) {
+			Ok(value) => !value.authenticated || value.key_id != self.active_key_id,
+			Err(_) => true,
+		}
+	}
 }
 
-// Function to combine IV and encrypted data
-pub fn combine_iv_and_data(iv: &[u8; 16], encrypted_data: &str) -> String {
-	format!("{}:{}", hex::encode(iv), encrypted_data)
+// Parts of a stored value in one of its formats:
+// `{IV}:{DATA}` (CBC, legacy key), `{KEY_ID}:{IV}:{DATA}` (CBC) and `{KEY_ID}:gcm:{NONCE}:{DATA}`
+struct StoredValue<'a> {
+	key_id: &'a str,
+	authenticated: bool,
+	iv: &'a str,
+	data: &'a str,
+}
+
+impl<'a> StoredValue<'a> {
+	fn parse(stored: &'a str) -> Result<Self, EncryptionError> {
+		let parts: Vec<&str> = stored.split(':').collect();
+		let (key_id, authenticated, iv, data) = match parts.as_slice() {
+			[iv, data] => (LEGACY_KEY_ID, false, *iv, *data),
+			[key_id, iv, data] => (*key_id, false, *iv, *data),
+			[key_id, marker, nonce, data] if *marker == GCM_MARKER => {
+				(*key_id, true, *nonce, *data)
+			}
+			_ => return Err(EncryptionError::new("Invalid encrypted data format")),
+		};
+
+		Ok(StoredValue {
+			key_id,
+			authenticated,
+			iv,
+			data,
+		})
+	}
+}
+
+// Make the keyring loaded from config available to every encrypt and decrypt call
+pub fn install_keyring(keyring: Keyring) {
+	if KEYRING.set(keyring).is_err() {
+		log::warn!("Encryption keyring is already installed");
+	}
+}
+
+pub fn keyring() -> Result<&'static Keyring, EncryptionError> {
+	KEYRING
+		.get()
+		.ok_or_else(|| EncryptionError::new("Encryption keys are not loaded"))
 }
 
 // Function to split IV and encrypted data
@@ -122,3 +315,123 @@ pub fn split_iv_and_data(
 
 	Ok((iv_bytes, parts[1].to_string()))
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const KEYS: &str = "2025:000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f,\n\
+		2026:1f1e1d1c1b1a191817161514131211100f0e0d0c0b0a09080706050403020100 # current";
+
+	// Written by the old code: CBC under the built-in key, padded twice, no key id
+	const LEGACY_SECRET: &str = "07070707070707070707070707070707:19b2e7bd7c511a5b04aecd97c25ea803046979316805c2f09e02dedcfc13ab35efb5ac8092d8689c2accc0687ee3d54a";
+	// A block-sized plaintext, which got a whole block of padding from each layer
+	const LEGACY_BLOCK: &str = "09090909090909090909090909090909:df442642705300ba73576d0db8a13f6ccf3a36bed691b1c8e109d3a2828558bca3e5da394d18f51ba324c52478fd8161";
+	// CBC under a configured key, as written before values were authenticated
+	const CBC_CLIENT_ID: &str =
+		"2025:03030303030303030303030303030303:24ca99562774ee10e1c378acb16a8c18f6713774fd778792e5013e09443a20ca";
+
+	// Flip one hex digit of the part at `index` of a stored value
+	fn tamper(stored: &str, index: usize) -> String {
+		let mut parts: Vec<String> = stored.split(':').map(str::to_string).collect();
+		let flipped = match parts[index].pop() {
+			Some('0') => '1',
+			_ => '0',
+		};
+		parts[index].push(flipped);
+		parts.join(":")
+	}
+
+	#[test]
+	fn test_keyring_round_trip() {
+		let keyring = Keyring::parse(KEYS, None).unwrap();
+		assert_eq!(keyring.active_key_id(), "2026");
+
+		let stored = keyring.encrypt("client-secret").unwrap();
+		assert!(stored.starts_with("2026:gcm:"));
+		assert_eq!(StoredValue::parse(&stored).unwrap().key_id, "2026");
+		assert_eq!(keyring.decrypt(&stored).unwrap(), "client-secret");
+		assert!(!keyring.needs_rotation(&stored));
+
+		// Every value gets its own nonce
+		assert_ne!(keyring.encrypt("client-secret").unwrap(), stored);
+		assert_eq!(keyring.decrypt(&keyring.encrypt("").unwrap()).unwrap(), "");
+	}
+
+	#[test]
+	fn test_keyring_reads_older_keys() {
+		let old = Keyring::parse(KEYS, Some("2025")).unwrap();
+		let stored = old.encrypt("client-id").unwrap();
+
+		let current = Keyring::parse(KEYS, None).unwrap();
+		assert!(current.needs_rotation(&stored));
+		assert_eq!(current.decrypt(&stored).unwrap(), "client-id");
+	}
+
+	#[test]
+	fn test_keyring_reads_existing_rows() {
+		let keyring = Keyring::parse(KEYS, Some("2025")).unwrap();
+
+		assert_eq!(
+			StoredValue::parse(LEGACY_SECRET).unwrap().key_id,
+			LEGACY_KEY_ID
+		);
+		assert_eq!(
+			keyring.decrypt(LEGACY_SECRET).unwrap(),
+			"avito-client-secret"
+		);
+		assert_eq!(keyring.decrypt(LEGACY_BLOCK).unwrap(), "0123456789abcdef");
+		assert!(keyring.needs_rotation(LEGACY_SECRET));
+
+		// On the active key, but still CBC, so it gets upgraded
+		assert_eq!(keyring.decrypt(CBC_CLIENT_ID).unwrap(), "client-id-42");
+		assert!(keyring.needs_rotation(CBC_CLIENT_ID));
+
+		let upgraded = keyring
+			.encrypt(&keyring.decrypt(CBC_CLIENT_ID).unwrap())
+			.unwrap();
+		assert!(!keyring.needs_rotation(&upgraded));
+		assert_eq!(keyring.decrypt(&upgraded).unwrap(), "client-id-42");
+	}
+
+	#[test]
+	fn test_keyring_detects_tampering() {
+		let keyring = Keyring::parse(KEYS, None).unwrap();
+		let stored = keyring.encrypt("client-secret").unwrap();
+
+		// Nonce, ciphertext and tag
+		assert!(keyring.decrypt(&tamper(&stored, 2)).is_err());
+		assert!(keyring.decrypt(&tamper(&stored, 3)).is_err());
+
+		// Truncated, so the tag is incomplete
+		assert!(keyring.decrypt(&stored[..stored.len() - 2]).is_err());
+
+		// Moved under another configured key id
+		let relabeled = stored.replacen("2026:", "2025:", 1);
+		assert!(keyring.decrypt(&relabeled).is_err());
+
+		// Downgraded to the CBC format
+		let downgraded = stored.replacen(":gcm", "", 1);
+		assert!(keyring.decrypt(&downgraded).is_err());
+
+		assert!(keyring.decrypt("2026:xyz:00:00").is_err());
+		assert!(keyring.decrypt("not encrypted").is_err());
+	}
+
+	#[test]
+	fn test_keyring_rejects_bad_config() {
+		assert!(Keyring::parse("", None).is_err());
+		assert!(Keyring::parse("2026:abcd", None).is_err());
+		assert!(Keyring::parse("no-separator", None).is_err());
+		assert!(Keyring::parse(KEYS, Some("2027")).is_err());
+		assert!(Keyring::parse(&format!("{},{}", KEYS, KEYS), None).is_err());
+
+		let keyring = Keyring::parse(KEYS, None).unwrap();
+		let other = Keyring::parse(
+			"2030:000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+			None,
+		)
+		.unwrap();
+		assert!(keyring.decrypt(&other.encrypt("secret").unwrap()).is_err());
+	}
+}
@@ -0,0 +1,214 @@
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncFileTransport, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::config::Config;
+
+pub struct Email {
+	pub to: String,
+	pub subject: String,
+	pub body: String,
+}
+
+#[derive(Debug)]
+pub struct MailerError {
+	message: String,
+}
+
+impl fmt::Display for MailerError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.message)
+	}
+}
+
+impl Error for MailerError {}
+
+impl MailerError {
+	fn new(message: impl Into<String>) -> Self {
+		MailerError {
+			message: message.into(),
+		}
+	}
+}
+
+// Where outgoing mail goes. Handlers reach it through AppState, so the transport is picked
+// by config: SMTP in production, a file outbox locally and in tests.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+	async fn send(&self, email: &Email) -> Result<(), MailerError>;
+}
+
+fn build_message(from: &Mailbox, email: &Email) -> Result<Message, MailerError> {
+	let to = email
+		.to
+		.parse::<Mailbox>()
+		.map_err(|e| MailerError::new(format!("Invalid recipient {}: {}", email.to, e)))?;
+
+	Message::builder()
+		.from(from.clone())
+		.to(to)
+		.subject(email.subject.as_str())
+		.body(email.body.clone())
+		.map_err(|e| MailerError::new(format!("Failed to build the message: {}", e)))
+}
+
+pub struct SmtpMailer {
+	transport: AsyncSmtpTransport<Tokio1Executor>,
+	from: Mailbox,
+}
+
+impl SmtpMailer {
+	pub fn from_config(config: &Config) -> Result<Self, MailerError> {
+		let host = config
+			.smtp_host
+			.as_deref()
+			.ok_or_else(|| MailerError::new("SMTP_HOST must be set to send mail over SMTP"))?;
+
+		let mut builder = match config.smtp_tls.as_str() {
+			"starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host),
+			"tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(host),
+			"none" => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+				host,
+			)),
+			other => {
+				return Err(MailerError::new(format!(
+					"Unknown SMTP_TLS '{}', expected starttls, tls or none",
+					other
+				)))
+			}
+		}
+		.map_err(|e| MailerError::new(format!("Invalid SMTP relay {}: {}", host, e)))?;
+
+		if let Some(port) = config.smtp_port {
+			builder = builder.port(port);
+		}
+		if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password) {
+			builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+		}
+
+		Ok(SmtpMailer {
+			transport: builder.build(),
+			from: parse_sender(&config.mail_from)?,
+		})
+	}
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+	async fn send(&self, email: &Email) -> Result<(), MailerError> {
+		let message = build_message(&self.from, email)?;
+		self.transport
+			.send(message)
+			.await
+			.map(|_| ())
+			.map_err(|e| MailerError::new(format!("Failed to send mail to {}: {}", email.to, e)))
+	}
+}
+
+// Writes every message to an .eml file in a directory instead of sending it
+pub struct OutboxMailer {
+	dir: String,
+	transport: AsyncFileTransport<Tokio1Executor>,
+	from: Mailbox,
+}
+
+impl OutboxMailer {
+	pub fn new(dir: &str, from: &str) -> Result<Self, MailerError> {
+		Ok(OutboxMailer {
+			dir: dir.to_string(),
+			transport: AsyncFileTransport::new(dir),
+			from: parse_sender(from)?,
+		})
+	}
+}
+
+#[async_trait]
+impl Mailer for OutboxMailer {
+	async fn send(&self, email: &Email) -> Result<(), MailerError> {
+		let message = build_message(&self.from, email)?;
+		tokio::fs::create_dir_all(&self.dir)
+			.await
+			.map_err(|e| MailerError::new(format!("Failed to create {}: {}", self.dir, e)))?;
+
+		let id = self.transport.send(message).await.map_err(|e| {
+			MailerError::new(format!("Failed to write mail to {}: {}", self.dir, e))
+		})?;
+		log::info!("Mail to {} written to {}/{}.eml", email.to, self.dir, id);
+		Ok(())
+	}
+}
+
+fn parse_sender(from: &str) -> Result<Mailbox, MailerError> {
+	from.parse()
+		.map_err(|e| MailerError::new(format!("Invalid MAIL_FROM {}: {}", from, e)))
+}
+
+pub fn mailer_from_config(config: &Config) -> Result<Arc<dyn Mailer>, MailerError> {
+	match config.mail_transport.as_str() {
+		"smtp" => Ok(Arc::new(SmtpMailer::from_config(config)?)),
+		"outbox" => Ok(Arc::new(OutboxMailer::new(
+			&config.mail_outbox_dir,
+			&config.mail_from,
+		)?)),
+		other => Err(MailerError::new(format!(
+			"Unknown MAIL_TRANSPORT '{}', expected smtp or outbox",
+			other
+		))),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn test_outbox_mailer_writes_messages() {
+		let dir = std::env::temp_dir().join(format!("mail_outbox_{}", uuid::Uuid::new_v4()));
+		let mailer =
+			OutboxMailer::new(dir.to_str().unwrap(), "Avito Tools <no-reply@example.com>").unwrap();
+
+		mailer
+			.send(&Email {
+				to: "user@example.com".to_string(),
+				subject: "Confirm your email".to_string(),
+				body: "Open the link to confirm".to_string(),
+			})
+			.await
+			.unwrap();
+
+		let files: Vec<_> = std::fs::read_dir(&dir)
+			.unwrap()
+			.map(|entry| entry.unwrap().path())
+			.collect();
+		assert_eq!(files.len(), 1);
+		assert_eq!(files[0].extension().unwrap(), "eml");
+
+		let message = std::fs::read_to_string(&files[0]).unwrap();
+		assert!(message.contains("To: user@example.com"));
+		assert!(message.contains("Subject: Confirm your email"));
+		assert!(message.contains("Open the link to confirm"));
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[tokio::test]
+	async fn test_outbox_mailer_rejects_bad_addresses() {
+		assert!(OutboxMailer::new("unused", "not an address").is_err());
+
+		let mailer = OutboxMailer::new("unused", "no-reply@example.com").unwrap();
+		let result = mailer
+			.send(&Email {
+				to: "nobody".to_string(),
+				subject: "Subject".to_string(),
+				body: "Body".to_string(),
+			})
+			.await;
+		assert!(result.is_err());
+		assert!(!std::path::Path::new("unused").exists());
+	}
+}
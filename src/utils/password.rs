@@ -0,0 +1,290 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+
+use argon2::password_hash::phc::{PasswordHash, Salt};
+use argon2::password_hash::{self, PasswordHasher, PasswordVerifier};
+use argon2::{Algorithm, Argon2, Params, Version, ARGON2ID_IDENT};
+
+use crate::config::Config;
+
+const MAX_PASSWORD_LENGTH: usize = 128;
+
+#[derive(Debug)]
+pub struct PasswordError {
+	message: String,
+}
+
+impl fmt::Display for PasswordError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.message)
+	}
+}
+
+impl Error for PasswordError {}
+
+impl PasswordError {
+	fn new(message: impl Into<String>) -> Self {
+		PasswordError {
+			message: message.into(),
+		}
+	}
+}
+
+// How a stored password hash was made
+#[derive(Debug, PartialEq, Eq)]
+pub enum HashScheme {
+	Argon2id,
+	// Every password set before Argon2id became the default
+	Bcrypt,
+	Unknown,
+}
+
+impl HashScheme {
+	pub fn detect(stored: &str) -> HashScheme {
+		if stored.starts_with("$argon2id$") {
+			HashScheme::Argon2id
+		} else if ["$2a$", "$2b$", "$2x$", "$2y$"]
+			.iter()
+			.any(|prefix| stored.starts_with(prefix))
+		{
+			HashScheme::Bcrypt
+		} else {
+			HashScheme::Unknown
+		}
+	}
+}
+
+// Argon2id with the parameters from config for new hashes; existing hashes are verified
+// with whatever scheme and parameters they were made with
+#[derive(Clone)]
+pub struct PasswordHashing {
+	params: Params,
+	// Verified when the user does not exist, so the answer takes as long as a wrong password
+	dummy_hash: String,
+}
+
+impl PasswordHashing {
+	pub fn new(memory_kib: u32, iterations: u32, parallelism: u32) -> Result<Self, PasswordError> {
+		let params = Params::new(memory_kib, iterations, parallelism, None)
+			.map_err(|e| PasswordError::new(format!("Invalid Argon2 parameters: {}", e)))?;
+		let mut hashing = PasswordHashing {
+			params,
+			dummy_hash: String::new(),
+		};
+		hashing.dummy_hash = hashing.hash("dummy password")?;
+		Ok(hashing)
+	}
+
+	pub fn from_config(config: &Config) -> Result<Self, PasswordError> {
+		PasswordHashing::new(
+			config.argon2_memory_kib,
+			config.argon2_iterations,
+			config.argon2_parallelism,
+		)
+	}
+
+	fn argon2(&self) -> Argon2<'static> {
+		Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+	}
+
+	pub fn hash(&self, password: &str) -> Result<String, PasswordError> {
+		let salt = Salt::generate();
+		self.argon2()
+			.hash_password(password.as_bytes(), &salt)
+			.map(|hash| hash.to_string())
+			.map_err(|e| PasswordError::new(format!("Failed to hash the password: {}", e)))
+	}
+
+	pub fn verify(&self, password: &str, stored: &str) -> Result<bool, PasswordError> {
+		match HashScheme::detect(stored) {
+			HashScheme::Argon2id => {
+				let hash = PasswordHash::new(stored)
+					.map_err(|e| PasswordError::new(format!("Invalid Argon2 hash: {}", e)))?;
+				match self.argon2().verify_password(password.as_bytes(), &hash) {
+					Ok(()) => Ok(true),
+					Err(password_hash::Error::PasswordInvalid) => Ok(false),
+					Err(e) => Err(PasswordError::new(format!(
+						"Failed to verify the password: {}",
+						e
+					))),
+				}
+			}
+			HashScheme::Bcrypt => bcrypt::verify(password, stored)
+				.map_err(|e| PasswordError::new(format!("Failed to verify the password: {}", e))),
+			HashScheme::Unknown => Err(PasswordError::new("Unknown password hash format")),
+		}
+	}
+
+	// Spend the time of a password check on a user that does not exist
+	pub fn verify_dummy(&self, password: &str) {
+		if let Err(e) = self.verify(password, &self.dummy_hash) {
+			log::error!("Failed to verify the dummy password hash: {}", e);
+		}
+	}
+
+	// Whether a hash that just verified should be replaced: bcrypt hashes, and Argon2id
+	// hashes made with other parameters than configured now
+	pub fn needs_rehash(&self, stored: &str) -> bool {
+		match HashScheme::detect(stored) {
+			HashScheme::Argon2id => PasswordHash::new(stored)
+				.ok()
+				.filter(|hash| hash.algorithm.as_str() == ARGON2ID_IDENT.as_str())
+				.and_then(|hash| Params::try_from(&hash).ok())
+				.is_none_or(|params| {
+					params.m_cost() != self.params.m_cost()
+						|| params.t_cost() != self.params.t_cost()
+						|| params.p_cost() != self.params.p_cost()
+				}),
+			HashScheme::Bcrypt => true,
+			HashScheme::Unknown => false,
+		}
+	}
+}
+
+// Rules a new password has to follow
+pub struct PasswordPolicy {
+	min_length: usize,
+	// Lowercased, so case variants of a breached password are refused too
+	breached: HashSet<String>,
+}
+
+impl PasswordPolicy {
+	pub fn new(min_length: usize, breached_passwords: &str) -> Self {
+		PasswordPolicy {
+			min_length,
+			breached: breached_passwords
+				.lines()
+				.map(|line| line.trim().to_lowercase())
+				.filter(|line| !line.is_empty())
+				.collect(),
+		}
+	}
+
+	pub fn from_config(config: &Config) -> Result<Self, PasswordError> {
+		let breached = match &config.breached_passwords_file {
+			Some(path) => std::fs::read_to_string(path).map_err(|e| {
+				PasswordError::new(format!(
+					"Failed to read BREACHED_PASSWORDS_FILE {}: {}",
+					path, e
+				))
+			})?,
+			None => String::new(),
+		};
+		Ok(PasswordPolicy::new(config.password_min_length, &breached))
+	}
+
+	pub fn breached_count(&self) -> usize {
+		self.breached.len()
+	}
+
+	pub fn check(&self, password: &str, password_confirm: &str) -> Result<(), String> {
+		let length = password.chars().count();
+		if length < self.min_length {
+			return Err(format!(
+				"Password must be at least {} characters long",
+				self.min_length
+			));
+		}
+		if length > MAX_PASSWORD_LENGTH {
+			return Err(format!(
+				"Password must be at most {} characters long",
+				MAX_PASSWORD_LENGTH
+			));
+		}
+		if self.breached.contains(&password.to_lowercase()) {
+			return Err(
+				"This password has appeared in a data breach, please choose another one"
+					.to_string(),
+			);
+		}
+		if password != password_confirm {
+			return Err("Passwords do not match".to_string());
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Small parameters keep the tests fast
+	fn hashing() -> PasswordHashing {
+		PasswordHashing::new(64, 1, 1).unwrap()
+	}
+
+	#[test]
+	fn test_argon2id_round_trip() {
+		let hashing = hashing();
+		let hash = hashing.hash("correct horse battery").unwrap();
+
+		assert!(hash.starts_with("$argon2id$v=19$m=64,t=1,p=1$"));
+		assert_eq!(HashScheme::detect(&hash), HashScheme::Argon2id);
+		assert!(hashing.verify("correct horse battery", &hash).unwrap());
+		assert!(!hashing.verify("correct horse battery!", &hash).unwrap());
+		assert_ne!(hash, hashing.hash("correct horse battery").unwrap());
+		assert!(!hashing.needs_rehash(&hash));
+	}
+
+	#[test]
+	fn test_bcrypt_hashes_are_verified_and_rehashed() {
+		let hashing = hashing();
+		let hash = bcrypt::hash("correct horse battery", 4).unwrap();
+
+		assert_eq!(HashScheme::detect(&hash), HashScheme::Bcrypt);
+		assert!(hashing.verify("correct horse battery", &hash).unwrap());
+		assert!(!hashing.verify("wrong", &hash).unwrap());
+		assert!(hashing.needs_rehash(&hash));
+	}
+
+	#[test]
+	fn test_argon2id_hashes_with_old_parameters_are_rehashed() {
+		let old = PasswordHashing::new(32, 1, 1)
+			.unwrap()
+			.hash("correct horse battery")
+			.unwrap();
+
+		let hashing = hashing();
+		assert!(hashing.verify("correct horse battery", &old).unwrap());
+		assert!(hashing.needs_rehash(&old));
+	}
+
+	#[test]
+	fn test_unknown_hashes_are_rejected() {
+		let hashing = hashing();
+		assert_eq!(HashScheme::detect("plaintext"), HashScheme::Unknown);
+		assert!(hashing.verify("plaintext", "plaintext").is_err());
+		assert!(!hashing.needs_rehash("plaintext"));
+		assert!(PasswordHashing::new(1, 0, 1).is_err());
+	}
+
+	#[test]
+	fn test_dummy_hash_uses_the_configured_parameters() {
+		let hashing = hashing();
+		assert!(hashing
+			.dummy_hash
+			.starts_with("$argon2id$v=19$m=64,t=1,p=1$"));
+		assert!(!hashing.needs_rehash(&hashing.dummy_hash));
+	}
+
+	#[test]
+	fn test_password_policy() {
+		let policy = PasswordPolicy::new(8, "Password1\r\n\nqwertyuiop\n");
+		assert_eq!(policy.breached_count(), 2);
+
+		assert!(policy
+			.check("a-long-unique-phrase", "a-long-unique-phrase")
+			.is_ok());
+		assert!(policy.check("short", "short").is_err());
+		assert!(policy.check(&"x".repeat(129), &"x".repeat(129)).is_err());
+		assert!(policy.check("QWERTYUIOP", "QWERTYUIOP").is_err());
+		assert!(policy.check("password1", "password1").is_err());
+		assert_eq!(
+			policy.check("a-long-unique-phrase", "a-long-unique-phrasE"),
+			Err("Passwords do not match".to_string())
+		);
+		// Length counts characters, not bytes
+		assert!(policy.check("пароль12", "пароль12").is_ok());
+	}
+}
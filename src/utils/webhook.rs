@@ -0,0 +1,155 @@
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use reqwest::redirect::Policy;
+use url::{Host, Url};
+
+// Whether a webhook may be delivered to the address. The server itself, private and
+// link-local networks (cloud metadata included) and reserved ranges are refused, so a
+// user's webhook URL can't be used to reach services that aren't public.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+	match ip {
+		IpAddr::V4(ip) => {
+			let [a, b, ..] = ip.octets();
+			!(ip.is_private()
+				|| ip.is_loopback()
+				|| ip.is_link_local()
+				|| ip.is_unspecified()
+				|| ip.is_broadcast()
+				|| ip.is_multicast()
+				|| ip.is_documentation()
+				|| a == 0
+				// Carrier-grade NAT, 100.64.0.0/10
+				|| (a == 100 && (64..128).contains(&b))
+				|| a >= 240)
+		}
+		IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+			Some(ip) => is_public_ip(IpAddr::V4(ip)),
+			None => {
+				!(ip.is_loopback()
+					|| ip.is_unspecified()
+					|| ip.is_multicast()
+					|| ip.is_unique_local()
+					|| ip.is_unicast_link_local())
+			}
+		},
+	}
+}
+
+// A webhook or other user-supplied URL whose host resolved to public addresses only
+pub struct WebhookTarget {
+	host: String,
+	addr: SocketAddr,
+}
+
+impl WebhookTarget {
+	// Client that connects to the checked address, so the host can't resolve elsewhere by
+	// the time the request is sent, and that doesn't follow redirects
+	pub fn client(&self, timeout: Duration) -> reqwest::Result<reqwest::Client> {
+		reqwest::Client::builder()
+			.timeout(timeout)
+			.redirect(Policy::none())
+			.resolve(&self.host, self.addr)
+			.build()
+	}
+}
+
+// Check a webhook URL set by a user: http(s) to a host that resolves to public addresses only
+pub async fn resolve_webhook_url(webhook_url: &str) -> Result<WebhookTarget, String> {
+	resolve_public_url(webhook_url, "webhook_url").await
+}
+
+// Check any URL the server is asked to request on a user's behalf. `field` names the URL
+// in the error messages.
+pub async fn resolve_public_url(url: &str, field: &str) -> Result<WebhookTarget, String> {
+	let url = Url::parse(url)
+		.ok()
+		.filter(|url| matches!(url.scheme(), "http" | "https"))
+		.ok_or_else(|| format!("{} must be an http(s) URL", field))?;
+	let port = url
+		.port_or_known_default()
+		.ok_or_else(|| format!("{} must be an http(s) URL", field))?;
+	let host = match url.host() {
+		Some(Host::Domain(domain)) => domain.to_string(),
+		Some(Host::Ipv4(ip)) => ip.to_string(),
+		Some(Host::Ipv6(ip)) => ip.to_string(),
+		None => return Err(format!("{} must have a host", field)),
+	};
+
+	let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), port))
+		.await
+		.map_err(|e| format!("{} host could not be resolved: {}", field, e))?
+		.collect();
+	if addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+		return Err(format!("{} must point to a public address", field));
+	}
+	let Some(&addr) = addrs.first() else {
+		return Err(format!("{} host could not be resolved", field));
+	};
+
+	Ok(WebhookTarget { host, addr })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_is_public_ip() {
+		for ip in [
+			"93.184.216.34",
+			"2a00:1450:4010:c0e::64",
+			"::ffff:93.184.216.34",
+		] {
+			assert!(is_public_ip(ip.parse().unwrap()), "{}", ip);
+		}
+		for ip in [
+			"127.0.0.1",
+			"10.1.2.3",
+			"172.16.0.1",
+			"192.168.1.1",
+			"169.254.169.254",
+			"100.64.0.1",
+			"0.0.0.0",
+			"255.255.255.255",
+			"::1",
+			"::",
+			"fd00::1",
+			"fe80::1",
+			"::ffff:127.0.0.1",
+		] {
+			assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+		}
+	}
+
+	#[actix_web::test]
+	async fn test_resolve_webhook_url() {
+		let target = resolve_webhook_url("https://93.184.216.34/hooks/balance")
+			.await
+			.unwrap();
+		assert_eq!(target.addr, "93.184.216.34:443".parse().unwrap());
+
+		for url in [
+			"http://127.0.0.1:8080/",
+			"http://localhost/",
+			"http://169.254.169.254/latest/meta-data/",
+			"http://[::1]/",
+		] {
+			assert_eq!(
+				resolve_webhook_url(url).await.err().as_deref(),
+				Some("webhook_url must point to a public address"),
+				"{}",
+				url
+			);
+		}
+		assert!(resolve_webhook_url("ftp://93.184.216.34/").await.is_err());
+		assert!(resolve_webhook_url("not a url").await.is_err());
+		assert_eq!(
+			resolve_public_url("http://10.0.0.5/feed.xml", "xml_url")
+				.await
+				.err()
+				.as_deref(),
+			Some("xml_url must point to a public address")
+		);
+	}
+}